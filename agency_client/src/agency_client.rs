@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use crate::agency_settings;
 
 use crate::error::{AgencyClientResult, AgencyClientErrorKind, AgencyClientError};
@@ -16,7 +18,8 @@ pub struct AgencyClient {
     agent_pwdid: String,
     agent_vk: String,
     my_pwdid: String,
-    my_vk: String
+    my_vk: String,
+    headers: HashMap<String, String>,
 }
 
 impl AgencyClient {
@@ -29,6 +32,8 @@ impl AgencyClient {
     pub fn get_agent_vk(&self) -> AgencyClientResult<String> { Ok(self.agent_vk.clone()) }
     pub fn get_my_pwdid(&self) -> AgencyClientResult<String> { Ok(self.my_pwdid.clone()) }
     pub fn get_my_vk(&self) -> AgencyClientResult<String> { Ok(self.my_vk.clone()) }
+    pub fn get_headers(&self) -> HashMap<String, String> { self.headers.clone() }
+    pub fn get_protocol_version(&self) -> String { agency_settings::get_agency_protocol_version() }
 
     pub fn set_wallet_handle(&mut self, wh: i32) { 
         self.wallet_handle = wh; 
@@ -72,6 +77,17 @@ impl AgencyClient {
         self.my_vk = vk.to_string();
     }
 
+    pub fn set_headers(&mut self, headers: HashMap<String, String>) {
+        agency_settings::set_config_value(agency_settings::CONFIG_AGENCY_HEADERS, &json!(headers).to_string());
+        self.headers = headers;
+    }
+
+    pub fn set_protocol_version(&mut self, protocol_version: &str) -> AgencyClientResult<()> {
+        validation::validate_agency_protocol_version(protocol_version)?;
+        agency_settings::set_config_value(agency_settings::CONFIG_AGENCY_PROTOCOL_VERSION, protocol_version);
+        Ok(())
+    }
+
     pub fn enable_test_mode(&self) { mocking::enable_agency_mocks() }
     pub fn disable_test_mode(&self) { mocking::disable_agency_mocks() }
 
@@ -85,6 +101,14 @@ impl AgencyClient {
         if let Value::Object(ref map) = configuration {
             for (key, value) in map {
                 trace!("AgencyClient::process_config_string >>> key {:?}, value {:?} ", key, value);
+                if key == agency_settings::CONFIG_AGENCY_HEADERS {
+                    let headers = match value {
+                        Value::String(value_) => value_.to_string(),
+                        _ => value.to_string()
+                    };
+                    self.set_headers(validation::validate_headers(&headers)?);
+                    continue;
+                }
                 let value = match value {
                     Value::String(value_) => value_,
                     _ => {
@@ -101,6 +125,20 @@ impl AgencyClient {
                    agency_settings::CONFIG_SDK_TO_REMOTE_DID => { self.set_my_pwdid(&value.to_string()); },
                    agency_settings::CONFIG_SDK_TO_REMOTE_VERKEY => { self.set_my_vk(&value.to_string()); },
                    agency_settings::CONFIG_ENABLE_TEST_MODE => { self.enable_test_mode(); },
+                   agency_settings::CONFIG_AGENCY_PROTOCOL_VERSION => { self.set_protocol_version(&value)?; },
+                   agency_settings::CONFIG_AGENCY_CIRCUIT_BREAKER_THRESHOLD => {
+                       validation::validate_circuit_breaker_threshold(&value)?;
+                       agency_settings::set_config_value(key, &value);
+                   },
+                   agency_settings::CONFIG_AGENCY_DOWNLOAD_PAGE_SIZE => {
+                       validation::validate_download_page_size(&value)?;
+                       agency_settings::set_config_value(key, &value);
+                   },
+                   agency_settings::CONFIG_AGENCY_CIRCUIT_BREAKER_COOLDOWN_MS => {
+                       value.parse::<u64>()
+                           .map_err(|err| AgencyClientError::from_msg(AgencyClientErrorKind::InvalidConfiguration, format!("Invalid value of {}: {:?}", key, err)))?;
+                       agency_settings::set_config_value(key, &value);
+                   },
                    _ => { trace!("AgencyClient::process_config_string >>> ignoring key {}", key); }
                 }
             }
@@ -1,10 +1,12 @@
 use std::borrow::Borrow;
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use serde_json::Value;
 use url::Url;
 
+use crate::circuit_breaker;
 use crate::error::{AgencyClientErrorKind, AgencyClientError, AgencyClientResult};
 use crate::utils::{error_utils, validation};
 
@@ -17,6 +19,23 @@ pub const CONFIG_SDK_TO_REMOTE_DID: &str = "sdk_to_remote_did";
 pub const CONFIG_SDK_TO_REMOTE_VERKEY: &str = "sdk_to_remote_verkey";
 pub const CONFIG_ENABLE_TEST_MODE: &str = "enable_test_mode";
 pub const CONFIG_WALLET_HANDLE: &str = "wallet_handle";
+// JSON object of HTTP headers attached to every agency request, eg. `{"Authorization": "Bearer <token>"}`
+pub const CONFIG_AGENCY_HEADERS: &str = "agency_headers";
+// version of the agency protocol outgoing agency messages are packed in, pins the version the agency is expected to speak,
+// unlike libvcx `protocol_version` setting which is the version of the ledger protocol
+pub const CONFIG_AGENCY_PROTOCOL_VERSION: &str = "agency_protocol_version";
+// consecutive failed agency requests after which further requests fail fast, circuit breaker is disabled if not set
+pub const CONFIG_AGENCY_CIRCUIT_BREAKER_THRESHOLD: &str = "agency_circuit_breaker_threshold";
+// milliseconds the circuit breaker stays open before a probe request is let through
+pub const CONFIG_AGENCY_CIRCUIT_BREAKER_COOLDOWN_MS: &str = "agency_circuit_breaker_cooldown_ms";
+// maximum number of message payloads downloaded per get-messages request, all are downloaded at once if not set
+pub const CONFIG_AGENCY_DOWNLOAD_PAGE_SIZE: &str = "agency_download_page_size";
+
+pub const DEFAULT_AGENCY_CIRCUIT_BREAKER_COOLDOWN_MS: u64 = 30_000;
+pub const MAX_DOWNLOAD_PAGE_SIZE: u32 = 1000;
+
+pub const DEFAULT_AGENCY_PROTOCOL_VERSION: &str = "2.0";
+pub static SUPPORTED_AGENCY_PROTOCOL_VERSIONS: &[&str] = &[DEFAULT_AGENCY_PROTOCOL_VERSION];
 
 pub static VALID_AGENCY_CONFIG_KEYS: &[&str] = &[
     CONFIG_AGENCY_ENDPOINT,
@@ -28,10 +47,21 @@ pub static VALID_AGENCY_CONFIG_KEYS: &[&str] = &[
     CONFIG_SDK_TO_REMOTE_VERKEY,
     CONFIG_ENABLE_TEST_MODE,
     CONFIG_WALLET_HANDLE,
+    CONFIG_AGENCY_HEADERS,
+    CONFIG_AGENCY_PROTOCOL_VERSION,
+    CONFIG_AGENCY_CIRCUIT_BREAKER_THRESHOLD,
+    CONFIG_AGENCY_CIRCUIT_BREAKER_COOLDOWN_MS,
+    CONFIG_AGENCY_DOWNLOAD_PAGE_SIZE,
 ];
 
+/**
+Supplies HTTP headers computed per agency request, such as tokens which need refreshing.
+ */
+pub type HeaderProvider = dyn Fn() -> AgencyClientResult<HashMap<String, String>> + Send + Sync;
+
 lazy_static! {
     static ref AGENCY_SETTINGS: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+    static ref HEADER_PROVIDER: RwLock<Option<Arc<HeaderProvider>>> = RwLock::new(None);
 }
 
 
@@ -78,6 +108,8 @@ pub fn clear_config_agency() {
     trace!("clear_config_agency >>>");
     let mut config = AGENCY_SETTINGS.write().unwrap();
     config.clear();
+    set_header_provider(None);
+    circuit_breaker::reset();
 }
 
 pub fn validate_agency_config(config: &HashMap<String, String>) -> AgencyClientResult<u32> {
@@ -94,6 +126,13 @@ pub fn validate_agency_config(config: &HashMap<String, String>) -> AgencyClientR
     validate_optional_config_val(config.get(CONFIG_REMOTE_TO_SDK_VERKEY), AgencyClientErrorKind::InvalidVerkey, validation::validate_verkey)?;
 
     validate_optional_config_val(config.get(CONFIG_AGENCY_ENDPOINT), AgencyClientErrorKind::InvalidUrl, Url::parse)?;
+    validate_optional_config_val(config.get(CONFIG_AGENCY_HEADERS), AgencyClientErrorKind::InvalidConfiguration, validation::validate_headers)?;
+    validate_optional_config_val(config.get(CONFIG_AGENCY_CIRCUIT_BREAKER_THRESHOLD), AgencyClientErrorKind::InvalidConfiguration, validation::validate_circuit_breaker_threshold)?;
+    validate_optional_config_val(config.get(CONFIG_AGENCY_CIRCUIT_BREAKER_COOLDOWN_MS), AgencyClientErrorKind::InvalidConfiguration, str::parse::<u64>)?;
+    validate_optional_config_val(config.get(CONFIG_AGENCY_DOWNLOAD_PAGE_SIZE), AgencyClientErrorKind::InvalidConfiguration, validation::validate_download_page_size)?;
+    if let Some(protocol_version) = config.get(CONFIG_AGENCY_PROTOCOL_VERSION) {
+        validation::validate_agency_protocol_version(protocol_version)?;
+    }
 
     Ok(error_utils::SUCCESS.code_num)
 }
@@ -113,6 +152,7 @@ pub fn process_agency_config_string(config: &str, validate: bool) -> AgencyClien
                 match value {
                     Value::String(value_) => set_config_value(key, &value_),
                     Value::Bool(value_) => set_config_value(key, &json!(value_).to_string()),
+                    Value::Object(_) => set_config_value(key, &value.to_string()),
                     _ => return Err(AgencyClientError::from_msg(AgencyClientErrorKind::InvalidJson,
                                                               format!("Invalid agency config value for key {}", key))),
                 }
@@ -140,6 +180,54 @@ pub fn get_config_value(key: &str) -> AgencyClientResult<String> {
         .ok_or(AgencyClientError::from_msg(AgencyClientErrorKind::InvalidConfiguration, format!("Cannot read \"{}\" from AGENCY_SETTINGS", key)))
 }
 
+pub fn get_agency_protocol_version() -> String {
+    get_config_value(CONFIG_AGENCY_PROTOCOL_VERSION)
+        .unwrap_or(DEFAULT_AGENCY_PROTOCOL_VERSION.to_string())
+}
+
+/**
+Returns failure threshold and cooldown of the agency circuit breaker, `None` if the breaker is not enabled.
+ */
+pub fn get_circuit_breaker_config() -> Option<(u32, Duration)> {
+    let threshold = get_config_value(CONFIG_AGENCY_CIRCUIT_BREAKER_THRESHOLD).ok()
+        .and_then(|threshold| validation::validate_circuit_breaker_threshold(&threshold).ok())?;
+    let cooldown = get_config_value(CONFIG_AGENCY_CIRCUIT_BREAKER_COOLDOWN_MS).ok()
+        .and_then(|cooldown| cooldown.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_AGENCY_CIRCUIT_BREAKER_COOLDOWN_MS);
+    Some((threshold, Duration::from_millis(cooldown)))
+}
+
+/**
+Returns number of message payloads downloaded per get-messages request, `None` if all are downloaded at once.
+ */
+pub fn get_download_page_size() -> Option<u32> {
+    get_config_value(CONFIG_AGENCY_DOWNLOAD_PAGE_SIZE).ok()
+        .and_then(|page_size| validation::validate_download_page_size(&page_size).ok())
+}
+
+/**
+Registers provider of headers attached to agency requests on top of `agency_headers` setting, `None` unregisters it.
+Headers returned by the provider override configured headers of the same name.
+ */
+pub fn set_header_provider(provider: Option<Arc<HeaderProvider>>) {
+    *HEADER_PROVIDER.write().unwrap() = provider;
+}
+
+pub fn get_agency_headers() -> AgencyClientResult<HashMap<String, String>> {
+    let mut headers = match get_config_value(CONFIG_AGENCY_HEADERS) {
+        Ok(headers) => validation::validate_headers(&headers)?,
+        Err(_) => HashMap::new()
+    };
+    // provider is cloned out so it can't deadlock by touching the settings itself
+    let provider = HEADER_PROVIDER.read().unwrap().clone();
+    if let Some(provider) = provider {
+        let provided = provider()?;
+        validation::validate_header_map(&provided)?;
+        headers.extend(provided);
+    }
+    Ok(headers)
+}
+
 pub fn set_config_value(key: &str, value: &str) {
     trace!("set_config_value >>> key: {}, value: {}", key, value);
     if !VALID_AGENCY_CONFIG_KEYS.contains(&key) {
@@ -0,0 +1,94 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{AgencyClientError, AgencyClientErrorKind, AgencyClientResult};
+
+/**
+Flag shared between the caller of a long running operation and whoever wants to abort it, e.g. UI thread.
+Cloned tokens share the flag, once cancelled the token stays cancelled.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn check(&self) -> AgencyClientResult<()> {
+        if self.is_cancelled() {
+            return Err(AgencyClientError::from_msg(AgencyClientErrorKind::Cancelled, "Operation was cancelled"));
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static CURRENT_TOKEN: RefCell<Option<CancellationToken>> = RefCell::new(None);
+}
+
+/**
+Runs the closure with the token observed by every agency request made from the current thread. Requests of
+a cancelled token fail with `Cancelled`, request which is already in flight is abandoned and its response dropped.
+ */
+pub fn with_cancellation<T, F>(token: Option<&CancellationToken>, f: F) -> T
+    where F: FnOnce() -> T {
+    let _guard = CurrentTokenGuard(CURRENT_TOKEN.with(|current| current.replace(token.cloned())));
+    f()
+}
+
+/**
+Restores the token observed before `with_cancellation` was called, also if the closure panics.
+ */
+struct CurrentTokenGuard(Option<CancellationToken>);
+
+impl Drop for CurrentTokenGuard {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT_TOKEN.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+pub fn current_token() -> Option<CancellationToken> {
+    CURRENT_TOKEN.with(|current| current.borrow().clone())
+}
+
+pub fn check_current() -> AgencyClientResult<()> {
+    match current_token() {
+        Some(token) => token.check(),
+        None => Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_with_cancellation_deregisters_token() {
+        let token = CancellationToken::new();
+
+        with_cancellation(Some(&token), || {
+            assert!(current_token().is_some());
+        });
+        assert!(current_token().is_none());
+
+        let result = panic::catch_unwind(|| {
+            with_cancellation(Some(&token), || panic!("operation failed"))
+        });
+        assert!(result.is_err());
+        assert!(current_token().is_none());
+    }
+}
@@ -0,0 +1,216 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::agency_settings;
+use crate::error::{AgencyClientError, AgencyClientErrorKind, AgencyClientResult};
+
+/**
+State of the circuit breaker guarding agency requests.
+Closed lets requests through, Open fails them fast and HalfOpen lets a single probe request through
+once the cooldown elapsed, its outcome closes or reopens the breaker.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircuitBreakerStatus {
+    pub enabled: bool,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> CircuitBreaker {
+        CircuitBreaker { state: CircuitState::Closed, consecutive_failures: 0, opened_at: None }
+    }
+}
+
+impl CircuitBreaker {
+    fn before_request(&mut self, cooldown: Duration) -> AgencyClientResult<()> {
+        match self.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open if self.opened_at.map_or(true, |opened_at| opened_at.elapsed() >= cooldown) => {
+                debug!("CircuitBreaker::before_request >>> cooldown elapsed, letting probe request through");
+                self.state = CircuitState::HalfOpen;
+                Ok(())
+            }
+            CircuitState::Open | CircuitState::HalfOpen => {
+                Err(AgencyClientError::from_msg(AgencyClientErrorKind::AgencyUnavailable,
+                                                format!("Agency request rejected, {} consecutive requests failed", self.consecutive_failures)))
+            }
+        }
+    }
+
+    fn on_success(&mut self) {
+        if self.state != CircuitState::Closed {
+            info!("CircuitBreaker::on_success >>> agency is available again, closing circuit breaker");
+        }
+        *self = CircuitBreaker::default();
+    }
+
+    fn on_failure(&mut self, threshold: u32) {
+        self.consecutive_failures += 1;
+        if self.state == CircuitState::HalfOpen || self.consecutive_failures >= threshold {
+            warn!("CircuitBreaker::on_failure >>> {} consecutive agency requests failed, opening circuit breaker", self.consecutive_failures);
+            self.state = CircuitState::Open;
+            self.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+lazy_static! {
+    static ref CIRCUIT_BREAKER: Mutex<CircuitBreaker> = Mutex::new(CircuitBreaker::default());
+}
+
+/**
+Runs the agency request through the circuit breaker configured by `agency_circuit_breaker_threshold` and
+`agency_circuit_breaker_cooldown_ms` settings. While the breaker is open the request is not made and fails
+with `AgencyUnavailable`. Only transport errors and 5xx responses count as failures, 4xx responses show the agency
+is up and rejected the particular request. Cancelled requests don't count at all. Without threshold the request
+is just run.
+ */
+pub fn call<F>(request: F) -> AgencyClientResult<(u16, Vec<u8>)>
+    where F: FnOnce() -> AgencyClientResult<(u16, Vec<u8>)> {
+    let (threshold, cooldown) = match agency_settings::get_circuit_breaker_config() {
+        Some(config) => config,
+        None => return request()
+    };
+
+    // lock is not held during the request, concurrent requests in HalfOpen state fail fast until the probe finishes
+    CIRCUIT_BREAKER.lock().unwrap().before_request(cooldown)?;
+    let result = request();
+    let mut breaker = CIRCUIT_BREAKER.lock().unwrap();
+    match result {
+        Ok((status, _)) if status >= 500 => breaker.on_failure(threshold),
+        Ok(_) => breaker.on_success(),
+        Err(ref err) if err.kind() == AgencyClientErrorKind::Cancelled => {
+            // the probe was abandoned without outcome, next request probes again
+            if breaker.state == CircuitState::HalfOpen {
+                breaker.state = CircuitState::Open;
+            }
+        }
+        Err(_) => breaker.on_failure(threshold),
+    }
+    result
+}
+
+/**
+Current state of the agency circuit breaker, for monitoring.
+ */
+pub fn status() -> CircuitBreakerStatus {
+    let breaker = CIRCUIT_BREAKER.lock().unwrap();
+    CircuitBreakerStatus {
+        enabled: agency_settings::get_circuit_breaker_config().is_some(),
+        state: breaker.state,
+        consecutive_failures: breaker.consecutive_failures,
+    }
+}
+
+pub fn reset() {
+    *CIRCUIT_BREAKER.lock().unwrap() = CircuitBreaker::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::thread;
+
+    use crate::httpclient::{self, Transport};
+    use crate::mocking::MockTransport;
+    use crate::utils::comm::post_to_agency;
+    use crate::utils::test_utils::SetupMocks;
+
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_circuit_breaker_opens_and_closes() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+        agency_settings::process_agency_config_string(&json!({
+            "agency_circuit_breaker_threshold": "2",
+            "agency_circuit_breaker_cooldown_ms": "100"
+        }).to_string(), true).unwrap();
+
+        for _ in 0..2 {
+            transport.queue_response(Ok((503, vec![])));
+            assert_eq!(post_to_agency(&vec![1]).unwrap_err().kind(), AgencyClientErrorKind::PostMessageFailed);
+        }
+        assert_eq!(status().state, CircuitState::Open);
+
+        // the agency is not contacted while the breaker is open
+        assert_eq!(post_to_agency(&vec![1]).unwrap_err().kind(), AgencyClientErrorKind::AgencyUnavailable);
+        assert_eq!(transport.sent_messages().len(), 2);
+
+        // failed probe reopens the breaker
+        thread::sleep(Duration::from_millis(150));
+        transport.queue_response(Ok((503, vec![])));
+        assert_eq!(post_to_agency(&vec![1]).unwrap_err().kind(), AgencyClientErrorKind::PostMessageFailed);
+        assert_eq!(status().state, CircuitState::Open);
+        assert_eq!(post_to_agency(&vec![1]).unwrap_err().kind(), AgencyClientErrorKind::AgencyUnavailable);
+
+        // successful probe closes it
+        thread::sleep(Duration::from_millis(150));
+        transport.queue_response(Ok((200, vec![])));
+        post_to_agency(&vec![1]).unwrap();
+        assert_eq!(status(), CircuitBreakerStatus { enabled: true, state: CircuitState::Closed, consecutive_failures: 0 });
+        assert_eq!(transport.sent_messages().len(), 4);
+
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_circuit_breaker_ignores_client_errors() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+        agency_settings::process_agency_config_string(&json!({
+            "agency_circuit_breaker_threshold": "2"
+        }).to_string(), true).unwrap();
+
+        for _ in 0..3 {
+            transport.queue_response(Ok((400, vec![])));
+            assert_eq!(post_to_agency(&vec![1]).unwrap_err().kind(), AgencyClientErrorKind::PostMessageFailed);
+        }
+        assert_eq!(status(), CircuitBreakerStatus { enabled: true, state: CircuitState::Closed, consecutive_failures: 0 });
+
+        // transport errors count
+        transport.queue_response(Err(AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, "connection refused")));
+        assert_eq!(post_to_agency(&vec![1]).unwrap_err().kind(), AgencyClientErrorKind::PostMessageFailed);
+        transport.queue_response(Ok((502, vec![])));
+        assert_eq!(post_to_agency(&vec![1]).unwrap_err().kind(), AgencyClientErrorKind::PostMessageFailed);
+        assert_eq!(status().state, CircuitState::Open);
+
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_circuit_breaker_disabled_without_threshold() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        for _ in 0..5 {
+            transport.queue_response(Ok((503, vec![])));
+            assert_eq!(post_to_agency(&vec![1]).unwrap_err().kind(), AgencyClientErrorKind::PostMessageFailed);
+        }
+        assert_eq!(status().enabled, false);
+        assert_eq!(status().state, CircuitState::Closed);
+
+        httpclient::set_mock_transport(None);
+    }
+}
@@ -0,0 +1,63 @@
+use serde_json::Value;
+
+/**
+Outcome of handing a message over to the recipient's agency.
+
+Agencies usually answer with an empty body, the receipt is then derived from the HTTP status: 202 means the message
+was queued for later delivery, other successful statuses are treated as accepted. Agencies which report the outcome
+in the body as `{"status": "accepted" | "queued" | "rejected", "reason": ..}` take precedence. Agencies which don't
+report anything are considered to have accepted the message, so `Accepted` only guarantees the agency didn't refuse it.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum DeliveryReceipt {
+    Accepted,
+    Queued,
+    Rejected { reason: String },
+}
+
+impl DeliveryReceipt {
+    pub fn from_response(status: u16, body: &[u8]) -> DeliveryReceipt {
+        if !(200..300).contains(&status) {
+            let reason = String::from_utf8_lossy(body).to_string();
+            let reason = if reason.is_empty() { format!("Agency responded with HTTP status {}", status) } else { reason };
+            return DeliveryReceipt::Rejected { reason };
+        }
+
+        let reported = serde_json::from_slice::<Value>(body).ok()
+            .and_then(|body| body.get("status").and_then(Value::as_str).map(|status| (status.to_lowercase(), body.clone())));
+
+        match reported {
+            Some((ref reported_status, _)) if reported_status == "accepted" => DeliveryReceipt::Accepted,
+            Some((ref reported_status, _)) if reported_status == "queued" => DeliveryReceipt::Queued,
+            Some((ref reported_status, ref body)) if reported_status == "rejected" => DeliveryReceipt::Rejected {
+                reason: body.get("reason").and_then(Value::as_str).unwrap_or("Rejected by agency").to_string()
+            },
+            _ if status == 202 => DeliveryReceipt::Queued,
+            _ => DeliveryReceipt::Accepted
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_delivery_receipt_from_http_status() {
+        assert_eq!(DeliveryReceipt::Accepted, DeliveryReceipt::from_response(200, b""));
+        assert_eq!(DeliveryReceipt::Queued, DeliveryReceipt::from_response(202, b""));
+        assert_eq!(DeliveryReceipt::Rejected { reason: String::from("unknown recipient") }, DeliveryReceipt::from_response(404, b"unknown recipient"));
+        assert_eq!(DeliveryReceipt::Rejected { reason: String::from("Agency responded with HTTP status 500") }, DeliveryReceipt::from_response(500, b""));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_delivery_receipt_reported_in_body() {
+        assert_eq!(DeliveryReceipt::Queued, DeliveryReceipt::from_response(200, br#"{"status":"queued"}"#));
+        assert_eq!(DeliveryReceipt::Accepted, DeliveryReceipt::from_response(202, br#"{"status":"ACCEPTED"}"#));
+        assert_eq!(DeliveryReceipt::Rejected { reason: String::from("mailbox full") }, DeliveryReceipt::from_response(200, br#"{"status":"rejected","reason":"mailbox full"}"#));
+        assert_eq!(DeliveryReceipt::Accepted, DeliveryReceipt::from_response(200, br#"{"foo":"bar"}"#));
+    }
+}
@@ -48,6 +48,10 @@ pub enum AgencyClientErrorKind {
     CreatePoolConfig,
     #[fail(display = "Message failed in post")]
     PostMessageFailed,
+    #[fail(display = "Operation was cancelled")]
+    Cancelled,
+    #[fail(display = "Agency is unavailable")]
+    AgencyUnavailable,
 
     // Wallet
     #[fail(display = "Invalid Wallet or Search Handle")]
@@ -196,6 +200,8 @@ impl From<AgencyClientErrorKind> for u32 {
             AgencyClientErrorKind::DuplicationMasterSecret => error_utils::DUPLICATE_MASTER_SECRET.code_num,
             AgencyClientErrorKind::DuplicationDid => error_utils::DID_ALREADY_EXISTS_IN_WALLET.code_num,
             AgencyClientErrorKind::PostMessageFailed => error_utils::POST_MSG_FAILURE.code_num,
+            AgencyClientErrorKind::Cancelled => error_utils::CANCELLED.code_num,
+            AgencyClientErrorKind::AgencyUnavailable => error_utils::AGENCY_UNAVAILABLE.code_num,
             AgencyClientErrorKind::UnknownError => error_utils::UNKNOWN_ERROR.code_num,
             AgencyClientErrorKind::InvalidDid => error_utils::INVALID_DID.code_num,
             AgencyClientErrorKind::InvalidVerkey => error_utils::INVALID_VERKEY.code_num,
@@ -236,6 +242,8 @@ impl From<u32> for AgencyClientErrorKind {
             _ if { error_utils::DUPLICATE_MASTER_SECRET.code_num == code } => AgencyClientErrorKind::DuplicationMasterSecret,
             _ if { error_utils::DID_ALREADY_EXISTS_IN_WALLET.code_num == code } => AgencyClientErrorKind::DuplicationDid,
             _ if { error_utils::POST_MSG_FAILURE.code_num == code } => AgencyClientErrorKind::PostMessageFailed,
+            _ if { error_utils::CANCELLED.code_num == code } => AgencyClientErrorKind::Cancelled,
+            _ if { error_utils::AGENCY_UNAVAILABLE.code_num == code } => AgencyClientErrorKind::AgencyUnavailable,
             _ if { error_utils::UNKNOWN_ERROR.code_num == code } => AgencyClientErrorKind::UnknownError,
             _ if { error_utils::INVALID_DID.code_num == code } => AgencyClientErrorKind::InvalidDid,
             _ if { error_utils::INVALID_VERKEY.code_num == code } => AgencyClientErrorKind::InvalidVerkey,
@@ -245,6 +245,8 @@ pub struct Message {
     pub delivery_details: Vec<DeliveryDetails>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub decrypted_msg: Option<String>,
+    #[serde(rename = "createdAt", default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
 }
 
 #[macro_export]
@@ -291,19 +293,80 @@ impl Message {
     }
 }
 
-pub fn get_connection_messages(pw_did: &str, pw_vk: &str, agent_did: &str, agent_vk: &str, msg_uid: Option<Vec<String>>, status_codes: Option<Vec<MessageStatusCode>>) -> AgencyClientResult<Vec<Message>> {
-    trace!("get_connection_messages >>> pw_did: {}, pw_vk: {}, agent_vk: {}, msg_uid: {:?}",
-           pw_did, pw_vk, agent_vk, msg_uid);
+// cancellation is reported as is, so the caller can tell it from a failure
+fn _map_get_messages_error(err: AgencyClientError) -> AgencyClientError {
+    match err.kind() {
+        AgencyClientErrorKind::Cancelled => err,
+        _ => err.map(AgencyClientErrorKind::PostMessageFailed, "Cannot get messages")
+    }
+}
 
-    let response = get_messages()
+/**
+Downloads messages of the connection agent. If `page_size` is set, uids of the messages are listed first and
+their payloads are then downloaded by at most `page_size` messages per request.
+ */
+pub fn get_connection_messages(pw_did: &str, pw_vk: &str, agent_did: &str, agent_vk: &str, msg_uid: Option<Vec<String>>,
+                               status_codes: Option<Vec<MessageStatusCode>>, page_size: Option<u32>) -> AgencyClientResult<Vec<Message>> {
+    trace!("get_connection_messages >>> pw_did: {}, pw_vk: {}, agent_vk: {}, msg_uid: {:?}, page_size: {:?}",
+           pw_did, pw_vk, agent_vk, msg_uid, page_size);
+
+    let page_size = match page_size {
+        Some(page_size) => page_size as usize,
+        None => return _get_connection_messages(pw_did, pw_vk, agent_did, agent_vk, msg_uid, status_codes, false)
+    };
+
+    let uids: Vec<String> = _get_connection_messages(pw_did, pw_vk, agent_did, agent_vk, msg_uid, status_codes.clone(), true)?
+        .into_iter()
+        .map(|message| message.uid)
+        .collect();
+
+    let mut messages = Vec::new();
+    for page in uids.chunks(page_size) {
+        messages.extend(_get_connection_messages(pw_did, pw_vk, agent_did, agent_vk, Some(page.to_vec()), status_codes.clone(), false)?);
+    }
+
+    trace!("message returned: {:?}", messages);
+    Ok(messages)
+}
+
+fn _get_connection_messages(pw_did: &str, pw_vk: &str, agent_did: &str, agent_vk: &str, msg_uid: Option<Vec<String>>,
+                            status_codes: Option<Vec<MessageStatusCode>>, exclude_payload: bool) -> AgencyClientResult<Vec<Message>> {
+    let mut builder = get_messages();
+    builder
         .to(&pw_did)?
         .to_vk(&pw_vk)?
         .agent_did(&agent_did)?
         .agent_vk(&agent_vk)?
         .uid(msg_uid)?
+        .status_codes(status_codes)?;
+    if exclude_payload {
+        builder.include_edge_payload("Y")?;
+    }
+    let response = builder
+        .send_secure()
+        .map_err(_map_get_messages_error)?;
+
+    trace!("message returned: {:?}", response);
+    Ok(response)
+}
+
+/**
+Same as `get_connection_messages`, but asks the agency to leave out message payloads.
+Useful when only message metadata (uids, statuses) is needed.
+ */
+pub fn get_connection_messages_without_payload(pw_did: &str, pw_vk: &str, agent_did: &str, agent_vk: &str, status_codes: Option<Vec<MessageStatusCode>>) -> AgencyClientResult<Vec<Message>> {
+    trace!("get_connection_messages_without_payload >>> pw_did: {}, pw_vk: {}, agent_vk: {}, status_codes: {:?}",
+           pw_did, pw_vk, agent_vk, status_codes);
+
+    let response = get_messages()
+        .to(&pw_did)?
+        .to_vk(&pw_vk)?
+        .agent_did(&agent_did)?
+        .agent_vk(&agent_vk)?
+        .include_edge_payload("Y")?
         .status_codes(status_codes)?
         .send_secure()
-        .map_err(|err| err.map(AgencyClientErrorKind::PostMessageFailed, "Cannot get messages"))?;
+        .map_err(_map_get_messages_error)?;
 
     trace!("message returned: {:?}", response);
     Ok(response)
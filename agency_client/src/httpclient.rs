@@ -1,29 +1,166 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::env;
 use std::io::Read;
+use std::rc::Rc;
+use std::sync::{Arc, mpsc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 use reqwest;
-use reqwest::header::CONTENT_TYPE;
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
 
+use crate::cancellation::{self, CancellationToken};
 use crate::error::{AgencyClientErrorKind, AgencyClientError, AgencyClientResult};
 use crate::mocking::{AgencyMock, AgencyMockDecrypted, HttpClientMockResponse};
 use crate::mocking;
 
+/**
+Replacement of the HTTP layer, every posted message is handed over to it instead of being sent over network.
+ */
+pub trait Transport {
+    fn post(&self, body_content: &[u8], url: &str) -> AgencyClientResult<(u16, Vec<u8>)>;
+
+    fn post_with_headers(&self, body_content: &[u8], url: &str, _headers: &HashMap<String, String>) -> AgencyClientResult<(u16, Vec<u8>)> {
+        self.post(body_content, url)
+    }
+}
+
+const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+lazy_static! {
+    // workers of cancelled requests, each with the flag set once the worker finished
+    static ref CANCELLED_WORKERS: Mutex<Vec<(Arc<AtomicBool>, JoinHandle<()>)>> = Mutex::new(Vec::new());
+}
+
+thread_local! {
+    // transport is set per thread so tests running in parallel don't affect each other
+    static TRANSPORT: RefCell<Option<Rc<dyn Transport>>> = RefCell::new(None);
+}
+
+/**
+Routes messages posted from the current thread through the given transport, `None` restores the HTTP layer.
+ */
+pub fn set_mock_transport(transport: Option<Rc<dyn Transport>>) {
+    TRANSPORT.with(|current| *current.borrow_mut() = transport);
+}
+
 pub fn post_message(body_content: &Vec<u8>, url: &str) -> AgencyClientResult<Vec<u8>> {
+    post_message_with_headers(body_content, url, &HashMap::new())
+}
+
+/**
+Posts message with additional HTTP headers, such as authorization required by the agency.
+ */
+pub fn post_message_with_headers(body_content: &Vec<u8>, url: &str, headers: &HashMap<String, String>) -> AgencyClientResult<Vec<u8>> {
+    check_status(_post_message_with_status(body_content, url, headers)?)
+}
+
+/**
+Returns body of the response, fails with PostMessageFailed if its status is not successful.
+ */
+pub fn check_status((status, content): (u16, Vec<u8>)) -> AgencyClientResult<Vec<u8>> {
+    if !(200..300).contains(&status) {
+        let content = String::from_utf8_lossy(&content);
+        info!("Request failed: {}", content);
+        return Err(AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, format!("POST failed with: {}", content)));
+    }
+    Ok(content)
+}
+
+/**
+Posts message and returns HTTP status alongside the response body, also when the status is not successful.
+Fails only if the request could not be made or the response could not be read.
+ */
+pub fn post_message_with_status(body_content: &Vec<u8>, url: &str) -> AgencyClientResult<(u16, Vec<u8>)> {
+    _post_message_with_status(body_content, url, &HashMap::new())
+}
+
+pub fn post_message_with_status_and_headers(body_content: &Vec<u8>, url: &str, headers: &HashMap<String, String>) -> AgencyClientResult<(u16, Vec<u8>)> {
+    _post_message_with_status(body_content, url, headers)
+}
+
+fn _post_message_with_status(body_content: &Vec<u8>, url: &str, headers: &HashMap<String, String>) -> AgencyClientResult<(u16, Vec<u8>)> {
+    cancellation::check_current()?;
+
+    if let Some(transport) = TRANSPORT.with(|transport| transport.borrow().clone()) {
+        let response = transport.post_with_headers(body_content, url, headers)?;
+        cancellation::check_current()?;
+        return Ok(response);
+    }
+
     // todo: this function should be general, not knowing that agency exists -> move agency mocks to agency module
     if mocking::agency_mocks_enabled() {
-        if HttpClientMockResponse::has_response() {
-            warn!("HttpClient has mocked response");
-            return HttpClientMockResponse::get_response();
+        let response = mocked_response()?;
+        cancellation::check_current()?;
+        return Ok(response);
+    }
+
+    match cancellation::current_token() {
+        Some(token) => post_cancellable(body_content, url, headers, token),
+        None => post_over_http(body_content, url, headers, None)
+    }
+}
+
+/**
+Posts the message from a worker thread so the caller can return as soon as the token is cancelled. Worker of
+a cancelled request stops reading the response and is kept to be joined, see `join_cancelled_requests`.
+ */
+fn post_cancellable(body_content: &Vec<u8>, url: &str, headers: &HashMap<String, String>, token: CancellationToken) -> AgencyClientResult<(u16, Vec<u8>)> {
+    let (sender, receiver) = mpsc::channel();
+    let body_content = body_content.clone();
+    let url = url.to_string();
+    let headers = headers.clone();
+    let worker_token = token.clone();
+    let finished = Arc::new(AtomicBool::new(false));
+    let worker_finished = finished.clone();
+    let worker = thread::spawn(move || {
+        let _ = sender.send(post_over_http(&body_content, &url, &headers, Some(&worker_token)));
+        worker_finished.store(true, Ordering::SeqCst);
+    });
+    loop {
+        if let Err(err) = token.check() {
+            _keep_cancelled_worker(finished, worker);
+            return Err(err);
         }
-        if AgencyMockDecrypted::has_decrypted_mock_responses() {
-            warn!("Agency requests returns empty response, decrypted mock response is available");
-            return Ok(vec!());
+        match receiver.recv_timeout(CANCELLATION_POLL_INTERVAL) {
+            Ok(response) => {
+                let _ = worker.join();
+                return response;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                let _ = worker.join();
+                return Err(AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, "HTTP worker terminated without response"));
+            }
         }
-        let mocked_response = AgencyMock::get_response();
-        debug!("Agency returns mocked response of length {}", mocked_response.len());
-        return Ok(mocked_response);
     }
+}
+
+fn _keep_cancelled_worker(finished: Arc<AtomicBool>, worker: JoinHandle<()>) {
+    let mut workers = CANCELLED_WORKERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (done, pending): (Vec<_>, Vec<_>) = workers.drain(..).partition(|(finished, _)| finished.load(Ordering::SeqCst));
+    for (_, done) in done {
+        let _ = done.join();
+    }
+    *workers = pending;
+    workers.push((finished, worker));
+}
+
+/**
+Waits until workers of cancelled requests finish. Request which is already sent can't be interrupted,
+so this blocks up to the HTTP client timeout. Called on shutdown.
+ */
+pub fn join_cancelled_requests() {
+    let workers: Vec<_> = CANCELLED_WORKERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).drain(..).collect();
+    for (_, worker) in workers {
+        let _ = worker.join();
+    }
+}
 
+fn post_over_http(body_content: &Vec<u8>, url: &str, headers: &HashMap<String, String>, token: Option<&CancellationToken>) -> AgencyClientResult<(u16, Vec<u8>)> {
     //Setting SSL Certs location. This is needed on android platform. Or openssl will fail to verify the certs
     if cfg!(target_os = "android") {
         info!("::Android code");
@@ -39,6 +176,7 @@ pub fn post_message(body_content: &Vec<u8>, url: &str) -> AgencyClientResult<Vec
     let mut response =
         client.post(url)
             .body(body_content.to_owned())
+            .headers(_header_map(headers)?)
             .header(CONTENT_TYPE, "application/ssi-agent-wire")
             .send()
             .map_err(|err| {
@@ -46,20 +184,50 @@ pub fn post_message(body_content: &Vec<u8>, url: &str) -> AgencyClientResult<Vec
                 AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, format!("Could not connect {:?}", err))
             })?;
     trace!("Response Header: {:?}", response);
-    if !response.status().is_success() {
-        let mut content = String::new();
-        match response.read_to_string(&mut content) {
-            Ok(_) => info!("Request failed: {}", content),
-            Err(_) => info!("could not read response"),
-        };
-        return Err(AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, format!("POST failed with: {}", content)));
-    }
 
+    // response of cancelled request is not read any further
     let mut content = Vec::new();
-    response.read_to_end(&mut content)
-        .or(Err(AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, "could not read response")))?;
+    let mut chunk = [0u8; 8192];
+    loop {
+        if let Some(token) = token {
+            token.check()?;
+        }
+        let read = response.read(&mut chunk)
+            .or(Err(AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, "could not read response")))?;
+        if read == 0 { break; }
+        content.extend_from_slice(&chunk[..read]);
+    }
 
-    Ok(content)
+    Ok((response.status().as_u16(), content))
+}
+
+fn _header_map(headers: &HashMap<String, String>) -> AgencyClientResult<HeaderMap> {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers.iter() {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|err| AgencyClientError::from_msg(AgencyClientErrorKind::InvalidConfiguration, format!("Invalid header name {}: {}", name, err)))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|err| AgencyClientError::from_msg(AgencyClientErrorKind::InvalidConfiguration, format!("Invalid value of header {}: {}", name, err)))?;
+        header_map.insert(name, value);
+    }
+    Ok(header_map)
+}
+
+/**
+Response of the mocked agency, used when agency mocks are enabled.
+ */
+pub fn mocked_response() -> AgencyClientResult<(u16, Vec<u8>)> {
+    if HttpClientMockResponse::has_response() {
+        warn!("HttpClient has mocked response");
+        return HttpClientMockResponse::get_response();
+    }
+    if AgencyMockDecrypted::has_decrypted_mock_responses() {
+        warn!("Agency requests returns empty response, decrypted mock response is available");
+        return Ok((200, vec!()));
+    }
+    let mocked_response = AgencyMock::get_response();
+    debug!("Agency returns mocked response of length {}", mocked_response.len());
+    Ok((200, mocked_response))
 }
 
 fn set_ssl_cert_location() {
@@ -26,6 +26,9 @@ pub mod payload;
 pub mod agency_settings;
 pub mod mocking;
 pub mod httpclient;
+pub mod cancellation;
+pub mod circuit_breaker;
+pub mod delivery_receipt;
 pub mod agency_client;
 pub mod agent_utils;
 pub mod error;
@@ -363,7 +366,7 @@ impl<'de> Deserialize<'de> for RemoteMessageType {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum MessageStatusCode {
     Created,
     Sent,
@@ -517,7 +520,20 @@ impl A2AMessageKinds {
 }
 
 pub fn prepare_message_for_agency(message: &A2AMessage, agency_did: &str) -> AgencyClientResult<Vec<u8>> {
-    pack_for_agency_v2(message, agency_did)
+    pack_for_agency(message, agency_did)
+}
+
+// messages are packed in the format of the agency protocol version pinned by `agency_protocol_version` setting
+fn pack_for_agency(message: &A2AMessage, agency_did: &str) -> AgencyClientResult<Vec<u8>> {
+    pack_for_agency_version(&agency_settings::get_agency_protocol_version(), message, agency_did)
+}
+
+fn pack_for_agency_version(version: &str, message: &A2AMessage, agency_did: &str) -> AgencyClientResult<Vec<u8>> {
+    match version {
+        agency_settings::DEFAULT_AGENCY_PROTOCOL_VERSION => pack_for_agency_v2(message, agency_did),
+        version => Err(AgencyClientError::from_msg(AgencyClientErrorKind::InvalidConfiguration,
+                                                   format!("Cannot pack message for unsupported agency protocol version {}", version)))
+    }
 }
 
 fn pack_for_agency_v2(message: &A2AMessage, agency_did: &str) -> AgencyClientResult<Vec<u8>> {
@@ -661,7 +677,7 @@ fn prepare_message_for_agent(messages: Vec<A2AMessage>, pw_vk: &str, agent_did:
 
     let to_did = agency_settings::get_config_value(agency_settings::CONFIG_REMOTE_TO_SDK_DID)?;
 
-    pack_for_agency_v2(&message, &to_did)
+    pack_for_agency(&message, &to_did)
 }
 
 pub trait GeneralMessage {
@@ -740,8 +756,24 @@ pub fn get_messages() -> GetMessagesBuilder { GetMessagesBuilder::create() }
 
 #[cfg(test)]
 pub mod tests {
+    use crate::utils::test_utils::SetupMocks;
+
     use super::*;
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_message_for_agency_is_packed_in_pinned_protocol_version() {
+        let _setup = SetupMocks::init();
+        let message = ForwardV2::new(String::from("VsKV7grR1BUE29mG2Fm2kX"), b"{}".to_vec()).unwrap();
+
+        let packed = prepare_message_for_agency(&message, "VsKV7grR1BUE29mG2Fm2kX").unwrap();
+        // messages are not encrypted in test mode, the forward message of the 2.0 protocol is sent as is
+        let packed: Value = serde_json::from_slice(&packed).unwrap();
+        assert_eq!(packed["@type"], json!("did:sov:123456789abcdefghi1234;spec/routing/1.0/FWD"));
+
+        assert_eq!(pack_for_agency_version("1.0", &message, "VsKV7grR1BUE29mG2Fm2kX").unwrap_err().kind(), AgencyClientErrorKind::InvalidConfiguration);
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_to_u8() {
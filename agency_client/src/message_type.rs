@@ -101,9 +101,16 @@ pub fn parse_message_type(message_type: &str) -> AgencyClientResult<(String, Str
             (?P<family>.*)/
             (?P<version>.*)/
             (?P<type>.*)").unwrap();
+        // URI based message types, e.g. https://didcomm.org/present-proof/2.0/request-presentation
+        static ref URI_RE: Regex = Regex::new(r"(?x)
+            ^(?P<did>https?://[^/]+)/
+            (?P<family>[^/]+)/
+            (?P<version>[^/]+)/
+            (?P<type>[^/]+)$").unwrap();
     }
 
     RE.captures(message_type)
+        .or_else(|| URI_RE.captures(message_type))
         .and_then(|cap| {
             let did = cap.name("did").as_ref().map(Match::as_str);
             let family = cap.name("family").as_ref().map(Match::as_str);
@@ -1,7 +1,11 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
 use std::sync::Mutex;
 
 use crate::error::AgencyClientResult;
 use crate::agency_settings;
+use crate::httpclient::{self, Transport};
+use crate::MessageStatusCode;
 
 lazy_static! {
     static ref AGENCY_MOCK: Mutex<AgencyMock> = Mutex::new(AgencyMock::default());
@@ -15,11 +19,19 @@ lazy_static! {
 
 #[derive(Default)]
 pub struct HttpClientMockResponse {
-    responses: Vec<AgencyClientResult<Vec<u8>>>
+    responses: Vec<AgencyClientResult<(u16, Vec<u8>)>>
 }
 
 impl HttpClientMockResponse {
     pub fn set_next_response(response: AgencyClientResult<Vec<u8>>) {
+        HttpClientMockResponse::_set_next_response(response.map(|body| (200, body)))
+    }
+
+    pub fn set_next_response_with_status(status: u16, body: Vec<u8>) {
+        HttpClientMockResponse::_set_next_response(Ok((status, body)))
+    }
+
+    fn _set_next_response(response: AgencyClientResult<(u16, Vec<u8>)>) {
         if agency_mocks_enabled() {
             HTTPCLIENT_MOCK_RESPONSES.lock().unwrap().responses.push(response);
         }
@@ -29,11 +41,121 @@ impl HttpClientMockResponse {
         HTTPCLIENT_MOCK_RESPONSES.lock().unwrap().responses.len() > 0
     }
 
-    pub fn get_response() -> AgencyClientResult<Vec<u8>> {
+    pub fn get_response() -> AgencyClientResult<(u16, Vec<u8>)> {
         HTTPCLIENT_MOCK_RESPONSES.lock().unwrap().responses.pop().unwrap()
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentMessage {
+    pub url: String,
+    pub body: Vec<u8>,
+    pub headers: HashMap<String, String>,
+}
+
+/**
+Transport recording every posted message. Posts are answered by queued responses in order of queueing, once the queue
+is empty by the agency mocks if they are enabled, otherwise by an empty successful response.
+Install it with `httpclient::set_mock_transport`.
+ */
+#[derive(Default)]
+pub struct MockTransport {
+    sent: RefCell<Vec<SentMessage>>,
+    responses: RefCell<VecDeque<AgencyClientResult<(u16, Vec<u8>)>>>,
+    last_uid: Cell<usize>,
+}
+
+impl MockTransport {
+    pub fn queue_response(&self, response: AgencyClientResult<(u16, Vec<u8>)>) {
+        self.responses.borrow_mut().push_back(response);
+    }
+
+    /**
+    Queues response of the agency carrying the message. Crypto is mocked along with the agency, so the message is
+    delivered as plain JSON and parsed the same way as a decrypted response of a real agency.
+     */
+    pub fn queue_agency_response(&self, message: &str) {
+        let response = json!({ "message": message }).to_string();
+        self.queue_response(Ok((200, response.into_bytes())));
+    }
+
+    /**
+    Queues response of the agency to a download of messages, the messages are delivered as if they were sent by
+    `sender_vk`. Returns uids the messages are stored under.
+     */
+    pub fn queue_messages(&self, sender_vk: &str, messages: &[&str]) -> Vec<String> {
+        let msgs: Vec<(String, serde_json::Value)> = messages.iter()
+            .map(|message| {
+                self.last_uid.set(self.last_uid.get() + 1);
+                let uid = format!("mock-uid-{}", self.last_uid.get());
+                let msg = json!({
+                    "statusCode": "MS-103",
+                    "uid": uid,
+                    "senderDID": "",
+                    "type": "aries",
+                    "payload": { "message": message, "sender_verkey": sender_vk }
+                });
+                (uid, msg)
+            })
+            .collect();
+        self.queue_agency_response(&json!({
+            "@type": "did:sov:123456789abcdefghi1234;spec/pairwise/1.0/MSGS",
+            "msgs": msgs.iter().map(|(_, msg)| msg).collect::<Vec<_>>()
+        }).to_string());
+        msgs.into_iter().map(|(uid, _)| uid).collect()
+    }
+
+    pub fn sent_messages(&self) -> Vec<SentMessage> {
+        self.sent.borrow().clone()
+    }
+
+    pub fn sent_to(&self, url: &str) -> Vec<SentMessage> {
+        self.sent.borrow().iter().filter(|message| message.url == url).cloned().collect()
+    }
+}
+
+impl Transport for MockTransport {
+    fn post(&self, body_content: &[u8], url: &str) -> AgencyClientResult<(u16, Vec<u8>)> {
+        self.post_with_headers(body_content, url, &HashMap::new())
+    }
+
+    fn post_with_headers(&self, body_content: &[u8], url: &str, headers: &HashMap<String, String>) -> AgencyClientResult<(u16, Vec<u8>)> {
+        self.sent.borrow_mut().push(SentMessage { url: url.to_string(), body: body_content.to_vec(), headers: headers.clone() });
+        if let Some(response) = self.responses.borrow_mut().pop_front() {
+            return response;
+        }
+        if agency_mocks_enabled() {
+            return httpclient::mocked_response();
+        }
+        Ok((200, vec![]))
+    }
+}
+
+thread_local! {
+    // status updates are recorded per thread so tests running in parallel don't affect each other
+    static UPDATED_MESSAGES: RefCell<Vec<(MessageStatusCode, String)>> = RefCell::new(Vec::new());
+}
+
+/**
+Records message status updates which are not sent to the agency while agency mocks are enabled.
+ */
+pub struct AgencyMockUpdatedMessages;
+
+impl AgencyMockUpdatedMessages {
+    pub fn record(status_code: &MessageStatusCode, uids: &[String]) {
+        UPDATED_MESSAGES.with(|updated| {
+            updated.borrow_mut().extend(uids.iter().map(|uid| (status_code.clone(), uid.clone())))
+        });
+    }
+
+    /**
+    Returns status updates recorded on the current thread as pairs of status and message uid, and clears them.
+     */
+    pub fn take() -> Vec<(MessageStatusCode, String)> {
+        UPDATED_MESSAGES.with(|updated| updated.borrow_mut().drain(..).collect())
+    }
+}
+
 #[derive(Default)]
 pub struct AgencyMockDecryptedMessages {
     messages: Vec<String>
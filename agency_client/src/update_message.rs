@@ -116,6 +116,9 @@ pub fn update_messages(status_code: MessageStatusCode, uids_by_conns: Vec<UIDsBy
 
     if mocking::agency_mocks_enabled() {
         trace!("update_messages >>> agency mocks enabled, returning empty response");
+        for uids_by_conn in uids_by_conns.iter() {
+            mocking::AgencyMockUpdatedMessages::record(&status_code, &uids_by_conn.uids);
+        }
         return Ok(());
     };
 
@@ -1,7 +1,48 @@
-use crate::{httpclient, agency_settings};
+use crate::{httpclient, agency_settings, circuit_breaker};
 use crate::error::AgencyClientResult;
 
 pub fn post_to_agency(body_content: &Vec<u8>) -> AgencyClientResult<Vec<u8>> {
     let endpoint = agency_settings::get_config_value(agency_settings::CONFIG_AGENCY_ENDPOINT)?;
-    httpclient::post_message(body_content, &endpoint)
+    let headers = agency_settings::get_agency_headers()?;
+    let response = circuit_breaker::call(|| httpclient::post_message_with_status_and_headers(body_content, &endpoint, &headers))?;
+    httpclient::check_status(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use crate::httpclient::Transport;
+    use crate::mocking::MockTransport;
+    use crate::utils::test_utils::SetupMocks;
+
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_post_to_agency_attaches_configured_and_provided_headers() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        agency_settings::process_agency_config_string(&json!({
+            "agency_headers": {"Authorization": "Bearer static", "X-Gateway-Key": "key"}
+        }).to_string(), true).unwrap();
+        agency_settings::set_header_provider(Some(Arc::new(|| {
+            let mut headers = HashMap::new();
+            headers.insert(String::from("Authorization"), String::from("Bearer refreshed"));
+            Ok(headers)
+        })));
+
+        post_to_agency(&vec![1, 2, 3]).unwrap();
+        agency_settings::set_header_provider(None);
+        httpclient::set_mock_transport(None);
+
+        let sent = transport.sent_to("http://127.0.0.1:8080");
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].headers.get("Authorization").unwrap(), "Bearer refreshed");
+        assert_eq!(sent[0].headers.get("X-Gateway-Key").unwrap(), "key");
+    }
 }
@@ -36,6 +36,8 @@ pub static INVALID_STATE: Error = Error { code_num: 1081, message: "Object is in
 pub static INVALID_MSGPACK: Error = Error { code_num: 1019, message: "Invalid MessagePack" };
 pub static DUPLICATE_MASTER_SECRET: Error = Error { code_num: 1084, message: "Attempted to add a Master Secret that already existed in wallet" };
 pub static DID_ALREADY_EXISTS_IN_WALLET: Error = Error { code_num: 1083, message: "Attempted to add a DID to wallet when that DID already exists in wallet" };
+pub static CANCELLED: Error = Error { code_num: 1115, message: "Operation was cancelled" };
+pub static AGENCY_UNAVAILABLE: Error = Error { code_num: 1119, message: "Agency is unavailable" };
 pub static CREATE_AGENT: Error = Error { code_num: 2000, message: "Failed to create agency client" };
 
 lazy_static! {
@@ -48,6 +50,8 @@ lazy_static! {
         insert_c_message(&mut m, &INVALID_DID);
         insert_c_message(&mut m, &INVALID_VERKEY);
         insert_c_message(&mut m, &POST_MSG_FAILURE);
+        insert_c_message(&mut m, &CANCELLED);
+        insert_c_message(&mut m, &AGENCY_UNAVAILABLE);
         insert_c_message(&mut m, &INVALID_URL);
         insert_c_message(&mut m, &NOT_BASE58);
         insert_c_message(&mut m, &INVALID_JSON);
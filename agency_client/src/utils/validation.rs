@@ -1,9 +1,13 @@
 extern crate rust_base58;
 
+use std::collections::HashMap;
+
 use regex::Regex;
+use reqwest::header::{HeaderName, HeaderValue};
 use url::Url;
 use self::rust_base58::FromBase58;
 
+use crate::agency_settings;
 use crate::error::{AgencyClientErrorKind, AgencyClientError, AgencyClientResult};
 
 lazy_static! {
@@ -49,6 +53,48 @@ pub fn validate_url(url: &str) -> AgencyClientResult<String> {
     Ok(url.to_string())
 }
 
+pub fn validate_headers(headers: &str) -> AgencyClientResult<HashMap<String, String>> {
+    let headers: HashMap<String, String> = serde_json::from_str(headers)
+        .map_err(|err| AgencyClientError::from_msg(AgencyClientErrorKind::InvalidConfiguration, format!("Headers must be JSON object of strings: {}", err)))?;
+    validate_header_map(&headers)?;
+    Ok(headers)
+}
+
+pub fn validate_header_map(headers: &HashMap<String, String>) -> AgencyClientResult<()> {
+    for (name, value) in headers.iter() {
+        HeaderName::from_bytes(name.as_bytes())
+            .map_err(|err| AgencyClientError::from_msg(AgencyClientErrorKind::InvalidConfiguration, format!("Invalid header name {}: {}", name, err)))?;
+        HeaderValue::from_str(value)
+            .map_err(|err| AgencyClientError::from_msg(AgencyClientErrorKind::InvalidConfiguration, format!("Invalid value of header {}: {}", name, err)))?;
+    }
+    Ok(())
+}
+
+pub fn validate_agency_protocol_version(version: &str) -> AgencyClientResult<String> {
+    if agency_settings::SUPPORTED_AGENCY_PROTOCOL_VERSIONS.contains(&version) {
+        Ok(version.to_string())
+    } else {
+        Err(AgencyClientError::from_msg(AgencyClientErrorKind::InvalidConfiguration,
+                                        format!("Unsupported agency protocol version {}, supported versions: {:?}", version, agency_settings::SUPPORTED_AGENCY_PROTOCOL_VERSIONS)))
+    }
+}
+
+pub fn validate_circuit_breaker_threshold(threshold: &str) -> AgencyClientResult<u32> {
+    match threshold.parse::<u32>() {
+        Ok(threshold) if threshold > 0 => Ok(threshold),
+        _ => Err(AgencyClientError::from_msg(AgencyClientErrorKind::InvalidConfiguration,
+                                             format!("Circuit breaker threshold must be a positive number, got {}", threshold)))
+    }
+}
+
+pub fn validate_download_page_size(page_size: &str) -> AgencyClientResult<u32> {
+    match page_size.parse::<u32>() {
+        Ok(page_size) if page_size > 0 && page_size <= agency_settings::MAX_DOWNLOAD_PAGE_SIZE => Ok(page_size),
+        _ => Err(AgencyClientError::from_msg(AgencyClientErrorKind::InvalidConfiguration,
+                                             format!("Download page size must be a number between 1 and {}, got {}", agency_settings::MAX_DOWNLOAD_PAGE_SIZE, page_size)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     // use utils::devsetup::SetupDefaults;
@@ -115,4 +161,40 @@ mod tests {
             Ok(_) => panic!("Should be invalid verkey"),
         }
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_validate_headers() {
+        let headers = validate_headers(r#"{"Authorization": "Bearer token"}"#).unwrap();
+        assert_eq!(headers.get("Authorization").unwrap(), "Bearer token");
+
+        assert_eq!(validate_headers(r#"{"Invalid Header": "value"}"#).unwrap_err().kind(), AgencyClientErrorKind::InvalidConfiguration);
+        assert_eq!(validate_headers(r#"{"Authorization": 1}"#).unwrap_err().kind(), AgencyClientErrorKind::InvalidConfiguration);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_validate_agency_protocol_version() {
+        assert_eq!(validate_agency_protocol_version("2.0").unwrap(), "2.0");
+        assert_eq!(validate_agency_protocol_version("1.0").unwrap_err().kind(), AgencyClientErrorKind::InvalidConfiguration);
+        assert_eq!(validate_agency_protocol_version("2").unwrap_err().kind(), AgencyClientErrorKind::InvalidConfiguration);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_validate_circuit_breaker_threshold() {
+        assert_eq!(validate_circuit_breaker_threshold("3").unwrap(), 3);
+        assert_eq!(validate_circuit_breaker_threshold("0").unwrap_err().kind(), AgencyClientErrorKind::InvalidConfiguration);
+        assert_eq!(validate_circuit_breaker_threshold("-1").unwrap_err().kind(), AgencyClientErrorKind::InvalidConfiguration);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_validate_download_page_size() {
+        assert_eq!(validate_download_page_size("1").unwrap(), 1);
+        assert_eq!(validate_download_page_size("1000").unwrap(), 1000);
+        assert_eq!(validate_download_page_size("0").unwrap_err().kind(), AgencyClientErrorKind::InvalidConfiguration);
+        assert_eq!(validate_download_page_size("1001").unwrap_err().kind(), AgencyClientErrorKind::InvalidConfiguration);
+        assert_eq!(validate_download_page_size("-1").unwrap_err().kind(), AgencyClientErrorKind::InvalidConfiguration);
+    }
 }
@@ -718,7 +718,7 @@ pub extern fn vcx_connection_send_message(command_handle: CommandHandle,
            command_handle, connection_handle, msg);
 
     spawn(move || {
-        match send_generic_message(connection_handle, &msg) {
+        match send_generic_message(connection_handle, &msg, None) {
             Ok(x) => {
                 trace!("vcx_connection_send_message_cb(command_handle: {}, rc: {}, msg_id: {})",
                        command_handle, error::SUCCESS.message, x);
@@ -774,7 +774,7 @@ pub extern fn vcx_connection_send_ping(command_handle: u32,
 
     spawn(move || {
         match send_ping(connection_handle, comment) {
-            Ok(()) => {
+            Ok(_) => {
                 trace!("vcx_connection_send_ping(command_handle: {}, rc: {})",
                        command_handle, error::SUCCESS.message);
                 cb(command_handle, error::SUCCESS.code_num);
@@ -1013,7 +1013,7 @@ pub extern fn vcx_connection_send_discovery_features(command_handle: u32,
 
     spawn(move || {
         match send_discovery_features(connection_handle, query, comment) {
-            Ok(()) => {
+            Ok(_) => {
                 trace!("vcx_connection_send_discovery_features(command_handle: {}, rc: {})",
                        command_handle, error::SUCCESS.message);
                 cb(command_handle, error::SUCCESS.code_num);
@@ -1313,7 +1313,7 @@ mod tests {
     fn test_vcx_connection_update_state_with_message() {
         let _setup = SetupMocks::init();
 
-        let handle = build_test_connection_inviter_requested();
+        let handle = build_test_connection_inviter_invited();
         assert!(handle > 0);
 
         let cb = return_types_u32::Return_U32_U32::new().unwrap();
@@ -477,7 +477,7 @@ pub extern fn vcx_v2_messages_download(command_handle: CommandHandle,
            command_handle, message_statuses, uids);
 
     spawn(move || {
-        match connection::download_messages(conn_handles, message_statuses, uids) {
+        match connection::download_messages(conn_handles, message_statuses, uids, None, None) {
             Ok(x) => {
                 match serde_json::to_string(&x) {
                     Ok(x) => {
@@ -169,6 +169,9 @@ pub extern fn vcx_shutdown(delete: bool) -> u32 {
     info!("vcx_shutdown >>>");
     trace!("vcx_shutdown(delete: {})", delete);
 
+    // requests cancelled while in flight are finished before their wallet and pool go away
+    agency_client::httpclient::join_cancelled_requests();
+
     match wallet::close_main_wallet() {
         Ok(()) => {}
         Err(_) => {}
@@ -178,6 +181,8 @@ pub extern fn vcx_shutdown(delete: bool) -> u32 {
         Ok(()) => {}
         Err(_) => {}
     };
+    // pools registered for did:indy namespaces are forgotten even if no default pool was open
+    pool::reset_pool_handle();
 
     crate::schema::release_all();
     crate::connection::release_all();
@@ -186,6 +191,7 @@ pub extern fn vcx_shutdown(delete: bool) -> u32 {
     crate::proof::release_all();
     crate::disclosed_proof::release_all();
     crate::credential::release_all();
+    crate::aries::utils::decorators::clear_global_decorators();
 
     if delete {
         let pool_name = settings::get_config_value(settings::CONFIG_POOL_NAME)
@@ -417,6 +423,9 @@ mod tests {
 
     use crate::{api, connection, credential, credential_def, disclosed_proof, issuer_credential, proof, schema};
     use crate::api::return_types_u32;
+    use crate::aries::messages::a2a::A2AMessage;
+    use crate::aries::messages::ack::tests::_ack;
+    use crate::aries::utils::decorators;
     use crate::api::wallet::tests::_test_add_and_get_wallet_record;
     use crate::libindy::utils::pool::get_pool_handle;
     use crate::libindy::utils::pool::tests::create_tmp_genesis_txn_file;
@@ -702,6 +711,8 @@ mod tests {
         let schema = schema::create_and_publish_schema("5", "VsKV7grR1BUE29mG2Fm2kX".to_string(), "name".to_string(), "0.1".to_string(), data.to_string()).unwrap();
         let disclosed_proof = disclosed_proof::create_proof("id", utils::mockdata::mockdata_proof::ARIES_PROOF_REQUEST_PRESENTATION).unwrap();
         let credential = credential::credential_create_with_offer("name", utils::mockdata::mockdata_credex::ARIES_CREDENTIAL_OFFER).unwrap();
+        pool::set_network_pool_handle("sovrin", Some(11));
+        decorators::register_global_decorator("~com.ourcorp/trace", json!({"trace_id": "abc"})).unwrap();
 
         vcx_shutdown(true);
         assert_eq!(connection::release(connection).unwrap_err().kind(), VcxErrorKind::InvalidConnectionHandle);
@@ -712,6 +723,9 @@ mod tests {
         assert_eq!(credential::release(credential).unwrap_err().kind(), VcxErrorKind::InvalidCredentialHandle);
         assert_eq!(disclosed_proof::release(disclosed_proof).unwrap_err().kind(), VcxErrorKind::InvalidDisclosedProofHandle);
         assert_eq!(wallet::get_wallet_handle(), INVALID_WALLET_HANDLE);
+        assert_eq!(pool::get_pool_handle_for("did:indy:sovrin:Th7MpTaRZVRYnPiabds81Y").unwrap_err().kind(), VcxErrorKind::NoPoolOpen);
+        let ack = A2AMessage::Ack(_ack());
+        assert_eq!(decorators::decorate(&ack, &Default::default()).unwrap(), ack);
     }
 
     #[test]
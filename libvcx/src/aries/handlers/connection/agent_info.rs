@@ -1,24 +1,85 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use crate::agency_client::get_message::{get_connection_messages, Message};
+use crate::agency_client::get_message::{get_connection_messages, get_connection_messages_without_payload, Message};
 use crate::agency_client::{MessageStatusCode, agency_settings};
 use crate::agency_client::update_connection::send_delete_connection_message;
 use crate::agency_client::update_message::{UIDsByConn, update_messages as update_messages_status};
 use crate::aries::messages::a2a::A2AMessage;
 use crate::aries::messages::connection::did_doc::DidDoc;
-use crate::aries::utils::encryption_envelope::EncryptionEnvelope;
+use crate::aries::messages::connection::response::ConnectionSignature;
+use crate::aries::utils::decorators;
+use crate::aries::utils::encryption_envelope::{EncryptionEnvelope, EnvelopeVersion};
 use crate::connection::create_agent_keys;
 use crate::error::prelude::*;
-use crate::libindy::utils::signus::create_and_store_my_did;
+use crate::libindy::utils::signus::{create_and_store_my_did, key_for_local_did};
 use crate::settings;
+use crate::agency_client::delivery_receipt::DeliveryReceipt;
 use crate::agency_client::httpclient;
 
+/**
+Origin of the connection, connections serialized before it was recorded are `Legacy`.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvitationType {
+    Legacy,
+    ConnectionInvitation,
+    OutOfBand,
+    /// Listen-only connection publishing its service, created without invitation to any peer
+    Receiver,
+    /// Connection restored from a bundle exported by another device or application
+    ContactImport,
+}
+
+impl Default for InvitationType {
+    fn default() -> InvitationType {
+        InvitationType::Legacy
+    }
+}
+
+impl InvitationType {
+    pub fn is_legacy(&self) -> bool {
+        *self == InvitationType::Legacy
+    }
+
+    pub fn is_receiver(&self) -> bool {
+        *self == InvitationType::Receiver
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentInfo {
     pub pw_did: String,
     pub pw_vk: String,
     pub agent_did: String,
     pub agent_vk: String,
+    /// Time (milliseconds since epoch) of the last state machine step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_activity: Option<u64>,
+    /// Error of the last state machine step, cleared once a step succeeds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Application defined tags used to organize connections
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    /// Envelope version negotiated with the counterparty
+    #[serde(default, skip_serializing_if = "EnvelopeVersion::is_v1")]
+    pub envelope_version: EnvelopeVersion,
+    /// Type of the invitation this connection was established from
+    #[serde(default, skip_serializing_if = "InvitationType::is_legacy")]
+    pub invitation_type: InvitationType,
+    /// Application defined decorators merged into every message sent over this connection
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub decorators: BTreeMap<String, serde_json::Value>,
+    /// Endpoint messages are delivered to directly, set for agentless connections which have no agent in the agency
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Signature structure (`connection~sig`) of the connection response sent as inviter or received as invitee
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_response: Option<ConnectionSignature>,
+    /// DidDoc customized by the application, inviter publishes it in the invitation and in the connection response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_did_doc: Option<DidDoc>,
 }
 
 impl Default for AgentInfo {
@@ -28,6 +89,15 @@ impl Default for AgentInfo {
             pw_vk: String::new(),
             agent_did: String::new(),
             agent_vk: String::new(),
+            last_activity: None,
+            last_error: None,
+            tags: BTreeSet::new(),
+            envelope_version: EnvelopeVersion::V1,
+            invitation_type: InvitationType::Legacy,
+            decorators: BTreeMap::new(),
+            endpoint: None,
+            signed_response: None,
+            local_did_doc: None,
         }
     }
 }
@@ -43,8 +113,38 @@ fn _log_messages_optionally(_a2a_messages: &HashMap<String, A2AMessage>) {
 }
 
 impl AgentInfo {
+    pub fn is_agentless(&self) -> bool {
+        self.endpoint.is_some()
+    }
+
     /**
-    Create connection agent in one's agency
+    Checks the pairwise DID and verkey are stored in the current wallet, so messages can be signed and decrypted.
+    Agent keys belong to the agency and can't be checked locally. Keys not created yet are considered valid.
+     */
+    pub fn validate_keys(&self) -> VcxResult<bool> {
+        trace!("AgentInfo::validate_keys >>> pw_did: {}, pw_vk: {}", self.pw_did, self.pw_vk);
+        if self.pw_did.is_empty() && self.pw_vk.is_empty() {
+            return Ok(true);
+        }
+        if settings::indy_mocks_enabled() {
+            return Ok(true);
+        }
+        match key_for_local_did(&self.pw_did) {
+            Ok(verkey) if verkey == self.pw_vk => Ok(true),
+            Ok(verkey) => {
+                warn!("AgentInfo::validate_keys >>> wallet holds verkey {} for pairwise DID {}, connection expects {}", verkey, self.pw_did, self.pw_vk);
+                Ok(false)
+            }
+            Err(err) if err.kind() == VcxErrorKind::WalletRecordNotFound => {
+                warn!("AgentInfo::validate_keys >>> pairwise DID {} is not stored in the wallet", self.pw_did);
+                Ok(false)
+            }
+            Err(err) => Err(err)
+        }
+    }
+
+    /**
+    Create connection agent in one's agency, agentless connections only get new pairwise keys
      */
     // TODO: There should be a way to set a specific agent_client for AgentInfo
     pub fn create_agent(&self) -> VcxResult<AgentInfo> {
@@ -53,13 +153,17 @@ impl AgentInfo {
         let method_name = settings::get_config_value(settings::CONFIG_DID_METHOD).ok();
         let (pw_did, pw_vk) = create_and_store_my_did(None, method_name.as_ref().map(String::as_str))?;
 
+        if self.is_agentless() {
+            return Ok(AgentInfo { pw_did, pw_vk, agent_did: String::new(), agent_vk: String::new(), ..self.clone() });
+        }
+
         /*
             Create User Pairwise Agent in old way.
             Send Messages corresponding to V2 Protocol to avoid code changes on Agency side.
         */
         let (agent_did, agent_vk) = create_agent_keys("", &pw_did, &pw_vk)?;
 
-        Ok(AgentInfo { pw_did, pw_vk, agent_did, agent_vk })
+        Ok(AgentInfo { pw_did, pw_vk, agent_did, agent_vk, ..self.clone() })
     }
 
     /**
@@ -70,7 +174,20 @@ impl AgentInfo {
             .map_err(|err| err.into())
     }
 
+    /**
+    Endpoint the counterparty sends messages to, one's agency unless the connection is agentless
+     */
+    pub fn service_endpoint(&self) -> VcxResult<String> {
+        match self.endpoint {
+            Some(ref endpoint) => Ok(endpoint.to_string()),
+            None => self.agency_endpoint()
+        }
+    }
+
     pub fn routing_keys(&self) -> VcxResult<Vec<String>> {
+        if self.is_agentless() {
+            return Ok(vec![]);
+        }
         let agency_vk = &settings::get_agency_client()?.get_agency_vk()?;
         Ok(vec![self.agent_vk.to_string(), agency_vk.to_string()])
     }
@@ -79,24 +196,75 @@ impl AgentInfo {
         vec![self.pw_vk.to_string()]
     }
 
+    /**
+    DidDoc generated from the pairwise keys, the endpoint and the routing keys of this agent.
+     */
+    pub fn did_doc(&self) -> VcxResult<DidDoc> {
+        let mut did_doc = DidDoc::default();
+        did_doc.set_id(self.pw_did.clone());
+        did_doc.set_service_endpoint(self.service_endpoint()?);
+        did_doc.set_keys(self.recipient_keys(), self.routing_keys()?);
+        Ok(did_doc)
+    }
+
+    /**
+    DidDoc published to the counterparty, the one customized by the application if set, otherwise the generated one.
+     */
+    pub fn local_did_doc(&self) -> VcxResult<DidDoc> {
+        match self.local_did_doc {
+            Some(ref did_doc) => Ok(did_doc.clone()),
+            None => self.did_doc()
+        }
+    }
+
     pub fn update_message_status(&self, uid: String) -> VcxResult<()> {
         trace!("Agent::update_message_status >>> uid: {:?}", uid);
+        if self.is_agentless() {
+            return Ok(());
+        }
 
         let messages_to_update = vec![UIDsByConn {
             pairwise_did: self.pw_did.clone(),
             uids: vec![uid],
         }];
 
-        update_messages_status(MessageStatusCode::Reviewed, messages_to_update)
+        update_messages_status(settings::get_processed_message_status(), messages_to_update)
             .map_err(|err| err.into())
     }
 
+    /**
+    Downloads messages of the pairwise agent, by `agency_download_page_size` messages per request if the setting is set.
+     */
     pub fn download_encrypted_messages(&self, msg_uid: Option<Vec<String>>, status_codes: Option<Vec<MessageStatusCode>>) -> VcxResult<Vec<Message>> {
         trace!("download_encrypted_messages >>>");
-        get_connection_messages(&self.pw_did, &self.pw_vk, &self.agent_did, &self.agent_vk, msg_uid, status_codes)
+        // agentless connections receive messages only through `update_state_with_message`
+        if self.is_agentless() {
+            return Ok(vec![]);
+        }
+        get_connection_messages(&self.pw_did, &self.pw_vk, &self.agent_did, &self.agent_vk, msg_uid, status_codes,
+                                agency_settings::get_download_page_size())
             .map_err(|err| err.into())
     }
 
+    /**
+    Counts messages stored on the agent by status, payloads are not downloaded.
+    Received, Reviewed and Rejected statuses are always present in the result.
+     */
+    pub fn get_message_counts(&self) -> VcxResult<HashMap<MessageStatusCode, u32>> {
+        trace!("Agent::get_message_counts >>>");
+        let status_codes = vec![MessageStatusCode::Received, MessageStatusCode::Reviewed, MessageStatusCode::Rejected];
+        let mut counts: HashMap<MessageStatusCode, u32> = status_codes.iter().map(|code| (code.clone(), 0)).collect();
+        if self.is_agentless() {
+            return Ok(counts);
+        }
+
+        let messages = get_connection_messages_without_payload(&self.pw_did, &self.pw_vk, &self.agent_did, &self.agent_vk, Some(status_codes))?;
+        for message in messages {
+            *counts.entry(message.status_code).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
     pub fn get_messages(&self, expect_sender_vk: &str) -> VcxResult<HashMap<String, A2AMessage>> {
         trace!("Agent::get_messages >>> expect_sender_vk={}", expect_sender_vk);
         let messages = self.download_encrypted_messages(None, Some(vec![MessageStatusCode::Received]))?;
@@ -107,8 +275,12 @@ impl AgentInfo {
     }
 
     pub fn get_messages_noauth(&self) -> VcxResult<HashMap<String, A2AMessage>> {
-        trace!("Agent::get_messages_noauth >>>");
-        let messages = self.download_encrypted_messages(None, Some(vec![MessageStatusCode::Received]))?;
+        self.get_messages_noauth_with_statuses(vec![MessageStatusCode::Received])
+    }
+
+    pub fn get_messages_noauth_with_statuses(&self, statuses: Vec<MessageStatusCode>) -> VcxResult<HashMap<String, A2AMessage>> {
+        trace!("Agent::get_messages_noauth_with_statuses >>> statuses: {:?}", statuses);
+        let messages = self.download_encrypted_messages(None, Some(statuses))?;
         debug!("Agent::get_messages_noauth >>> obtained {} messages", messages.len());
         let a2a_messages = self.decrypt_decode_messages_noauth(&messages)?;
         _log_messages_optionally(&a2a_messages);
@@ -141,7 +313,7 @@ impl AgentInfo {
         return Ok(a2a_messages);
     }
 
-    fn decrypt_decode_message(&self, message: &Message, expected_sender_vk: &str) -> VcxResult<A2AMessage> {
+    pub fn decrypt_decode_message(&self, message: &Message, expected_sender_vk: &str) -> VcxResult<A2AMessage> {
         EncryptionEnvelope::auth_unpack(message.payload()?, &expected_sender_vk)
     }
 
@@ -154,16 +326,42 @@ impl AgentInfo {
      */
     pub fn send_message(&self, message: &A2AMessage, did_dod: &DidDoc) -> VcxResult<()> {
         trace!("Agent::send_message >>> message: {:?}, did_doc: {:?}", message, did_dod);
-        let envelope = EncryptionEnvelope::create(&message, Some(&self.pw_vk), &did_dod)?;
+        let envelope = self.pack(&message, &did_dod, None)?;
         httpclient::post_message(&envelope.0, &did_dod.get_endpoint())?;
         Ok(())
     }
 
+    /**
+    Sends message to connection counterparty, returns receipt of the delivery to its agency
+     */
+    pub fn send_message_with_receipt(&self, message: &A2AMessage, did_doc: &DidDoc) -> VcxResult<DeliveryReceipt> {
+        trace!("Agent::send_message_with_receipt >>> message: {:?}, did_doc: {:?}", message, did_doc);
+        let envelope = self.pack(&message, &did_doc, None)?;
+        let (status, body) = httpclient::post_message_with_status(&envelope.0, &did_doc.get_endpoint())?;
+        Ok(DeliveryReceipt::from_response(status, &body))
+    }
+
+    /**
+    Sends authenticated message to connection counterparty, packed only for the given recipient key of its DidDoc
+     */
+    pub fn send_message_to_recipient_key(&self, message: &A2AMessage, did_doc: &DidDoc, recipient_key: &str) -> VcxResult<()> {
+        trace!("Agent::send_message_to_recipient_key >>> message: {:?}, did_doc: {:?}, recipient_key: {}", message, did_doc, recipient_key);
+        let envelope = self.pack(&message, &did_doc, Some(recipient_key))?;
+        httpclient::post_message(&envelope.0, &did_doc.get_endpoint())?;
+        Ok(())
+    }
+
+    fn pack(&self, message: &A2AMessage, did_doc: &DidDoc, recipient_key: Option<&str>) -> VcxResult<EncryptionEnvelope> {
+        let message = decorators::decorate(message, &self.decorators)?;
+        EncryptionEnvelope::create_for_recipient_key(&message, Some(&self.pw_vk), did_doc, recipient_key)
+    }
+
     /**
     Sends anonymous message to connection counterparty
      */
     pub fn send_message_anonymously(message: &A2AMessage, did_dod: &DidDoc) -> VcxResult<()> {
         trace!("Agent::send_message_anonymously >>> message: {:?}, did_doc: {:?}", message, did_dod);
+        let message = decorators::decorate(message, &BTreeMap::new())?;
         let envelope = EncryptionEnvelope::create(&message, None, &did_dod)?;
         httpclient::post_message(&envelope.0, &did_dod.get_endpoint())?;
         Ok(())
@@ -174,6 +372,9 @@ impl AgentInfo {
      */
     pub fn delete(&self) -> VcxResult<()> {
         trace!("Agent::delete >>>");
+        if self.is_agentless() {
+            return Ok(());
+        }
         send_delete_connection_message(&self.pw_did, &self.pw_vk, &self.agent_did, &self.agent_vk)
             .map_err(|err| err.into())
     }
@@ -1,19 +1,58 @@
 use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use agency_client::delivery_receipt::DeliveryReceipt;
+use agency_client::MessageStatusCode;
+
+use crate::api::VcxStateType;
 use crate::error::prelude::*;
-use crate::aries::handlers::connection::agent_info::AgentInfo;
+use crate::aries::handlers::connection::agent_info::{AgentInfo, InvitationType};
 use crate::aries::handlers::connection::invitee::state_machine::{InviteeState, SmConnectionInvitee};
 use crate::aries::handlers::connection::inviter::state_machine::{InviterState, SmConnectionInviter};
 use crate::aries::handlers::connection::messages::DidExchangeMessages;
 use crate::aries::messages::a2a::A2AMessage;
+use crate::aries::messages::a2a::message_family::MessageFamilies;
 use crate::aries::messages::basic_message::message::BasicMessage;
 use crate::aries::messages::connection::did_doc::DidDoc;
 use crate::aries::messages::connection::invite::Invitation;
+use crate::aries::messages::connection::problem_report::{ProblemCode, ProblemReport};
+use crate::aries::messages::connection::response::ConnectionSignature;
 use crate::aries::messages::discovery::disclose::ProtocolDescriptor;
+use crate::aries::messages::discovery::query::{ProtocolQuery, Query};
+use crate::aries::messages::out_of_band::invitation::OutOfBandInvitation;
+use crate::aries::utils::decorators;
+use crate::aries::utils::encryption_envelope::EnvelopeVersion;
+use crate::aries::messages::proof_presentation::v2;
+use crate::aries::messages::trust_ping::ping::Ping;
+use crate::settings::Actors;
+use crate::utils::clock;
+
+const REACHABILITY_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Connection {
-    connection_sm: SmConnection
+    connection_sm: SmConnection,
+    /// Time (milliseconds since epoch) when connecting was started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    connect_time: Option<u64>,
+    /// Time (milliseconds since epoch) when connection was completed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    established_time: Option<u64>,
+}
+
+/**
+Data of serialized connection, agent info of its state machine along with the timestamps kept by the connection.
+Timestamps are flattened into the agent info so connections serialized before they were kept apart are read as is.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionData {
+    #[serde(flatten)]
+    pub agent_info: AgentInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_time: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub established_time: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,21 +67,24 @@ pub enum SmConnectionState {
     Invitee(InviteeState),
 }
 
-#[derive(Debug, Serialize)]
-struct ConnectionInfo {
-    my: SideConnectionInfo,
-    their: Option<SideConnectionInfo>,
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionInfo {
+    pub my: SideConnectionInfo,
+    pub their: Option<SideConnectionInfo>,
+    pub protocol_version: String,
+    pub state: u32,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
-struct SideConnectionInfo {
-    did: String,
-    recipient_keys: Vec<String>,
-    routing_keys: Vec<String>,
-    service_endpoint: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    protocols: Option<Vec<ProtocolDescriptor>>,
+pub struct SideConnectionInfo {
+    pub did: String,
+    pub recipient_keys: Vec<String>,
+    pub routing_keys: Vec<String>,
+    pub service_endpoint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocols: Option<Vec<ProtocolDescriptor>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -51,6 +93,27 @@ pub enum Actor {
     Invitee,
 }
 
+/**
+Redacted connection report meant to be attached to support tickets. Contains no key material
+and only the host of the counterparty's endpoint, unless the full DidDoc is requested.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionDiagnostics {
+    pub source_id: String,
+    pub state: u32,
+    pub role: Actor,
+    pub protocol_version: String,
+    pub envelope_version: EnvelopeVersion,
+    pub endpoint_host: Option<String>,
+    pub last_activity: Option<u64>,
+    pub last_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub did_doc: Option<DidDoc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_response: Option<ConnectionSignature>,
+}
+
 impl Connection {
     /**
     Create Inviter connection state machine
@@ -58,19 +121,182 @@ impl Connection {
     pub fn create(source_id: &str) -> Connection {
         trace!("Connection::create >>> source_id: {}", source_id);
 
-        Connection {
-            connection_sm: SmConnection::Inviter(SmConnectionInviter::new(source_id))
+        let mut connection = Connection {
+            connection_sm: SmConnection::Inviter(SmConnectionInviter::new(source_id)),
+            connect_time: None,
+            established_time: None,
+        };
+        connection.agent_info_mut().invitation_type = InvitationType::ConnectionInvitation;
+        connection
+    }
+
+    /**
+    Create Inviter connection state machine which receives messages directly at `endpoint` instead of one's agency,
+    messages have to be passed to it by `update_state_with_message`
+     */
+    pub fn create_agentless(source_id: &str, endpoint: &str) -> VcxResult<Connection> {
+        trace!("Connection::create_agentless >>> source_id: {}, endpoint: {}", source_id, endpoint);
+
+        let mut connection = Connection::create(source_id);
+        connection.set_agentless_endpoint(endpoint)?;
+        Ok(connection)
+    }
+
+    /**
+    Create Invitee connection state machine which receives messages directly at `endpoint` instead of one's agency
+     */
+    pub fn create_agentless_with_invite(source_id: &str, endpoint: &str, invitation: Invitation) -> VcxResult<Connection> {
+        trace!("Connection::create_agentless_with_invite >>> source_id: {}, endpoint: {}", source_id, endpoint);
+
+        let mut connection = Connection::create_with_invite(source_id, invitation)?;
+        connection.set_agentless_endpoint(endpoint)?;
+        Ok(connection)
+    }
+
+    /**
+    Create listen-only connection which only receives messages and never initiates protocols. The connection agent
+    is created right away and its service, see `get_service_did_doc`, can be published so peers can target it.
+    Inbound messages are processed by `update_state` and message handlers as on any other connection.
+     */
+    pub fn create_receiver(source_id: &str) -> VcxResult<Connection> {
+        trace!("Connection::create_receiver >>> source_id: {}", source_id);
+
+        let mut connection = Connection::create(source_id);
+        connection.agent_info_mut().invitation_type = InvitationType::Receiver;
+        let agent_info = connection.agent_info().create_agent()?;
+        *connection.agent_info_mut() = agent_info;
+        Ok(connection)
+    }
+
+    pub fn is_receiver(&self) -> bool {
+        self.agent_info().invitation_type.is_receiver()
+    }
+
+    /**
+    DidDoc of the service of listen-only connection, peers encrypt messages for its recipient key and route them
+    through its routing keys to the endpoint.
+     */
+    pub fn get_service_did_doc(&self) -> VcxResult<DidDoc> {
+        if !self.is_receiver() {
+            return Err(VcxError::from_msg(VcxErrorKind::ActionNotSupported, "Service DidDoc is published only by listen-only connections"));
+        }
+        self.agent_info().did_doc()
+    }
+
+    /**
+    DidDoc Inviter publishes to the invitee, available only before `connect` is called. The connection agent is
+    created on the first call, so the DidDoc already contains the keys the invitation is generated with.
+     */
+    pub fn get_local_did_doc(&mut self) -> VcxResult<DidDoc> {
+        trace!("Connection::get_local_did_doc >>> source_id: {}", self.source_id());
+        self.ensure_local_agent()?;
+        self.agent_info().local_did_doc()
+    }
+
+    /**
+    Replaces DidDoc Inviter publishes to the invitee, so the application can add services or keys before `connect`
+    is called. DidDoc with additional services is published in out-of-band invitation, see `get_invite_details`,
+    and in the DidDoc of the connection response. The DidDoc must be valid and its primary service must keep
+    the recipient key of the connection, otherwise the counterparty's messages couldn't be decrypted.
+     */
+    pub fn set_local_did_doc(&mut self, did_doc: DidDoc) -> VcxResult<()> {
+        trace!("Connection::set_local_did_doc >>> source_id: {}, did_doc: {:?}", self.source_id(), did_doc);
+        self.ensure_local_agent()?;
+        did_doc.validate()?;
+        let pw_vk = self.agent_info().pw_vk.clone();
+        if !did_doc.recipient_keys().contains(&pw_vk) {
+            return Err(VcxError::from_msg(VcxErrorKind::InvalidJson,
+                                          format!("DIDDoc validation failed: connection recipient key {} is missing in the first service", pw_vk)));
+        }
+        self.agent_info_mut().local_did_doc = Some(did_doc);
+        Ok(())
+    }
+
+    /**
+    Sends trust ping anonymously to the endpoint of one's own DidDoc, through all its routing keys, and waits until
+    it arrives at the connection agent. Returns false if the ping can't be delivered to the endpoint or doesn't
+    arrive within `timeout`, which reveals misconfigured mediators before the DidDoc is published in an invitation.
+    Inviter connection agent is created if it doesn't exist yet.
+     */
+    pub fn verify_endpoint_reachable(&mut self, timeout: Duration) -> VcxResult<bool> {
+        trace!("Connection::verify_endpoint_reachable >>> source_id: {}, timeout: {:?}", self.source_id(), timeout);
+        if self.agent_info().pw_did.is_empty() {
+            self.ensure_local_agent()?;
         }
+        if self.agent_info().is_agentless() {
+            return Err(VcxError::from_msg(VcxErrorKind::ActionNotSupported, "Agentless connection has no agent the test message could arrive at"));
+        }
+
+        let ping = Ping::create().set_comment(Some(String::from("endpoint reachability check")));
+        let did_doc = self.agent_info().local_did_doc()?;
+        match AgentInfo::send_message_anonymously(&ping.to_a2a_message(), &did_doc) {
+            Ok(()) => {}
+            Err(err) if err.kind() == VcxErrorKind::PostMessageFailed => {
+                warn!("Connection::verify_endpoint_reachable >>> endpoint {} is not reachable: {}", did_doc.get_endpoint(), err);
+                return Ok(false);
+            }
+            Err(err) => return Err(err)
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let messages = self.agent_info().get_messages_noauth()?;
+            let received = messages.into_iter()
+                .find(|(_, message)| match message {
+                    A2AMessage::Ping(received) => received.id == ping.id,
+                    _ => false
+                });
+            if let Some((uid, _)) = received {
+                self.agent_info().update_message_status(uid)?;
+                return Ok(true);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                warn!("Connection::verify_endpoint_reachable >>> test message didn't arrive from endpoint {} in time", did_doc.get_endpoint());
+                return Ok(false);
+            }
+            thread::sleep(std::cmp::min(REACHABILITY_POLL_INTERVAL, deadline - now));
+        }
+    }
+
+    fn ensure_local_agent(&mut self) -> VcxResult<()> {
+        match self.connection_sm {
+            SmConnection::Inviter(ref sm_inviter) if sm_inviter.is_in_null_state() && !self.is_receiver() => {}
+            _ => return Err(VcxError::from_msg(VcxErrorKind::InvalidState, "Local DidDoc is available only to Inviter before connecting"))
+        }
+        if self.agent_info().pw_did.is_empty() {
+            let agent_info = self.agent_info().create_agent()?;
+            *self.agent_info_mut() = agent_info;
+        }
+        Ok(())
     }
 
-    pub fn from_parts(source_id: String, agent_info: AgentInfo, state: SmConnectionState) -> Connection {
-        match state {
+    fn set_agentless_endpoint(&mut self, endpoint: &str) -> VcxResult<()> {
+        url::Url::parse(endpoint)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidUrl, format!("Invalid agentless endpoint {}: {}", endpoint, err)))?;
+        self.agent_info_mut().endpoint = Some(endpoint.to_string());
+        Ok(())
+    }
+
+    pub fn from_parts(source_id: String, data: ConnectionData, state: SmConnectionState) -> Connection {
+        let ConnectionData { agent_info, connect_time, established_time } = data;
+        let connection_sm = match state {
             SmConnectionState::Inviter(state) => {
-                Connection { connection_sm: SmConnection::Inviter(SmConnectionInviter::from(source_id, agent_info, state)) }
+                SmConnection::Inviter(SmConnectionInviter::from(source_id, agent_info, state))
             }
             SmConnectionState::Invitee(state) => {
-                Connection { connection_sm: SmConnection::Invitee(SmConnectionInvitee::from(source_id, agent_info, state)) }
+                SmConnection::Invitee(SmConnectionInvitee::from(source_id, agent_info, state))
             }
+        };
+        Connection { connection_sm, connect_time, established_time }
+    }
+
+    pub fn data(&self) -> ConnectionData {
+        ConnectionData {
+            agent_info: self.agent_info().to_owned(),
+            connect_time: self.connect_time,
+            established_time: self.established_time,
         }
     }
 
@@ -81,14 +307,79 @@ impl Connection {
         trace!("Connection::create_with_invite >>> source_id: {}", source_id);
 
         let mut connection = Connection {
-            connection_sm: SmConnection::Invitee(SmConnectionInvitee::new(source_id))
+            connection_sm: SmConnection::Invitee(SmConnectionInvitee::new(source_id)),
+            connect_time: None,
+            established_time: None,
         };
 
         connection.process_invite(invitation)?;
+        connection.agent_info_mut().invitation_type = InvitationType::ConnectionInvitation;
+
+        Ok(connection)
+    }
+
+    /**
+    Create Invitee connection state machine which completes only if the counterparty uses the expected DID
+     */
+    pub fn create_with_invite_expecting(source_id: &str, invitation: Invitation, expected_did: &str) -> VcxResult<Connection> {
+        trace!("Connection::create_with_invite_expecting >>> source_id: {}, expected_did: {}", source_id, expected_did);
+
+        let mut connection = Connection::create_with_invite(source_id, invitation)?;
+
+        match connection.connection_sm {
+            SmConnection::Invitee(ref mut sm_invitee) => sm_invitee.set_expected_did(expected_did)?,
+            SmConnection::Inviter(_) => return Err(VcxError::from_msg(VcxErrorKind::InvalidState, "Expected DID can be set only for Invitee"))
+        };
 
         Ok(connection)
     }
 
+    /**
+    Create Invitee connection state machine from out-of-band invitation, keeping request attached to it
+     */
+    pub fn create_with_oob_invite(source_id: &str, oob_invitation: OutOfBandInvitation) -> VcxResult<Connection> {
+        trace!("Connection::create_with_oob_invite >>> source_id: {}", source_id);
+        oob_invitation.validate()?;
+
+        // v1 is picked whenever the inviter accepts it, messages can't be packed into v2 envelope yet
+        let envelope_version = EnvelopeVersion::negotiate(&oob_invitation.accept);
+        if !envelope_version.is_v1() {
+            return Err(VcxError::from_msg(VcxErrorKind::ActionNotSupported,
+                                          format!("Inviter accepts only DIDComm v2 envelope which is not supported, accepted media types: {:?}", oob_invitation.accept)));
+        }
+
+        let mut connection = Connection::create_with_invite(source_id, oob_invitation.to_connection_invitation()?)?;
+        connection.agent_info_mut().envelope_version = envelope_version;
+        connection.agent_info_mut().invitation_type = InvitationType::OutOfBand;
+
+        if let Some(request) = oob_invitation.attached_request()? {
+            match connection.connection_sm {
+                SmConnection::Invitee(ref mut sm_invitee) => sm_invitee.set_attached_request(request)?,
+                SmConnection::Inviter(_) => return Err(VcxError::from_msg(VcxErrorKind::InvalidState, "Attached request can be set only for Invitee"))
+            };
+        }
+
+        Ok(connection)
+    }
+
+    /**
+    Returns credential offer or presentation request attached to the out-of-band invitation this connection was created with
+     */
+    pub fn get_attached_request(&self) -> Option<A2AMessage> {
+        match &self.connection_sm {
+            SmConnection::Inviter(_) => None,
+            SmConnection::Invitee(sm_invitee) => sm_invitee.get_attached_request().cloned()
+        }
+    }
+
+    /**
+    Returns number of messages on the connection agent in each message status
+     */
+    pub fn get_message_counts(&self) -> VcxResult<HashMap<MessageStatusCode, u32>> {
+        trace!("Connection::get_message_counts >>>");
+        self.agent_info().get_message_counts()
+    }
+
     pub fn source_id(&self) -> String {
         match &self.connection_sm {
             SmConnection::Inviter(sm_inviter) => {
@@ -122,6 +413,27 @@ impl Connection {
         }
     }
 
+    fn agent_info_mut(&mut self) -> &mut AgentInfo {
+        match &mut self.connection_sm {
+            SmConnection::Inviter(sm_inviter) => {
+                sm_inviter.agent_info_mut()
+            }
+            SmConnection::Invitee(sm_invitee) => {
+                sm_invitee.agent_info_mut()
+            }
+        }
+    }
+
+    /**
+    Time it took from calling `connect` until the connection was completed. None until the connection is completed.
+     */
+    pub fn get_establishment_latency(&self) -> Option<Duration> {
+        match (self.connect_time, self.established_time) {
+            (Some(connect_time), Some(established_time)) => Some(Duration::from_millis(established_time.saturating_sub(connect_time))),
+            _ => None
+        }
+    }
+
     pub fn bootstrap_agent_info(&self) -> Option<&AgentInfo> {
         match &self.connection_sm {
             SmConnection::Inviter(sm_inviter) => {
@@ -153,6 +465,19 @@ impl Connection {
         }
     }
 
+    /**
+    Returns problem report which moved the connection to the Null state, received from the counterparty
+    or sent to it.
+     */
+    pub fn get_last_problem_report(&self) -> VcxResult<String> {
+        trace!("Connection::get_last_problem_report >>>");
+        let problem_report = match &self.connection_sm {
+            SmConnection::Inviter(sm_inviter) => sm_inviter.problem_report()?,
+            SmConnection::Invitee(sm_invitee) => sm_invitee.problem_report()?,
+        };
+        Ok(json!(problem_report).to_string())
+    }
+
     pub fn state_object(&self) -> SmConnectionState {
         match &self.connection_sm {
             SmConnection::Inviter(sm_inviter) => {
@@ -208,6 +533,40 @@ impl Connection {
         }
     }
 
+    pub fn is_deactivated(&self) -> bool {
+        match &self.connection_sm {
+            SmConnection::Inviter(sm_inviter) => {
+                sm_inviter.is_deactivated()
+            }
+            SmConnection::Invitee(sm_invitee) => {
+                sm_invitee.is_deactivated()
+            }
+        }
+    }
+
+    fn ensure_not_deactivated(&self) -> VcxResult<()> {
+        if self.is_deactivated() {
+            return Err(VcxError::from_msg(VcxErrorKind::ConnectionDeactivated,
+                                          format!("Connection {} was deactivated", self.source_id())));
+        }
+        Ok(())
+    }
+
+    /**
+    Returns the invitation recipient key the inviter's Response signature is verified against.
+    Available while the connection is being established (Invited, Requested and Responded states).
+     */
+    pub fn get_invitation_recipient_key(&self) -> VcxResult<String> {
+        match &self.connection_sm {
+            SmConnection::Inviter(sm_inviter) => {
+                sm_inviter.invitation_recipient_key()
+            }
+            SmConnection::Invitee(sm_invitee) => {
+                sm_invitee.invitation_recipient_key()
+            }
+        }.ok_or(VcxError::from_msg(VcxErrorKind::NotReady, "Invitation recipient key is not available in current connection state"))
+    }
+
     pub fn their_did_doc(&self) -> Option<DidDoc> {
         match &self.connection_sm {
             SmConnection::Inviter(sm_inviter) => {
@@ -231,12 +590,20 @@ impl Connection {
     If called on Inviter in Invited state returns invitation to connect with him. Returns error in other states.
     If called on Invitee, returns error
      */
+    /**
+    Returns invitation generated by `connect`. If the application added services to the local DidDoc, RFC 0160
+    invitation has no room for them and out-of-band invitation carrying all of them inline is returned instead.
+     */
     pub fn get_invite_details(&self) -> Option<String> {
         trace!("Connection::get_invite_details >>>");
         match &self.connection_sm {
             SmConnection::Inviter(sm_inviter) => {
                 sm_inviter.get_invitation().map(|invitation| {
-                    json!(invitation.to_a2a_message()).to_string()
+                    match self.agent_info().local_did_doc {
+                        Some(ref did_doc) if self.get_invitation_type() == InvitationType::OutOfBand =>
+                            json!(OutOfBandInvitation::from_connection_invitation(invitation, did_doc)).to_string(),
+                        _ => json!(invitation.to_a2a_message()).to_string()
+                    }
                 })
             }
             SmConnection::Invitee(_sm_invitee) => {
@@ -245,6 +612,13 @@ impl Connection {
         }
     }
 
+    pub fn can_handle_message(&self, message: &A2AMessage) -> bool {
+        match &self.connection_sm {
+            SmConnection::Inviter(sm_inviter) => sm_inviter.can_handle_message(message),
+            SmConnection::Invitee(sm_invitee) => sm_invitee.can_handle_message(message)
+        }
+    }
+
     pub fn find_message_to_handle(&self, messages: HashMap<String, A2AMessage>) -> Option<(String, A2AMessage)> {
         match &self.connection_sm {
             SmConnection::Inviter(sm_inviter) => {
@@ -256,13 +630,35 @@ impl Connection {
         }
     }
 
+    /**
+    Counts messages the connection protocol can handle in the current state.
+     */
+    pub fn count_messages_to_handle(&self, messages: &HashMap<String, A2AMessage>) -> u32 {
+        messages.values().filter(|message| self.can_handle_message(message)).count() as u32
+    }
+
     /**
     If called on Inviter, creates initial connection agent and generates invitation
     If called on Invitee, creates connection agent and send connection request using info from connection invitation
      */
     pub fn connect(&mut self) -> VcxResult<()> {
         trace!("Connection::connect >>> source_id: {}", self.source_id());
-        self.step(DidExchangeMessages::Connect())
+        self.ensure_not_deactivated()?;
+        if self.is_receiver() {
+            return Err(VcxError::from_msg(VcxErrorKind::ActionNotSupported, "Listen-only connection doesn't connect to peers"));
+        }
+        let connect_time = clock::now_millis();
+        self.step(DidExchangeMessages::Connect())?;
+        let has_additional_services = self.agent_info().local_did_doc.as_ref()
+            .map(|did_doc| did_doc.service.len() > 1)
+            .unwrap_or(false);
+        if has_additional_services {
+            self.agent_info_mut().invitation_type = InvitationType::OutOfBand;
+        }
+        if self.connect_time.is_none() {
+            self.connect_time = Some(connect_time);
+        }
+        Ok(())
     }
 
     /**
@@ -270,10 +666,18 @@ impl Connection {
      */
     pub fn update_state_with_message(&mut self, message: &A2AMessage) -> VcxResult<()> {
         trace!("Connection: update_state_with_message: {:?}", message);
+        self.ensure_not_deactivated()?;
         if self.is_in_null_state() {
             warn!("Connection::update_state_with_message :: update state on connection in null state is ignored");
             return Ok(());
         }
+        if !self.can_handle_message(message) {
+            let message_type = serde_json::to_value(message).ok()
+                .and_then(|message| message["@type"].as_str().map(String::from))
+                .unwrap_or_else(|| String::from("unknown"));
+            return Err(VcxError::from_msg(VcxErrorKind::InvalidMessageForState,
+                                          format!("Connection in state {:?} cannot handle message of type {}", VcxStateType::from_u32(self.state()), message_type)));
+        }
 
         self.handle_message(message.clone().into())?;
 
@@ -285,6 +689,7 @@ impl Connection {
      */
     pub fn handle_message(&mut self, message: DidExchangeMessages) -> VcxResult<()> {
         trace!("Connection: handle_message >>> {:?}", message);
+        self.ensure_not_deactivated()?;
         self.step(message)
     }
 
@@ -300,13 +705,18 @@ impl Connection {
 Get messages received from connection counterparty.
  */
     pub fn get_messages_noauth(&self) -> VcxResult<HashMap<String, A2AMessage>> {
+        self.get_messages_noauth_with_statuses(vec![MessageStatusCode::Received])
+    }
+
+    pub fn get_messages_noauth_with_statuses(&self, statuses: Vec<MessageStatusCode>) -> VcxResult<HashMap<String, A2AMessage>> {
+        self.ensure_not_deactivated()?;
         match &self.connection_sm {
             SmConnection::Inviter(sm_inviter) => {
-                let messages = sm_inviter.agent_info().get_messages_noauth()?;
+                let messages = sm_inviter.agent_info().get_messages_noauth_with_statuses(statuses)?;
                 Ok(messages)
             }
             SmConnection::Invitee(sm_invitee) => {
-                let messages = sm_invitee.agent_info().get_messages_noauth()?;
+                let messages = sm_invitee.agent_info().get_messages_noauth_with_statuses(statuses)?;
                 Ok(messages)
             }
         }
@@ -316,6 +726,11 @@ Get messages received from connection counterparty.
     Get messages received from connection counterparty.
      */
     pub fn get_messages(&self) -> VcxResult<HashMap<String, A2AMessage>> {
+        self.ensure_not_deactivated()?;
+        // listen-only connection has no counterparty, messages come from anyone who knows its service
+        if self.is_receiver() {
+            return self.get_messages_noauth();
+        }
         let expected_sender_vk = self.get_expected_sender_vk()?;
         match &self.connection_sm {
             SmConnection::Inviter(sm_inviter) => {
@@ -351,6 +766,7 @@ Get messages received from connection counterparty.
      */
     pub fn send_message(&self, message: &A2AMessage) -> VcxResult<()> {
         trace!("Connection::send_message >>> message: {:?}", message);
+        self.ensure_not_deactivated()?;
 
         let did_doc = self.their_did_doc()
             .ok_or(VcxError::from_msg(VcxErrorKind::NotReady, "Cannot send message: Remote Connection information is not set"))?;
@@ -359,14 +775,69 @@ Get messages received from connection counterparty.
         self.agent_info().send_message(message, &did_doc)
     }
 
+    /**
+    Sends authenticated message to connection counterparty, returns receipt of the delivery to counterparty's agency
+     */
+    pub fn send_message_with_receipt(&self, message: &A2AMessage) -> VcxResult<DeliveryReceipt> {
+        trace!("Connection::send_message_with_receipt >>> message: {:?}", message);
+        self.ensure_not_deactivated()?;
+
+        let did_doc = self.their_did_doc()
+            .ok_or(VcxError::from_msg(VcxErrorKind::NotReady, "Cannot send message: Remote Connection information is not set"))?;
+
+        self.agent_info().send_message_with_receipt(message, &did_doc)
+    }
+
+    /**
+    Sends authenticated message to connection counterparty, readable only by the holder of the given recipient key
+    of counterparty's DidDoc. Messages sent by `send_message` are packed for all its recipient keys.
+     */
+    pub fn send_message_to_recipient_key(&self, message: &A2AMessage, recipient_key: &str) -> VcxResult<()> {
+        trace!("Connection::send_message_to_recipient_key >>> message: {:?}, recipient_key: {}", message, recipient_key);
+        self.ensure_not_deactivated()?;
+
+        let did_doc = self.their_did_doc()
+            .ok_or(VcxError::from_msg(VcxErrorKind::NotReady, "Cannot send message: Remote Connection information is not set"))?;
+
+        self.agent_info().send_message_to_recipient_key(message, &did_doc, recipient_key)
+    }
+
+    /**
+    Sends present-proof message to connection counterparty in the given present-proof version. Messages of an exchange
+    are sent in the version its presentation request was exchanged in.
+     */
+    pub fn send_present_proof_message(&self, message: &A2AMessage, version: &str) -> VcxResult<()> {
+        trace!("Connection::send_present_proof_message >>> message: {:?}, version: {}", message, version);
+        let message = v2::for_version(message.clone(), version)?;
+        self.send_message(&message)
+    }
+
+    /**
+    Returns envelope version messages for the counterparty are packed into, "v1" unless negotiated otherwise.
+     */
+    pub fn envelope_version(&self) -> EnvelopeVersion {
+        self.agent_info().envelope_version
+    }
+
+    /**
+    Returns present-proof version to use with the counterparty, "1.0" unless it disclosed support of "2.0".
+     */
+    pub fn present_proof_version(&self) -> &'static str {
+        v2::negotiate_version(self.get_remote_protocols().as_ref())
+    }
+
+    /**
+    Sends anoncrypted message to the endpoint of supplied DidDoc. The message is wrapped into forward message
+    for every routing key of the DidDoc, so it can be delivered through a chain of mediators.
+     */
     pub fn send_message_to_self_endpoint(message: &A2AMessage, did_doc: &DidDoc) -> VcxResult<()> {
         trace!("Connection::send_message_to_self_endpoint >>> message: {:?}, did_doc: {:?}", message, did_doc);
 
         AgentInfo::send_message_anonymously(message, did_doc)
     }
 
-    fn parse_generic_message(message: &str) -> A2AMessage {
-        match ::serde_json::from_str::<A2AMessage>(message) {
+    fn parse_generic_message(message: &str, thread_id: Option<&str>) -> VcxResult<A2AMessage> {
+        let message = match ::serde_json::from_str::<A2AMessage>(message) {
             Ok(a2a_message) => a2a_message,
             Err(_) => {
                 BasicMessage::create()
@@ -374,53 +845,288 @@ Get messages received from connection counterparty.
                     .set_time()
                     .to_a2a_message()
             }
+        };
+        match (message, thread_id) {
+            (message, None) => Ok(message),
+            (A2AMessage::BasicMessage(basic_message), Some(thread_id)) => Ok(basic_message.set_thread_id(thread_id).to_a2a_message()),
+            (A2AMessage::Generic(mut generic_message), Some(thread_id)) => {
+                if !generic_message.is_object() {
+                    return Err(VcxError::from_msg(VcxErrorKind::InvalidJson, "Thread id can be set only on message which is JSON object"));
+                }
+                generic_message["~thread"] = json!({"thid": thread_id});
+                Ok(A2AMessage::Generic(generic_message))
+            }
+            (_, Some(_)) => Err(VcxError::from_msg(VcxErrorKind::InvalidOption, "Thread id can be set only on basic or generic messages"))
         }
     }
 
-    pub fn send_generic_message(&self, message: &str) -> VcxResult<String> {
-        trace!("Connection::send_generic_message >>> message: {:?}", message);
+    /**
+    Sends message to connection counterparty, plain text is sent as basic message. With `thread_id` the message
+    gets `~thread` decorator, so the counterparty can correlate it with an earlier message, otherwise it starts
+    a new thread.
+     */
+    pub fn send_generic_message(&self, message: &str, thread_id: Option<&str>) -> VcxResult<String> {
+        trace!("Connection::send_generic_message >>> message: {:?}, thread_id: {:?}", message, thread_id);
 
-        let message = Connection::parse_generic_message(message);
+        let message = Connection::parse_generic_message(message, thread_id)?;
         self.send_message(&message).map(|_| String::new())
     }
 
-    pub fn send_ping(&mut self, comment: Option<String>) -> VcxResult<()> {
+    /**
+    Sends trust ping, returns its @id so the ping response can be correlated with it. Ping can be sent only once
+    the connection is completed (or responded by inviter), None is returned if the ping was ignored.
+     */
+    pub fn send_ping(&mut self, comment: Option<String>) -> VcxResult<Option<String>> {
         trace!("Connection::send_ping >>> comment: {:?}", comment);
-        self.handle_message(DidExchangeMessages::SendPing(comment))
+        self.ensure_not_deactivated()?;
+        if !self.can_send_ping() {
+            debug!("Connection::send_ping >>> ping ignored, connection {} is in state {}", self.source_id(), self.state());
+            return Ok(None);
+        }
+        let ping = Ping::create()
+            .request_response()
+            .set_comment(comment);
+        let id = ping.id.0.clone();
+        self.handle_message(DidExchangeMessages::SendPing(ping))?;
+        Ok(Some(id))
+    }
+
+    fn can_send_ping(&self) -> bool {
+        match &self.connection_sm {
+            SmConnection::Inviter(sm_inviter) => match sm_inviter.state_object() {
+                InviterState::Responded(_) | InviterState::Completed(_) => true,
+                _ => false
+            },
+            SmConnection::Invitee(sm_invitee) => match sm_invitee.state_object() {
+                InviteeState::Completed(_) => true,
+                _ => false
+            }
+        }
     }
 
     pub fn delete(&self) -> VcxResult<()> {
         trace!("Connection: delete >>> {:?}", self.source_id());
+        if self.is_deactivated() {
+            debug!("Connection::delete :: pairwise agent of deactivated connection was already deleted");
+            return Ok(());
+        }
         self.agent_info().delete()
     }
 
+    /**
+    Re-sends the connection Request of Invitee waiting for inviter's Response. The request built on connect is sent
+    again as is, so no new keys are provisioned and the thread id is kept. Returns the thread id of the request.
+     */
+    pub fn resend_request(&mut self) -> VcxResult<String> {
+        trace!("Connection::resend_request >>> source_id: {}", self.source_id());
+        self.ensure_not_deactivated()?;
+        let thread_id = match self.connection_sm {
+            SmConnection::Invitee(ref sm_invitee) => sm_invitee.request_thread_id(),
+            SmConnection::Inviter(_) => None
+        }.ok_or(VcxError::from_msg(VcxErrorKind::InvalidState, "Connection request can be re-sent only by Invitee in Requested state"))?;
+        self.step(DidExchangeMessages::Connect())?;
+        Ok(thread_id)
+    }
+
+    /**
+    Cancels connection of Invitee which is not completed yet. The inviter is notified by problem report if a request
+    was already sent, provisioned pairwise agent is deleted and connection returns to Null state.
+     */
+    pub fn cancel(&mut self) -> VcxResult<()> {
+        trace!("Connection::cancel >>> source_id: {}", self.source_id());
+        self.ensure_not_deactivated()?;
+        match self.connection_sm {
+            SmConnection::Inviter(_) => Err(VcxError::from_msg(VcxErrorKind::InvalidState, "Only Invitee can cancel connection")),
+            SmConnection::Invitee(_) => self.step(DidExchangeMessages::Cancel())
+        }
+    }
+
+    /**
+    Deletes the pairwise agent in one's agency, but keeps the local connection record (read-only).
+     */
+    pub fn deactivate(&mut self) -> VcxResult<()> {
+        trace!("Connection::deactivate >>> source_id: {}", self.source_id());
+        self.ensure_not_deactivated()?;
+        self.agent_info().delete()?;
+        self.step(DidExchangeMessages::Deactivate())
+    }
+
     fn step(&mut self, message: DidExchangeMessages) -> VcxResult<()> {
-        self.connection_sm = match &self.connection_sm {
+        let prev_state = self.state();
+        let is_response = match message {
+            DidExchangeMessages::ExchangeResponseReceived(_) => true,
+            _ => false
+        };
+        let connection_sm = match &self.connection_sm {
             SmConnection::Inviter(sm_inviter) => {
-                SmConnection::Inviter(sm_inviter.clone().step(message)?)
+                sm_inviter.clone().step(message).map(SmConnection::Inviter)
             }
             SmConnection::Invitee(sm_invitee) => {
-                SmConnection::Invitee(sm_invitee.clone().step(message)?)
+                sm_invitee.clone().step(message).map(SmConnection::Invitee)
+            }
+        };
+        self.connection_sm = match connection_sm {
+            Ok(connection_sm) => connection_sm,
+            Err(err) => {
+                self.agent_info_mut().last_error = Some(err.to_string());
+                return Err(err);
             }
         };
+        self.agent_info_mut().last_activity = Some(clock::now_millis());
+        self.agent_info_mut().last_error = None;
+        let new_state = self.state();
+        if prev_state != new_state {
+            connection_transition_event!(self.source_id(), prev_state, new_state);
+            if new_state == VcxStateType::VcxStateAccepted as u32 && self.established_time.is_none() {
+                self.established_time = Some(clock::now_millis());
+            }
+        }
+        // invitee rejects Response of unexpected counterparty by moving to null state, the caller is told why
+        if is_response && self.is_in_null_state() {
+            if let SmConnection::Invitee(sm_invitee) = &self.connection_sm {
+                if let Ok(ProblemReport { problem_code: Some(ProblemCode::ResponseNotAccepted), explain, .. }) = sm_invitee.problem_report() {
+                    let err = VcxError::from_msg(VcxErrorKind::ConnectionDidMismatch, explain.unwrap_or_default());
+                    self.agent_info_mut().last_error = Some(err.to_string());
+                    return Err(err);
+                }
+            }
+        }
         Ok(())
     }
 
-    pub fn send_discovery_features(&mut self, query: Option<String>, comment: Option<String>) -> VcxResult<()> {
+    /**
+    Sends discover features query, returns its @id which is the thread id of the Disclose answering it.
+     */
+    pub fn send_discovery_features(&mut self, query: Option<String>, comment: Option<String>) -> VcxResult<String> {
         trace!("Connection::send_discovery_features_query >>> query: {:?}, comment: {:?}", query, comment);
-        self.handle_message(DidExchangeMessages::DiscoverFeatures((query, comment)))
+        let query_ = Query::create()
+            .set_query(query)
+            .set_comment(comment);
+        let id = query_.id.0.clone();
+        self.handle_message(DidExchangeMessages::DiscoverFeatures(query_))?;
+        Ok(id)
+    }
+
+    pub fn send_discovery_features_query(&mut self, query: &ProtocolQuery, comment: Option<String>) -> VcxResult<String> {
+        trace!("Connection::send_discovery_features_query >>> query: {:?}, comment: {:?}", query, comment);
+        self.send_discovery_features(Some(query.to_query_string()), comment)
+    }
+
+    /**
+    Returns protocols disclosed by the counterparty as (protocol id, roles) pairs.
+     */
+    pub fn get_disclosed_protocols(&self) -> VcxResult<Vec<(String, Vec<Actors>)>> {
+        trace!("Connection::get_disclosed_protocols >>>");
+        let protocols = self.get_remote_protocols()
+            .ok_or(VcxError::from_msg(VcxErrorKind::NotReady, "Disclose message has not been received yet"))?;
+        Ok(protocols.iter().map(ProtocolDescriptor::to_pid_and_roles).collect())
+    }
+
+    /**
+    Adds application defined tag to the connection, returns false if the connection already had it.
+     */
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        trace!("Connection::add_tag >>> tag: {}", tag);
+        self.agent_info_mut().tags.insert(tag.to_string())
+    }
+
+    /**
+    Removes tag from the connection, returns false if the connection didn't have it.
+     */
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        trace!("Connection::remove_tag >>> tag: {}", tag);
+        self.agent_info_mut().tags.remove(tag)
+    }
+
+    pub fn get_tags(&self) -> Vec<String> {
+        self.agent_info().tags.iter().cloned().collect()
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.agent_info().tags.contains(tag)
+    }
+
+    pub fn get_invitation_type(&self) -> InvitationType {
+        self.agent_info().invitation_type
+    }
+
+    pub fn set_invitation_type(&mut self, invitation_type: InvitationType) {
+        self.agent_info_mut().invitation_type = invitation_type;
+    }
+
+    /**
+    Sets application defined decorator merged into every message sent over this connection, `None` removes it.
+     */
+    pub fn set_decorator(&mut self, name: &str, value: Option<serde_json::Value>) -> VcxResult<()> {
+        trace!("Connection::set_decorator >>> name: {}, value: {:?}", name, value);
+        decorators::validate_decorator_name(name)?;
+        match value {
+            Some(value) => self.agent_info_mut().decorators.insert(name.to_string(), value),
+            None => self.agent_info_mut().decorators.remove(name)
+        };
+        Ok(())
+    }
+
+    pub fn actor(&self) -> Actor {
+        match &self.connection_sm {
+            SmConnection::Inviter(_) => Actor::Inviter,
+            SmConnection::Invitee(_) => Actor::Invitee
+        }
+    }
+
+    pub fn get_diagnostics(&self, include_did_doc: bool, include_signed_response: bool) -> ConnectionDiagnostics {
+        trace!("Connection::get_diagnostics >>> include_did_doc: {}, include_signed_response: {}", include_did_doc, include_signed_response);
+
+        let did_doc = self.their_did_doc();
+        let endpoint_host = did_doc.as_ref()
+            .and_then(|did_doc| url::Url::parse(&did_doc.get_endpoint()).ok())
+            .and_then(|endpoint| endpoint.host_str().map(String::from));
+        let agent_info = self.agent_info();
+
+        ConnectionDiagnostics {
+            source_id: self.source_id(),
+            state: self.state(),
+            role: self.actor(),
+            protocol_version: MessageFamilies::Connections.version().to_string(),
+            envelope_version: agent_info.envelope_version,
+            endpoint_host,
+            last_activity: agent_info.last_activity,
+            last_error: agent_info.last_error.clone(),
+            did_doc: if include_did_doc { did_doc } else { None },
+            signed_response: if include_signed_response { agent_info.signed_response.clone() } else { None },
+        }
+    }
+
+    /**
+    Signature structure of the connection response sent as inviter or received as invitee, kept as proof of the handshake
+     */
+    pub fn get_signed_response(&self) -> VcxResult<Option<String>> {
+        trace!("Connection::get_signed_response >>>");
+        self.agent_info().signed_response.as_ref()
+            .map(|signed_response| serde_json::to_string(signed_response)
+                .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize connection signature: {:?}", err))))
+            .transpose()
     }
 
     pub fn get_connection_info(&self) -> VcxResult<String> {
         trace!("Connection::get_connection_info >>>");
 
+        let connection_info = self.get_connection_info_typed()?;
+
+        serde_json::to_string(&connection_info)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidState, format!("Cannot serialize ConnectionInfo: {:?}", err)))
+    }
+
+    pub fn get_connection_info_typed(&self) -> VcxResult<ConnectionInfo> {
+        trace!("Connection::get_connection_info_typed >>>");
+
         let agent_info = self.agent_info().clone();
 
         let current = SideConnectionInfo {
             did: agent_info.pw_did.clone(),
             recipient_keys: agent_info.recipient_keys().clone(),
             routing_keys: agent_info.routing_keys()?,
-            service_endpoint: agent_info.agency_endpoint()?,
+            service_endpoint: agent_info.service_endpoint()?,
             protocols: Some(self.get_protocols()),
         };
 
@@ -436,11 +1142,11 @@ Get messages received from connection counterparty.
             None => None
         };
 
-        let connection_info = ConnectionInfo { my: current, their: remote };
-
-        let connection_info_json = serde_json::to_string(&connection_info)
-            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidState, format!("Cannot serialize ConnectionInfo: {:?}", err)))?;
-
-        return Ok(connection_info_json);
+        Ok(ConnectionInfo {
+            my: current,
+            their: remote,
+            protocol_version: MessageFamilies::Connections.version().to_string(),
+            state: self.state(),
+        })
     }
 }
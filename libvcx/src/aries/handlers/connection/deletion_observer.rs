@@ -0,0 +1,51 @@
+use std::sync::{Arc, RwLock};
+
+/**
+Reason why a connection was deleted, reported to the deletion observer for analytics.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletionReason {
+    UserInitiated,
+    Expired,
+    Superseded,
+    Error,
+}
+
+/**
+Observer of connection deletions. `on_delete` is notified before the connection is deleted, while its handle is
+still valid, `on_delete_completed` then reports whether the deletion succeeded. Observer is invoked synchronously,
+so it should return quickly.
+ */
+pub trait DeletionObserver: Send + Sync {
+    fn on_delete(&self, handle: u32, source_id: &str, reason: DeletionReason);
+
+    fn on_delete_completed(&self, handle: u32, source_id: &str, reason: DeletionReason, deleted: bool);
+}
+
+lazy_static! {
+    static ref DELETION_OBSERVER: RwLock<Option<Arc<dyn DeletionObserver>>> = RwLock::new(None);
+}
+
+/**
+Registers the observer globally, replacing any previously set. `None` disables observing.
+ */
+pub fn set_deletion_observer(observer: Option<Arc<dyn DeletionObserver>>) {
+    *DELETION_OBSERVER.write().unwrap() = observer;
+}
+
+fn _observer() -> Option<Arc<dyn DeletionObserver>> {
+    DELETION_OBSERVER.read().ok().and_then(|observer| observer.clone())
+}
+
+pub(crate) fn notify_delete(handle: u32, source_id: &str, reason: DeletionReason) {
+    if let Some(observer) = _observer() {
+        observer.on_delete(handle, source_id, reason);
+    }
+}
+
+pub(crate) fn notify_delete_completed(handle: u32, source_id: &str, reason: DeletionReason, deleted: bool) {
+    if let Some(observer) = _observer() {
+        observer.on_delete_completed(handle, source_id, reason, deleted);
+    }
+}
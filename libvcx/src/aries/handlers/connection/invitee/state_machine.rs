@@ -2,14 +2,16 @@ use std::collections::HashMap;
 
 use crate::api::VcxStateType;
 use crate::error::prelude::*;
+use crate::settings;
 use crate::aries::handlers::connection::agent_info::AgentInfo;
 use crate::aries::handlers::connection::invitee::states::complete::CompleteState;
+use crate::aries::handlers::connection::invitee::states::deactivated::DeactivatedState;
 use crate::aries::handlers::connection::invitee::states::invited::InvitedState;
 use crate::aries::handlers::connection::invitee::states::null::NullState;
 use crate::aries::handlers::connection::invitee::states::requested::RequestedState;
 use crate::aries::handlers::connection::messages::DidExchangeMessages;
 use crate::aries::messages::a2a::A2AMessage;
-use crate::aries::messages::a2a::protocol_registry::ProtocolRegistry;
+use crate::aries::messages::a2a::protocol_registry::supported_protocols;
 use crate::aries::messages::connection::did_doc::DidDoc;
 use crate::aries::messages::connection::invite::Invitation;
 use crate::aries::messages::connection::problem_report::{ProblemCode, ProblemReport};
@@ -29,6 +31,7 @@ pub enum InviteeState {
     Invited(InvitedState),
     Requested(RequestedState),
     Completed(CompleteState),
+    Deactivated(DeactivatedState),
 }
 
 impl InviteeState {
@@ -38,6 +41,7 @@ impl InviteeState {
             InviteeState::Invited(_) => VcxStateType::VcxStateOfferSent as u32,
             InviteeState::Requested(_) => VcxStateType::VcxStateRequestReceived as u32,
             InviteeState::Completed(_) => VcxStateType::VcxStateAccepted as u32,
+            InviteeState::Deactivated(_) => VcxStateType::VcxStateNone as u32,
         }
     }
 }
@@ -46,7 +50,7 @@ impl SmConnectionInvitee {
     pub fn _build_invitee(source_id: &str) -> Self {
         SmConnectionInvitee {
             source_id: source_id.to_string(),
-            state: InviteeState::Null(NullState {}),
+            state: InviteeState::Null(NullState { problem_report: None }),
             agent_info: AgentInfo::default(),
         }
     }
@@ -62,6 +66,13 @@ impl SmConnectionInvitee {
         }
     }
 
+    pub fn is_deactivated(&self) -> bool {
+        match self.state {
+            InviteeState::Deactivated(_) => true,
+            _ => false
+        }
+    }
+
     pub fn from(source_id: String, agent_info: AgentInfo, state: InviteeState) -> Self {
         SmConnectionInvitee {
             source_id,
@@ -74,6 +85,10 @@ impl SmConnectionInvitee {
         &self.agent_info
     }
 
+    pub fn agent_info_mut(&mut self) -> &mut AgentInfo {
+        &mut self.agent_info
+    }
+
     pub fn source_id(&self) -> &str {
         &self.source_id
     }
@@ -88,6 +103,11 @@ impl SmConnectionInvitee {
 
     pub fn step(self, message: DidExchangeMessages) -> VcxResult<SmConnectionInvitee> {
         trace!("SmConnectionInvitee::step >>> message: {:?}", message);
+        if let DidExchangeMessages::Deactivate() = message {
+            let did_doc = self.their_did_doc();
+            return Ok(SmConnectionInvitee { state: InviteeState::Deactivated(did_doc.into()), ..self });
+        }
+
         let SmConnectionInvitee { source_id, agent_info, state } = self;
 
         let (new_state, agent_info) =
@@ -102,6 +122,57 @@ impl SmConnectionInvitee {
             InviteeState::Invited(ref state) => Some(DidDoc::from(state.invitation.clone())),
             InviteeState::Requested(ref state) => Some(state.did_doc.clone()),
             InviteeState::Completed(ref state) => Some(state.did_doc.clone()),
+            InviteeState::Deactivated(ref state) => state.did_doc.clone(),
+        }
+    }
+
+    pub fn set_expected_did(&mut self, did: &str) -> VcxResult<()> {
+        match self.state {
+            InviteeState::Invited(ref mut state) => {
+                state.expected_did = Some(did.to_string());
+                Ok(())
+            }
+            _ => Err(VcxError::from_msg(VcxErrorKind::NotReady, "Expected DID can be set only on connection in Invited state"))
+        }
+    }
+
+    pub fn set_attached_request(&mut self, request: A2AMessage) -> VcxResult<()> {
+        match self.state {
+            InviteeState::Invited(ref mut state) => {
+                state.attached_request = Some(request);
+                Ok(())
+            }
+            _ => Err(VcxError::from_msg(VcxErrorKind::NotReady, "Attached request can be set only on connection in Invited state"))
+        }
+    }
+
+    pub fn get_attached_request(&self) -> Option<&A2AMessage> {
+        match self.state {
+            InviteeState::Invited(ref state) => state.attached_request.as_ref(),
+            InviteeState::Requested(ref state) => state.attached_request.as_ref(),
+            InviteeState::Completed(ref state) => state.attached_request.as_ref(),
+            _ => None
+        }
+    }
+
+    /**
+    Recipient key of the invitation, the Response of inviter must be signed by it.
+     */
+    pub fn invitation_recipient_key(&self) -> Option<String> {
+        match self.state {
+            InviteeState::Invited(ref state) => state.invitation.recipient_keys.get(0).cloned(),
+            InviteeState::Requested(ref state) => state.did_doc.recipient_keys().get(0).cloned(),
+            _ => None
+        }
+    }
+
+    /**
+    Id of the sent connection Request, it's the thread id of the connection protocol. Available in Requested state.
+     */
+    pub fn request_thread_id(&self) -> Option<String> {
+        match self.state {
+            InviteeState::Requested(ref state) => Some(state.request.id.0.clone()),
+            _ => None
         }
     }
 
@@ -122,7 +193,7 @@ impl SmConnectionInvitee {
     }
 
     pub fn get_protocols(&self) -> Vec<ProtocolDescriptor> {
-        ProtocolRegistry::init().protocols()
+        supported_protocols()
     }
 
     pub fn get_remote_protocols(&self) -> Option<Vec<ProtocolDescriptor>> {
@@ -144,6 +215,13 @@ impl SmConnectionInvitee {
             .ok_or(VcxError::from_msg(VcxErrorKind::NotReady, "Remote Connection Verkey is not set"))
     }
 
+    pub fn problem_report(&self) -> VcxResult<ProblemReport> {
+        match self.state {
+            InviteeState::Null(NullState { problem_report: Some(ref problem_report) }) => Ok(problem_report.clone()),
+            _ => Err(VcxError::from_msg(VcxErrorKind::NotReady, "Connection has not failed with a problem report"))
+        }
+    }
+
     pub fn can_handle_message(&self, message: &A2AMessage) -> bool {
         match self.state {
             InviteeState::Requested(_) => {
@@ -166,7 +244,8 @@ impl SmConnectionInvitee {
                 match message {
                     A2AMessage::Ping(_) => {
                         debug!("Ping message received");
-                        true
+                        // left for the application if it answers pings itself
+                        settings::auto_respond_to_ping()
                     }
                     A2AMessage::PingResponse(_) => {
                         debug!("PingResponse message received");
@@ -212,7 +291,7 @@ impl SmConnectionInvitee {
                         let request = Request::create()
                             .set_label(source_id.to_string())
                             .set_did(agent_info.pw_did.to_string())
-                            .set_service_endpoint(agent_info.agency_endpoint()?)
+                            .set_service_endpoint(agent_info.service_endpoint()?)
                             .set_keys(agent_info.recipient_keys(), agent_info.routing_keys()?);
 
                         trace!("invitation {:?}", state.invitation);
@@ -222,6 +301,12 @@ impl SmConnectionInvitee {
                     DidExchangeMessages::ProblemReportReceived(problem_report) => {
                         InviteeState::Null((state, problem_report).into())
                     }
+                    DidExchangeMessages::Cancel() => {
+                        // no request was sent and no agent provisioned yet, so there is nobody to notify
+                        let problem_report = ProblemReport::create()
+                            .set_explain(String::from("Connection was cancelled by invitee"));
+                        InviteeState::Null((state, problem_report).into())
+                    }
                     _ => {
                         InviteeState::Invited(state)
                     }
@@ -230,13 +315,19 @@ impl SmConnectionInvitee {
             InviteeState::Requested(state) => {
                 match message {
                     DidExchangeMessages::ExchangeResponseReceived(response) => {
+                        let connection_sig = response.connection_sig.clone();
                         match state.handle_connection_response(response, &agent_info) {
                             Ok(response) => {
+                                agent_info.signed_response = Some(connection_sig);
                                 InviteeState::Completed((state, response).into())
                             }
                             Err(err) => {
+                                let problem_code = match err.kind() {
+                                    VcxErrorKind::ConnectionDidMismatch => ProblemCode::ResponseNotAccepted,
+                                    _ => ProblemCode::ResponseProcessingError
+                                };
                                 let problem_report = ProblemReport::create()
-                                    .set_problem_code(ProblemCode::ResponseProcessingError)
+                                    .set_problem_code(problem_code)
                                     .set_explain(err.to_string())
                                     .set_thread_id(&state.request.id.0);
                                 agent_info.send_message(&problem_report.to_a2a_message(), &state.did_doc).ok();
@@ -247,14 +338,37 @@ impl SmConnectionInvitee {
                     DidExchangeMessages::ProblemReportReceived(problem_report) => {
                         InviteeState::Null((state, problem_report).into())
                     }
+                    DidExchangeMessages::Connect() => {
+                        // resuming connection, re-send the original request instead of provisioning new keys
+                        agent_info.send_message(&state.request.to_a2a_message(), &state.did_doc)?;
+                        InviteeState::Requested(state)
+                    }
+                    DidExchangeMessages::Cancel() => {
+                        let problem_report = ProblemReport::create()
+                            .set_explain(String::from("Connection was cancelled by invitee"))
+                            .set_thread_id(&state.request.id.0);
+                        if let Err(err) = agent_info.send_message(&problem_report.to_a2a_message(), &state.did_doc) {
+                            warn!("Unable to notify inviter about cancelled connection: {}", err);
+                        }
+                        agent_info.delete()?;
+                        agent_info = AgentInfo::default();
+                        InviteeState::Null((state, problem_report).into())
+                    }
                     _ => {
                         InviteeState::Requested(state)
                     }
                 }
             }
             InviteeState::Completed(state) => {
+                if let DidExchangeMessages::Cancel() = message {
+                    return Err(VcxError::from_msg(VcxErrorKind::InvalidState,
+                                                  "Completed connection cannot be cancelled, delete or deactivate it instead"));
+                }
                 state.handle_message(message, &agent_info)?
             }
+            InviteeState::Deactivated(state) => {
+                InviteeState::Deactivated(state)
+            }
         };
         Ok((new_state, agent_info))
     }
@@ -270,7 +384,9 @@ pub mod test {
     use crate::aries::messages::connection::request::tests::_request;
     use crate::aries::messages::connection::response::tests::_signed_response;
     use crate::aries::messages::discovery::disclose::tests::_disclose;
+    use crate::aries::messages::discovery::query::Query;
     use crate::aries::messages::discovery::query::tests::_query;
+    use crate::aries::messages::trust_ping::ping::Ping;
     use crate::aries::messages::trust_ping::ping::tests::_ping;
     use crate::aries::messages::trust_ping::ping_response::tests::_ping_response;
     use crate::aries::test::source_id;
@@ -335,7 +451,11 @@ pub mod test {
         }
 
         mod step {
+            use std::time::Duration;
+
             use super::*;
+            use crate::aries::messages::timing::Timing;
+            use crate::utils::clock::SetupFakeClock;
             use crate::utils::devsetup::{SetupIndyMocks};
 
             #[test]
@@ -413,6 +533,33 @@ pub mod test {
                 assert_match!(InviteeState::Invited(_), did_exchange_sm.state);
             }
 
+            #[test]
+            #[cfg(feature = "general_test")]
+            fn test_did_exchange_handle_connect_from_requested_state_resends_request() {
+                let _setup = SetupIndyMocks::init();
+
+                let mut did_exchange_sm = invitee_sm().to_invitee_requested_state();
+                let agent_info = did_exchange_sm.agent_info().clone();
+                let request = match did_exchange_sm.state {
+                    InviteeState::Requested(ref state) => state.request.clone(),
+                    _ => panic!("Expected Requested state")
+                };
+
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::Connect()).unwrap();
+
+                match did_exchange_sm.state {
+                    InviteeState::Requested(ref state) => {
+                        assert_eq!(request, state.request);
+                        assert_eq!(request.id, _request().id);
+                        assert_eq!(request.connection.did_doc.recipient_keys(), state.request.connection.did_doc.recipient_keys());
+                    }
+                    _ => panic!("Expected Requested state")
+                };
+                assert_eq!(agent_info.pw_did, did_exchange_sm.agent_info().pw_did);
+                assert_eq!(agent_info.pw_vk, did_exchange_sm.agent_info().pw_vk);
+                assert_eq!(agent_info.agent_vk, did_exchange_sm.agent_info().agent_vk);
+            }
+
             #[test]
             #[cfg(feature = "general_test")]
             fn test_did_exchange_handle_response_message_from_requested_state() {
@@ -427,6 +574,94 @@ pub mod test {
                 assert_match!(InviteeState::Completed(_), did_exchange_sm.state);
             }
 
+            #[test]
+            #[cfg(feature = "general_test")]
+            fn test_did_exchange_handle_response_with_other_thread_from_requested_state() {
+                let _setup = SetupIndyMocks::init();
+
+                let key = "GJ1SzoWzavQYfNL9XkaJdrQejfztN4XqdsiV4ct3LXKL";
+
+                let mut did_exchange_sm = invitee_sm().to_invitee_requested_state();
+
+                let response = Response::default()
+                    .set_service_endpoint(_service_endpoint())
+                    .set_keys(vec![key.to_string()], vec![])
+                    .set_thread_id("other_thread")
+                    .encode(&key).unwrap();
+
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::ExchangeResponseReceived(response)).unwrap();
+
+                assert_match!(InviteeState::Null(_), did_exchange_sm.state);
+            }
+
+            #[test]
+            #[cfg(feature = "general_test")]
+            fn test_did_exchange_handle_response_with_expected_did_from_requested_state() {
+                let _setup = SetupIndyMocks::init();
+
+                let key = "GJ1SzoWzavQYfNL9XkaJdrQejfztN4XqdsiV4ct3LXKL";
+                let did = "VsKV7grR1BUE29mG2Fm2kX";
+
+                let mut did_exchange_sm = invitee_sm().to_invitee_invited_state();
+                did_exchange_sm.set_expected_did(did).unwrap();
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::Connect()).unwrap();
+
+                let response = Response::default()
+                    .set_did(did.to_string())
+                    .set_service_endpoint(_service_endpoint())
+                    .set_keys(vec![key.to_string()], vec![])
+                    .set_thread_id(&_request().id.0)
+                    .encode(&key).unwrap();
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::ExchangeResponseReceived(response)).unwrap();
+
+                assert_match!(InviteeState::Completed(_), did_exchange_sm.state);
+            }
+
+            #[test]
+            #[cfg(feature = "general_test")]
+            fn test_did_exchange_handle_response_with_unexpected_did_from_requested_state() {
+                let _setup = SetupIndyMocks::init();
+
+                let key = "GJ1SzoWzavQYfNL9XkaJdrQejfztN4XqdsiV4ct3LXKL";
+
+                let mut did_exchange_sm = invitee_sm().to_invitee_invited_state();
+                did_exchange_sm.set_expected_did("VsKV7grR1BUE29mG2Fm2kX").unwrap();
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::Connect()).unwrap();
+
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::ExchangeResponseReceived(_response(key))).unwrap();
+
+                assert_match!(InviteeState::Null(_), did_exchange_sm.state);
+                let problem_report = did_exchange_sm.problem_report().unwrap();
+                assert_eq!(Some(ProblemCode::ResponseNotAccepted), problem_report.problem_code);
+                assert!(problem_report.explain.unwrap().contains("VsKV7grR1BUE29mG2Fm2kX"));
+            }
+
+            #[test]
+            #[cfg(feature = "general_test")]
+            fn test_did_exchange_handle_expired_response_from_requested_state() {
+                let _setup = SetupIndyMocks::init();
+                let clock = SetupFakeClock::init(1_600_000_000);
+
+                let key = "GJ1SzoWzavQYfNL9XkaJdrQejfztN4XqdsiV4ct3LXKL";
+
+                let response = Response::default()
+                    .set_service_endpoint(_service_endpoint())
+                    .set_keys(vec![key.to_string()], vec![])
+                    .set_thread_id(&_request().id.0)
+                    .set_timing(Some(Timing::expiring_in(Duration::from_secs(60))))
+                    .encode(&key).unwrap();
+
+                let did_exchange_sm = invitee_sm().to_invitee_requested_state()
+                    .step(DidExchangeMessages::ExchangeResponseReceived(response.clone())).unwrap();
+                assert_match!(InviteeState::Completed(_), did_exchange_sm.state);
+
+                clock.clock.advance(Duration::from_secs(61));
+
+                let did_exchange_sm = invitee_sm().to_invitee_requested_state()
+                    .step(DidExchangeMessages::ExchangeResponseReceived(response)).unwrap();
+                assert_match!(InviteeState::Null(_), did_exchange_sm.state);
+            }
+
             #[test]
             #[cfg(feature = "general_test")]
             fn test_did_exchange_handle_invalid_response_message_from_requested_state() {
@@ -476,7 +711,7 @@ pub mod test {
                 let mut did_exchange_sm = invitee_sm().to_invitee_completed_state();
 
                 // Send Ping
-                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::SendPing(None)).unwrap();
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::SendPing(Ping::create().request_response())).unwrap();
                 assert_match!(InviteeState::Completed(_), did_exchange_sm.state);
 
                 // Ping
@@ -488,7 +723,7 @@ pub mod test {
                 assert_match!(InviteeState::Completed(_), did_exchange_sm.state);
 
                 // Discovery Features
-                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::DiscoverFeatures((None, None))).unwrap();
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::DiscoverFeatures(Query::create())).unwrap();
                 assert_match!(InviteeState::Completed(_), did_exchange_sm.state);
 
                 // Query
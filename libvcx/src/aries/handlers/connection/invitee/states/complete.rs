@@ -3,9 +3,10 @@ use crate::aries::handlers::connection::agent_info::AgentInfo;
 use crate::aries::handlers::connection::invitee::state_machine::InviteeState;
 use crate::aries::handlers::connection::messages::DidExchangeMessages;
 use crate::aries::handlers::connection::util::handle_ping;
-use crate::aries::messages::a2a::protocol_registry::ProtocolRegistry;
+use crate::aries::messages::a2a::A2AMessage;
+use crate::aries::messages::a2a::protocol_registry::disclose_for_query;
 use crate::aries::messages::connection::did_doc::DidDoc;
-use crate::aries::messages::discovery::disclose::{Disclose, ProtocolDescriptor};
+use crate::aries::messages::discovery::disclose::ProtocolDescriptor;
 use crate::aries::messages::discovery::query::Query;
 use crate::aries::messages::trust_ping::ping::Ping;
 
@@ -13,20 +14,22 @@ use crate::aries::messages::trust_ping::ping::Ping;
 pub struct CompleteState {
     pub did_doc: DidDoc,
     pub protocols: Option<Vec<ProtocolDescriptor>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attached_request: Option<A2AMessage>,
 }
 
 impl From<(CompleteState, Vec<ProtocolDescriptor>)> for CompleteState {
     fn from((state, protocols): (CompleteState, Vec<ProtocolDescriptor>)) -> CompleteState {
         trace!("ConnectionInvitee: transit state from CompleteState to CompleteState");
-        CompleteState { did_doc: state.did_doc, protocols: Some(protocols) }
+        CompleteState { did_doc: state.did_doc, protocols: Some(protocols), attached_request: state.attached_request }
     }
 }
 
 impl CompleteState {
     pub fn handle_message(self, message: DidExchangeMessages, agent_info: &AgentInfo) -> VcxResult<InviteeState> {
         Ok(match message {
-            DidExchangeMessages::SendPing(comment) => {
-                self.handle_send_ping(comment, agent_info)?;
+            DidExchangeMessages::SendPing(ping) => {
+                self.handle_send_ping(ping, agent_info)?;
                 InviteeState::Completed(self)
             }
             DidExchangeMessages::PingReceived(ping) => {
@@ -36,8 +39,8 @@ impl CompleteState {
             DidExchangeMessages::PingResponseReceived(_) => {
                 InviteeState::Completed(self)
             }
-            DidExchangeMessages::DiscoverFeatures((query_, comment)) => {
-                self.handle_discover_features(query_, comment, agent_info)?;
+            DidExchangeMessages::DiscoverFeatures(query_) => {
+                self.handle_discover_features(query_, agent_info)?;
                 InviteeState::Completed(self)
             }
             DidExchangeMessages::QueryReceived(query) => {
@@ -53,12 +56,7 @@ impl CompleteState {
         })
     }
 
-    fn handle_send_ping(&self, comment: Option<String>, agent_info: &AgentInfo) -> VcxResult<()> {
-        let ping =
-            Ping::create()
-                .request_response()
-                .set_comment(comment);
-
+    fn handle_send_ping(&self, ping: Ping, agent_info: &AgentInfo) -> VcxResult<()> {
         agent_info.send_message(&ping.to_a2a_message(), &self.did_doc).ok();
         Ok(())
     }
@@ -67,22 +65,12 @@ impl CompleteState {
         handle_ping(ping, agent_info, &self.did_doc)
     }
 
-    fn handle_discover_features(&self, query: Option<String>, comment: Option<String>, agent_info: &AgentInfo) -> VcxResult<()> {
-        let query_ =
-            Query::create()
-                .set_query(query)
-                .set_comment(comment);
-
+    fn handle_discover_features(&self, query_: Query, agent_info: &AgentInfo) -> VcxResult<()> {
         agent_info.send_message(&query_.to_a2a_message(), &self.did_doc)
     }
 
     fn handle_discovery_query(&self, query: Query, agent_info: &AgentInfo) -> VcxResult<()> {
-        let protocols = ProtocolRegistry::init().get_protocols_for_query(query.query.as_ref().map(String::as_str));
-
-        let disclose = Disclose::create()
-            .set_protocols(protocols)
-            .set_thread_id(query.id.0.clone());
-
+        let disclose = disclose_for_query(&query);
         agent_info.send_message(&disclose.to_a2a_message(), &self.did_doc)
     }
 }
\ No newline at end of file
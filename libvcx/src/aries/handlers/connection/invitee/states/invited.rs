@@ -1,5 +1,6 @@
 use crate::aries::handlers::connection::invitee::states::null::NullState;
 use crate::aries::handlers::connection::invitee::states::requested::RequestedState;
+use crate::aries::messages::a2a::A2AMessage;
 use crate::aries::messages::connection::did_doc::DidDoc;
 use crate::aries::messages::connection::invite::Invitation;
 use crate::aries::messages::connection::problem_report::ProblemReport;
@@ -7,19 +8,23 @@ use crate::aries::messages::connection::request::Request;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InvitedState {
-    pub invitation: Invitation
+    pub invitation: Invitation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_did: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attached_request: Option<A2AMessage>,
 }
 
 impl From<(InvitedState, ProblemReport)> for NullState {
-    fn from((_state, _error): (InvitedState, ProblemReport)) -> NullState {
+    fn from((_state, problem_report): (InvitedState, ProblemReport)) -> NullState {
         trace!("ConnectionInvitee: transit state from InvitedState to NullState");
-        NullState {}
+        NullState { problem_report: Some(problem_report) }
     }
 }
 
 impl From<(InvitedState, Request)> for RequestedState {
     fn from((state, request): (InvitedState, Request)) -> RequestedState {
         trace!("ConnectionInvitee: transit state from InvitedState to RequestedState");
-        RequestedState { request, did_doc: DidDoc::from(state.invitation) }
+        RequestedState { request, did_doc: DidDoc::from(state.invitation), expected_did: state.expected_did, attached_request: state.attached_request }
     }
 }
@@ -1,12 +1,16 @@
 use crate::aries::handlers::connection::invitee::states::invited::InvitedState;
 use crate::aries::messages::connection::invite::Invitation;
+use crate::aries::messages::connection::problem_report::ProblemReport;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NullState {}
+pub struct NullState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub problem_report: Option<ProblemReport>,
+}
 
 impl From<(NullState, Invitation)> for InvitedState {
     fn from((_state, invitation): (NullState, Invitation)) -> InvitedState {
         trace!("ConnectionInvitee: transit state from NullState to InvitedState");
-        InvitedState { invitation }
+        InvitedState { invitation, expected_did: None, attached_request: None }
     }
 }
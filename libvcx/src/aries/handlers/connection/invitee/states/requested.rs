@@ -2,6 +2,7 @@ use crate::error::prelude::*;
 use crate::aries::handlers::connection::agent_info::AgentInfo;
 use crate::aries::handlers::connection::invitee::states::complete::CompleteState;
 use crate::aries::handlers::connection::invitee::states::null::NullState;
+use crate::aries::messages::a2a::A2AMessage;
 use crate::aries::messages::ack::Ack;
 use crate::aries::messages::connection::did_doc::DidDoc;
 use crate::aries::messages::connection::problem_report::ProblemReport;
@@ -13,20 +14,24 @@ use crate::aries::messages::trust_ping::ping::Ping;
 pub struct RequestedState {
     pub request: Request,
     pub did_doc: DidDoc,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_did: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attached_request: Option<A2AMessage>,
 }
 
 
 impl From<(RequestedState, ProblemReport)> for NullState {
-    fn from((_state, _error): (RequestedState, ProblemReport)) -> NullState {
+    fn from((_state, problem_report): (RequestedState, ProblemReport)) -> NullState {
         trace!("ConnectionInvitee: transit state from RequestedState to NullState");
-        NullState {}
+        NullState { problem_report: Some(problem_report) }
     }
 }
 
 impl From<(RequestedState, Response)> for CompleteState {
-    fn from((_state, response): (RequestedState, Response)) -> CompleteState {
+    fn from((state, response): (RequestedState, Response)) -> CompleteState {
         trace!("ConnectionInvitee: transit state from RequestedState to CompleteState");
-        CompleteState { did_doc: response.connection.did_doc, protocols: None }
+        CompleteState { did_doc: response.connection.did_doc, protocols: None, attached_request: state.attached_request }
     }
 }
 
@@ -39,10 +44,21 @@ impl RequestedState {
 
         let response: Response = response.decode(&remote_vk)?;
 
+        if let Some(ref timing) = response.timing {
+            timing.check_expiration()?;
+        }
+
         if !response.from_thread(&self.request.id.0) {
             return Err(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot handle Response: thread id does not match: {:?}", response.thread)));
         }
 
+        if let Some(ref expected_did) = self.expected_did {
+            if &response.connection.did != expected_did {
+                return Err(VcxError::from_msg(VcxErrorKind::ConnectionDidMismatch,
+                                              format!("Cannot handle Response: expected DID {}, but counterparty uses DID {}", expected_did, response.connection.did)));
+            }
+        }
+
         let message = if response.please_ack.is_some() {
             Ack::create()
                 .set_thread_id(&response.thread.thid.clone().unwrap_or_default())
@@ -2,18 +2,19 @@ use std::collections::HashMap;
 
 use crate::aries::handlers::connection::agent_info::AgentInfo;
 use crate::aries::handlers::connection::inviter::states::complete::CompleteState;
+use crate::aries::handlers::connection::inviter::states::deactivated::DeactivatedState;
 use crate::aries::handlers::connection::inviter::states::invited::InvitedState;
 use crate::aries::handlers::connection::inviter::states::null::NullState;
 use crate::aries::handlers::connection::inviter::states::responded::RespondedState;
 use crate::aries::handlers::connection::messages::DidExchangeMessages;
 use crate::aries::messages::a2a::A2AMessage;
-use crate::aries::messages::a2a::protocol_registry::ProtocolRegistry;
+use crate::aries::messages::a2a::protocol_registry::supported_protocols;
 use crate::aries::messages::connection::did_doc::DidDoc;
 use crate::aries::messages::connection::invite::Invitation;
 use crate::aries::messages::connection::problem_report::{ProblemCode, ProblemReport};
 use crate::aries::messages::discovery::disclose::ProtocolDescriptor;
-use crate::aries::messages::trust_ping::ping::Ping;
 use crate::error::prelude::*;
+use crate::settings;
 use crate::api::VcxStateType;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +30,7 @@ pub enum InviterState {
     Invited(InvitedState),
     Responded(RespondedState),
     Completed(CompleteState),
+    Deactivated(DeactivatedState),
 }
 
 impl InviterState {
@@ -38,6 +40,7 @@ impl InviterState {
             InviterState::Invited(_) => VcxStateType::VcxStateOfferSent as u32,
             InviterState::Responded(_) => VcxStateType::VcxStateRequestReceived as u32,
             InviterState::Completed(_) => VcxStateType::VcxStateAccepted as u32,
+            InviterState::Deactivated(_) => VcxStateType::VcxStateNone as u32,
         }
     }
 }
@@ -46,7 +49,7 @@ impl SmConnectionInviter {
     pub fn _build_inviter(source_id: &str) -> Self {
         SmConnectionInviter {
             source_id: source_id.to_string(),
-            state: InviterState::Null(NullState {}),
+            state: InviterState::Null(NullState { problem_report: None }),
             agent_info: AgentInfo::default(),
         }
     }
@@ -58,6 +61,13 @@ impl SmConnectionInviter {
         }
     }
 
+    pub fn is_deactivated(&self) -> bool {
+        match self.state {
+            InviterState::Deactivated(_) => true,
+            _ => false
+        }
+    }
+
     pub fn from(source_id: String, agent_info: AgentInfo, state: InviterState) -> Self {
         SmConnectionInviter {
             source_id,
@@ -70,6 +80,10 @@ impl SmConnectionInviter {
         &self.agent_info
     }
 
+    pub fn agent_info_mut(&mut self) -> &mut AgentInfo {
+        &mut self.agent_info
+    }
+
     pub fn source_id(&self) -> &str {
         &self.source_id
     }
@@ -84,6 +98,11 @@ impl SmConnectionInviter {
 
     pub fn step(self, message: DidExchangeMessages) -> VcxResult<SmConnectionInviter> {
         trace!("SmConnectionInviter::step >>> message: {:?}", message);
+        if let DidExchangeMessages::Deactivate() = message {
+            let did_doc = self.their_did_doc();
+            return Ok(SmConnectionInviter { state: InviterState::Deactivated(did_doc.into()), ..self });
+        }
+
         let SmConnectionInviter { source_id, agent_info, state } = self;
 
         trace!("SmConnectionInviter::step :: current state = {:?}", &state);
@@ -100,6 +119,18 @@ impl SmConnectionInviter {
             InviterState::Invited(ref _state) => None,
             InviterState::Responded(ref state) => Some(state.did_doc.clone()),
             InviterState::Completed(ref state) => Some(state.did_doc.clone()),
+            InviterState::Deactivated(ref state) => state.did_doc.clone(),
+        }
+    }
+
+    /**
+    Recipient key of the invitation, the Response is signed by it.
+     */
+    pub fn invitation_recipient_key(&self) -> Option<String> {
+        match self.state {
+            InviterState::Invited(ref state) => state.invitation.recipient_keys.get(0).cloned(),
+            InviterState::Responded(ref state) => Some(state.prev_agent_info.pw_vk.clone()),
+            _ => None
         }
     }
 
@@ -120,7 +151,7 @@ impl SmConnectionInviter {
     }
 
     pub fn get_protocols(&self) -> Vec<ProtocolDescriptor> {
-        ProtocolRegistry::init().protocols()
+        supported_protocols()
     }
 
     pub fn get_remote_protocols(&self) -> Option<Vec<ProtocolDescriptor>> {
@@ -142,6 +173,13 @@ impl SmConnectionInviter {
             .ok_or(VcxError::from_msg(VcxErrorKind::NotReady, "Remote Connection Verkey is not set"))
     }
 
+    pub fn problem_report(&self) -> VcxResult<ProblemReport> {
+        match self.state {
+            InviterState::Null(NullState { problem_report: Some(ref problem_report) }) => Ok(problem_report.clone()),
+            _ => Err(VcxError::from_msg(VcxErrorKind::NotReady, "Connection has not failed with a problem report"))
+        }
+    }
+
     pub fn prev_agent_info(&self) -> Option<&AgentInfo> {
         match self.state {
             InviterState::Responded(ref state) => Some(&state.prev_agent_info),
@@ -153,6 +191,15 @@ impl SmConnectionInviter {
         SmConnectionInviter::_build_inviter(source_id)
     }
 
+    fn _reject_message(state: RespondedState, err: VcxError, agent_info: &AgentInfo) -> InviterState {
+        warn!("ConnectionInviter: rejecting message in Responded state: {}", err);
+        let problem_report = ProblemReport::create()
+            .set_explain(err.to_string())
+            .set_thread_id(&state.response.thread.thid.clone().unwrap_or_default());
+        agent_info.send_message(&problem_report.to_a2a_message(), &state.did_doc).ok();
+        InviterState::Responded(state)
+    }
+
     pub fn can_handle_message(&self, message: &A2AMessage) -> bool {
         match self.state {
             InviterState::Invited(_) => {
@@ -199,7 +246,8 @@ impl SmConnectionInviter {
                 match message {
                     A2AMessage::Ping(_) => {
                         debug!("Ping message received");
-                        true
+                        // left for the application if it answers pings itself
+                        settings::auto_respond_to_ping()
                     }
                     A2AMessage::PingResponse(_) => {
                         debug!("PingResponse message received");
@@ -231,13 +279,12 @@ impl SmConnectionInviter {
             InviterState::Null(state) => {
                 match message {
                     DidExchangeMessages::Connect() => {
-                        agent_info = agent_info.create_agent()?;
+                        // the agent is already created if the local DidDoc was inspected or customized before connecting
+                        if agent_info.pw_did.is_empty() {
+                            agent_info = agent_info.create_agent()?;
+                        }
 
-                        let invite: Invitation = Invitation::create()
-                            .set_label(source_id.to_string())
-                            .set_service_endpoint(agent_info.agency_endpoint()?)
-                            .set_recipient_keys(agent_info.recipient_keys())
-                            .set_routing_keys(agent_info.routing_keys()?);
+                        let invite = Invitation::from_did_doc(source_id.to_string(), &agent_info.local_did_doc()?);
 
                         InviterState::Invited((state, invite).into())
                     }
@@ -277,27 +324,35 @@ impl SmConnectionInviter {
             InviterState::Responded(state) => {
                 match message {
                     DidExchangeMessages::AckReceived(ack) => {
-                        InviterState::Completed((state, ack).into())
+                        match state.check_thread(&ack.thread) {
+                            Ok(()) => InviterState::Completed((state, ack).into()),
+                            Err(err) => SmConnectionInviter::_reject_message(state, err, &agent_info)
+                        }
                     }
                     DidExchangeMessages::PingReceived(ping) => {
-                        state.handle_ping(&ping, &agent_info)?;
-                        InviterState::Completed((state, ping).into())
+                        match ping.thread.as_ref().map(|thread| state.check_thread(thread)).unwrap_or(Ok(())) {
+                            Ok(()) => {
+                                state.handle_ping(&ping, &agent_info)?;
+                                InviterState::Completed((state, ping).into())
+                            }
+                            Err(err) => SmConnectionInviter::_reject_message(state, err, &agent_info)
+                        }
                     }
                     DidExchangeMessages::ProblemReportReceived(problem_report) => {
                         InviterState::Null((state, problem_report).into())
                     }
-                    DidExchangeMessages::SendPing(comment) => {
-                        let ping =
-                            Ping::create()
-                                .request_response()
-                                .set_comment(comment);
-
+                    DidExchangeMessages::SendPing(ping) => {
                         agent_info.send_message(&ping.to_a2a_message(), &state.did_doc).ok();
                         InviterState::Responded(state)
                     }
                     DidExchangeMessages::PingResponseReceived(ping_response) => {
                         InviterState::Completed((state, ping_response).into())
                     }
+                    DidExchangeMessages::Connect() => {
+                        // resuming connection, re-send response with the keys already provisioned
+                        agent_info.send_message(&state.response.to_a2a_message(), &state.did_doc)?;
+                        InviterState::Responded(state)
+                    }
                     _ => {
                         InviterState::Responded(state)
                     }
@@ -306,6 +361,9 @@ impl SmConnectionInviter {
             InviterState::Completed(state) => {
                 state.handle_message(message, &agent_info)?
             }
+            InviterState::Deactivated(state) => {
+                InviterState::Deactivated(state)
+            }
         };
         Ok((new_state, agent_info))
     }
@@ -314,12 +372,15 @@ impl SmConnectionInviter {
 
 #[cfg(test)]
 pub mod test {
+    use crate::aries::messages::ack::Ack;
     use crate::aries::messages::ack::tests::_ack;
     use crate::aries::messages::connection::problem_report::tests::_problem_report;
     use crate::aries::messages::connection::request::tests::_request;
     use crate::aries::messages::connection::response::tests::_signed_response;
     use crate::aries::messages::discovery::disclose::tests::_disclose;
+    use crate::aries::messages::discovery::query::Query;
     use crate::aries::messages::discovery::query::tests::_query;
+    use crate::aries::messages::trust_ping::ping::Ping;
     use crate::aries::messages::trust_ping::ping::tests::_ping;
     use crate::aries::messages::trust_ping::ping_response::tests::_ping_response;
     use crate::aries::test::source_id;
@@ -334,6 +395,14 @@ pub mod test {
             SmConnectionInviter::new(&source_id())
         }
 
+        fn _request_ack() -> Ack {
+            _ack().set_thread_id(&_request().id.0)
+        }
+
+        fn _request_ping() -> Ping {
+            _ping().set_thread_id(_request().id.0)
+        }
+
         impl SmConnectionInviter {
             fn to_inviter_invited_state(mut self) -> SmConnectionInviter {
                 self = self.step(DidExchangeMessages::Connect()).unwrap();
@@ -349,7 +418,7 @@ pub mod test {
             fn to_inviter_completed_state(mut self) -> SmConnectionInviter {
                 self = self.step(DidExchangeMessages::Connect()).unwrap();
                 self = self.step(DidExchangeMessages::ExchangeRequestReceived(_request())).unwrap();
-                self = self.step(DidExchangeMessages::AckReceived(_ack())).unwrap();
+                self = self.step(DidExchangeMessages::AckReceived(_request_ack())).unwrap();
                 self
             }
         }
@@ -370,6 +439,7 @@ pub mod test {
         }
 
         mod step {
+            use crate::aries::handlers::connection::invitee::state_machine::test::invitee::invitee_sm;
             use crate::utils::devsetup::SetupIndyMocks;
 
             use super::*;
@@ -420,6 +490,41 @@ pub mod test {
                 assert_match!(InviterState::Responded(_), did_exchange_sm.state);
             }
 
+            #[test]
+            #[cfg(feature = "general_test")]
+            fn test_did_exchange_response_carries_customized_local_did_doc() {
+                let _setup = SetupIndyMocks::init();
+
+                let mut did_exchange_sm = inviter_sm();
+                did_exchange_sm.agent_info = did_exchange_sm.agent_info.create_agent().unwrap();
+                let mut local_did_doc = did_exchange_sm.agent_info.did_doc().unwrap();
+                local_did_doc.service[0].service_endpoint = String::from("https://mediator.example.org/endpoint");
+                let mut custom_service = local_did_doc.service[0].clone();
+                custom_service.type_ = String::from("CustomProtocol");
+                custom_service.service_endpoint = String::from("https://custom.example.org/endpoint");
+                local_did_doc.service.push(custom_service);
+                did_exchange_sm.agent_info.local_did_doc = Some(local_did_doc);
+
+                did_exchange_sm = did_exchange_sm.to_inviter_invited_state();
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::ExchangeRequestReceived(_request())).unwrap();
+                let response = match did_exchange_sm.state {
+                    InviterState::Responded(ref state) => state.response.clone(),
+                    ref state => panic!("Unexpected inviter state {:?}", state)
+                };
+
+                let mut invitee_sm = invitee_sm().to_invitee_requested_state();
+                invitee_sm = invitee_sm.step(DidExchangeMessages::ExchangeResponseReceived(response)).unwrap();
+
+                let their_did_doc = invitee_sm.their_did_doc().unwrap();
+                their_did_doc.validate().unwrap();
+                assert_eq!(their_did_doc.service.len(), 2);
+                assert_eq!(their_did_doc.get_endpoint(), "https://mediator.example.org/endpoint");
+                assert_eq!(their_did_doc.recipient_keys(), vec![did_exchange_sm.agent_info.pw_vk.clone()]);
+                assert_eq!(their_did_doc.service[1].type_, "CustomProtocol");
+                assert_eq!(their_did_doc.service[1].service_endpoint, "https://custom.example.org/endpoint");
+                assert_eq!(their_did_doc.service[1].recipient_keys, vec![did_exchange_sm.agent_info.pw_vk.clone()]);
+            }
+
             #[test]
             #[cfg(feature = "general_test")]
             fn test_did_exchange_handle_invalid_exchange_request_message_from_invited_state() {
@@ -457,7 +562,7 @@ pub mod test {
                 did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::Connect()).unwrap();
                 assert_match!(InviterState::Invited(_), did_exchange_sm.state);
 
-                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::AckReceived(_ack())).unwrap();
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::AckReceived(_request_ack())).unwrap();
                 assert_match!(InviterState::Invited(_), did_exchange_sm.state);
             }
 
@@ -481,11 +586,35 @@ pub mod test {
 
                 let mut did_exchange_sm = inviter_sm().to_inviter_responded_state();
 
-                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::PingReceived(_ping())).unwrap();
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::PingReceived(_request_ping())).unwrap();
 
                 assert_match!(InviterState::Completed(_), did_exchange_sm.state);
             }
 
+            #[test]
+            #[cfg(feature = "general_test")]
+            fn test_did_exchange_handle_ack_message_with_other_thread_from_responded_state() {
+                let _setup = SetupIndyMocks::init();
+
+                let mut did_exchange_sm = inviter_sm().to_inviter_responded_state();
+
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::AckReceived(_ack().set_thread_id("other_thread"))).unwrap();
+
+                assert_match!(InviterState::Responded(_), did_exchange_sm.state);
+            }
+
+            #[test]
+            #[cfg(feature = "general_test")]
+            fn test_did_exchange_handle_ping_message_with_other_thread_from_responded_state() {
+                let _setup = SetupIndyMocks::init();
+
+                let mut did_exchange_sm = inviter_sm().to_inviter_responded_state();
+
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::PingReceived(_ping().set_thread_id(String::from("other_thread")))).unwrap();
+
+                assert_match!(InviterState::Responded(_), did_exchange_sm.state);
+            }
+
             #[test]
             #[cfg(feature = "general_test")]
             fn test_did_exchange_handle_problem_report_message_from_responded_state() {
@@ -518,7 +647,7 @@ pub mod test {
                 let mut did_exchange_sm = inviter_sm().to_inviter_completed_state();
 
                 // Send Ping
-                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::SendPing(None)).unwrap();
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::SendPing(Ping::create().request_response())).unwrap();
                 assert_match!(InviterState::Completed(_), did_exchange_sm.state);
 
                 // Ping
@@ -530,7 +659,7 @@ pub mod test {
                 assert_match!(InviterState::Completed(_), did_exchange_sm.state);
 
                 // Discovery Features
-                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::DiscoverFeatures((None, None))).unwrap();
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::DiscoverFeatures(Query::create())).unwrap();
                 assert_match!(InviterState::Completed(_), did_exchange_sm.state);
 
                 // Query
@@ -554,6 +683,24 @@ pub mod test {
                 did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::ProblemReportReceived(_problem_report())).unwrap();
                 assert_match!(InviterState::Completed(_), did_exchange_sm.state);
             }
+
+            #[test]
+            #[cfg(feature = "general_test")]
+            fn test_did_exchange_handle_deactivate_from_completed_state() {
+                let _setup = SetupIndyMocks::init();
+
+                let mut did_exchange_sm = inviter_sm().to_inviter_completed_state();
+                let did_doc = did_exchange_sm.their_did_doc().unwrap();
+
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::Deactivate()).unwrap();
+                assert_match!(InviterState::Deactivated(_), did_exchange_sm.state);
+                assert!(did_exchange_sm.is_deactivated());
+                assert_eq!(did_doc, did_exchange_sm.their_did_doc().unwrap());
+
+                // ignore
+                did_exchange_sm = did_exchange_sm.step(DidExchangeMessages::PingReceived(_ping())).unwrap();
+                assert_match!(InviterState::Deactivated(_), did_exchange_sm.state);
+            }
         }
 
         mod find_message_to_handle {
@@ -0,0 +1,13 @@
+use crate::aries::messages::connection::did_doc::DidDoc;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeactivatedState {
+    pub did_doc: Option<DidDoc>
+}
+
+impl From<Option<DidDoc>> for DeactivatedState {
+    fn from(did_doc: Option<DidDoc>) -> DeactivatedState {
+        trace!("ConnectionInviter: transit state to DeactivatedState");
+        DeactivatedState { did_doc }
+    }
+}
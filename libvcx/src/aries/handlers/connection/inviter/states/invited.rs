@@ -2,10 +2,13 @@ use crate::error::prelude::*;
 use crate::aries::handlers::connection::agent_info::AgentInfo;
 use crate::aries::handlers::connection::inviter::states::null::NullState;
 use crate::aries::handlers::connection::inviter::states::responded::RespondedState;
+use crate::aries::messages::connection::did_doc::Service;
 use crate::aries::messages::connection::invite::Invitation;
 use crate::aries::messages::connection::problem_report::ProblemReport;
 use crate::aries::messages::connection::request::Request;
 use crate::aries::messages::connection::response::{Response, SignedResponse};
+use crate::aries::messages::timing::Timing;
+use crate::settings;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InvitedState {
@@ -13,9 +16,9 @@ pub struct InvitedState {
 }
 
 impl From<(InvitedState, ProblemReport)> for NullState {
-    fn from((_state, _error): (InvitedState, ProblemReport)) -> NullState {
+    fn from((_state, problem_report): (InvitedState, ProblemReport)) -> NullState {
         trace!("ConnectionInviter: transit state from InvitedState to NullState");
-        NullState {}
+        NullState { problem_report: Some(problem_report) }
     }
 }
 
@@ -36,17 +39,43 @@ impl InvitedState {
         let prev_agent_info = agent_info.clone();
 
         // provision a new keys
-        let new_agent_info: AgentInfo = agent_info.create_agent()?;
+        let mut new_agent_info: AgentInfo = agent_info.create_agent()?;
+
+        // DidDoc customized by the application is kept, keys of the invitation agent are replaced by the new ones
+        let rotate_key = |key: &String| {
+            if key == &agent_info.pw_vk { new_agent_info.pw_vk.clone() } else if key == &agent_info.agent_vk { new_agent_info.agent_vk.clone() } else { key.clone() }
+        };
+        let (service_endpoint, recipient_keys, routing_keys, additional_services) = match agent_info.local_did_doc {
+            Some(ref local_did_doc) => {
+                let (recipient_keys, routing_keys) = local_did_doc.resolve_keys();
+                let additional_services: Vec<Service> = local_did_doc.resolve_additional_services()
+                    .into_iter()
+                    .map(|service| Service {
+                        recipient_keys: service.recipient_keys.iter().map(rotate_key).collect(),
+                        routing_keys: service.routing_keys.iter().map(rotate_key).collect(),
+                        ..service
+                    })
+                    .collect();
+                (local_did_doc.get_endpoint(),
+                 recipient_keys.iter().map(rotate_key).collect(),
+                 routing_keys.iter().map(rotate_key).collect(),
+                 additional_services)
+            }
+            None => (new_agent_info.service_endpoint()?, new_agent_info.recipient_keys(), new_agent_info.routing_keys()?, vec![])
+        };
 
         let response = Response::create()
             .set_did(new_agent_info.pw_did.to_string())
-            .set_service_endpoint(new_agent_info.agency_endpoint()?)
-            .set_keys(new_agent_info.recipient_keys(), new_agent_info.routing_keys()?)
+            .set_service_endpoint(service_endpoint)
+            .set_keys(recipient_keys, routing_keys)
+            .add_services(additional_services)
+            .set_timing(settings::get_connection_response_ttl().map(Timing::expiring_in))
             .ask_for_ack();
 
         let signed_response = response.clone()
             .set_thread_id(&request.id.0)
             .encode(&prev_agent_info.pw_vk)?;
+        new_agent_info.signed_response = Some(signed_response.connection_sig.clone());
 
         new_agent_info.send_message(&signed_response.to_a2a_message(), &request.connection.did_doc)?;
 
@@ -7,6 +7,7 @@ use crate::aries::messages::ack::Ack;
 use crate::aries::messages::connection::did_doc::DidDoc;
 use crate::aries::messages::connection::problem_report::ProblemReport;
 use crate::aries::messages::connection::response::SignedResponse;
+use crate::aries::messages::thread::Thread;
 use crate::aries::messages::trust_ping::ping::Ping;
 use crate::aries::messages::trust_ping::ping_response::PingResponse;
 
@@ -19,9 +20,9 @@ pub struct RespondedState {
 
 
 impl From<(RespondedState, ProblemReport)> for NullState {
-    fn from((_state, _error): (RespondedState, ProblemReport)) -> NullState {
+    fn from((_state, problem_report): (RespondedState, ProblemReport)) -> NullState {
         trace!("ConnectionInviter: transit state from RespondedState to NullState");
-        NullState {}
+        NullState { problem_report: Some(problem_report) }
     }
 }
 
@@ -47,6 +48,18 @@ impl From<(RespondedState, PingResponse)> for CompleteState {
 }
 
 impl RespondedState {
+    /**
+    Ensures the message continues the thread of the Response (which is the thread started by the Request).
+     */
+    pub fn check_thread(&self, thread: &Thread) -> VcxResult<()> {
+        let thid = self.response.thread.thid.clone().unwrap_or_default();
+        if !thread.is_reply(&thid) {
+            return Err(VcxError::from_msg(VcxErrorKind::InvalidJson,
+                                          format!("Message thread id {:?} does not match connection thread id {:?}", thread.thid, thid)));
+        }
+        Ok(())
+    }
+
     pub fn handle_ping(&self, ping: &Ping, agent_info: &AgentInfo) -> VcxResult<()> {
         handle_ping(ping, agent_info, &self.did_doc)
     }
@@ -0,0 +1,24 @@
+use serde_json::Value;
+
+use crate::error::prelude::*;
+
+/**
+Upgrades connection serialized by released versions to the current `SerializableObjectWithState` JSON.
+
+Supported schemas:
+  - "1.0" written by libvcx 0.15.x, fields added since are optional so it's read as is. Connections of every state
+    serialized by that release are kept as `CONNECTION_SM_*` fixtures in `mockdata_connection`.
+
+Unknown versions are rejected, support for a schema is added together with connections captured from the release
+which wrote it.
+ */
+pub fn upgrade_connection(data: &str) -> VcxResult<Value> {
+    let legacy: Value = serde_json::from_str(data)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize legacy Connection: {}", err)))?;
+
+    match legacy["version"].as_str() {
+        Some("1.0") => Ok(legacy),
+        version => Err(VcxError::from_msg(VcxErrorKind::InvalidJson,
+                                          format!("Unsupported legacy Connection version: {:?}, supported versions are \"1.0\"", version)))
+    }
+}
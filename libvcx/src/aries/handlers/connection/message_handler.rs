@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::aries::messages::a2a::A2AMessage;
+use crate::error::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HandlerResult {
+    /// Message was consumed, it will be marked as reviewed and not offered to other handlers
+    Handled,
+    /// Message is offered to the next handler
+    Passed,
+}
+
+/**
+Application handler of messages received on a connection. Registered handlers are tried in order of their
+registration during `update_state`, before the message is offered to the connection protocol itself.
+Handlers are invoked while the connection is locked, hence they must not call back into the same connection.
+ */
+pub trait MessageHandler: Send + Sync {
+    fn handle(&self, connection_handle: u32, message: &A2AMessage) -> VcxResult<HandlerResult>;
+}
+
+lazy_static! {
+    static ref MESSAGE_HANDLERS: RwLock<MessageHandlers> = RwLock::new(MessageHandlers::default());
+}
+
+#[derive(Default)]
+struct MessageHandlers {
+    next_id: u32,
+    handlers: Vec<(u32, Arc<dyn MessageHandler>)>,
+}
+
+/**
+Registers handler at the end of the chain, returns id which can be used to unregister it.
+ */
+pub fn register_message_handler(handler: Arc<dyn MessageHandler>) -> VcxResult<u32> {
+    let mut registry = MESSAGE_HANDLERS.write()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::Common(10), "Cannot lock message handlers"))?;
+    registry.next_id += 1;
+    let id = registry.next_id;
+    registry.handlers.push((id, handler));
+    Ok(id)
+}
+
+pub fn unregister_message_handler(id: u32) -> VcxResult<()> {
+    let mut registry = MESSAGE_HANDLERS.write()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::Common(10), "Cannot lock message handlers"))?;
+    let count = registry.handlers.len();
+    registry.handlers.retain(|(handler_id, _)| *handler_id != id);
+    if registry.handlers.len() == count {
+        return Err(VcxError::from_msg(VcxErrorKind::InvalidHandle, format!("Message handler {} is not registered", id)));
+    }
+    Ok(())
+}
+
+/**
+Offers messages to registered handlers in order of their uids. Returns uids of consumed messages and the messages
+which were passed by all handlers, to be offered to the connection protocol. Failure of a handler is logged and
+the message is offered to the next handler as if it was passed, so one faulty handler doesn't block the others.
+ */
+pub fn dispatch(connection_handle: u32, mut messages: HashMap<String, A2AMessage>) -> VcxResult<(Vec<String>, HashMap<String, A2AMessage>)> {
+    let handlers: Vec<Arc<dyn MessageHandler>> = MESSAGE_HANDLERS.read()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::Common(10), "Cannot lock message handlers"))?
+        .handlers.iter()
+        .map(|(_, handler)| handler.clone())
+        .collect();
+
+    if handlers.is_empty() {
+        return Ok((vec![], messages));
+    }
+
+    let mut uids: Vec<String> = messages.keys().cloned().collect();
+    uids.sort();
+
+    let mut handled = vec![];
+    for uid in uids {
+        for handler in handlers.iter() {
+            match handler.handle(connection_handle, &messages[&uid]) {
+                Ok(HandlerResult::Handled) => {
+                    trace!("message_handler::dispatch >>> message uid: {} was handled by application handler", uid);
+                    messages.remove(&uid);
+                    handled.push(uid);
+                    break;
+                }
+                Ok(HandlerResult::Passed) => {}
+                Err(err) => warn!("message_handler::dispatch >>> application handler failed on message uid: {}, passing it on: {}", uid, err)
+            }
+        }
+    }
+    Ok((handled, messages))
+}
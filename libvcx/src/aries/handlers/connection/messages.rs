@@ -17,12 +17,14 @@ pub enum DidExchangeMessages {
     ExchangeResponseReceived(SignedResponse),
     AckReceived(Ack),
     ProblemReportReceived(ProblemReport),
-    SendPing(Option<String>),
+    SendPing(Ping),
     PingReceived(Ping),
     PingResponseReceived(PingResponse),
-    DiscoverFeatures((Option<String>, Option<String>)),
+    DiscoverFeatures(Query),
     QueryReceived(Query),
     DiscloseReceived(Disclose),
+    Deactivate(),
+    Cancel(),
     Unknown,
 }
 
@@ -1,6 +1,10 @@
 pub mod agent_info;
 pub mod connection;
+pub mod deletion_observer;
+pub mod legacy;
 pub mod messages;
+pub mod message_handler;
+pub mod send_rate_limiter;
 mod invitee;
 mod inviter;
 mod util;
@@ -117,7 +121,7 @@ pub mod tests {
                 faber.activate();
 
                 let basic_message = r#"Hi there"#;
-                connection::send_generic_message(faber.connection_handle, basic_message).unwrap();
+                connection::send_generic_message(faber.connection_handle, basic_message, None).unwrap();
 
                 alice.activate();
 
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::error::prelude::*;
+use crate::settings;
+use crate::utils::clock;
+
+lazy_static! {
+    static ref SEND_BUCKETS: Mutex<HashMap<String, TokenBucket>> = Mutex::new(HashMap::new());
+}
+
+/**
+Bucket refilled by `rate` tokens per second up to `burst`, each sent message takes one token.
+ */
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    refilled_at_millis: u64,
+}
+
+impl TokenBucket {
+    fn full(burst: u32, now_millis: u64) -> TokenBucket {
+        TokenBucket { tokens: burst as f64, refilled_at_millis: now_millis }
+    }
+
+    fn refill(&mut self, rate: u32, burst: u32, now_millis: u64) {
+        let elapsed_secs = now_millis.saturating_sub(self.refilled_at_millis) as f64 / 1000.0;
+        self.tokens = (self.tokens + elapsed_secs * rate as f64).min(burst as f64);
+        self.refilled_at_millis = now_millis;
+    }
+
+    fn has_token(&self) -> bool {
+        self.tokens >= 1.0
+    }
+
+    fn take(&mut self) {
+        self.tokens = (self.tokens - 1.0).max(0.0);
+    }
+
+    fn give_back(&mut self, burst: u32) {
+        self.tokens = (self.tokens + 1.0).min(burst as f64);
+    }
+}
+
+/**
+Sends message from the connection with the given pairwise DID by calling `send`. Fails with `RateLimited` without
+touching the agency once the connection exceeds limit configured by `send_rate_limit` and `send_rate_burst`.
+The token is checked and taken under one lock, so concurrent senders can't overdraw the bucket, and it's given
+back if the message was not sent.
+ */
+pub fn send_limited<T, F>(pw_did: &str, send: F) -> VcxResult<T>
+    where F: FnOnce() -> VcxResult<T> {
+    let (rate, burst) = match settings::get_send_rate_limit() {
+        Some(limit) => limit,
+        None => return send()
+    };
+
+    {
+        let now_millis = clock::now_millis();
+        let mut buckets = SEND_BUCKETS.lock()?;
+        let bucket = buckets.entry(pw_did.to_string()).or_insert_with(|| TokenBucket::full(burst, now_millis));
+        bucket.refill(rate, burst, now_millis);
+        if !bucket.has_token() {
+            return Err(VcxError::from_msg(VcxErrorKind::RateLimited,
+                                          format!("Connection {} exceeded send rate limit of {} messages per second", pw_did, rate)));
+        }
+        bucket.take();
+    }
+
+    // the lock is not held while the message is sent
+    send().map_err(|err| {
+        if let Ok(mut buckets) = SEND_BUCKETS.lock() {
+            // bucket is gone if the connection was released meanwhile
+            if let Some(bucket) = buckets.get_mut(pw_did) {
+                bucket.give_back(burst);
+            }
+        }
+        err
+    })
+}
+
+pub fn release(pw_did: &str) -> VcxResult<()> {
+    SEND_BUCKETS.lock()?.remove(pw_did);
+    Ok(())
+}
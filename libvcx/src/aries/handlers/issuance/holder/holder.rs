@@ -21,8 +21,15 @@ impl Holder {
         Ok(Holder { holder_sm })
     }
 
-    pub fn send_request(&mut self, connection_handle: u32) -> VcxResult<()> {
-        self.step(CredentialIssuanceMessage::CredentialRequestSend(connection_handle))
+    /**
+    Sends credential request, the received credential is stored in the wallet under id generated by libindy
+    and tagged with `app_id` if given.
+     */
+    pub fn send_request(&mut self, connection_handle: u32, app_id: Option<String>) -> VcxResult<()> {
+        if app_id.as_ref().map_or(false, String::is_empty) {
+            return Err(VcxError::from_msg(VcxErrorKind::InvalidOption, "Credential application id must not be empty"));
+        }
+        self.step(CredentialIssuanceMessage::CredentialRequestSend(connection_handle, app_id))
     }
 
     pub fn maybe_update_connection_handle(&mut self, connection_handle: Option<u32>) -> u32 {
@@ -55,6 +62,10 @@ impl Holder {
         self.holder_sm.get_attributes()
     }
 
+    pub fn get_offered_attributes(&self) -> VcxResult<String> {
+        self.holder_sm.get_offered_attributes()
+    }
+
     pub fn get_attachment(&self) -> VcxResult<String> {
         self.holder_sm.get_attachment()
     }
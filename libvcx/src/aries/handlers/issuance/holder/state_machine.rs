@@ -6,6 +6,7 @@ use crate::aries::handlers::issuance::holder::states::offer_received::OfferRecei
 use crate::aries::handlers::issuance::holder::states::request_sent::RequestSentState;
 use crate::aries::handlers::issuance::messages::CredentialIssuanceMessage;
 use crate::aries::messages::a2a::A2AMessage;
+use crate::aries::messages::ack::PleaseAck;
 use crate::aries::messages::error::ProblemReport;
 use crate::aries::messages::issuance::credential::Credential;
 use crate::aries::messages::issuance::credential_ack::CredentialAck;
@@ -51,8 +52,11 @@ pub struct HolderSM {
 
 impl HolderSM {
     pub fn new(offer: CredentialOffer, source_id: String) -> Self {
+        let thread_id = offer.thread.as_ref()
+            .and_then(|thread| thread.thid.clone())
+            .unwrap_or(offer.id.0.clone());
         HolderSM {
-            thread_id: offer.id.0.clone(),
+            thread_id,
             state: HolderState::OfferReceived(OfferReceivedState::new(offer)),
             source_id,
         }
@@ -117,14 +121,14 @@ impl HolderSM {
         let HolderSM { state, source_id, thread_id } = self;
         let state = match state {
             HolderState::OfferReceived(state_data) => match cim {
-                CredentialIssuanceMessage::CredentialRequestSend(connection_handle) => {
+                CredentialIssuanceMessage::CredentialRequestSend(connection_handle, app_id) => {
                     let request = _make_credential_request(connection_handle, &state_data.offer);
                     match request {
                         Ok((cred_request, req_meta, cred_def_json)) => {
                             let cred_request = cred_request
                                 .set_thread_id(&thread_id);
                             connection::send_message(connection_handle, cred_request.to_a2a_message())?;
-                            HolderState::RequestSent((state_data, req_meta, cred_def_json, connection_handle).into())
+                            HolderState::RequestSent((state_data, req_meta, cred_def_json, connection_handle, app_id).into())
                         }
                         Err(err) => {
                             let problem_report = ProblemReport::create()
@@ -142,12 +146,15 @@ impl HolderSM {
             },
             HolderState::RequestSent(state_data) => match cim {
                 CredentialIssuanceMessage::Credential(credential) => {
-                    let result = _store_credential(&credential, &state_data.req_meta, &state_data.cred_def_json);
+                    let result = _store_credential(&credential, &state_data.req_meta, &state_data.cred_def_json, state_data.app_id.as_ref().map(String::as_str));
                     match result {
                         Ok((cred_id, rev_reg_def_json)) => {
-                            if credential.please_ack.is_some() {
-                                let ack = CredentialAck::create().set_thread_id(&thread_id);
-                                connection::send_message(state_data.connection_handle, A2AMessage::CredentialAck(ack))?;
+                            // credential is stored already, failure to ack it doesn't change the outcome
+                            if let Some(please_ack) = credential.please_ack.as_ref().filter(|please_ack| please_ack.on_receipt()) {
+                                _send_ack(state_data.connection_handle, CredentialAck::create().set_status(please_ack.receipt_status()).set_thread_id(&thread_id));
+                            }
+                            if credential.please_ack.as_ref().map_or(false, PleaseAck::on_outcome) {
+                                _send_ack(state_data.connection_handle, CredentialAck::create().set_thread_id(&thread_id));
                             }
 
                             HolderState::Finished((state_data, cred_id, credential, rev_reg_def_json).into())
@@ -219,6 +226,13 @@ impl HolderSM {
         }
     }
 
+    pub fn get_offered_attributes(&self) -> VcxResult<String> {
+        match self.state {
+            HolderState::OfferReceived(ref state) => state.get_offered_attributes(),
+            _ => Err(VcxError::from_msg(VcxErrorKind::NotReady, "Cannot get offered attributes: credential offer is not received or was already processed"))
+        }
+    }
+
     pub fn get_attachment(&self) -> VcxResult<String> {
         match self.state {
             HolderState::Finished(ref state) => state.get_attachment(),
@@ -285,7 +299,7 @@ fn _parse_rev_reg_id_from_credential(credential: &str) -> VcxResult<Option<Strin
 }
 
 fn _store_credential(credential: &Credential,
-                     req_meta: &str, cred_def_json: &str) -> VcxResult<(String, Option<String>)> {
+                     req_meta: &str, cred_def_json: &str, app_id: Option<&str>) -> VcxResult<(String, Option<String>)> {
     trace!("Holder::_store_credential >>>");
 
     let credential_json = credential.credentials_attach.content()?;
@@ -297,14 +311,28 @@ fn _store_credential(credential: &Credential,
         None
     };
 
-    let cred_id = libindy_prover_store_credential(None,
-                                                  req_meta,
-                                                  &credential_json,
-                                                  cred_def_json,
-                                                  rev_reg_def_json.as_ref().map(String::as_str))?;
+    let cred_id = match app_id {
+        Some(app_id) => anoncreds::libindy_prover_store_credential_with_app_id(None,
+                                                                              app_id,
+                                                                              req_meta,
+                                                                              &credential_json,
+                                                                              cred_def_json,
+                                                                              rev_reg_def_json.as_ref().map(String::as_str))?,
+        None => libindy_prover_store_credential(None,
+                                                req_meta,
+                                                &credential_json,
+                                                cred_def_json,
+                                                rev_reg_def_json.as_ref().map(String::as_str))?
+    };
     Ok((cred_id, rev_reg_def_json))
 }
 
+fn _send_ack(connection_handle: u32, ack: CredentialAck) {
+    if let Err(err) = connection::send_message(connection_handle, A2AMessage::CredentialAck(ack)) {
+        warn!("Failed to send credential ack: {}", err);
+    }
+}
+
 fn _delete_credential(cred_id: &str) -> VcxResult<()> {
     trace!("Holder::_delete_credential >>> cred_id: {}", cred_id);
 
@@ -332,9 +360,16 @@ fn _make_credential_request(conn_handle: u32, offer: &CredentialOffer) -> VcxRes
 
 #[cfg(test)]
 mod test {
+    use std::rc::Rc;
+
+    use agency_client::error::{AgencyClientError, AgencyClientErrorKind};
+    use agency_client::httpclient::{self, Transport};
+    use agency_client::mocking::MockTransport;
+
+    use crate::aries::messages::ack::AckOn;
     use crate::aries::handlers::connection::tests::mock_connection;
     use crate::aries::messages::issuance::credential::tests::_credential;
-    use crate::aries::messages::issuance::credential_offer::tests::_credential_offer;
+    use crate::aries::messages::issuance::credential_offer::tests::{_credential_offer, _credential_offer_starting_thread};
     use crate::aries::messages::issuance::credential_proposal::tests::_credential_proposal;
     use crate::aries::messages::issuance::credential_request::tests::_credential_request;
     use crate::aries::messages::issuance::test::{_ack, _problem_report};
@@ -345,17 +380,17 @@ mod test {
     use super::*;
 
     fn _holder_sm() -> HolderSM {
-        HolderSM::new(_credential_offer(), source_id())
+        HolderSM::new(_credential_offer_starting_thread(), source_id())
     }
 
     impl HolderSM {
         fn to_request_sent_state(mut self) -> HolderSM {
-            self = self.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection())).unwrap();
+            self = self.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection(), None)).unwrap();
             self
         }
 
         fn to_finished_state(mut self) -> HolderSM {
-            self = self.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection())).unwrap();
+            self = self.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection(), None)).unwrap();
             self = self.handle_message(CredentialIssuanceMessage::Credential(_credential())).unwrap();
             self
         }
@@ -374,6 +409,19 @@ mod test {
             assert_match!(HolderState::OfferReceived(_), holder_sm.state);
             assert_eq!(source_id(), holder_sm.get_source_id());
         }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_holder_new_uses_thread_of_counter_offer() {
+            let _setup = SetupMocks::init();
+
+            let counter_offer = _credential_offer().set_thread_id("proposal_thread_id");
+            let holder_sm = HolderSM::new(counter_offer, source_id());
+            assert_eq!("proposal_thread_id", holder_sm.thread_id);
+
+            let holder_sm = HolderSM::new(_credential_offer_starting_thread(), source_id());
+            assert_eq!(_credential_offer().id.0, holder_sm.thread_id);
+        }
     }
 
     mod step {
@@ -394,7 +442,7 @@ mod test {
             let _setup = SetupMocks::init();
 
             let mut holder_sm = _holder_sm();
-            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection())).unwrap();
+            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection(), None)).unwrap();
 
             assert_match!(HolderState::RequestSent(_), holder_sm.state);
         }
@@ -407,7 +455,7 @@ mod test {
             let credential_offer = CredentialOffer::create().set_offers_attach(r#"{"credential offer": {}}"#).unwrap();
 
             let mut holder_sm = HolderSM::new(credential_offer, "test source".to_string());
-            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection())).unwrap();
+            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection(), None)).unwrap();
 
             assert_match!(HolderState::Finished(_), holder_sm.state);
             assert_eq!(Status::Failed(ProblemReport::default()).code(), holder_sm.credential_status());
@@ -433,20 +481,63 @@ mod test {
             let _setup = SetupMocks::init();
 
             let mut holder_sm = _holder_sm();
-            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection())).unwrap();
+            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection(), None)).unwrap();
             holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::Credential(_credential())).unwrap();
 
             assert_match!(HolderState::Finished(_), holder_sm.state);
             assert_eq!(Status::Success.code(), holder_sm.credential_status());
         }
 
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_holder_acks_credential_on_requested_conditions() {
+            let _setup = SetupMocks::init();
+            let transport = Rc::new(MockTransport::default());
+            httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+            for (please_ack, acks) in vec![(None, 0),
+                                           (Some(PleaseAck::default()), 1),
+                                           (Some(PleaseAck::on(vec![AckOn::Receipt])), 1),
+                                           (Some(PleaseAck::on(vec![AckOn::Outcome])), 1),
+                                           (Some(PleaseAck::on(vec![AckOn::Receipt, AckOn::Outcome])), 2)] {
+                let mut holder_sm = _holder_sm().to_request_sent_state();
+                let sent = transport.sent_messages().len();
+
+                let mut credential = _credential();
+                credential.please_ack = please_ack;
+                holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::Credential(credential)).unwrap();
+
+                assert_eq!(Status::Success.code(), holder_sm.credential_status());
+                assert_eq!(transport.sent_messages().len() - sent, acks);
+            }
+
+            // credential which can't be stored is answered by problem report only
+            let holder_sm = _holder_sm().to_request_sent_state();
+            let sent = transport.sent_messages().len();
+            let credential = Credential::create().ask_for_ack_on(vec![AckOn::Receipt, AckOn::Outcome]);
+            let holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::Credential(credential)).unwrap();
+            assert_eq!(Status::Failed(ProblemReport::default()).code(), holder_sm.credential_status());
+            assert_eq!(transport.sent_messages().len() - sent, 1);
+
+            // failing to deliver acks doesn't fail stored credential
+            let holder_sm = _holder_sm().to_request_sent_state();
+            transport.queue_response(Err(AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, "connection refused")));
+            transport.queue_response(Err(AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, "connection refused")));
+            let credential = _credential().ask_for_ack_on(vec![AckOn::Receipt, AckOn::Outcome]);
+            let holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::Credential(credential)).unwrap();
+            assert_match!(HolderState::Finished(_), holder_sm.state);
+            assert_eq!(Status::Success.code(), holder_sm.credential_status());
+
+            httpclient::set_mock_transport(None);
+        }
+
         #[test]
         #[cfg(feature = "general_test")]
         fn test_issuer_handle_invalid_credential_message_from_request_sent_state() {
             let _setup = SetupMocks::init();
 
             let mut holder_sm = _holder_sm();
-            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection())).unwrap();
+            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection(), None)).unwrap();
             holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::Credential(Credential::create())).unwrap();
 
             assert_match!(HolderState::Finished(_), holder_sm.state);
@@ -459,7 +550,7 @@ mod test {
             let _setup = SetupMocks::init();
 
             let mut holder_sm = _holder_sm();
-            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection())).unwrap();
+            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection(), None)).unwrap();
             holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::ProblemReport(_problem_report())).unwrap();
 
             assert_match!(HolderState::Finished(_), holder_sm.state);
@@ -472,7 +563,7 @@ mod test {
             let _setup = SetupMocks::init();
 
             let mut holder_sm = _holder_sm();
-            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection())).unwrap();
+            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection(), None)).unwrap();
 
             holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialOffer(_credential_offer())).unwrap();
             assert_match!(HolderState::RequestSent(_), holder_sm.state);
@@ -487,7 +578,7 @@ mod test {
             let _setup = SetupMocks::init();
 
             let mut holder_sm = _holder_sm();
-            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection())).unwrap();
+            holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialRequestSend(mock_connection(), None)).unwrap();
             holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::Credential(_credential())).unwrap();
 
             holder_sm = holder_sm.handle_message(CredentialIssuanceMessage::CredentialOffer(_credential_offer())).unwrap();
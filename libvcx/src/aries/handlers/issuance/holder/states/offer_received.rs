@@ -10,13 +10,14 @@ pub struct OfferReceivedState {
     pub offer: CredentialOffer
 }
 
-impl From<(OfferReceivedState, String, String, u32)> for RequestSentState {
-    fn from((_state, req_meta, cred_def_json, connection_handle): (OfferReceivedState, String, String, u32)) -> Self {
+impl From<(OfferReceivedState, String, String, u32, Option<String>)> for RequestSentState {
+    fn from((_state, req_meta, cred_def_json, connection_handle, app_id): (OfferReceivedState, String, String, u32, Option<String>)) -> Self {
         trace!("SM is now in RequestSent state");
         RequestSentState {
             req_meta,
             cred_def_json,
             connection_handle,
+            app_id,
         }
     }
 }
@@ -48,6 +49,17 @@ impl OfferReceivedState {
         Ok(serde_json::Value::Object(new_map).to_string())
     }
 
+    pub fn get_offered_attributes(&self) -> VcxResult<String> {
+        let attributes: Vec<serde_json::Value> = self.offer.credential_preview.attributes.iter()
+            .map(|attribute| json!({
+                "name": attribute.name,
+                "value": attribute.value,
+                "mime-type": attribute.mime_type()
+            }))
+            .collect();
+        Ok(serde_json::Value::Array(attributes).to_string())
+    }
+
     pub fn get_attachment(&self) -> VcxResult<String> {
         self.offer.offers_attach.content()
     }
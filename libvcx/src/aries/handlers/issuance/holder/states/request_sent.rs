@@ -8,6 +8,9 @@ pub struct RequestSentState {
     pub req_meta: String,
     pub cred_def_json: String,
     pub connection_handle: u32,
+    /// Id chosen by the application the stored credential is tagged with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
 }
 
 impl From<(RequestSentState, String, Credential, Option<String>)> for FinishedHolderState {
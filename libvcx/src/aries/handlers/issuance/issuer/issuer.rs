@@ -26,10 +26,19 @@ impl Issuer {
         self.step(CredentialIssuanceMessage::CredentialInit(connection_handle, comment))
     }
 
+    pub fn send_counter_offer(&mut self, connection_handle: u32, credential_json: &str) -> VcxResult<()> {
+        self.step(CredentialIssuanceMessage::CredentialCounterOffer(connection_handle, credential_json.to_string()))
+    }
+
     pub fn send_credential(&mut self, connection_handle: u32) -> VcxResult<()> {
         self.step(CredentialIssuanceMessage::CredentialSend(connection_handle))
     }
 
+    pub fn abandon(&mut self, connection_handle: u32, comment: &str) -> VcxResult<()> {
+        self.issuer_sm = self.issuer_sm.clone().abandon(connection_handle, comment)?;
+        Ok(())
+    }
+
     pub fn get_state(&self) -> VcxResult<u32> {
         Ok(self.issuer_sm.state())
     }
@@ -46,10 +55,18 @@ impl Issuer {
         self.issuer_sm.find_message_to_handle(messages)
     }
 
-    pub fn revoke_credential(&self, publish: bool) -> VcxResult<()> {
+    pub fn revoke_credential(&mut self, publish: bool) -> VcxResult<()> {
         self.issuer_sm.revoke(publish)
     }
 
+    pub fn is_revoked(&self) -> bool {
+        self.issuer_sm.is_revoked()
+    }
+
+    pub fn send_revocation_notification(&self, connection_handle: u32, comment: Option<String>) -> VcxResult<()> {
+        self.issuer_sm.send_revocation_notification(connection_handle, comment)
+    }
+
     pub fn get_rev_reg_id(&self) -> VcxResult<String> {
         self.issuer_sm.get_rev_reg_id()
     }
@@ -6,6 +6,7 @@ use crate::aries::handlers::issuance::issuer::states::credential_sent::Credentia
 use crate::aries::handlers::issuance::issuer::states::finished::FinishedState;
 use crate::aries::handlers::issuance::issuer::states::initial::InitialState;
 use crate::aries::handlers::issuance::issuer::states::offer_sent::OfferSentState;
+use crate::aries::handlers::issuance::issuer::states::proposal_received::ProposalReceivedState;
 use crate::aries::handlers::issuance::issuer::states::requested_received::RequestReceivedState;
 use crate::aries::handlers::issuance::issuer::utils::encode_attributes;
 use crate::aries::handlers::issuance::messages::CredentialIssuanceMessage;
@@ -15,6 +16,7 @@ use crate::aries::messages::issuance::credential::Credential;
 use crate::aries::messages::issuance::credential_offer::CredentialOffer;
 use crate::aries::messages::issuance::credential_request::CredentialRequest;
 use crate::aries::messages::mime_type::MimeType;
+use crate::aries::messages::revocation_notification::revocation_notification::RevocationNotification;
 use crate::aries::messages::status::Status;
 use crate::error::{VcxError, VcxErrorKind, VcxResult};
 use crate::api::VcxStateType;
@@ -23,6 +25,7 @@ use crate::connection::send_message;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum IssuerState {
     Initial(InitialState),
+    ProposalReceived(ProposalReceivedState),
     OfferSent(OfferSentState),
     RequestReceived(RequestReceivedState),
     CredentialSent(CredentialSentState),
@@ -40,6 +43,7 @@ impl IssuerState {
     pub fn get_connection_handle(&self) -> u32 {
         match self {
             IssuerState::Initial(_) => 0,
+            IssuerState::ProposalReceived(_) => 0,
             IssuerState::OfferSent(state) => state.connection_handle,
             IssuerState::RequestReceived(state) => state.connection_handle,
             IssuerState::CredentialSent(state) => state.connection_handle,
@@ -59,6 +63,7 @@ impl IssuerState {
     pub fn thread_id(&self) -> String {
         match self {
             IssuerState::Initial(_) => String::new(),
+            IssuerState::ProposalReceived(state) => state.thread_id.clone(),
             IssuerState::OfferSent(state) => state.thread_id.clone(),
             IssuerState::RequestReceived(state) => state.thread_id.clone(),
             IssuerState::CredentialSent(state) => state.thread_id.clone(),
@@ -92,34 +97,60 @@ impl IssuerSM {
         }
     }
 
-    pub fn revoke(&self, publish: bool) -> VcxResult<()> {
+    pub fn revoke(&mut self, publish: bool) -> VcxResult<()> {
         trace!("Issuer::revoke >>> publish={}", publish);
-        match &self.state {
+        match &mut self.state {
             IssuerState::Finished(state) => {
+                if state.revoked {
+                    return Err(VcxError::from_msg(VcxErrorKind::InvalidState, "Credential has already been revoked"));
+                }
                 match &state.revocation_info_v1 {
                     Some(rev_info) => {
                         if let (Some(cred_rev_id), Some(rev_reg_id), Some(tails_file)) = (&rev_info.cred_rev_id, &rev_info.rev_reg_id, &rev_info.tails_file) {
                             if publish {
-                                anoncreds::revoke_credential(tails_file, rev_reg_id, cred_rev_id)?;
+                                let (_payment, delta) = anoncreds::revoke_credential(tails_file, rev_reg_id, cred_rev_id)?;
+                                debug!("Issuer::revoke >>> published revocation registry delta: {}", delta);
                             } else {
                                 anoncreds::revoke_credential_local(tails_file, rev_reg_id, cred_rev_id)?;
                             }
-                            Ok(())
                         } else {
                             warn!("Missing data to perform revocation. rev_info={:?}", rev_info);
-                            Err(VcxError::from(VcxErrorKind::InvalidRevocationDetails))
+                            return Err(VcxError::from(VcxErrorKind::InvalidRevocationDetails));
                         }
                     }
-                    None => Err(VcxError::from(VcxErrorKind::NotReady))
+                    None => return Err(VcxError::from(VcxErrorKind::NotReady))
                 }
+                state.revoked = true;
+                Ok(())
             }
             _ => Err(VcxError::from(VcxErrorKind::NotReady))
         }
     }
 
+    pub fn is_revoked(&self) -> bool {
+        match &self.state {
+            IssuerState::Finished(state) => state.revoked,
+            _ => false
+        }
+    }
+
+    pub fn send_revocation_notification(&self, connection_handle: u32, comment: Option<String>) -> VcxResult<()> {
+        trace!("Issuer::send_revocation_notification >>> connection_handle: {}, comment: {:?}", connection_handle, comment);
+        match &self.state {
+            IssuerState::Finished(state) if state.revoked => {
+                let notification = RevocationNotification::create()
+                    .set_credential_thread_id(&state.thread_id)
+                    .set_comment(comment);
+                send_message(connection_handle, notification.to_a2a_message())
+            }
+            _ => Err(VcxError::from_msg(VcxErrorKind::NotReady, "Credential has not been revoked"))
+        }
+    }
+
     pub fn get_rev_reg_id(&self) -> VcxResult<String> {
         let rev_registry = match &self.state {
             IssuerState::Initial(state) => state.rev_reg_id.clone(),
+            IssuerState::ProposalReceived(state) => state.rev_reg_id.clone(),
             IssuerState::OfferSent(state) => state.rev_reg_id.clone(),
             IssuerState::RequestReceived(state) => state.rev_reg_id.clone(),
             IssuerState::CredentialSent(state) => state.revocation_info_v1.clone()
@@ -146,6 +177,12 @@ impl IssuerSM {
         for (uid, message) in messages {
             match self.state {
                 IssuerState::Initial(_) => {
+                    // holder may start the issuance by proposing a credential
+                    if let A2AMessage::CredentialProposal(credential_proposal) = message {
+                        return Some((uid, A2AMessage::CredentialProposal(credential_proposal)));
+                    }
+                }
+                IssuerState::ProposalReceived(_) => {
                     // do not process messages
                 }
                 IssuerState::OfferSent(_) => {
@@ -200,6 +237,7 @@ impl IssuerSM {
     pub fn state(&self) -> u32 {
         match self.state {
             IssuerState::Initial(_) => VcxStateType::VcxStateInitialized as u32,
+            IssuerState::ProposalReceived(_) => VcxStateType::VcxStateInitialized as u32,
             IssuerState::OfferSent(_) => VcxStateType::VcxStateOfferSent as u32,
             IssuerState::RequestReceived(_) => VcxStateType::VcxStateRequestReceived as u32,
             IssuerState::CredentialSent(_) => VcxStateType::VcxStateAccepted as u32,
@@ -227,11 +265,29 @@ impl IssuerSM {
                     send_message(connection_handle, cred_offer_msg.to_a2a_message())?;
                     IssuerState::OfferSent((state_data, cred_offer, connection_handle, cred_offer_msg.id).into())
                 }
+                CredentialIssuanceMessage::CredentialProposal(proposal) => {
+                    IssuerState::ProposalReceived((state_data, proposal).into())
+                }
                 _ => {
-                    warn!("Credential Issuance can only start on issuer side with init");
+                    warn!("Credential Issuance can only start on issuer side with init or proposal");
                     IssuerState::Initial(state_data)
                 }
             }
+            IssuerState::ProposalReceived(state_data) => match cim {
+                CredentialIssuanceMessage::CredentialInit(connection_handle, comment) => {
+                    let credential_json = state_data.credential_json.clone();
+                    let cred_offer = _send_offer_on_thread(&state_data, &credential_json, connection_handle, comment)?;
+                    IssuerState::OfferSent((state_data, cred_offer, credential_json, connection_handle).into())
+                }
+                CredentialIssuanceMessage::CredentialCounterOffer(connection_handle, credential_json) => {
+                    let cred_offer = _send_offer_on_thread(&state_data, &credential_json, connection_handle, None)?;
+                    IssuerState::OfferSent((state_data, cred_offer, credential_json, connection_handle).into())
+                }
+                _ => {
+                    warn!("In this state Credential Issuance can accept only Init and CounterOffer");
+                    IssuerState::ProposalReceived(state_data)
+                }
+            }
             IssuerState::OfferSent(state_data) => match cim {
                 CredentialIssuanceMessage::CredentialRequest(request) => {
                     IssuerState::RequestReceived((state_data, request).into())
@@ -299,6 +355,29 @@ impl IssuerSM {
         Ok(IssuerSM::step(state, source_id))
     }
 
+    /**
+    Gives up on the exchange while the holder waits on the issuer, problem report with the `comment` is sent to
+    the holder and the exchange is finished. Exchange which doesn't wait on the issuer is returned unchanged.
+     */
+    pub fn abandon(self, connection_handle: u32, comment: &str) -> VcxResult<IssuerSM> {
+        trace!("IssuerSM::abandon >>> connection_handle: {}, comment: {}", connection_handle, comment);
+
+        let IssuerSM { state, source_id } = self;
+        let state = match state {
+            IssuerState::OfferSent(state_data) => {
+                let problem_report = _abandon_problem_report(connection_handle, comment, &state_data.thread_id)?;
+                IssuerState::Finished((state_data, problem_report).into())
+            }
+            IssuerState::RequestReceived(state_data) => {
+                let problem_report = _abandon_problem_report(connection_handle, comment, &state_data.thread_id)?;
+                IssuerState::Finished((state_data, problem_report).into())
+            }
+            state => state
+        };
+
+        Ok(IssuerSM::step(state, source_id))
+    }
+
     pub fn credential_status(&self) -> u32 {
         trace!("Issuer::credential_status >>>");
 
@@ -329,16 +408,50 @@ fn _append_credential_preview(cred_offer_msg: CredentialOffer, credential_json:
     let mut new_offer = cred_offer_msg;
     for item in values_map.iter() {
         let (key, value) = item;
+        let (value, mime_type) = match value {
+            // typed input such as {"photo": {"value": "<base64>", "mime-type": "image/png"}}
+            serde_json::Value::Object(typed_value) => {
+                let mime_type = typed_value.get("mime-type")
+                    .map(|mime_type| mime_type.as_str()
+                        .map(MimeType::from)
+                        .ok_or(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Problem adding credential preview data {}: mime-type is not string", key))))
+                    .transpose()?
+                    .unwrap_or_default();
+                (typed_value.get("value").and_then(serde_json::Value::as_str), mime_type)
+            }
+            _ => (value.as_str(), MimeType::Plain)
+        };
         new_offer = new_offer.add_credential_preview_data(
             key,
-            value.as_str()
-                .ok_or(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Problem adding credential preview data {}:{:?}", key, value.as_str())))?,
-            MimeType::Plain,
+            value
+                .ok_or(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Problem adding credential preview data {}:{:?}", key, value)))?,
+            mime_type,
         )?;
     }
     Ok(new_offer)
 }
 
+fn _send_offer_on_thread(state: &ProposalReceivedState, credential_json: &str, connection_handle: u32, comment: Option<String>) -> VcxResult<String> {
+    trace!("Issuer::_send_offer_on_thread >>> credential_json: {:?}, connection_handle: {}, comment: {:?}", credential_json, connection_handle, comment);
+
+    let cred_offer = libindy_issuer_create_credential_offer(&state.cred_def_id)?;
+    let cred_offer_msg = CredentialOffer::create()
+        .set_offers_attach(&cred_offer)?
+        .set_comment(comment)
+        .set_thread_id(&state.thread_id);
+    let cred_offer_msg = _append_credential_preview(cred_offer_msg, credential_json)?;
+    send_message(connection_handle, cred_offer_msg.to_a2a_message())?;
+    Ok(cred_offer)
+}
+
+fn _abandon_problem_report(connection_handle: u32, comment: &str, thread_id: &str) -> VcxResult<ProblemReport> {
+    let problem_report = ProblemReport::create()
+        .set_comment(comment.to_string())
+        .set_thread_id(thread_id);
+    send_message(connection_handle, problem_report.to_a2a_message())?;
+    Ok(problem_report)
+}
+
 fn _create_credential(request: &CredentialRequest, rev_reg_id: &Option<String>, tails_file: &Option<String>, offer: &str, cred_data: &str) -> VcxResult<(Credential, Option<String>)> {
     trace!("Issuer::_create_credential >>> request: {:?}, rev_reg_id: {:?}, tails_file: {:?}, offer: {:?}, cred_data: {:?}", request, rev_reg_id, tails_file, offer, cred_data);
 
@@ -467,6 +580,70 @@ pub mod test {
             assert_match!(IssuerState::RequestReceived(_), issuer_sm.state);
         }
 
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_issuer_handle_credential_proposal_message_from_initial_state() {
+            let _setup = SetupMocks::init();
+
+            let mut issuer_sm = _issuer_sm();
+            issuer_sm = issuer_sm.handle_message(CredentialIssuanceMessage::CredentialProposal(_credential_proposal())).unwrap();
+
+            assert_match!(IssuerState::ProposalReceived(_), issuer_sm.state);
+            assert_eq!(VcxStateType::VcxStateInitialized as u32, issuer_sm.state());
+            assert_eq!(_credential_proposal().thread.unwrap().thid.unwrap(), issuer_sm.thread_id());
+        }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_issuer_accept_proposal_sends_offer_on_proposal_thread() {
+            let _setup = SetupMocks::init();
+
+            let mut issuer_sm = _issuer_sm();
+            issuer_sm = issuer_sm.handle_message(CredentialIssuanceMessage::CredentialProposal(_credential_proposal())).unwrap();
+            issuer_sm = issuer_sm.handle_message(CredentialIssuanceMessage::CredentialInit(mock_connection(), None)).unwrap();
+
+            assert_match!(IssuerState::OfferSent(_), issuer_sm.state);
+            assert_eq!(_credential_proposal().thread.unwrap().thid.unwrap(), issuer_sm.thread_id());
+        }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_issuer_counter_offer_negotiation_to_finished_state() {
+            let _setup = SetupMocks::init();
+
+            let counter_offer = json!({"name": "bob"}).to_string();
+
+            let mut issuer_sm = _issuer_sm();
+            issuer_sm = issuer_sm.handle_message(CredentialIssuanceMessage::CredentialProposal(_credential_proposal())).unwrap();
+            issuer_sm = issuer_sm.handle_message(CredentialIssuanceMessage::CredentialCounterOffer(mock_connection(), counter_offer.clone())).unwrap();
+
+            match issuer_sm.state {
+                IssuerState::OfferSent(ref state) => assert_eq!(counter_offer, state.cred_data),
+                _ => panic!("expected OfferSent state")
+            }
+            assert_eq!(_credential_proposal().thread.unwrap().thid.unwrap(), issuer_sm.thread_id());
+
+            issuer_sm = issuer_sm.handle_message(CredentialIssuanceMessage::CredentialRequest(_credential_request())).unwrap();
+            assert_match!(IssuerState::RequestReceived(_), issuer_sm.state);
+
+            issuer_sm = issuer_sm.handle_message(CredentialIssuanceMessage::CredentialSend(mock_connection())).unwrap();
+            assert_match!(IssuerState::Finished(_), issuer_sm.state);
+            assert_eq!(Status::Success.code(), issuer_sm.credential_status());
+        }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_issuer_counter_offer_with_invalid_credential_json_fails() {
+            let _setup = SetupMocks::init();
+
+            let mut issuer_sm = _issuer_sm();
+            issuer_sm = issuer_sm.handle_message(CredentialIssuanceMessage::CredentialProposal(_credential_proposal())).unwrap();
+            let err = issuer_sm.clone().handle_message(CredentialIssuanceMessage::CredentialCounterOffer(mock_connection(), String::from("not json"))).unwrap_err();
+
+            assert_eq!(VcxErrorKind::InvalidJson, err.kind());
+            assert_match!(IssuerState::ProposalReceived(_), issuer_sm.state);
+        }
+
         #[test]
         #[cfg(feature = "general_test")]
         fn test_issuer_handle_credential_proposal_message_from_offer_sent_state() {
@@ -587,12 +764,24 @@ pub mod test {
 
             let issuer = _issuer_sm();
 
+            // CredentialProposal
+            {
+                let messages = map!(
+                    "key_1".to_string() => A2AMessage::CredentialOffer(_credential_offer()),
+                    "key_2".to_string() => A2AMessage::CredentialRequest(_credential_request()),
+                    "key_3".to_string() => A2AMessage::CredentialProposal(_credential_proposal())
+                );
+
+                let (uid, message) = issuer.find_message_to_handle(messages).unwrap();
+                assert_eq!("key_3", uid);
+                assert_match!(A2AMessage::CredentialProposal(_), message);
+            }
+
             // No messages
             {
                 let messages = map!(
                     "key_1".to_string() => A2AMessage::CredentialOffer(_credential_offer()),
                     "key_2".to_string() => A2AMessage::CredentialRequest(_credential_request()),
-                    "key_3".to_string() => A2AMessage::CredentialProposal(_credential_proposal()),
                     "key_4".to_string() => A2AMessage::Credential(_credential()),
                     "key_5".to_string() => A2AMessage::CredentialAck(_ack()),
                     "key_6".to_string() => A2AMessage::CommonProblemReport(_problem_report())
@@ -748,4 +937,121 @@ pub mod test {
             assert_eq!(_rev_reg_id(), _issuer_sm().to_finished_state().get_rev_reg_id().unwrap());
         }
     }
+
+    mod revoke {
+        use super::*;
+
+        fn _revocable_issuer_sm() -> IssuerSM {
+            let state = FinishedState {
+                cred_id: None,
+                thread_id: String::from("cred_thread_id"),
+                revocation_info_v1: Some(RevocationInfoV1 {
+                    cred_rev_id: Some(String::from("1")),
+                    rev_reg_id: Some(_rev_reg_id()),
+                    tails_file: Some(_tails_file()),
+                }),
+                status: Status::Success,
+                revoked: false,
+            };
+            IssuerSM::step(IssuerState::Finished(state), source_id())
+        }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_issuer_revoke_publishes_delta() {
+            let _setup = SetupMocks::init();
+
+            let mut issuer_sm = _revocable_issuer_sm();
+            assert!(!issuer_sm.is_revoked());
+
+            issuer_sm.revoke(true).unwrap();
+            assert!(issuer_sm.is_revoked());
+            assert_eq!(VcxStateType::VcxStateAccepted as u32, issuer_sm.state());
+        }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_issuer_revoke_fails_when_already_revoked() {
+            let _setup = SetupMocks::init();
+
+            let mut issuer_sm = _revocable_issuer_sm();
+            issuer_sm.revoke(true).unwrap();
+
+            assert_eq!(VcxErrorKind::InvalidState, issuer_sm.revoke(true).unwrap_err().kind());
+            assert!(issuer_sm.is_revoked());
+        }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_issuer_revoke_fails_before_finished_state() {
+            let _setup = SetupMocks::init();
+
+            let mut issuer_sm = _issuer_sm().to_request_received_state();
+            assert_eq!(VcxErrorKind::NotReady, issuer_sm.revoke(true).unwrap_err().kind());
+            assert!(!issuer_sm.is_revoked());
+        }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_issuer_send_revocation_notification() {
+            let _setup = SetupMocks::init();
+
+            let mut issuer_sm = _revocable_issuer_sm();
+            assert_eq!(VcxErrorKind::NotReady, issuer_sm.send_revocation_notification(mock_connection(), None).unwrap_err().kind());
+
+            issuer_sm.revoke(true).unwrap();
+            issuer_sm.send_revocation_notification(mock_connection(), Some(String::from("revoked"))).unwrap();
+        }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_finished_state_deserializes_without_revoked_flag() {
+            let _setup = SetupMocks::init();
+
+            let state: FinishedState = serde_json::from_str(r#"{"cred_id":null,"thread_id":"cred_thread_id","revocation_info_v1":null,"status":"Success"}"#).unwrap();
+            assert!(!state.revoked);
+        }
+    }
+
+    mod typed_attributes {
+        use crate::aries::handlers::issuance::holder::holder::Holder;
+
+        use super::*;
+
+        const PHOTO: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk+M9QDwADhgGAWjR9awAAAABJRU5ErkJggg==";
+
+        fn _typed_credential_json() -> String {
+            json!({
+                "name": "alice",
+                "photo": {"value": PHOTO, "mime-type": "image/png"}
+            }).to_string()
+        }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_issue_credential_with_text_and_image_attributes() {
+            let _setup = SetupMocks::init();
+
+            let issuer_sm = IssuerSM::new("test", &_typed_credential_json(), None, None, &source_id()).to_finished_state();
+            assert_match!(IssuerState::Finished(_), issuer_sm.state);
+            assert_eq!(Status::Success.code(), issuer_sm.credential_status());
+
+            let offer = _append_credential_preview(CredentialOffer::create(), &_typed_credential_json()).unwrap();
+            let holder = Holder::create(offer, &source_id()).unwrap();
+            let offered: serde_json::Value = serde_json::from_str(&holder.get_offered_attributes().unwrap()).unwrap();
+            let offered = offered.as_array().unwrap();
+            assert_eq!(2, offered.len());
+            assert!(offered.contains(&json!({"name": "name", "value": "alice", "mime-type": "text/plain"})));
+            assert!(offered.contains(&json!({"name": "photo", "value": PHOTO, "mime-type": "image/png"})));
+        }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_append_credential_preview_rejects_typed_value_without_value() {
+            let _setup = SetupMocks::init();
+
+            let credential_json = json!({"photo": {"mime-type": "image/png"}}).to_string();
+            assert_eq!(VcxErrorKind::InvalidJson, _append_credential_preview(CredentialOffer::create(), &credential_json).unwrap_err().kind());
+        }
+    }
 }
@@ -18,6 +18,7 @@ impl From<CredentialSentState> for FinishedState {
             thread_id: state.thread_id,
             revocation_info_v1: state.revocation_info_v1,
             status: Status::Success,
+            revoked: false,
         }
     }
 }
\ No newline at end of file
@@ -7,6 +7,8 @@ pub struct FinishedState {
     pub thread_id: String,
     pub revocation_info_v1: Option<RevocationInfoV1>,
     pub status: Status,
+    #[serde(default)]
+    pub revoked: bool,
 }
 
 
@@ -30,6 +30,7 @@ impl From<InitialState> for FinishedState {
             thread_id: String::new(),
             revocation_info_v1: None,
             status: Status::Undefined,
+            revoked: false,
         }
     }
 }
@@ -2,4 +2,5 @@ pub(super) mod credential_sent;
 pub(super) mod finished;
 pub(super) mod initial;
 pub(super) mod offer_sent;
-pub(super) mod requested_received;
\ No newline at end of file
+pub(super) mod proposal_received;
+pub(super) mod requested_received;
@@ -27,6 +27,7 @@ impl From<OfferSentState> for FinishedState {
                 tails_file: state.tails_file,
             }),
             status: Status::Undefined,
+            revoked: false,
         }
     }
 }
@@ -58,6 +59,7 @@ impl From<(OfferSentState, ProblemReport)> for FinishedState {
                 tails_file: state.tails_file,
             }),
             status: Status::Failed(err),
+            revoked: false,
         }
     }
 }
\ No newline at end of file
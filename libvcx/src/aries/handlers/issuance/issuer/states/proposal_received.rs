@@ -0,0 +1,44 @@
+use crate::aries::handlers::issuance::issuer::states::initial::InitialState;
+use crate::aries::handlers::issuance::issuer::states::offer_sent::OfferSentState;
+use crate::aries::messages::issuance::credential_proposal::CredentialProposal;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProposalReceivedState {
+    pub cred_def_id: String,
+    pub credential_json: String,
+    pub rev_reg_id: Option<String>,
+    pub tails_file: Option<String>,
+    pub proposal: CredentialProposal,
+    pub thread_id: String,
+}
+
+impl From<(InitialState, CredentialProposal)> for ProposalReceivedState {
+    fn from((state, proposal): (InitialState, CredentialProposal)) -> Self {
+        trace!("SM is now in ProposalReceived state");
+        let thread_id = proposal.thread.as_ref()
+            .and_then(|thread| thread.thid.clone())
+            .unwrap_or(proposal.id.0.clone());
+        ProposalReceivedState {
+            cred_def_id: state.cred_def_id,
+            credential_json: state.credential_json,
+            rev_reg_id: state.rev_reg_id,
+            tails_file: state.tails_file,
+            proposal,
+            thread_id,
+        }
+    }
+}
+
+impl From<(ProposalReceivedState, String, String, u32)> for OfferSentState {
+    fn from((state, offer, credential_json, connection_handle): (ProposalReceivedState, String, String, u32)) -> Self {
+        trace!("SM is now in OfferSent state");
+        OfferSentState {
+            offer,
+            cred_data: credential_json,
+            rev_reg_id: state.rev_reg_id,
+            tails_file: state.tails_file,
+            connection_handle,
+            thread_id: state.thread_id,
+        }
+    }
+}
@@ -44,6 +44,7 @@ impl From<(RequestReceivedState, Option<String>)> for FinishedState {
                 tails_file: state.tails_file,
             }),
             status: Status::Success,
+            revoked: false,
         }
     }
 }
@@ -60,6 +61,7 @@ impl From<(RequestReceivedState, ProblemReport)> for FinishedState {
                 tails_file: state.tails_file,
             }),
             status: Status::Failed(err),
+            revoked: false,
         }
     }
 }
\ No newline at end of file
@@ -31,6 +31,16 @@ pub fn encode_attributes(attributes: &str) -> VcxResult<String> {
 
             // new style input such as {"address2":"101 Wilson Lane"}
             serde_json::Value::String(str_type) => str_type,
+            // typed input such as {"photo":{"value":"<base64>","mime-type":"image/png"}}, raw value is kept encoded
+            serde_json::Value::Object(typed_value) => {
+                match typed_value.get("value").and_then(serde_json::Value::as_str) {
+                    Some(x) => x,
+                    None => {
+                        warn!("Cannot encode attribute: {}", error::INVALID_ATTRIBUTES_STRUCTURE.message);
+                        return Err(VcxError::from_msg(VcxErrorKind::InvalidAttributesStructure, "Attribute value not found"));
+                    }
+                }
+            }
             // anything else is an error
             _ => {
                 warn!("Invalid Json for Attribute data");
@@ -9,10 +9,11 @@ use crate::aries::messages::issuance::credential_request::CredentialRequest;
 #[derive(Debug, Clone)]
 pub enum CredentialIssuanceMessage {
     CredentialInit(u32, Option<String>),
+    CredentialCounterOffer(u32, String),
     CredentialSend(u32),
     CredentialProposal(CredentialProposal),
     CredentialOffer(CredentialOffer),
-    CredentialRequestSend(u32),
+    CredentialRequestSend(u32, Option<String>),
     CredentialRequest(CredentialRequest),
     Credential(Credential),
     CredentialAck(CredentialAck),
@@ -10,7 +10,7 @@ pub enum ProverMessages {
     PresentationRequestReceived(PresentationRequestData),
     RejectPresentationRequest((u32, String)),
     SetPresentation(Presentation),
-    PreparePresentation((String, String)),
+    PreparePresentation((String, String, Option<String>)),
     SendPresentation(u32),
     PresentationAckReceived(PresentationAck),
     PresentationRejectReceived(ProblemReport),
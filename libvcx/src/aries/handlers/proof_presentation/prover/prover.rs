@@ -3,12 +3,19 @@ use std::collections::HashMap;
 use crate::aries::handlers::proof_presentation::prover::messages::ProverMessages;
 use crate::aries::handlers::proof_presentation::prover::state_machine::ProverSM;
 use crate::aries::messages::a2a::A2AMessage;
+use crate::aries::messages::connection::did_doc::DidDoc;
 use crate::aries::messages::proof_presentation::presentation::Presentation;
 use crate::aries::messages::proof_presentation::presentation_proposal::PresentationPreview;
 use crate::aries::messages::proof_presentation::presentation_request::PresentationRequest;
 use crate::connection;
 use crate::error::prelude::*;
-use crate::libindy::utils::anoncreds;
+use crate::aries::utils::credential_store::{self, CredentialStore, WalletProfileCredentialStore};
+use crate::aries::utils::encryption_envelope::EncryptionEnvelope;
+use crate::libindy::proofs::proof_request::ProofRequestData;
+use crate::libindy::proofs::prover::prover;
+use crate::libindy::proofs::prover::prover_internal::{self, CredentialSelection};
+use crate::libindy::utils::wallet;
+use crate::utils::clock;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Prover {
@@ -30,15 +37,61 @@ impl Prover {
         self.prover_sm.presentation_status()
     }
 
-    pub fn retrieve_credentials(&self) -> VcxResult<String> {
-        trace!("Prover::retrieve_credentials >>>");
+    /**
+    Retrieves credentials matching the presentation request. With `wallet_profile` only credentials held in the wallet
+    of that profile are considered, otherwise the registered credential store is searched.
+     */
+    pub fn retrieve_credentials(&self, wallet_profile: Option<&str>) -> VcxResult<String> {
+        trace!("Prover::retrieve_credentials >>> wallet_profile: {:?}", wallet_profile);
+        match wallet_profile {
+            Some(wallet_profile) => self.retrieve_credentials_from(&WalletProfileCredentialStore::new(wallet_profile)?),
+            None => self.retrieve_credentials_from(credential_store::get_credential_store()?.as_ref())
+        }
+    }
+
+    pub fn retrieve_credentials_from(&self, store: &dyn CredentialStore) -> VcxResult<String> {
+        trace!("Prover::retrieve_credentials_from >>>");
         let presentation_request = self.presentation_request_data()?;
-        anoncreds::libindy_prover_get_credentials_for_proof_req(&presentation_request)
+        store.get_credentials_for_proof_req(&presentation_request)
+    }
+
+    /**
+    Selects credentials for the presentation, `selection` maps referents of the presentation request to the credential
+    to use: `{"<referent>": {"cred_id": "<cred_id>", "tails_file": "<optional tails file>"}}`. The result can be passed
+    to `generate_presentation`.
+     */
+    pub fn select_credentials(&self, selection: &str, wallet_profile: Option<&str>) -> VcxResult<String> {
+        trace!("Prover::select_credentials >>> selection: {}, wallet_profile: {:?}", selection, wallet_profile);
+        let selection: HashMap<String, CredentialSelection> = serde_json::from_str(selection)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize credential selection: {}", err)))?;
+        let retrieved_credentials = self.retrieve_credentials(wallet_profile)?;
+        prover_internal::select_credentials(&retrieved_credentials, &selection)
+    }
+
+    /**
+    Previews what the presentation generated from the same arguments would disclose, without generating it.
+     */
+    pub fn preview_presentation(&self, credentials: &str, self_attested_attrs: &str) -> VcxResult<PresentationPreview> {
+        trace!("Prover::preview_presentation >>> credentials: {}, self_attested_attrs: {:?}", credentials, self_attested_attrs);
+        let presentation_request = self.presentation_request_data()?;
+        let proof_request: ProofRequestData = serde_json::from_str(&presentation_request)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize proof request: {}", err)))?;
+        prover_internal::build_presentation_preview(credentials, self_attested_attrs, &proof_request)
+    }
+
+    pub fn warm_up_revocation_states(&self, credentials: &str) -> VcxResult<()> {
+        trace!("Prover::warm_up_revocation_states >>> credentials: {}", credentials);
+        prover::warm_up_revocation_states(credentials, &self.presentation_request_data()?)
     }
 
-    pub fn generate_presentation(&mut self, credentials: String, self_attested_attrs: String) -> VcxResult<()> {
-        trace!("Prover::generate_presentation >>> credentials: {}, self_attested_attrs: {:?}", credentials, self_attested_attrs);
-        self.step(ProverMessages::PreparePresentation((credentials, self_attested_attrs)))
+    /**
+    Generates presentation from selected credentials. With `wallet_profile` the proof is created in the wallet of that
+    profile, so the credentials must have been retrieved from the same profile.
+     */
+    pub fn generate_presentation(&mut self, credentials: String, self_attested_attrs: String, wallet_profile: Option<&str>) -> VcxResult<()> {
+        trace!("Prover::generate_presentation >>> credentials: {}, self_attested_attrs: {:?}, wallet_profile: {:?}", credentials, self_attested_attrs, wallet_profile);
+        wallet::get_profile_wallet_handle(wallet_profile)?;
+        self.step(ProverMessages::PreparePresentation((credentials, self_attested_attrs, wallet_profile.map(String::from))))
     }
 
     pub fn generate_presentation_msg(&self) -> VcxResult<String> {
@@ -47,6 +100,26 @@ impl Prover {
         Ok(json!(proof).to_string())
     }
 
+    /**
+    Packs generated presentation for the verifier service given in `~service` decorator of the proof request, so it can
+    be delivered later through any channel. The prover stays in PresentationPrepared state, the presentation may be
+    exported again or still sent over a connection.
+     */
+    pub fn export_presentation(&self) -> VcxResult<String> {
+        trace!("Prover::export_presentation >>>");
+        let presentation = self.prover_sm.presentation()?;
+        let service = self.prover_sm.presentation_request().service.as_ref()
+            .ok_or(VcxError::from_msg(VcxErrorKind::ActionNotSupported, "Presentation can be exported only for proof request with `~service` decorator"))?;
+
+        let mut did_doc = DidDoc::default();
+        did_doc.set_service_endpoint(service.service_endpoint.clone());
+        did_doc.set_keys(service.recipient_keys.clone(), service.routing_keys.clone().unwrap_or_default());
+
+        let envelope = EncryptionEnvelope::create(&presentation.to_a2a_message(), None, &did_doc)?;
+        String::from_utf8(envelope.0)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Packed presentation is not valid UTF-8: {}", err)))
+    }
+
     pub fn set_presentation(&mut self, presentation: Presentation) -> VcxResult<()> {
         trace!("Prover::set_presentation >>>");
         self.step(ProverMessages::SetPresentation(presentation))
@@ -120,6 +193,10 @@ impl Prover {
         Ok(presentation_requests)
     }
 
+    pub fn presentation_request(&self) -> PresentationRequest {
+        self.prover_sm.presentation_request().clone()
+    }
+
     pub fn presentation_request_data(&self) -> VcxResult<String> {
         self.prover_sm.presentation_request().request_presentations_attach.content()
     }
@@ -133,11 +210,21 @@ impl Prover {
 
     pub fn get_source_id(&self) -> String { self.prover_sm.source_id() }
 
+    pub fn get_parent_thread_id(&self) -> Option<String> {
+        self.prover_sm.presentation_request().parent_thread_id()
+    }
+
     pub fn step(&mut self, message: ProverMessages) -> VcxResult<()> {
         self.prover_sm = self.prover_sm.clone().step(message)?;
         Ok(())
     }
 
+    pub fn decline_request(&mut self, connection_handle: u32, reason: Option<String>) -> VcxResult<()> {
+        trace!("Prover::decline_request >>> connection_handle: {}, reason: {:?}", connection_handle, reason);
+        let reason = reason.unwrap_or(String::from("Presentation Request was declined"));
+        self.decline_presentation_request(connection_handle, Some(reason), None)
+    }
+
     pub fn decline_presentation_request(&mut self, connection_handle: u32, reason: Option<String>, proposal: Option<String>) -> VcxResult<()> {
         trace!("Prover::decline_presentation_request >>> connection_handle: {}, reason: {:?}, proposal: {:?}", connection_handle, reason, proposal);
         match (reason, proposal) {
@@ -164,12 +251,40 @@ impl Prover {
 mod tests {
     use crate::{libindy, settings, utils};
     use crate::aries::messages::proof_presentation::presentation_request::{PresentationRequest, PresentationRequestData};
+    use crate::aries::messages::proof_presentation::presentation_request::tests::_presentation_request;
     use crate::utils::constants::TEST_TAILS_FILE;
     use crate::utils::devsetup::*;
     use crate::utils::get_temp_dir_path;
 
     use super::*;
 
+    struct MockCredentialStore;
+
+    impl CredentialStore for MockCredentialStore {
+        fn get_credentials_for_proof_req(&self, _proof_req: &str) -> VcxResult<String> {
+            Ok(json!({"attrs": {"attribute_0": []}, "predicates": {}}).to_string())
+        }
+
+        fn store_credential(&self, _cred_id: Option<&str>, _cred_req_meta: &str, _cred_json: &str, _cred_def_json: &str, _rev_reg_def_json: Option<&str>) -> VcxResult<String> {
+            Ok(String::from("mock_cred_id"))
+        }
+
+        fn delete_credential(&self, _cred_id: &str) -> VcxResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_retrieve_credentials_from_injected_store() {
+        let _setup = SetupMocks::init();
+
+        let prover = Prover::create("1", _presentation_request()).unwrap();
+
+        let retrieved_creds = prover.retrieve_credentials_from(&MockCredentialStore).unwrap();
+        assert_eq!(json!({"attrs": {"attribute_0": []}, "predicates": {}}).to_string(), retrieved_creds);
+    }
+
     #[test]
     #[cfg(feature = "pool_tests")]
     fn test_retrieve_credentials() {
@@ -182,10 +297,40 @@ mod tests {
         let proof_req = PresentationRequest::create().set_request_presentations_attach(&pres_req_data).unwrap();
         let proof: Prover = Prover::create("1", proof_req).unwrap();
 
-        let retrieved_creds = proof.retrieve_credentials().unwrap();
+        let retrieved_creds = proof.retrieve_credentials(None).unwrap();
         assert!(retrieved_creds.len() > 500);
     }
 
+    #[test]
+    #[cfg(feature = "pool_tests")]
+    fn test_retrieve_credentials_excludes_other_wallet_profile() {
+        let _setup = SetupLibraryWalletPoolZeroFees::init();
+
+        libindy::utils::anoncreds::tests::create_and_store_credential(utils::constants::DEFAULT_SCHEMA_ATTRS, false);
+        let (_, _, req, _) = libindy::utils::anoncreds::tests::create_proof();
+
+        let main_wallet_handle = wallet::get_wallet_handle();
+        let personal_wallet = libindy::utils::tests::test_setup::setup_wallet();
+        wallet::set_wallet_handle(main_wallet_handle);
+        wallet::register_wallet_profile("personal", personal_wallet.wh).unwrap();
+        wallet::register_wallet_profile("work", main_wallet_handle).unwrap();
+
+        let pres_req_data: PresentationRequestData = serde_json::from_str(&req).unwrap();
+        let proof_req = PresentationRequest::create().set_request_presentations_attach(&pres_req_data).unwrap();
+        let proof: Prover = Prover::create("1", proof_req).unwrap();
+
+        let work_creds: serde_json::Value = serde_json::from_str(&proof.retrieve_credentials(Some("work")).unwrap()).unwrap();
+        let personal_creds: serde_json::Value = serde_json::from_str(&proof.retrieve_credentials(Some("personal")).unwrap()).unwrap();
+        let all_creds: serde_json::Value = serde_json::from_str(&proof.retrieve_credentials(None).unwrap()).unwrap();
+
+        assert!(!work_creds["attrs"]["address1_1"].as_array().unwrap().is_empty());
+        assert!(personal_creds["attrs"]["address1_1"].as_array().unwrap().is_empty());
+        assert_eq!(work_creds, all_creds);
+
+        wallet::unregister_wallet_profile("personal").unwrap();
+        wallet::unregister_wallet_profile("work").unwrap();
+    }
+
     #[cfg(feature = "pool_tests")]
     #[test]
     fn test_retrieve_credentials_emtpy() {
@@ -203,7 +348,7 @@ mod tests {
         let proof_req = PresentationRequest::create().set_request_presentations_attach(&pres_req_data).unwrap();
         let proof: Prover = Prover::create("1", proof_req).unwrap();
 
-        let retrieved_creds = proof.retrieve_credentials().unwrap();
+        let retrieved_creds = proof.retrieve_credentials(None).unwrap();
         assert_eq!(retrieved_creds, "{}".to_string());
 
         req["requested_attributes"]["address1_1"] = json!({"name": "address1"});
@@ -211,7 +356,7 @@ mod tests {
         let proof_req = PresentationRequest::create().set_request_presentations_attach(&pres_req_data).unwrap();
         let proof: Prover = Prover::create("2", proof_req).unwrap();
 
-        let retrieved_creds = proof.retrieve_credentials().unwrap();
+        let retrieved_creds = proof.retrieve_credentials(None).unwrap();
         assert_eq!(retrieved_creds, json!({"attrs":{"address1_1":[]}}).to_string());
     }
 
@@ -240,7 +385,7 @@ mod tests {
         let proof: Prover = Prover::create("1", proof_req).unwrap();
 
         // All lower case
-        let retrieved_creds = proof.retrieve_credentials().unwrap();
+        let retrieved_creds = proof.retrieve_credentials(None).unwrap();
         assert!(retrieved_creds.contains(r#""zip":"84000""#));
         let ret_creds_as_value: serde_json::Value = serde_json::from_str(&retrieved_creds).unwrap();
         assert_eq!(ret_creds_as_value["attrs"]["zip_1"][0]["cred_info"]["attrs"]["zip"], "84000");
@@ -250,7 +395,7 @@ mod tests {
         let pres_req_data: PresentationRequestData = serde_json::from_str(&req.to_string()).unwrap();
         let proof_req = PresentationRequest::create().set_request_presentations_attach(&pres_req_data).unwrap();
         let proof: Prover = Prover::create("2", proof_req).unwrap();
-        let retrieved_creds2 = proof.retrieve_credentials().unwrap();
+        let retrieved_creds2 = proof.retrieve_credentials(None).unwrap();
         assert!(retrieved_creds2.contains(r#""zip":"84000""#));
 
         // Entire word upper
@@ -258,7 +403,7 @@ mod tests {
         let pres_req_data: PresentationRequestData = serde_json::from_str(&req.to_string()).unwrap();
         let proof_req = PresentationRequest::create().set_request_presentations_attach(&pres_req_data).unwrap();
         let proof: Prover = Prover::create("1", proof_req).unwrap();
-        let retrieved_creds3 = proof.retrieve_credentials().unwrap();
+        let retrieved_creds3 = proof.retrieve_credentials(None).unwrap();
         assert!(retrieved_creds3.contains(r#""zip":"84000""#));
     }
 
@@ -269,7 +414,7 @@ mod tests {
 
         let proof_req = PresentationRequest::create();
         let proof = Prover::create("1", proof_req).unwrap();
-        assert_eq!(proof.retrieve_credentials().unwrap_err().kind(), VcxErrorKind::InvalidJson);
+        assert_eq!(proof.retrieve_credentials(None).unwrap_err().kind(), VcxErrorKind::InvalidJson);
     }
 
     #[cfg(feature = "pool_tests")]
@@ -279,7 +424,7 @@ mod tests {
 
         let did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap();
         libindy::utils::anoncreds::tests::create_and_store_credential(utils::constants::DEFAULT_SCHEMA_ATTRS, true);
-        let to = time::get_time().sec;
+        let to = clock::now_secs() as i64;
         let indy_proof_req = json!({
             "nonce": "123432421212",
             "name": "proof_req_1",
@@ -303,7 +448,7 @@ mod tests {
         let proof_req = PresentationRequest::create().set_request_presentations_attach(&pres_req_data).unwrap();
         let mut proof: Prover = Prover::create("1", proof_req).unwrap();
 
-        let all_creds: serde_json::Value = serde_json::from_str(&proof.retrieve_credentials().unwrap()).unwrap();
+        let all_creds: serde_json::Value = serde_json::from_str(&proof.retrieve_credentials(None).unwrap()).unwrap();
         let selected_credentials: serde_json::Value = json!({
            "attrs":{
               "address1_1": {
@@ -322,7 +467,7 @@ mod tests {
               "self_attested_attr_3":"attested_val"
         });
 
-        let generated_proof = proof.generate_presentation(selected_credentials.to_string(), self_attested.to_string());
+        let generated_proof = proof.generate_presentation(selected_credentials.to_string(), self_attested.to_string(), None);
         assert!(generated_proof.is_ok());
     }
 
@@ -355,7 +500,7 @@ mod tests {
               "address1_1":"attested_address",
               "zip_2": "attested_zip"
         });
-        let generated_proof = proof.generate_presentation(selected_credentials.to_string(), self_attested.to_string());
+        let generated_proof = proof.generate_presentation(selected_credentials.to_string(), self_attested.to_string(), None);
         assert!(generated_proof.is_ok());
     }
 
@@ -366,7 +511,7 @@ mod tests {
 
         let did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap();
         libindy::utils::anoncreds::tests::create_and_store_credential(utils::constants::DEFAULT_SCHEMA_ATTRS, true);
-        let to = time::get_time().sec;
+        let to = clock::now_secs() as i64;
         let indy_proof_req = json!({
             "nonce": "123432421212",
             "name": "proof_req_1",
@@ -392,7 +537,7 @@ mod tests {
         let proof_req = PresentationRequest::create().set_request_presentations_attach(&pres_req_data).unwrap();
         let mut proof: Prover = Prover::create("1", proof_req).unwrap();
 
-        let all_creds: serde_json::Value = serde_json::from_str(&proof.retrieve_credentials().unwrap()).unwrap();
+        let all_creds: serde_json::Value = serde_json::from_str(&proof.retrieve_credentials(None).unwrap()).unwrap();
         let selected_credentials: serde_json::Value = json!({
            "attrs":{
               "address1_1": {
@@ -413,7 +558,47 @@ mod tests {
         let self_attested: serde_json::Value = json!({
               "self_attested_attr_3":"attested_val"
         });
-        let generated_proof = proof.generate_presentation(selected_credentials.to_string(), self_attested.to_string());
+        let generated_proof = proof.generate_presentation(selected_credentials.to_string(), self_attested.to_string(), None);
         assert!(generated_proof.is_ok());
     }
+
+    #[cfg(feature = "pool_tests")]
+    #[test]
+    fn test_generate_proof_from_distinct_credentials() {
+        let _setup = SetupLibraryWalletPoolZeroFees::init();
+
+        let (_, _, diploma_cred_def_id, _, _, _, _, diploma_cred_id, _, _) = libindy::utils::anoncreds::tests::create_and_store_credential(utils::constants::DEFAULT_SCHEMA_ATTRS, false);
+        let (_, _, id_cred_def_id, _, _, _, _, id_cred_id, _, _) = libindy::utils::anoncreds::tests::create_and_store_credential(utils::constants::DEFAULT_SCHEMA_ATTRS, false);
+        let indy_proof_req = json!({
+            "nonce": "123432421212",
+            "name": "proof_req_1",
+            "version": "0.1",
+            "requested_attributes": {
+                "address1_1": {
+                    "name": "address1",
+                    "restrictions": [{"cred_def_id": diploma_cred_def_id}]
+                },
+                "zip_2": {
+                    "name": "zip",
+                    "restrictions": [{"cred_def_id": id_cred_def_id}]
+                }
+            },
+            "requested_predicates": {}
+        }).to_string();
+
+        let pres_req_data: PresentationRequestData = serde_json::from_str(&indy_proof_req).unwrap();
+        let proof_req = PresentationRequest::create().set_request_presentations_attach(&pres_req_data).unwrap();
+        let mut proof: Prover = Prover::create("1", proof_req).unwrap();
+
+        let selection = json!({
+            "address1_1": {"cred_id": diploma_cred_id},
+            "zip_2": {"cred_id": id_cred_id}
+        });
+        let selected_credentials = proof.select_credentials(&selection.to_string(), None).unwrap();
+        proof.generate_presentation(selected_credentials, "{}".to_string(), None).unwrap();
+
+        let presentation: Presentation = serde_json::from_str(&proof.generate_presentation_msg().unwrap()).unwrap();
+        let indy_proof: serde_json::Value = serde_json::from_str(&presentation.presentations_attach.content().unwrap()).unwrap();
+        assert_eq!(indy_proof["identifiers"].as_array().unwrap().len(), 2);
+    }
 }
@@ -5,7 +5,7 @@ use crate::connection;
 use crate::error::prelude::*;
 use crate::aries::handlers::proof_presentation::prover::messages::ProverMessages;
 use crate::aries::messages::a2a::A2AMessage;
-use crate::aries::messages::error::ProblemReport;
+use crate::aries::messages::error::{ProblemReport, REQUEST_DECLINED};
 use crate::aries::messages::proof_presentation::presentation::Presentation;
 use crate::aries::messages::proof_presentation::presentation_proposal::{PresentationPreview, PresentationProposal};
 use crate::aries::messages::proof_presentation::presentation_request::PresentationRequest;
@@ -23,11 +23,14 @@ pub struct ProverSM {
     source_id: String,
     thread_id: String,
     state: ProverState,
+    // pairwise DID of the connection, unlike the connection handle it stays valid after restart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    connection_pw_did: Option<String>,
 }
 
 impl ProverSM {
     pub fn new(presentation_request: PresentationRequest, source_id: String) -> ProverSM {
-        ProverSM { source_id, thread_id: presentation_request.id.0.clone(), state: ProverState::Initiated(InitialState { presentation_request }) }
+        ProverSM { source_id, thread_id: presentation_request.id.0.clone(), state: ProverState::Initiated(InitialState { presentation_request }), connection_pw_did: None }
     }
 }
 
@@ -93,7 +96,16 @@ impl ProverSM {
     pub fn step(self, message: ProverMessages) -> VcxResult<ProverSM> {
         trace!("ProverSM::step >>> message: {:?}", message);
 
-        let ProverSM { source_id, state, thread_id } = self;
+        let ProverSM { source_id, state, thread_id, connection_pw_did } = self;
+
+        // every message binding the protocol to a connection records its pairwise DID, so the connection is found
+        // after restart whichever way the protocol ended
+        let connection_pw_did = match message {
+            ProverMessages::SendPresentation(connection_handle) |
+            ProverMessages::RejectPresentationRequest((connection_handle, _)) |
+            ProverMessages::ProposePresentation((connection_handle, _)) => connection::get_pw_did(connection_handle).ok().or(connection_pw_did),
+            _ => connection_pw_did
+        };
 
         let state = match state {
             ProverState::Initiated(state) => {
@@ -102,8 +114,8 @@ impl ProverSM {
                         let presentation = presentation.set_thread_id(&thread_id);
                         ProverState::PresentationPrepared((state, presentation).into())
                     }
-                    ProverMessages::PreparePresentation((credentials, self_attested_attrs)) => {
-                        match state.build_presentation(&credentials, &self_attested_attrs) {
+                    ProverMessages::PreparePresentation((credentials, self_attested_attrs, wallet_profile)) => {
+                        match state.build_presentation(&credentials, &self_attested_attrs, wallet_profile.as_ref().map(String::as_str)) {
                             Ok(presentation) => {
                                 let presentation = Presentation::create()
                                     .ask_for_ack()
@@ -140,7 +152,7 @@ impl ProverSM {
                     ProverMessages::SendPresentation(connection_handle) => {
                         match state.presentation_request.service.clone() {
                             None => {
-                                connection::send_message(connection_handle, state.presentation.to_a2a_message())?;
+                                connection::send_present_proof_message(connection_handle, state.presentation.to_a2a_message(), state.presentation_request.present_proof_version())?;
                                 ProverState::PresentationSent((state, connection_handle).into())
                             }
                             Some(service) => {
@@ -167,7 +179,7 @@ impl ProverSM {
                     ProverMessages::SendPresentation(connection_handle) => {
                         match state.presentation_request.service.clone() {
                             None => {
-                                connection::send_message(connection_handle, state.problem_report.to_a2a_message())?;
+                                connection::send_present_proof_message(connection_handle, state.problem_report.to_a2a_message(), state.presentation_request.present_proof_version())?;
                             }
                             Some(service) => {
                                 connection::send_message_to_self_endpoint(state.problem_report.to_a2a_message(), &service.into())?;
@@ -200,16 +212,17 @@ impl ProverSM {
             ProverState::Finished(state) => ProverState::Finished(state)
         };
 
-        Ok(ProverSM { source_id, state, thread_id })
+        Ok(ProverSM { source_id, state, thread_id, connection_pw_did })
     }
 
     fn _handle_reject_presentation_request(connection_handle: u32, reason: &str, presentation_request: &PresentationRequest, thread_id: &str) -> VcxResult<()> {
         let problem_report = ProblemReport::create()
+            .set_description(REQUEST_DECLINED)
             .set_comment(reason.to_string())
             .set_thread_id(thread_id);
 
         match presentation_request.service.clone() {
-            None => connection::send_message(connection_handle, problem_report.to_a2a_message())?,
+            None => connection::send_present_proof_message(connection_handle, problem_report.to_a2a_message(), presentation_request.present_proof_version())?,
             Some(service) => connection::send_message_to_self_endpoint(problem_report.to_a2a_message(), &service.into())?
         }
 
@@ -264,7 +277,22 @@ impl ProverSM {
         }
     }
 
+    /**
+    Returns handle of the connection the presentation was sent over. If the connection was reloaded under a new handle,
+    e.g. after restart, it's looked up by its pairwise DID.
+     */
     pub fn connection_handle(&self) -> VcxResult<u32> {
+        let connection_handle = self.stored_connection_handle()?;
+        match self.connection_pw_did {
+            Some(ref pw_did) if connection::get_pw_did(connection_handle).ok().as_ref() != Some(pw_did) => {
+                connection::find_by_pw_did(pw_did)?
+                    .ok_or(VcxError::from_msg(VcxErrorKind::InvalidConnectionHandle, format!("Connection with pairwise DID {} is not loaded", pw_did)))
+            }
+            _ => Ok(connection_handle)
+        }
+    }
+
+    fn stored_connection_handle(&self) -> VcxResult<u32> {
         match self.state {
             ProverState::Initiated(_) => Err(VcxError::from_msg(VcxErrorKind::NotReady, "Connection handle isn't set")),
             ProverState::PresentationPrepared(_) => Err(VcxError::from_msg(VcxErrorKind::NotReady, "Connection handle isn't set")),
@@ -275,6 +303,9 @@ impl ProverSM {
     }
 
     pub fn set_connection_handle(&mut self, connection_handle: u32) {
+        if let Ok(pw_did) = connection::get_pw_did(connection_handle) {
+            self.connection_pw_did = Some(pw_did);
+        }
         match self.state {
             ProverState::PresentationSent(ref mut state) => {
                 state.connection_handle = connection_handle;
@@ -326,18 +357,18 @@ pub mod test {
 
     impl ProverSM {
         fn to_presentation_prepared_state(mut self) -> ProverSM {
-            self = self.step(ProverMessages::PreparePresentation((_credentials(), _self_attested()))).unwrap();
+            self = self.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
             self
         }
 
         fn to_presentation_sent_state(mut self) -> ProverSM {
-            self = self.step(ProverMessages::PreparePresentation((_credentials(), _self_attested()))).unwrap();
+            self = self.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
             self = self.step(ProverMessages::SendPresentation(mock_connection())).unwrap();
             self
         }
 
         fn to_finished_state(mut self) -> ProverSM {
-            self = self.step(ProverMessages::PreparePresentation((_credentials(), _self_attested()))).unwrap();
+            self = self.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
             self = self.step(ProverMessages::SendPresentation(mock_connection())).unwrap();
             self = self.step(ProverMessages::PresentationAckReceived(_ack())).unwrap();
             self
@@ -381,7 +412,14 @@ pub mod test {
     }
 
     mod step {
+        use std::rc::Rc;
+
+        use agency_client::httpclient::{self, Transport};
+        use agency_client::mocking::MockTransport;
+        use serde_json::Value;
+
         use super::*;
+        use crate::aries::messages::proof_presentation::v2;
         use crate::utils::constants::CREDS_FROM_PROOF_REQ;
         use crate::utils::mockdata::mock_settings::MockBuilder;
 
@@ -400,11 +438,40 @@ pub mod test {
             let _setup = SetupMocks::init();
 
             let mut prover_sm = _prover_sm();
-            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested()))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
 
             assert_match!(ProverState::PresentationPrepared(_), prover_sm.state);
         }
 
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_prover_answers_present_proof_2_0_request_in_2_0() {
+            let _setup = SetupMocks::init();
+            let transport = Rc::new(MockTransport::default());
+            httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+            // counterparty did not disclose its protocols, the version is taken from the received request
+            let request = serde_json::to_value(&v2::to_v2_message(A2AMessage::PresentationRequest(_presentation_request())).unwrap()).unwrap();
+            let request = match serde_json::from_value::<A2AMessage>(request).unwrap() {
+                A2AMessage::PresentationRequest(request) => request,
+                message => panic!("Unexpected message: {:?}", message)
+            };
+            let connection_handle = mock_connection();
+            assert_eq!(v2::PRESENT_PROOF_V1, connection::get_present_proof_version(connection_handle).unwrap());
+
+            let mut prover_sm = ProverSM::new(request, source_id());
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::SendPresentation(connection_handle)).unwrap();
+            assert_match!(ProverState::PresentationSent(_), prover_sm.state);
+
+            // aries messages are not encrypted in test mode
+            let sent: Value = serde_json::from_slice(&transport.sent_messages().last().unwrap().body).unwrap();
+            assert_eq!(sent["@type"], "https://didcomm.org/present-proof/2.0/presentation");
+            assert_eq!(sent["formats"][0]["format"], v2::PROOF_FORMAT);
+
+            httpclient::set_mock_transport(None);
+        }
+
         #[test]
         #[cfg(feature = "general_test")]
         fn test_prover_handle_prepare_presentation_message_from_initiated_state_for_invalid_credentials() {
@@ -413,7 +480,7 @@ pub mod test {
                 set_mock_creds_retrieved_for_proof_request(CREDS_FROM_PROOF_REQ);
 
             let mut prover_sm = _prover_sm();
-            prover_sm = prover_sm.step(ProverMessages::PreparePresentation(("invalid".to_string(), _self_attested()))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation(("invalid".to_string(), _self_attested(), None))).unwrap();
 
             assert_match!(ProverState::PresentationPreparationFailed(_), prover_sm.state);
         }
@@ -427,6 +494,7 @@ pub mod test {
             prover_sm = prover_sm.step(ProverMessages::RejectPresentationRequest((mock_connection(), String::from("reject request")))).unwrap();
 
             assert_match!(ProverState::Finished(_), prover_sm.state);
+            assert_eq!(Status::Declined.code(), prover_sm.presentation_status());
         }
 
         #[test]
@@ -440,6 +508,32 @@ pub mod test {
             assert_match!(ProverState::Finished(_), prover_sm.state);
         }
 
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_prover_resolves_connection_bound_on_any_path_after_restart() {
+            let _setup = SetupMocks::init();
+
+            let bindings: Vec<fn(u32) -> ProverMessages> = vec![
+                |connection_handle| ProverMessages::SendPresentation(connection_handle),
+                |connection_handle| ProverMessages::RejectPresentationRequest((connection_handle, String::from("reject request"))),
+                |connection_handle| ProverMessages::ProposePresentation((connection_handle, _presentation_preview())),
+            ];
+            for bind in bindings {
+                let connection_handle = mock_connection();
+                let prover_sm = _prover_sm().to_presentation_prepared_state().step(bind(connection_handle)).unwrap();
+
+                // connection is reloaded under another handle, e.g. after restart
+                let pw_did = connection::get_pw_did(connection_handle).unwrap();
+                let serialized_connection = connection::to_string(connection_handle).unwrap();
+                connection::release(connection_handle).unwrap();
+                let reloaded = connection::from_string(&serialized_connection).unwrap();
+
+                let prover_sm: ProverSM = serde_json::from_str(&serde_json::to_string(&prover_sm).unwrap()).unwrap();
+                assert_eq!(connection::get_pw_did(prover_sm.connection_handle().unwrap()).unwrap(), pw_did);
+                connection::release(reloaded).unwrap();
+            }
+        }
+
         #[test]
         #[cfg(feature = "general_test")]
         fn test_prover_handle_other_messages_from_initiated_state() {
@@ -460,7 +554,7 @@ pub mod test {
             let _setup = SetupMocks::init();
 
             let mut prover_sm = _prover_sm();
-            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested()))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
             prover_sm = prover_sm.step(ProverMessages::SendPresentation(mock_connection())).unwrap();
 
             assert_match!(ProverState::PresentationSent(_), prover_sm.state);
@@ -472,7 +566,7 @@ pub mod test {
             let _setup = SetupMocks::init();
 
             let mut prover_sm = ProverSM::new(_presentation_request_with_service(), source_id());
-            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested()))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
             prover_sm = prover_sm.step(ProverMessages::SendPresentation(mock_connection())).unwrap();
 
             assert_match!(ProverState::Finished(_), prover_sm.state);
@@ -522,7 +616,7 @@ pub mod test {
                 set_mock_creds_retrieved_for_proof_request(CREDS_FROM_PROOF_REQ);
 
             let mut prover_sm = _prover_sm();
-            prover_sm = prover_sm.step(ProverMessages::PreparePresentation(("invalid".to_string(), _self_attested()))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation(("invalid".to_string(), _self_attested(), None))).unwrap();
             assert_match!(ProverState::PresentationPreparationFailed(_), prover_sm.state);
 
             prover_sm = prover_sm.step(ProverMessages::SendPresentation(mock_connection())).unwrap();
@@ -538,7 +632,7 @@ pub mod test {
                 set_mock_creds_retrieved_for_proof_request(CREDS_FROM_PROOF_REQ);
 
             let mut prover_sm = _prover_sm();
-            prover_sm = prover_sm.step(ProverMessages::PreparePresentation(("invalid".to_string(), _self_attested()))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation(("invalid".to_string(), _self_attested(), None))).unwrap();
 
             prover_sm = prover_sm.step(ProverMessages::PresentationRejectReceived(_problem_report())).unwrap();
             assert_match!(ProverState::PresentationPreparationFailed(_), prover_sm.state);
@@ -553,7 +647,7 @@ pub mod test {
             let _setup = SetupMocks::init();
 
             let mut prover_sm = _prover_sm();
-            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested()))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
             prover_sm = prover_sm.step(ProverMessages::SendPresentation(mock_connection())).unwrap();
             prover_sm = prover_sm.step(ProverMessages::PresentationAckReceived(_ack())).unwrap();
 
@@ -577,7 +671,7 @@ pub mod test {
             let _setup = SetupMocks::init();
 
             let mut prover_sm = _prover_sm();
-            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested()))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
             prover_sm = prover_sm.step(ProverMessages::SendPresentation(mock_connection())).unwrap();
             prover_sm = prover_sm.step(ProverMessages::PresentationRejectReceived(_problem_report())).unwrap();
 
@@ -591,10 +685,10 @@ pub mod test {
             let _setup = SetupMocks::init();
 
             let mut prover_sm = _prover_sm();
-            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested()))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
             prover_sm = prover_sm.step(ProverMessages::SendPresentation(mock_connection())).unwrap();
 
-            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested()))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
             assert_match!(ProverState::PresentationSent(_), prover_sm.state);
 
             prover_sm = prover_sm.step(ProverMessages::SendPresentation(mock_connection())).unwrap();
@@ -607,7 +701,7 @@ pub mod test {
             let _setup = SetupMocks::init();
 
             let mut prover_sm = _prover_sm();
-            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested()))).unwrap();
+            prover_sm = prover_sm.step(ProverMessages::PreparePresentation((_credentials(), _self_attested(), None))).unwrap();
             prover_sm = prover_sm.step(ProverMessages::SendPresentation(mock_connection())).unwrap();
             prover_sm = prover_sm.step(ProverMessages::PresentationAckReceived(_ack())).unwrap();
 
@@ -13,10 +13,11 @@ pub struct InitialState {
 
 
 impl InitialState {
-    pub fn build_presentation(&self, credentials: &str, self_attested_attrs: &str) -> VcxResult<String> {
+    pub fn build_presentation(&self, credentials: &str, self_attested_attrs: &str, wallet_profile: Option<&str>) -> VcxResult<String> {
         generate_indy_proof(credentials,
                                             self_attested_attrs,
-                                            &self.presentation_request.request_presentations_attach.content()?)
+                                            &self.presentation_request.request_presentations_attach.content()?,
+                                            wallet_profile)
     }
 }
 
@@ -0,0 +1,144 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+
+use crate::aries::messages::proof_presentation::presentation_request::{PresentationRequest, PresentationRequestData};
+use crate::error::prelude::*;
+
+/*
+Nonces of proof requests for which a valid presentation was already received. A presentation can be verified only
+against the nonce of its proof request, so a presentation for an already consumed nonce is a replay.
+The set is bounded, the oldest nonces are forgotten first. It lives in memory only, applications wanting to keep it
+across restarts can export it and import it back.
+ */
+
+pub const MAX_CONSUMED_NONCES: usize = 10_000;
+
+lazy_static! {
+    static ref CONSUMED_NONCES: Mutex<ConsumedNonces> = Mutex::new(ConsumedNonces::default());
+}
+
+#[derive(Default)]
+struct ConsumedNonces {
+    order: VecDeque<String>,
+    nonces: HashSet<String>,
+}
+
+impl ConsumedNonces {
+    fn insert(&mut self, nonce: &str) -> bool {
+        if !self.nonces.insert(nonce.to_string()) {
+            return false;
+        }
+        self.order.push_back(nonce.to_string());
+        while self.order.len() > MAX_CONSUMED_NONCES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.nonces.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+pub fn request_nonce(presentation_request: &PresentationRequest) -> VcxResult<String> {
+    let request_data: PresentationRequestData = serde_json::from_str(&presentation_request.request_presentations_attach.content()?)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize proof request: {:?}", err)))?;
+    Ok(request_data.nonce)
+}
+
+pub fn is_consumed(nonce: &str) -> VcxResult<bool> {
+    Ok(CONSUMED_NONCES.lock()?.nonces.contains(nonce))
+}
+
+/**
+Marks nonce as consumed, fails with ReplayedPresentation if it already was. Requests without nonce are not tracked.
+ */
+pub fn consume(nonce: &str) -> VcxResult<()> {
+    if nonce.is_empty() {
+        return Ok(());
+    }
+    if !CONSUMED_NONCES.lock()?.insert(nonce) {
+        return Err(VcxError::from_msg(VcxErrorKind::ReplayedPresentation, format!("Presentation for nonce {} was already received", nonce)));
+    }
+    Ok(())
+}
+
+/**
+Store of consumed nonces used by the stateless verification. Verifier instances scaled across processes have to
+share one store, e.g. backed by their database, so a presentation verified by one of them is a replay for the others.
+ */
+pub trait NonceStore {
+    /**
+    Atomically marks nonce as consumed, fails with ReplayedPresentation if it already was.
+     */
+    fn consume(&self, nonce: &str) -> VcxResult<()>;
+}
+
+/**
+Store of consumed nonces of this process only.
+ */
+pub struct ProcessNonceStore;
+
+impl NonceStore for ProcessNonceStore {
+    fn consume(&self, nonce: &str) -> VcxResult<()> {
+        consume(nonce)
+    }
+}
+
+/**
+Returns consumed nonces from the oldest to the newest.
+ */
+pub fn export_consumed_nonces() -> VcxResult<Vec<String>> {
+    Ok(CONSUMED_NONCES.lock()?.order.iter().cloned().collect())
+}
+
+pub fn import_consumed_nonces(nonces: Vec<String>) -> VcxResult<()> {
+    let mut consumed_nonces = CONSUMED_NONCES.lock()?;
+    for nonce in nonces.iter().filter(|nonce| !nonce.is_empty()) {
+        consumed_nonces.insert(nonce);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::utils::devsetup::SetupDefaults;
+
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_consume_nonce_twice_fails() {
+        let _setup = SetupDefaults::init();
+
+        consume("consumed_nonces_test_1").unwrap();
+        assert!(is_consumed("consumed_nonces_test_1").unwrap());
+        assert_eq!(consume("consumed_nonces_test_1").unwrap_err().kind(), VcxErrorKind::ReplayedPresentation);
+
+        consume("").unwrap();
+        consume("").unwrap();
+        assert!(!is_consumed("").unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_consumed_nonces_are_bounded() {
+        let _setup = SetupDefaults::init();
+
+        let mut consumed_nonces = ConsumedNonces::default();
+        for i in 0..MAX_CONSUMED_NONCES + 1 {
+            assert!(consumed_nonces.insert(&i.to_string()));
+        }
+        assert_eq!(consumed_nonces.nonces.len(), MAX_CONSUMED_NONCES);
+        assert!(!consumed_nonces.nonces.contains("0"));
+        assert!(consumed_nonces.insert("0"));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_import_consumed_nonces() {
+        let _setup = SetupDefaults::init();
+
+        import_consumed_nonces(vec![String::from("consumed_nonces_test_2")]).unwrap();
+        assert!(export_consumed_nonces().unwrap().contains(&String::from("consumed_nonces_test_2")));
+        assert_eq!(consume("consumed_nonces_test_2").unwrap_err().kind(), VcxErrorKind::ReplayedPresentation);
+    }
+}
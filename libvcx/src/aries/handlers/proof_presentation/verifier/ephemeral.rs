@@ -0,0 +1,194 @@
+use crate::aries::messages::a2a::A2AMessage;
+use crate::aries::messages::connection::service::Service;
+use crate::aries::messages::proof_presentation::presentation::Presentation;
+use crate::aries::messages::proof_presentation::presentation_request::{PresentationRequest, PresentationRequestData};
+use crate::aries::handlers::proof_presentation::verifier::consumed_nonces::{self, NonceStore};
+use crate::aries::utils::ephemeral_key::EphemeralKey;
+use crate::aries::utils::wire_observer;
+use crate::error::prelude::*;
+use crate::libindy::proofs::verifier::verifier::validate_indy_proof;
+use crate::libindy::proofs::verifier::verifier_internal::TrustedIssuers;
+
+/*
+Stateless connectionless verification. The proof request carries `~service` decorator, so the prover sends
+the presentation directly to the given endpoint and no connection is established. No connection or proof handle
+is created and nothing is written to the wallet: every request is addressed to a fresh key pair held in memory,
+so requests can't be linked to the institution. The caller keeps the request and its key pair until the presentation
+arrives, any verifier instance given both can verify it. Nonce of the request is consumed in the passed nonce store
+once its presentation is verified, instances sharing the store reject presentations replayed to any of them.
+ */
+
+/**
+Builds connectionless proof request which instructs the prover to send the presentation to `service_endpoint`,
+encrypted for a newly generated key pair. The key pair is returned along with the request.
+ */
+pub fn create_request(presentation_request_data: &PresentationRequestData, service_endpoint: &str, comment: Option<String>) -> VcxResult<(PresentationRequest, EphemeralKey)> {
+    trace!("ephemeral::create_request >>> presentation_request_data: {:?}, service_endpoint: {:?}", presentation_request_data, service_endpoint);
+
+    let recipient_key = EphemeralKey::generate()?;
+    let service = Service::create()
+        .set_service_endpoint(service_endpoint.to_string())
+        .set_recipient_keys(vec![recipient_key.verkey.clone()])
+        .set_routing_keys(vec![]);
+
+    let presentation_request = PresentationRequest::create()
+        .set_request_presentations_attach(presentation_request_data)?
+        .set_service(Some(service));
+
+    let presentation_request = match comment {
+        Some(comment) => presentation_request.set_comment(comment),
+        None => presentation_request
+    };
+    Ok((presentation_request, recipient_key))
+}
+
+/**
+Unpacks presentation received at the endpoint of the proof request with the key pair created along with the request
+and verifies it against the request. Returns the verified presentation, fails with InvalidProof if it's not valid
+or contains credentials of untrusted issuers and with ReplayedPresentation if `nonce_store` already consumed
+the request nonce. Acknowledgement requested by the prover is not sent, as there is no connection to send it over.
+ */
+pub fn verify_presentation(presentation_request: &PresentationRequest, recipient_key: &EphemeralKey, packed_presentation: Vec<u8>,
+                           trusted_issuers: Option<&TrustedIssuers>, nonce_store: &dyn NonceStore) -> VcxResult<Presentation> {
+    trace!("ephemeral::verify_presentation >>> presentation_request: {:?}, recipient_key: {:?}, trusted_issuers: {:?}", presentation_request, recipient_key, trusted_issuers);
+
+    let (message, _sender_vk) = recipient_key.unpack(&packed_presentation)?;
+    wire_observer::notify_unpacked(&message);
+    let message: A2AMessage = serde_json::from_str(&message)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize A2A message: {}", err)))?;
+
+    let presentation = match message {
+        A2AMessage::Presentation(presentation) => presentation,
+        A2AMessage::CommonProblemReport(problem_report) => {
+            return Err(VcxError::from_msg(VcxErrorKind::InvalidMessages,
+                                          format!("Prover rejected the proof request: {}", problem_report.comment.unwrap_or_default())));
+        }
+        message => {
+            return Err(VcxError::from_msg(VcxErrorKind::InvalidMessages, format!("Expected presentation, received: {:?}", message)));
+        }
+    };
+
+    if !presentation.from_thread(&presentation_request.id.0) {
+        return Err(VcxError::from_msg(VcxErrorKind::InvalidMessages,
+                                      format!("Presentation is not an answer to proof request {}", presentation_request.id.0)));
+    }
+    if presentation.please_ack.is_some() {
+        debug!("ephemeral::verify_presentation >>> acknowledgement was requested, but can't be sent without connection");
+    }
+
+    let proof = presentation.presentations_attach.content()?;
+    if !validate_indy_proof(&proof, &presentation_request.request_presentations_attach.content()?)? {
+        return Err(VcxError::from_msg(VcxErrorKind::InvalidProof, "Presentation verification failed"));
+    }
+    if let Some(trusted_issuers) = trusted_issuers {
+        trusted_issuers.validate_proof_issuers(&proof)?;
+    }
+
+    let nonce = consumed_nonces::request_nonce(presentation_request)?;
+    if !nonce.is_empty() {
+        nonce_store.consume(&nonce)?;
+    }
+
+    Ok(presentation)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+
+    use crate::aries::handlers::proof_presentation::verifier::consumed_nonces::ProcessNonceStore;
+    use crate::aries::messages::proof_presentation::presentation::tests::_presentation;
+    use crate::aries::messages::proof_presentation::presentation_request::tests::_presentation_request_data;
+    use crate::aries::messages::a2a::MessageId;
+    use crate::aries::utils::ephemeral_key::tests::anon_pack;
+    use crate::utils::devsetup::SetupMocks;
+    use crate::utils::mockdata::mock_settings::MockBuilder;
+
+    use super::*;
+
+    // store shared by verifier instances, standing in for a database
+    #[derive(Clone, Default)]
+    struct SharedNonceStore(Arc<Mutex<HashSet<String>>>);
+
+    impl NonceStore for SharedNonceStore {
+        fn consume(&self, nonce: &str) -> VcxResult<()> {
+            if !self.0.lock().unwrap().insert(nonce.to_string()) {
+                return Err(VcxError::from_msg(VcxErrorKind::ReplayedPresentation, "replayed"));
+            }
+            Ok(())
+        }
+    }
+
+    fn _packed_presentation(request: &PresentationRequest) -> Vec<u8> {
+        let recipient_key = &request.service.as_ref().unwrap().recipient_keys[0];
+        anon_pack(&json!(_presentation().to_a2a_message()).to_string(), recipient_key)
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_stateless_round_trip() {
+        let _setup = SetupMocks::init();
+        let _mock_builder = MockBuilder::init().
+            set_mock_result_for_validate_indy_proof(Ok(true));
+
+        let (request, recipient_key) = create_request(&_presentation_request_data(), "http://verifier.example.org/presentations", None).unwrap();
+        let service = request.service.clone().unwrap();
+        assert_eq!(service.recipient_keys, vec![recipient_key.verkey.clone()]);
+        assert_eq!(service.service_endpoint, "http://verifier.example.org/presentations");
+
+        // the request and its key travel serialized to whichever verifier instance receives the presentation
+        let request: PresentationRequest = serde_json::from_str(&request.to_json().unwrap()).unwrap();
+        let recipient_key: EphemeralKey = serde_json::from_str(&serde_json::to_string(&recipient_key).unwrap()).unwrap();
+        assert_eq!(request.id, MessageId::id());
+
+        let presentation = verify_presentation(&request, &recipient_key, _packed_presentation(&request), None, &SharedNonceStore::default()).unwrap();
+        assert_eq!(presentation, _presentation());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_requests_are_addressed_to_distinct_keys() {
+        let _setup = SetupMocks::init();
+
+        let (request, recipient_key) = create_request(&_presentation_request_data(), "http://verifier.example.org/presentations", None).unwrap();
+        let (_, other_key) = create_request(&_presentation_request_data(), "http://verifier.example.org/presentations", None).unwrap();
+        assert_ne!(recipient_key.verkey, other_key.verkey);
+
+        assert_eq!(verify_presentation(&request, &other_key, _packed_presentation(&request), None, &SharedNonceStore::default()).unwrap_err().kind(),
+                   VcxErrorKind::InvalidMessagePack);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_verify_presentation_fails_for_invalid_proof() {
+        let _setup = SetupMocks::init();
+        let _mock_builder = MockBuilder::init().
+            set_mock_result_for_validate_indy_proof(Ok(false));
+
+        let (request, recipient_key) = create_request(&_presentation_request_data(), "http://verifier.example.org/presentations", None).unwrap();
+
+        assert_eq!(verify_presentation(&request, &recipient_key, _packed_presentation(&request), None, &ProcessNonceStore).unwrap_err().kind(),
+                   VcxErrorKind::InvalidProof);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_verify_presentation_fails_for_presentation_replayed_to_other_instance() {
+        let _setup = SetupMocks::init();
+        let _mock_builder = MockBuilder::init().
+            set_mock_result_for_validate_indy_proof(Ok(true));
+
+        let mut request_data = _presentation_request_data();
+        request_data.nonce = String::from("ephemeral-replay-nonce");
+        let (request, recipient_key) = create_request(&request_data, "http://verifier.example.org/presentations", None).unwrap();
+
+        let shared_store = SharedNonceStore::default();
+        let first_instance_store = shared_store.clone();
+        let second_instance_store = shared_store.clone();
+        verify_presentation(&request, &recipient_key, _packed_presentation(&request), None, &first_instance_store).unwrap();
+
+        assert_eq!(verify_presentation(&request, &recipient_key, _packed_presentation(&request), None, &second_instance_store).unwrap_err().kind(),
+                   VcxErrorKind::ReplayedPresentation);
+    }
+}
@@ -1,4 +1,6 @@
 pub mod verifier;
+pub mod consumed_nonces;
+pub mod ephemeral;
 mod messages;
 mod state_machine;
 mod states;
\ No newline at end of file
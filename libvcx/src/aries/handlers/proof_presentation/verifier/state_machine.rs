@@ -4,6 +4,7 @@ use crate::api::VcxStateType;
 use crate::connection::{get_pw_did, get_their_pw_verkey};
 use crate::{connection, settings};
 use crate::error::prelude::*;
+use crate::aries::handlers::proof_presentation::verifier::consumed_nonces;
 use crate::aries::handlers::proof_presentation::verifier::messages::VerifierMessages;
 use crate::aries::messages::a2a::A2AMessage;
 use crate::aries::messages::error::ProblemReport;
@@ -13,16 +14,27 @@ use crate::aries::messages::status::Status;
 use crate::aries::handlers::proof_presentation::verifier::states::initial::InitialState;
 use crate::aries::handlers::proof_presentation::verifier::states::presentation_request_sent::PresentationRequestSentState;
 use crate::aries::handlers::proof_presentation::verifier::states::finished::FinishedState;
+use crate::libindy::proofs::verifier::verifier_internal::TrustedIssuers;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct VerifierSM {
     source_id: String,
     state: VerifierState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trusted_issuers: Option<TrustedIssuers>,
 }
 
 impl VerifierSM {
     pub fn new(presentation_request: PresentationRequestData, source_id: String) -> VerifierSM {
-        VerifierSM { source_id, state: VerifierState::Initiated(InitialState { presentation_request_data: presentation_request }) }
+        VerifierSM { source_id, state: VerifierState::Initiated(InitialState { presentation_request_data: presentation_request }), trusted_issuers: None }
+    }
+
+    pub fn set_trusted_issuers(&mut self, trusted_issuers: Option<TrustedIssuers>) {
+        self.trusted_issuers = trusted_issuers;
+    }
+
+    pub fn trusted_issuers(&self) -> Option<&TrustedIssuers> {
+        self.trusted_issuers.as_ref()
     }
 }
 
@@ -81,7 +93,7 @@ impl VerifierSM {
     pub fn step(self, message: VerifierMessages) -> VcxResult<VerifierSM> {
         trace!("VerifierSM::step >>> message: {:?}", message);
 
-        let VerifierSM { source_id, state } = self;
+        let VerifierSM { source_id, state, trusted_issuers } = self;
 
         let state = match state {
             VerifierState::Initiated(state) => {
@@ -100,9 +112,10 @@ impl VerifierSM {
                         let presentation_request =
                             PresentationRequest::create()
                                 .set_comment(title)
-                                .set_request_presentations_attach(&presentation_request)?;
+                                .set_request_presentations_attach(&presentation_request)?
+                                .set_present_proof_version(&connection::get_present_proof_version(connection_handle)?);
 
-                        connection::send_message(connection_handle, presentation_request.to_a2a_message())?;
+                        connection::send_present_proof_message(connection_handle, presentation_request.to_a2a_message(), presentation_request.present_proof_version())?;
                         VerifierState::PresentationRequestSent((state, presentation_request, connection_handle).into())
                     }
                     _ => {
@@ -113,7 +126,7 @@ impl VerifierSM {
             VerifierState::PresentationRequestSent(state) => {
                 match message {
                     VerifierMessages::VerifyPresentation(presentation) => {
-                        match state.verify_presentation(&presentation) {
+                        match state.verify_presentation(&presentation, trusted_issuers.as_ref()) {
                             Ok(()) => {
                                 VerifierState::Finished((state, presentation, RevocationStatus::NonRevoked).into())
                             }
@@ -123,12 +136,13 @@ impl VerifierSM {
                                         .set_comment(err.to_string())
                                         .set_thread_id(&state.presentation_request.id.0);
 
-                                connection::send_message(state.connection_handle, problem_report.to_a2a_message())?;
+                                connection::send_present_proof_message(state.connection_handle, problem_report.to_a2a_message(), state.presentation_request.present_proof_version())?;
                                 match err.kind() {
                                     VcxErrorKind::InvalidProof => {
                                         VerifierState::Finished((state, presentation, RevocationStatus::Revoked).into())
                                     }
-                                    _ => VerifierState::Finished((state, problem_report).into())
+                                    // presentation is kept, so it can still be verified offline
+                                    _ => VerifierState::Finished((state, presentation, problem_report).into())
                                 }
                             }
                         }
@@ -142,7 +156,7 @@ impl VerifierSM {
                                 .set_comment(String::from("PresentationProposal is not supported"))
                                 .set_thread_id(&state.presentation_request.id.0);
 
-                        connection::send_message(state.connection_handle, problem_report.to_a2a_message())?;
+                        connection::send_present_proof_message(state.connection_handle, problem_report.to_a2a_message(), state.presentation_request.present_proof_version())?;
                         VerifierState::Finished((state, problem_report).into())
                     }
                     _ => {
@@ -150,10 +164,22 @@ impl VerifierSM {
                     }
                 }
             }
-            VerifierState::Finished(state) => VerifierState::Finished(state)
+            VerifierState::Finished(state) => {
+                match message {
+                    VerifierMessages::VerifyPresentation(_) => {
+                        let nonce = consumed_nonces::request_nonce(&state.presentation_request)?;
+                        if consumed_nonces::is_consumed(&nonce)? {
+                            return Err(VcxError::from_msg(VcxErrorKind::ReplayedPresentation,
+                                                          format!("Presentation for proof request {} was already received", state.presentation_request.id.0)));
+                        }
+                        VerifierState::Finished(state)
+                    }
+                    _ => VerifierState::Finished(state)
+                }
+            }
         };
 
-        Ok(VerifierSM { source_id, state })
+        Ok(VerifierSM { source_id, state, trusted_issuers })
     }
 
     pub fn source_id(&self) -> String { self.source_id.clone() }
@@ -237,6 +263,13 @@ impl VerifierSM {
             _ => Err(VcxError::from_msg(VcxErrorKind::NotReady, "Presentation is not received yet"))
         }
     }
+
+    pub fn problem_report(&self) -> VcxResult<ProblemReport> {
+        match self.state {
+            VerifierState::Finished(FinishedState { status: Status::Failed(ref problem_report), .. }) => Ok(problem_report.clone()),
+            _ => Err(VcxError::from_msg(VcxErrorKind::NotReady, "Problem report is not received"))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -248,6 +281,7 @@ pub mod test {
     use crate::aries::messages::proof_presentation::presentation_request::tests::_presentation_request;
     use crate::aries::messages::proof_presentation::presentation_request::tests::_presentation_request_data;
     use crate::aries::messages::proof_presentation::test::{_ack, _problem_report};
+    use crate::aries::messages::error::REQUEST_DECLINED;
     use crate::aries::test::source_id;
 
     use super::*;
@@ -285,7 +319,15 @@ pub mod test {
     }
 
     mod step {
+        use std::rc::Rc;
+
+        use agency_client::error::{AgencyClientError, AgencyClientErrorKind};
+        use agency_client::httpclient::{self, Transport};
+        use agency_client::mocking::MockTransport;
+        use serde_json::Value;
+
         use super::*;
+        use crate::aries::messages::ack::{AckOn, PleaseAck};
         use crate::utils::mockdata::mock_settings::MockBuilder;
 
         #[test]
@@ -353,6 +395,46 @@ pub mod test {
             assert_eq!(Status::Failed(ProblemReport::create()).code(), verifier_sm.presentation_status());
         }
 
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_verifier_acks_presentation_only_once_verified() {
+            let _setup = SetupMocks::init();
+            let transport = Rc::new(MockTransport::default());
+            httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+            let mut presentation = _presentation();
+            presentation.please_ack = Some(PleaseAck::on(vec![AckOn::Receipt, AckOn::Outcome]));
+
+            // invalid presentation is answered by problem report only
+            {
+                let _mock_builder = MockBuilder::init().
+                    set_mock_result_for_validate_indy_proof(Ok(false));
+                let verifier_sm = _verifier_sm().step(VerifierMessages::SendPresentationRequest(mock_connection())).unwrap();
+                let sent = transport.sent_messages().len();
+
+                let verifier_sm = verifier_sm.step(VerifierMessages::VerifyPresentation(presentation.clone())).unwrap();
+                assert_eq!(Status::Failed(ProblemReport::create()).code(), verifier_sm.presentation_status());
+                assert_eq!(transport.sent_messages().len() - sent, 1);
+                let sent: Value = serde_json::from_slice(&transport.sent_messages().last().unwrap().body).unwrap();
+                assert!(sent["@type"].as_str().unwrap().ends_with("problem-report"));
+            }
+
+            // failing to deliver acks doesn't fail valid presentation
+            let _mock_builder = MockBuilder::init().
+                set_mock_result_for_validate_indy_proof(Ok(true));
+            let verifier_sm = _verifier_sm().step(VerifierMessages::SendPresentationRequest(mock_connection())).unwrap();
+            let sent = transport.sent_messages().len();
+            transport.queue_response(Err(AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, "connection refused")));
+            transport.queue_response(Err(AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, "connection refused")));
+
+            let verifier_sm = verifier_sm.step(VerifierMessages::VerifyPresentation(presentation)).unwrap();
+            assert_match!(VerifierState::Finished(_), verifier_sm.state);
+            assert_eq!(Status::Success.code(), verifier_sm.presentation_status());
+            assert_eq!(transport.sent_messages().len() - sent, 2);
+
+            httpclient::set_mock_transport(None);
+        }
+
         //    #[test]
         //    fn test_prover_handle_verify_presentation_message_from_presentation_request_sent_state_for_invalid_presentation() {
         //        let _setup = Setup::init();
@@ -418,6 +500,66 @@ pub mod test {
             verifier_sm = verifier_sm.step(VerifierMessages::PresentationProposalReceived(_presentation_proposal())).unwrap();
             assert_match!(VerifierState::Finished(_), verifier_sm.state);
         }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_verifier_rejects_replayed_presentation() {
+            let _setup = SetupMocks::init();
+            let _mock_builder = MockBuilder::init().
+                set_mock_result_for_validate_indy_proof(Ok(true));
+            let transport = Rc::new(MockTransport::default());
+            httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+            let request_data = _presentation_request_data().set_nonce().unwrap();
+            let nonce = request_data.nonce.clone();
+            let mut verifier_sm = VerifierSM::new(request_data.clone(), source_id());
+            verifier_sm = verifier_sm.step(VerifierMessages::SendPresentationRequest(mock_connection())).unwrap();
+
+            // nonce is consumed even though the ack couldn't be delivered
+            transport.queue_response(Err(AgencyClientError::from_msg(AgencyClientErrorKind::PostMessageFailed, "connection refused")));
+            verifier_sm = verifier_sm.step(VerifierMessages::VerifyPresentation(_presentation())).unwrap();
+            assert_eq!(Status::Success.code(), verifier_sm.presentation_status());
+            assert!(consumed_nonces::is_consumed(&nonce).unwrap());
+
+            let err = verifier_sm.clone().step(VerifierMessages::VerifyPresentation(_presentation())).unwrap_err();
+            assert_eq!(VcxErrorKind::ReplayedPresentation, err.kind());
+
+            // other verifier issuing the same nonce doesn't accept the presentation either
+            let mut other_verifier_sm = VerifierSM::new(request_data, source_id());
+            other_verifier_sm = other_verifier_sm.step(VerifierMessages::SendPresentationRequest(mock_connection())).unwrap();
+            let sent = transport.sent_messages().len();
+            other_verifier_sm = other_verifier_sm.step(VerifierMessages::VerifyPresentation(_presentation())).unwrap();
+            assert_eq!(VcxStateType::VcxStateNone as u32, other_verifier_sm.state());
+            assert!(other_verifier_sm.problem_report().unwrap().comment.unwrap().contains("already received"));
+
+            // replay is not acked, only rejected
+            assert_eq!(transport.sent_messages().len() - sent, 1);
+            let sent: Value = serde_json::from_slice(&transport.sent_messages().last().unwrap().body).unwrap();
+            assert!(sent["@type"].as_str().unwrap().ends_with("problem-report"));
+
+            httpclient::set_mock_transport(None);
+        }
+
+        #[test]
+        #[cfg(feature = "general_test")]
+        fn test_verifier_handle_request_declined_problem_report() {
+            let _setup = SetupMocks::init();
+
+            let mut verifier_sm = _verifier_sm();
+            verifier_sm = verifier_sm.step(VerifierMessages::SendPresentationRequest(mock_connection())).unwrap();
+
+            let problem_report = ProblemReport::create()
+                .set_description(REQUEST_DECLINED)
+                .set_comment(String::from("not sharing"));
+            verifier_sm = verifier_sm.step(VerifierMessages::PresentationRejectReceived(problem_report)).unwrap();
+
+            assert_match!(VerifierState::Finished(_), verifier_sm.state);
+            assert_eq!(Status::Failed(ProblemReport::default()).code(), verifier_sm.presentation_status());
+
+            let problem_report = verifier_sm.problem_report().unwrap();
+            assert_eq!(Some(REQUEST_DECLINED.to_string()), problem_report.code());
+            assert_eq!(Some(String::from("not sharing")), problem_report.comment);
+        }
     }
 
     mod find_message_to_handle {
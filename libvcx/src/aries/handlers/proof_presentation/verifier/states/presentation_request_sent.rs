@@ -1,14 +1,17 @@
 use crate::connection;
 use crate::error::{VcxError, VcxErrorKind, VcxResult};
+use crate::aries::handlers::proof_presentation::verifier::consumed_nonces;
 use crate::aries::handlers::proof_presentation::verifier::states::finished::FinishedState;
 use crate::aries::handlers::proof_presentation::verifier::state_machine::RevocationStatus;
 use crate::aries::messages::a2a::A2AMessage;
+use crate::aries::messages::ack::PleaseAck;
 use crate::aries::messages::error::ProblemReport;
 use crate::aries::messages::proof_presentation::presentation::Presentation;
 use crate::aries::messages::proof_presentation::presentation_ack::PresentationAck;
 use crate::aries::messages::proof_presentation::presentation_request::PresentationRequest;
 use crate::aries::messages::status::Status;
 use crate::libindy::proofs::verifier::verifier::validate_indy_proof;
+use crate::libindy::proofs::verifier::verifier_internal::TrustedIssuers;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PresentationRequestSentState {
@@ -17,20 +20,44 @@ pub struct PresentationRequestSentState {
 }
 
 impl PresentationRequestSentState {
-    pub fn verify_presentation(&self, presentation: &Presentation) -> VcxResult<()> {
-        let valid = validate_indy_proof(&presentation.presentations_attach.content()?,
-                                               &self.presentation_request.request_presentations_attach.content()?)?;
+    /**
+    Verifies the presentation and sends acks requested by the prover once it's valid, the request nonce is consumed
+    last. Failure to send an ack doesn't affect the verification, the prover can still learn the outcome by asking
+    for the status.
+     */
+    pub fn verify_presentation(&self, presentation: &Presentation, trusted_issuers: Option<&TrustedIssuers>) -> VcxResult<()> {
+        let proof = presentation.presentations_attach.content()?;
+        let valid = validate_indy_proof(&proof,
+                                        &self.presentation_request.request_presentations_attach.content()?)?;
 
         if !valid {
             return Err(VcxError::from_msg(VcxErrorKind::InvalidProof, "Presentation verification failed"));
         }
 
-        if presentation.please_ack.is_some() {
-            let ack = PresentationAck::create().set_thread_id(&self.presentation_request.id.0);
-            connection::send_message(self.connection_handle, A2AMessage::PresentationAck(ack))?;
+        if let Some(trusted_issuers) = trusted_issuers {
+            trusted_issuers.validate_proof_issuers(&proof)?;
         }
 
-        Ok(())
+        let nonce = consumed_nonces::request_nonce(&self.presentation_request)?;
+        if consumed_nonces::is_consumed(&nonce)? {
+            return Err(VcxError::from_msg(VcxErrorKind::ReplayedPresentation, format!("Presentation for nonce {} was already received", nonce)));
+        }
+
+        if let Some(please_ack) = presentation.please_ack.as_ref().filter(|please_ack| please_ack.on_receipt()) {
+            self._send_ack(PresentationAck::create().set_status(please_ack.receipt_status()));
+        }
+        if presentation.please_ack.as_ref().map_or(false, PleaseAck::on_outcome) {
+            self._send_ack(PresentationAck::create());
+        }
+
+        consumed_nonces::consume(&nonce)
+    }
+
+    fn _send_ack(&self, ack: PresentationAck) {
+        let ack = ack.set_thread_id(&self.presentation_request.id.0);
+        if let Err(err) = connection::send_present_proof_message(self.connection_handle, A2AMessage::PresentationAck(ack), self.presentation_request.present_proof_version()) {
+            warn!("Failed to send presentation ack: {}", err);
+        }
     }
 }
 
@@ -60,3 +87,16 @@ impl From<(PresentationRequestSentState, ProblemReport)> for FinishedState {
         }
     }
 }
+
+impl From<(PresentationRequestSentState, Presentation, ProblemReport)> for FinishedState {
+    fn from((state, presentation, problem_report): (PresentationRequestSentState, Presentation, ProblemReport)) -> Self {
+        trace!("transit state from PresentationRequestSentState to FinishedState");
+        FinishedState {
+            connection_handle: state.connection_handle,
+            presentation_request: state.presentation_request,
+            presentation: Some(presentation),
+            status: Status::Failed(problem_report),
+            revocation_status: None,
+        }
+    }
+}
@@ -1,10 +1,15 @@
 use crate::error::prelude::*;
+use crate::aries::handlers::proof_presentation::verifier::consumed_nonces;
 use crate::aries::handlers::proof_presentation::verifier::messages::VerifierMessages;
 use crate::aries::handlers::proof_presentation::verifier::state_machine::VerifierSM;
 use crate::aries::messages::a2a::A2AMessage;
 use crate::aries::messages::proof_presentation::presentation::Presentation;
 use crate::aries::messages::proof_presentation::presentation_request::*;
-use std::collections::HashMap;
+use crate::aries::messages::status::Status;
+use crate::aries::utils::encryption_envelope::EncryptionEnvelope;
+use crate::libindy::proofs::verifier::verifier::validate_indy_proof_offline;
+use crate::libindy::proofs::verifier::verifier_internal::{get_credential_info, get_presentation_identifiers, LedgerObjects, PresentationIdentifier, TrustedIssuers};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Verifier {
@@ -33,6 +38,17 @@ impl Verifier {
         })
     }
 
+    /**
+    Creates verifier from proof request built with `ProofRequestBuilder`
+     */
+    pub fn create_from_request(source_id: String, presentation_request: PresentationRequestData) -> VcxResult<Verifier> {
+        trace!("Verifier::create_from_request >>> source_id: {:?}, presentation_request: {:?}", source_id, presentation_request);
+
+        Ok(Verifier {
+            verifier_sm: VerifierSM::new(presentation_request, source_id),
+        })
+    }
+
     pub fn get_source_id(&self) -> String { self.verifier_sm.source_id() }
 
     pub fn state(&self) -> u32 {
@@ -66,6 +82,18 @@ impl Verifier {
         self.step(VerifierMessages::VerifyPresentation(presentation))
     }
 
+    /**
+    Verifies presentation exported by the prover with `export_presentation` and delivered out of band.
+    Packed problem report of the prover is accepted as well and rejects the presentation request.
+     */
+    pub fn verify_exported_presentation(&mut self, packed_presentation: &str) -> VcxResult<()> {
+        trace!("Verifier::verify_exported_presentation >>>");
+        match EncryptionEnvelope::anon_unpack(packed_presentation.as_bytes().to_vec())? {
+            message @ A2AMessage::Presentation(_) | message @ A2AMessage::CommonProblemReport(_) => self.handle_message(message.into()),
+            message => Err(VcxError::from_msg(VcxErrorKind::InvalidMessages, format!("Expected exported presentation, received: {:?}", message)))
+        }
+    }
+
     pub fn send_presentation_request(&mut self, connection_handle: u32) -> VcxResult<()> {
         trace!("Verifier::send_presentation_request >>> connection_handle: {:?}", connection_handle);
         self.step(VerifierMessages::SendPresentationRequest(connection_handle))
@@ -86,6 +114,75 @@ impl Verifier {
         Ok(json!(proof).to_string())
     }
 
+    /**
+    Sets allow-list of issuers, presentations containing credentials of other issuers are rejected even if they are
+    cryptographically valid. Without allow-list credentials of any issuer are accepted.
+     */
+    pub fn set_trusted_issuers(&mut self, trusted_issuers: Option<TrustedIssuers>) {
+        trace!("Verifier::set_trusted_issuers >>> trusted_issuers: {:?}", trusted_issuers);
+        self.verifier_sm.set_trusted_issuers(trusted_issuers)
+    }
+
+    pub fn get_presentation_identifiers(&self) -> VcxResult<Vec<PresentationIdentifier>> {
+        trace!("Verifier::get_presentation_identifiers >>>");
+
+        if self.presentation_status() != Status::Success.code() {
+            return Err(VcxError::from_msg(VcxErrorKind::InvalidProof, "Presentation was not successfully verified"));
+        }
+        let presentation = self.verifier_sm.presentation()?;
+        get_presentation_identifiers(&presentation.presentations_attach.content()?)
+    }
+
+    /**
+    Verifies received presentation against the request using supplied ledger objects instead of fetching them.
+     */
+    pub fn verify_presentation_offline(&self, ledger_objects: &LedgerObjects) -> VcxResult<bool> {
+        trace!("Verifier::verify_presentation_offline >>> ledger_objects: {:?}", ledger_objects);
+
+        let presentation = self.verifier_sm.presentation()?;
+        let presentation_request = self.verifier_sm.presentation_request()?;
+        validate_indy_proof_offline(&presentation.presentations_attach.content()?,
+                                    &presentation_request.request_presentations_attach.content()?,
+                                    ledger_objects)
+    }
+
+    /**
+    Verifies supplied presentation against the request of this verifier without side effects: neither the state
+    of the verifier nor consumed nonces are changed. Ledger objects missing in `ledger_objects` are fetched and added
+    there, so verifiers sharing them fetch every object only once. Fails with ReplayedPresentation if the nonce
+    of the request was already consumed or is in `verified_nonces`, nonce of a valid presentation is added there.
+     */
+    pub fn verify_presentation_with_ledger_objects(&self, presentation: &Presentation, ledger_objects: &mut LedgerObjects,
+                                                   verified_nonces: &mut HashSet<String>) -> VcxResult<bool> {
+        trace!("Verifier::verify_presentation_with_ledger_objects >>> presentation: {:?}", presentation);
+
+        let presentation_request = self.verifier_sm.presentation_request()?;
+        let nonce = consumed_nonces::request_nonce(&presentation_request)?;
+        if !nonce.is_empty() && (verified_nonces.contains(&nonce) || consumed_nonces::is_consumed(&nonce)?) {
+            return Err(VcxError::from_msg(VcxErrorKind::ReplayedPresentation, format!("Presentation for nonce {} was already received", nonce)));
+        }
+        let proof = presentation.presentations_attach.content()?;
+        ledger_objects.fetch_missing(&get_credential_info(&proof)?)?;
+
+        let valid = validate_indy_proof_offline(&proof, &presentation_request.request_presentations_attach.content()?, ledger_objects)?;
+        if valid {
+            if let Some(trusted_issuers) = self.verifier_sm.trusted_issuers() {
+                trusted_issuers.validate_proof_issuers(&proof)?;
+            }
+            if !nonce.is_empty() {
+                verified_nonces.insert(nonce);
+            }
+        }
+        Ok(valid)
+    }
+
+    pub fn get_problem_report(&self) -> VcxResult<String> {
+        trace!("Verifier::get_problem_report >>>");
+
+        let problem_report = self.verifier_sm.problem_report()?;
+        Ok(json!(problem_report).to_string())
+    }
+
     pub fn step(&mut self, message: VerifierMessages) -> VcxResult<()> {
         self.verifier_sm = self.verifier_sm.clone().step(message)?;
         Ok(())
@@ -52,6 +52,10 @@ impl Serialize for MessageType {
 
 impl std::string::ToString for MessageType {
     fn to_string(&self) -> String {
-        format!("{};spec/{}/{}/{}", self.did, self.family.to_string(), self.version, self.type_)
+        if self.did.starts_with("http") {
+            format!("{}/{}/{}/{}", self.did, self.family.to_string(), self.version, self.type_)
+        } else {
+            format!("{};spec/{}/{}/{}", self.did, self.family.to_string(), self.version, self.type_)
+        }
     }
 }
\ No newline at end of file
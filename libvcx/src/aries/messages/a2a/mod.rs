@@ -27,12 +27,15 @@ use crate::aries::messages::issuance::credential::Credential;
 use crate::aries::messages::proof_presentation::presentation_proposal::PresentationProposal;
 use crate::aries::messages::proof_presentation::presentation_request::PresentationRequest;
 use crate::aries::messages::proof_presentation::presentation::Presentation;
+use crate::aries::messages::proof_presentation::v2::{PRESENT_PROOF_V2, PresentationV2, RequestPresentationV2};
 
 use crate::aries::messages::discovery::query::Query;
 use crate::aries::messages::discovery::disclose::Disclose;
 
 use crate::aries::messages::basic_message::message::BasicMessage;
 
+use crate::aries::messages::revocation_notification::revocation_notification::RevocationNotification;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum A2AMessage {
     /// routing
@@ -72,6 +75,12 @@ pub enum A2AMessage {
     /// basic message
     BasicMessage(BasicMessage),
 
+    /// revocation notification
+    RevocationNotification(RevocationNotification),
+
+    /// Message of a type this library doesn't support (yet), kept as received
+    Unknown { type_: String, raw: Value },
+
     /// Any Raw Message
     Generic(Value),
 }
@@ -93,6 +102,9 @@ impl<'de> Deserialize<'de> for A2AMessage {
             Err(_) => return Ok(A2AMessage::Generic(value))
         };
 
+        let full_type = message_type.to_string();
+        let version = message_type.version.clone();
+
         match (message_type.family, message_type.type_.as_str()) {
             (MessageFamilies::Routing, A2AMessage::FORWARD) => {
                 Forward::deserialize(value)
@@ -164,6 +176,23 @@ impl<'de> Deserialize<'de> for A2AMessage {
                     .map(|msg| A2AMessage::CredentialAck(msg))
                     .map_err(de::Error::custom)
             }
+            (MessageFamilies::PresentProof, A2AMessage::REQUEST_PRESENTATION) if version == PRESENT_PROOF_V2 => {
+                RequestPresentationV2::deserialize(value)
+                    .map_err(de::Error::custom)
+                    .and_then(|msg| msg.into_v1().map_err(de::Error::custom))
+                    .map(|msg| A2AMessage::PresentationRequest(msg))
+            }
+            (MessageFamilies::PresentProof, A2AMessage::PRESENTATION) if version == PRESENT_PROOF_V2 => {
+                PresentationV2::deserialize(value)
+                    .map_err(de::Error::custom)
+                    .and_then(|msg| msg.into_v1().map_err(de::Error::custom))
+                    .map(|msg| A2AMessage::Presentation(msg))
+            }
+            (MessageFamilies::PresentProof, A2AMessage::PROBLEM_REPORT) => {
+                CommonProblemReport::deserialize(value)
+                    .map(|msg| A2AMessage::CommonProblemReport(msg))
+                    .map_err(de::Error::custom)
+            }
             (MessageFamilies::PresentProof, A2AMessage::PROPOSE_PRESENTATION) => {
                 PresentationProposal::deserialize(value)
                     .map(|msg| A2AMessage::PresentationProposal(msg))
@@ -199,9 +228,14 @@ impl<'de> Deserialize<'de> for A2AMessage {
                     .map(|msg| A2AMessage::BasicMessage(msg))
                     .map_err(de::Error::custom)
             }
+            (MessageFamilies::RevocationNotification, A2AMessage::REVOKE) => {
+                RevocationNotification::deserialize(value)
+                    .map(|msg| A2AMessage::RevocationNotification(msg))
+                    .map_err(de::Error::custom)
+            }
             (_, other_type) => {
-                warn!("Unexpected @type field structure: {}", other_type);
-                Ok(A2AMessage::Generic(value))
+                warn!("Unsupported message type: {}, full @type: {}", other_type, full_type);
+                Ok(A2AMessage::Unknown { type_: full_type, raw: value })
             }
         }
     }
@@ -238,6 +272,8 @@ impl Serialize for A2AMessage {
             A2AMessage::Query(msg) => set_a2a_message_type(msg, MessageFamilies::DiscoveryFeatures, A2AMessage::QUERY),
             A2AMessage::Disclose(msg) => set_a2a_message_type(msg, MessageFamilies::DiscoveryFeatures, A2AMessage::DISCLOSE),
             A2AMessage::BasicMessage(msg) => set_a2a_message_type(msg, MessageFamilies::Basicmessage, A2AMessage::BASIC_MESSAGE),
+            A2AMessage::RevocationNotification(msg) => set_a2a_message_type(msg, MessageFamilies::RevocationNotification, A2AMessage::REVOKE),
+            A2AMessage::Unknown { raw, .. } => Ok(raw.clone()),
             A2AMessage::Generic(msg) => Ok(msg.clone())
         }.map_err(ser::Error::custom)?;
 
@@ -281,7 +317,7 @@ impl A2AMessage {
     const PING: &'static str = "ping";
     const PING_RESPONSE: &'static str = "ping_response";
     const ACK: &'static str = "ack";
-    const PROBLEM_REPORT: &'static str = "problem-report";
+    pub(crate) const PROBLEM_REPORT: &'static str = "problem-report";
     const CREDENTIAL_OFFER: &'static str = "offer-credential";
     const CREDENTIAL: &'static str = "issue-credential";
     const PROPOSE_CREDENTIAL: &'static str = "propose-credential";
@@ -292,6 +328,7 @@ impl A2AMessage {
     const QUERY: &'static str = "query";
     const DISCLOSE: &'static str = "disclose";
     const BASIC_MESSAGE: &'static str = "message";
+    const REVOKE: &'static str = "revoke";
 }
 
 #[macro_export]
@@ -312,3 +349,46 @@ macro_rules! a2a_message {
         }
     );
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    const UNKNOWN_MESSAGE: &str = r#"{"@id":"abc","@type":"did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/some-future-protocol/2.0/do-something","content":"hello"}"#;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_unknown_message_type_deserializes_into_unknown() {
+        let message: A2AMessage = serde_json::from_str(UNKNOWN_MESSAGE).unwrap();
+
+        match message {
+            A2AMessage::Unknown { ref type_, ref raw } => {
+                assert_eq!("did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/some-future-protocol/2.0/do-something", type_);
+                assert_eq!("hello", raw["content"]);
+            }
+            other => panic!("Expected unknown message, got {:?}", other)
+        }
+
+        let serialized: Value = serde_json::to_value(&message).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(UNKNOWN_MESSAGE).unwrap(), serialized);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_message_without_type_deserializes_into_generic() {
+        let message: A2AMessage = serde_json::from_str(r#"{"@id":"abc","content":"hello"}"#).unwrap();
+        assert_match!(A2AMessage::Generic(_), message);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_uri_message_type_of_present_proof_2_0_deserializes() {
+        let message: A2AMessage = serde_json::from_value(json!({
+            "@id": "abc",
+            "@type": "https://didcomm.org/present-proof/2.0/ack",
+            "status": "OK",
+            "~thread": {"thid": "123"}
+        })).unwrap();
+        assert_match!(A2AMessage::PresentationAck(_), message);
+    }
+}
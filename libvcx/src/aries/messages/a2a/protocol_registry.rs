@@ -2,7 +2,8 @@ use regex::Regex;
 use strum::IntoEnumIterator;
 
 use crate::aries::messages::a2a::message_family::MessageFamilies;
-use crate::aries::messages::discovery::disclose::ProtocolDescriptor;
+use crate::aries::messages::discovery::disclose::{Disclose, ProtocolDescriptor};
+use crate::aries::messages::discovery::query::Query;
 use crate::settings::Actors;
 use crate::settings;
 
@@ -14,8 +15,14 @@ impl ProtocolRegistry {
     pub fn init() -> ProtocolRegistry {
         let mut registry = ProtocolRegistry { protocols: Vec::new() };
         let actors = settings::get_actors();
+        let supported = settings::get_supported_protocols();
 
         for family in MessageFamilies::iter() {
+            if let Some(ref supported) = supported {
+                if !supported.contains(&family) {
+                    continue;
+                }
+            }
             match family {
                 family @ MessageFamilies::Routing |
                 family @ MessageFamilies::ReportProblem |
@@ -25,6 +32,7 @@ impl ProtocolRegistry {
                 family @ MessageFamilies::PresentProof |
                 family @ MessageFamilies::TrustPing |
                 family @ MessageFamilies::Basicmessage |
+                family @ MessageFamilies::RevocationNotification |
                 family @ MessageFamilies::DiscoveryFeatures => registry.add_protocol(&actors, family),
                 MessageFamilies::Signature => {}
                 MessageFamilies::Unknown(_) => {}
@@ -74,6 +82,24 @@ impl ProtocolRegistry {
     }
 }
 
+/**
+Protocols implemented by the library which are advertised to peers, restricted by `actors` and `supported_protocols` settings.
+ */
+pub fn supported_protocols() -> Vec<ProtocolDescriptor> {
+    ProtocolRegistry::init().protocols()
+}
+
+/**
+Builds the answer to a discover-features query received over a connection.
+ */
+pub fn disclose_for_query(query: &Query) -> Disclose {
+    let protocols = ProtocolRegistry::init().get_protocols_for_query(query.query.as_ref().map(String::as_str));
+
+    Disclose::create()
+        .set_protocols(protocols)
+        .set_thread_id(query.id.0.clone())
+}
+
 #[cfg(test)]
 pub mod tests {
     use crate::settings;
@@ -205,4 +231,35 @@ pub mod tests {
         ];
         assert_eq!(expected_protocols, protocols);
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_supported_protocols_lists_implemented_protocols() {
+        let _setup = SetupEmpty::init();
+
+        let pids: Vec<String> = supported_protocols().into_iter().map(|protocol| protocol.pid).collect();
+        for family in vec![MessageFamilies::Connections, MessageFamilies::PresentProof, MessageFamilies::CredentialIssuance,
+                           MessageFamilies::Basicmessage, MessageFamilies::TrustPing, MessageFamilies::DiscoveryFeatures] {
+            assert!(pids.contains(&family.id()), "{} is not supported", family.id());
+        }
+        assert!(!pids.contains(&MessageFamilies::Signature.id()));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_disclose_for_query_lists_protocols_restricted_by_settings() {
+        let _setup = SetupEmpty::init();
+
+        settings::set_config_value(settings::CONFIG_SUPPORTED_PROTOCOLS, &json!(["connections", "trust_ping"]).to_string());
+
+        let query = Query::create().set_query(Some("*".to_string()));
+        let disclose = disclose_for_query(&query);
+
+        let expected_protocols = vec![
+            ProtocolDescriptor { pid: MessageFamilies::Connections.id(), roles: None },
+            ProtocolDescriptor { pid: MessageFamilies::TrustPing.id(), roles: None },
+        ];
+        assert_eq!(expected_protocols, disclose.protocols);
+        assert_eq!(Some(query.id.0), disclose.thread.thid);
+    }
+}
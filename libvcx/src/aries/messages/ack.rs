@@ -40,14 +40,80 @@ impl Ack {
 threadlike!(Ack);
 a2a_message!(Ack);
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct PleaseAck {}
+/**
+Request for ack, `on` holds conditions as received, values other than RECEIPT and OUTCOME are kept but ignored.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct PleaseAck {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AckOn {
+    Receipt,
+    Outcome,
+}
+
+impl AckOn {
+    const RECEIPT: &'static str = "RECEIPT";
+    const OUTCOME: &'static str = "OUTCOME";
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AckOn::Receipt => AckOn::RECEIPT,
+            AckOn::Outcome => AckOn::OUTCOME,
+        }
+    }
+}
+
+impl PleaseAck {
+    pub fn on(on: Vec<AckOn>) -> PleaseAck {
+        PleaseAck { on: on.iter().map(|on| on.as_str().to_string()).collect() }
+    }
+
+    /**
+    Conditions the ack was requested on, ack requested without conditions is sent on outcome.
+     */
+    pub fn ack_on(&self) -> Vec<AckOn> {
+        if self.on.is_empty() {
+            return vec![AckOn::Outcome];
+        }
+        let mut ack_on = vec![];
+        for condition in [AckOn::Receipt, AckOn::Outcome].iter() {
+            if self.on.iter().any(|on| on == condition.as_str()) {
+                ack_on.push(*condition);
+            }
+        }
+        ack_on
+    }
+
+    pub fn on_receipt(&self) -> bool {
+        self.ack_on().contains(&AckOn::Receipt)
+    }
+
+    pub fn on_outcome(&self) -> bool {
+        self.ack_on().contains(&AckOn::Outcome)
+    }
+
+    /**
+    Ack sent on receipt tells the sender the outcome is pending if it was requested as well.
+     */
+    pub fn receipt_status(&self) -> AckStatus {
+        if self.on_outcome() { AckStatus::Pending } else { AckStatus::Ok }
+    }
+}
 
 #[macro_export]
 macro_rules! please_ack (($type:ident) => (
     impl $type {
         pub fn ask_for_ack(mut self) -> $type {
-            self.please_ack = Some(PleaseAck {});
+            self.please_ack = Some(PleaseAck::default());
+            self
+        }
+
+        pub fn ask_for_ack_on(mut self, on: Vec<crate::aries::messages::ack::AckOn>) -> $type {
+            self.please_ack = Some(PleaseAck::on(on));
             self
         }
     }
@@ -76,4 +142,23 @@ pub mod tests {
 
         assert_eq!(_ack(), ack);
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_please_ack_parses_conditions() {
+        let please_ack: PleaseAck = serde_json::from_str(r#"{"on": ["RECEIPT", "OUTCOME"]}"#).unwrap();
+        assert_eq!(please_ack.ack_on(), vec![AckOn::Receipt, AckOn::Outcome]);
+        assert_eq!(please_ack.receipt_status(), AckStatus::Pending);
+
+        let please_ack: PleaseAck = serde_json::from_str(r#"{"on": ["RECEIPT", "SOMETHING_NEW"]}"#).unwrap();
+        assert_eq!(please_ack.ack_on(), vec![AckOn::Receipt]);
+        assert_eq!(please_ack.receipt_status(), AckStatus::Ok);
+        assert_eq!(json!(please_ack), json!({"on": ["RECEIPT", "SOMETHING_NEW"]}));
+
+        let please_ack: PleaseAck = serde_json::from_str(r#"{}"#).unwrap();
+        assert_eq!(please_ack.ack_on(), vec![AckOn::Outcome]);
+        assert_eq!(json!(please_ack), json!({}));
+
+        assert_eq!(json!(PleaseAck::on(vec![AckOn::Outcome])), json!({"on": ["OUTCOME"]}));
+    }
 }
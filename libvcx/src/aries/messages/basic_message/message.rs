@@ -2,6 +2,8 @@ use chrono::prelude::*;
 
 use crate::aries::messages::a2a::{A2AMessage, MessageId};
 use crate::aries::messages::localization::Localization;
+use crate::aries::messages::thread::Thread;
+use crate::utils::clock;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct BasicMessage {
@@ -12,6 +14,9 @@ pub struct BasicMessage {
     #[serde(rename = "~l10n")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub l10n: Option<Localization>,
+    #[serde(rename = "~thread")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread: Option<Thread>,
 }
 
 impl BasicMessage {
@@ -25,7 +30,12 @@ impl BasicMessage {
     }
 
     pub fn set_time(mut self) -> Self {
-        self.sent_time = format!("{:?}", Utc::now());
+        self.sent_time = format!("{:?}", DateTime::<Utc>::from(clock::now()));
+        self
+    }
+
+    pub fn set_thread_id(mut self, id: &str) -> Self {
+        self.thread = Some(Thread::new().set_thid(id.to_string()));
         self
     }
 
@@ -56,4 +66,14 @@ pub mod tests {
             .set_default_localization();
         assert_eq!(_content(), basic_message.content);
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_basic_message_with_thread_id_has_thread_decorator() {
+        let basic_message: serde_json::Value = json!(BasicMessage::create().set_content(_content()).set_thread_id("thread-1"));
+        assert_eq!(basic_message["~thread"]["thid"], json!("thread-1"));
+
+        let basic_message: serde_json::Value = json!(BasicMessage::create().set_content(_content()));
+        assert!(basic_message.get("~thread").is_none());
+    }
 }
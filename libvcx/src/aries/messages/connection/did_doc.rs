@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::aries::messages::connection::invite::Invitation;
 
 use crate::error::prelude::*;
@@ -59,6 +61,39 @@ pub struct Service {
     pub service_endpoint: String,
 }
 
+/**
+Single problem found by `DidDoc::validation_issues`.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "issue", content = "value", rename_all = "snake_case")]
+pub enum DidDocValidationIssue {
+    UnsupportedContext(String),
+    MissingService,
+    InvalidEndpoint(String),
+    MissingPublicKey(String),
+    UnsupportedPublicKeyType(String),
+    InvalidVerkey(String),
+    MissingAuthentication(String),
+    UnsupportedAuthenticationType(String),
+    EmptyRoutingKey,
+}
+
+impl fmt::Display for DidDocValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DidDocValidationIssue::UnsupportedContext(context) => write!(f, "Unsupported @context value: {:?}", context),
+            DidDocValidationIssue::MissingService => write!(f, "No service is defined"),
+            DidDocValidationIssue::InvalidEndpoint(endpoint) => write!(f, "Invalid endpoint {:?}", endpoint),
+            DidDocValidationIssue::MissingPublicKey(key) => write!(f, "Cannot find PublicKey definition for key: {:?}", key),
+            DidDocValidationIssue::UnsupportedPublicKeyType(type_) => write!(f, "Unsupported PublicKey type: {:?}", type_),
+            DidDocValidationIssue::InvalidVerkey(key) => write!(f, "Invalid verkey: {:?}", key),
+            DidDocValidationIssue::MissingAuthentication(key) => write!(f, "Cannot find Authentication section for key: {:?}", key),
+            DidDocValidationIssue::UnsupportedAuthenticationType(type_) => write!(f, "Unsupported Authentication type: {:?}", type_),
+            DidDocValidationIssue::EmptyRoutingKey => write!(f, "Routing key is empty"),
+        }
+    }
+}
+
 impl Default for DidDoc {
     fn default() -> DidDoc {
         DidDoc {
@@ -142,77 +177,106 @@ impl DidDoc {
             });
     }
 
+    /**
+    Fails with all validation issues of the DidDoc listed in the error message, see `validation_issues`.
+     */
     pub fn validate(&self) -> VcxResult<()> {
+        let issues = self.validation_issues();
+        if issues.is_empty() {
+            return Ok(());
+        }
+        let issues: Vec<String> = issues.iter().map(DidDocValidationIssue::to_string).collect();
+        Err(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("DIDDoc validation failed: {}", issues.join("; "))))
+    }
+
+    /**
+    Checks the whole DidDoc and returns every problem found, empty if the DidDoc is valid.
+     */
+    pub fn validation_issues(&self) -> Vec<DidDocValidationIssue> {
+        let mut issues = Vec::new();
+
         if self.context != CONTEXT {
-            return Err(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("DIDDoc validation failed: Unsupported @context value: {:?}", self.context)));
+            issues.push(DidDocValidationIssue::UnsupportedContext(self.context.clone()));
         }
 
 //        if self.id.is_empty() {
 //            return Err(VcxError::from_msg(VcxErrorKind::InvalidJson, "DIDDoc validation failed: id is empty"));
 //        }
 
+        if self.service.is_empty() {
+            issues.push(DidDocValidationIssue::MissingService);
+        }
+
         for service in self.service.iter() {
-            Url::parse(&service.service_endpoint)
-                .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("DIDDoc validation failed: Invalid endpoint \"{:?}\", err: {:?}", service.service_endpoint, err)))?;
+            if Url::parse(&service.service_endpoint).is_err() {
+                issues.push(DidDocValidationIssue::InvalidEndpoint(service.service_endpoint.clone()));
+            }
 
-            service.recipient_keys
-                .iter()
-                .map(|key| self.validate_recipient_key(key))
-                .collect::<VcxResult<()>>()?;
+            for key in service.recipient_keys.iter() {
+                self.validate_recipient_key(key, &mut issues);
+            }
 
-            service.routing_keys
-                .iter()
-                .map(|key| self.validate_routing_key(key))
-                .collect::<VcxResult<()>>()?;
+            for key in service.routing_keys.iter() {
+                self.validate_routing_key(key, &mut issues);
+            }
         }
 
-        Ok(())
+        issues
     }
 
-    fn validate_recipient_key(&self, key: &str) -> VcxResult<()> {
-        let public_key = self.validate_public_key(key)?;
-        self.validate_authentication(&public_key.id)
+    fn validate_recipient_key(&self, key: &str, issues: &mut Vec<DidDocValidationIssue>) {
+        if let Some(public_key) = self.validate_public_key(key, issues) {
+            self.validate_authentication(&public_key.id, issues);
+        }
     }
 
-    fn validate_routing_key(&self, key: &str) -> VcxResult<()> {
-        if DidDoc::_key_parts(key).len() == 2 {
-            self.validate_public_key(key)?;
-        } else {
-            validate_verkey(key)?;
+    fn validate_routing_key(&self, key: &str, issues: &mut Vec<DidDocValidationIssue>) {
+        if key.is_empty() {
+            issues.push(DidDocValidationIssue::EmptyRoutingKey);
+        } else if DidDoc::_key_parts(key).len() == 2 {
+            self.validate_public_key(key, issues);
+        } else if validate_verkey(key).is_err() {
+            issues.push(DidDocValidationIssue::InvalidVerkey(key.to_string()));
         }
-        Ok(())
     }
 
-    fn validate_public_key(&self, target_key: &str) -> VcxResult<&Ed25519PublicKey> {
+    fn validate_public_key(&self, target_key: &str, issues: &mut Vec<DidDocValidationIssue>) -> Option<&Ed25519PublicKey> {
         let id = DidDoc::_parse_key_reference(target_key);
 
-        let key = self.public_key.iter().find(|key_| key_.id == id.to_string() || key_.public_key_base_58 == id.to_string())
-            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("DIDDoc validation failed: Cannot find PublicKey definition for key: {:?}", id)))?;
+        let key = match self.public_key.iter().find(|key_| key_.id == id.to_string() || key_.public_key_base_58 == id.to_string()) {
+            Some(key) => key,
+            None => {
+                issues.push(DidDocValidationIssue::MissingPublicKey(id));
+                return None;
+            }
+        };
 
         if key.type_ != KEY_TYPE {
-            return Err(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("DIDDoc validation failed: Unsupported PublicKey type: {:?}", key.type_)));
+            issues.push(DidDocValidationIssue::UnsupportedPublicKeyType(key.type_.clone()));
         }
 
-        validate_verkey(&key.public_key_base_58)?;
+        if validate_verkey(&key.public_key_base_58).is_err() {
+            issues.push(DidDocValidationIssue::InvalidVerkey(key.public_key_base_58.clone()));
+        }
 
-        Ok(key)
+        Some(key)
     }
 
-    fn validate_authentication(&self, target_key: &str) -> VcxResult<()> {
+    fn validate_authentication(&self, target_key: &str, issues: &mut Vec<DidDocValidationIssue>) {
         if self.authentication.is_empty() {
-            return Ok(());
+            return;
         }
 
         let key = self.authentication.iter().find(|key_|
             key_.public_key == target_key.to_string() ||
-                DidDoc::_parse_key_reference(&key_.public_key) == target_key.to_string())
-            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("DIDDoc validation failed: Cannot find Authentication section for key: {:?}", target_key)))?;
+                DidDoc::_parse_key_reference(&key_.public_key) == target_key.to_string());
 
-        if key.type_ != KEY_AUTHENTICATION_TYPE && key.type_ != KEY_TYPE {
-            return Err(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("DIDDoc validation failed: Unsupported Authentication type: {:?}", key.type_)));
+        match key {
+            None => issues.push(DidDocValidationIssue::MissingAuthentication(target_key.to_string())),
+            Some(key) if key.type_ != KEY_AUTHENTICATION_TYPE && key.type_ != KEY_TYPE =>
+                issues.push(DidDocValidationIssue::UnsupportedAuthenticationType(key.type_.clone())),
+            Some(_) => {}
         }
-
-        Ok(())
     }
 
     pub fn resolve_keys(&self) -> (Vec<String>, Vec<String>) {
@@ -236,6 +300,21 @@ impl DidDoc {
         (recipient_keys, routing_keys)
     }
 
+    /**
+    Services following the primary one, with key references resolved to keys, so they can be published without
+    the rest of the DidDoc.
+     */
+    pub fn resolve_additional_services(&self) -> Vec<Service> {
+        self.service.iter()
+            .skip(1)
+            .map(|service| Service {
+                recipient_keys: service.recipient_keys.iter().map(|key| self.key_for_reference(key)).collect(),
+                routing_keys: service.routing_keys.iter().map(|key| self.key_for_reference(key)).collect(),
+                ..service.clone()
+            })
+            .collect()
+    }
+
     pub fn recipient_keys(&self) -> Vec<String> {
         let (recipient_keys, _) = self.resolve_keys();
         recipient_keys
@@ -481,6 +560,43 @@ pub mod tests {
         _did_doc_5().validate().unwrap();
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_did_doc_validation_issues_lists_all_problems() {
+        let mut did_doc = _did_doc_2();
+        did_doc.context = String::from("https://example.com/context");
+        did_doc.public_key[0].type_ = String::from("RsaVerificationKey2018");
+        did_doc.authentication.clear();
+        did_doc.service[0].service_endpoint = String::from("not an endpoint");
+        did_doc.service[0].recipient_keys.push(String::from("unknown#2"));
+        did_doc.service[0].routing_keys.push(String::new());
+
+        assert_eq!(did_doc.validation_issues(), vec![
+            DidDocValidationIssue::UnsupportedContext(String::from("https://example.com/context")),
+            DidDocValidationIssue::InvalidEndpoint(String::from("not an endpoint")),
+            DidDocValidationIssue::UnsupportedPublicKeyType(String::from("RsaVerificationKey2018")),
+            DidDocValidationIssue::MissingPublicKey(String::from("2")),
+            DidDocValidationIssue::EmptyRoutingKey,
+        ]);
+
+        let err = did_doc.validate().unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::InvalidJson);
+        assert!(err.to_string().contains("Unsupported @context value"));
+        assert!(err.to_string().contains("Routing key is empty"));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_did_doc_validation_issues_reports_missing_service_and_authentication() {
+        let mut did_doc = _did_doc_4();
+        did_doc.authentication[0].public_key = _key_2();
+        assert_eq!(did_doc.validation_issues(), vec![DidDocValidationIssue::MissingAuthentication(_key_1())]);
+
+        did_doc.service.clear();
+        assert_eq!(did_doc.validation_issues(), vec![DidDocValidationIssue::MissingService]);
+        assert!(_did_doc_4().validation_issues().is_empty());
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_did_doc_key_for_reference_works() {
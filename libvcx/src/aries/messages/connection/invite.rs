@@ -1,4 +1,5 @@
 use crate::aries::messages::a2a::{A2AMessage, MessageId};
+use crate::aries::messages::connection::did_doc::DidDoc;
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Default)]
 pub struct Invitation {
@@ -19,6 +20,18 @@ impl Invitation {
         Invitation::default()
     }
 
+    /**
+    Invitation to connect to the service of the DidDoc, with its recipient and routing keys.
+     */
+    pub fn from_did_doc(label: String, did_doc: &DidDoc) -> Invitation {
+        let (recipient_keys, routing_keys) = did_doc.resolve_keys();
+        Invitation::create()
+            .set_label(label)
+            .set_service_endpoint(did_doc.get_endpoint())
+            .set_recipient_keys(recipient_keys)
+            .set_routing_keys(routing_keys)
+    }
+
     pub fn set_label(mut self, label: String) -> Invitation {
         self.label = label;
         self
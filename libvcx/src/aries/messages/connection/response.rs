@@ -1,5 +1,4 @@
 use base64;
-use time;
 
 use crate::aries::messages::a2a::{A2AMessage, MessageId};
 use crate::aries::messages::a2a::message_family::MessageFamilies;
@@ -7,8 +6,10 @@ use crate::aries::messages::a2a::message_type::MessageType;
 use crate::aries::messages::ack::PleaseAck;
 use crate::aries::messages::connection::did_doc::*;
 use crate::aries::messages::thread::Thread;
+use crate::aries::messages::timing::Timing;
 use crate::error::prelude::*;
 use crate::libindy::utils::crypto;
+use crate::utils::clock;
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
 pub struct Response {
@@ -20,6 +21,9 @@ pub struct Response {
     #[serde(rename = "~please_ack")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub please_ack: Option<PleaseAck>,
+    #[serde(rename = "~timing")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<Timing>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
@@ -41,6 +45,9 @@ pub struct SignedResponse {
     #[serde(rename = "~please_ack")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub please_ack: Option<PleaseAck>,
+    #[serde(rename = "~timing")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<Timing>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -73,10 +80,20 @@ impl Response {
         self
     }
 
+    pub fn add_services(mut self, services: Vec<Service>) -> Response {
+        self.connection.did_doc.service.extend(services);
+        self
+    }
+
+    pub fn set_timing(mut self, timing: Option<Timing>) -> Response {
+        self.timing = timing;
+        self
+    }
+
     pub fn encode(&self, key: &str) -> VcxResult<SignedResponse> {
         let connection_data = json!(self.connection).to_string();
 
-        let now: u64 = time::get_time().sec as u64;
+        let now: u64 = clock::now_secs();
 
         let mut sig_data = now.to_be_bytes().to_vec();
 
@@ -100,6 +117,7 @@ impl Response {
             thread: self.thread.clone(),
             connection_sig,
             please_ack: self.please_ack.clone(),
+            timing: self.timing.clone(),
         };
 
         Ok(signed_response)
@@ -133,6 +151,7 @@ impl SignedResponse {
             thread: self.thread,
             connection,
             please_ack: self.please_ack,
+            timing: self.timing,
         })
     }
 }
@@ -155,6 +174,7 @@ pub mod tests {
     use crate::aries::messages::connection::did_doc::tests::*;
     use crate::libindy::utils::tests::test_setup;
     use crate::libindy::utils::tests::test_setup::create_trustee_key;
+    use crate::utils::clock::SetupFakeClock;
 
     use super::*;
 
@@ -183,6 +203,7 @@ pub mod tests {
                 did_doc: _did_doc(),
             },
             please_ack: None,
+            timing: None,
         }
     }
 
@@ -197,6 +218,7 @@ pub mod tests {
                 ..Default::default()
             },
             please_ack: None,
+            timing: None,
         }
     }
 
@@ -220,4 +242,18 @@ pub mod tests {
         let signed_response: SignedResponse = _response().encode(&trustee_key).unwrap();
         assert_eq!(_response(), signed_response.decode(&trustee_key).unwrap());
     }
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_response_encode_signs_current_clock_time() {
+        let setup = test_setup::setup_wallet();
+        let _clock = SetupFakeClock::init(1_600_000_000);
+        let trustee_key = create_trustee_key(setup.wh);
+
+        let signed_response: SignedResponse = _response().encode(&trustee_key).unwrap();
+
+        let sig_data = base64::decode_config(&signed_response.connection_sig.sig_data.as_bytes(), base64::URL_SAFE).unwrap();
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&sig_data[..8]);
+        assert_eq!(1_600_000_000, u64::from_be_bytes(timestamp));
+    }
 }
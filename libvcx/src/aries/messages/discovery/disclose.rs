@@ -18,6 +18,12 @@ pub struct ProtocolDescriptor {
     pub roles: Option<Vec<Actors>>
 }
 
+impl ProtocolDescriptor {
+    pub fn to_pid_and_roles(&self) -> (String, Vec<Actors>) {
+        (self.pid.clone(), self.roles.clone().unwrap_or_default())
+    }
+}
+
 impl Disclose {
     pub fn create() -> Disclose {
         Disclose::default()
@@ -69,4 +75,13 @@ pub mod tests {
 
         assert_eq!(_disclose(), disclose);
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_protocol_descriptor_to_pid_and_roles() {
+        let descriptor = ProtocolDescriptor { pid: String::from("did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/present-proof/1.0"), roles: Some(vec![Actors::Prover]) };
+        assert_eq!((descriptor.pid.clone(), vec![Actors::Prover]), descriptor.to_pid_and_roles());
+
+        assert_eq!((_protocol_descriptor().pid, vec![]), _protocol_descriptor().to_pid_and_roles());
+    }
 }
@@ -1,4 +1,5 @@
 use crate::aries::messages::a2a::{A2AMessage, MessageId};
+use crate::aries::messages::a2a::message_family::MessageFamilies;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct Query {
@@ -20,6 +21,11 @@ impl Query {
         self
     }
 
+    pub fn set_protocol_query(mut self, query: &ProtocolQuery) -> Self {
+        self.query = Some(query.to_query_string());
+        self
+    }
+
     pub fn set_comment(mut self, comment: Option<String>) -> Self {
         self.comment = comment;
         self
@@ -30,6 +36,31 @@ impl Query {
     }
 }
 
+/**
+Structured form of the RFC 0031 query: protocol family plus optional version glob (e.g. `1.*`).
+Missing version matches all versions of the family.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolQuery {
+    pub family: MessageFamilies,
+    pub version: Option<String>,
+}
+
+impl ProtocolQuery {
+    pub fn create(family: MessageFamilies) -> ProtocolQuery {
+        ProtocolQuery { family, version: None }
+    }
+
+    pub fn set_version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    pub fn to_query_string(&self) -> String {
+        format!("{};spec/{}/{}", MessageFamilies::DID, self.family.to_string(), self.version.as_ref().map(String::as_str).unwrap_or("*"))
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -60,4 +91,24 @@ pub mod tests {
 
         assert_eq!(_query(), query);
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_protocol_query_to_query_string() {
+        let query = ProtocolQuery::create(MessageFamilies::PresentProof);
+        assert_eq!("did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/present-proof/*", query.to_query_string());
+
+        let query = query.set_version("1.*");
+        assert_eq!("did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/present-proof/1.*", query.to_query_string());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_query_build_with_protocol_query_works() {
+        let protocol_query = ProtocolQuery::create(MessageFamilies::PresentProof).set_version("1.*");
+        let query: Query = Query::default()
+            .set_protocol_query(&protocol_query);
+
+        assert_eq!(Some(protocol_query.to_query_string()), query.query);
+    }
 }
\ No newline at end of file
@@ -1,7 +1,15 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+
+use serde::{de, Deserialize, Deserializer};
+use serde_json::Value;
 
 use crate::aries::messages::thread::Thread;
 use crate::aries::messages::a2a::{A2AMessage, MessageId};
+use crate::aries::messages::a2a::message_family::MessageFamilies;
+use crate::aries::messages::a2a::message_type::MessageType;
+use crate::error::prelude::*;
+
+pub const REQUEST_DECLINED: &str = "request_declined";
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct ProblemReport {
@@ -30,7 +38,7 @@ pub struct ProblemReport {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub location: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub problem_items: Option<HashMap<String, String>>,
+    pub problem_items: Option<BTreeMap<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub comment: Option<String>,
 }
@@ -40,14 +48,18 @@ impl ProblemReport {
         ProblemReport::default()
     }
 
-    pub fn set_description(mut self, code: u32) -> Self {
+    pub fn set_description(mut self, code: &str) -> Self {
         self.description = Some(Description {
             en: None,
-            code,
+            code: code.to_string(),
         });
         self
     }
 
+    pub fn code(&self) -> Option<String> {
+        self.description.as_ref().map(|description| description.code.clone())
+    }
+
     pub fn set_comment(mut self, comment: String) -> Self {
         self.comment = Some(comment);
         self
@@ -57,11 +69,37 @@ impl ProblemReport {
 threadlike!(ProblemReport);
 a2a_message!(ProblemReport, CommonProblemReport);
 
+impl ProblemReport {
+    /**
+    Problem report typed in the given protocol family, eg. `present-proof/1.0/problem-report`, instead of
+    the generic `report-problem` family. Protocols adopting the problem report keep its structure.
+     */
+    pub fn to_a2a_message_for_family(&self, family: MessageFamilies) -> VcxResult<A2AMessage> {
+        if family == MessageFamilies::ReportProblem {
+            return Ok(self.to_a2a_message());
+        }
+        let mut value = serde_json::to_value(self.to_a2a_message())
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize problem report: {}", err)))?;
+        value["@type"] = json!(MessageType::build(family, A2AMessage::PROBLEM_REPORT));
+        Ok(A2AMessage::Generic(value))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct Description {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub en: Option<String>,
-    pub code: u32,
+    #[serde(deserialize_with = "deserialize_code")]
+    pub code: String,
+}
+
+// older versions of libvcx sent numeric codes
+fn deserialize_code<'de, D>(deserializer: D) -> Result<String, D::Error> where D: Deserializer<'de> {
+    match Value::deserialize(deserializer)? {
+        Value::String(code) => Ok(code),
+        Value::Number(code) => Ok(code.to_string()),
+        other => Err(de::Error::custom(format!("Unexpected problem report code: {}", other)))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -106,7 +144,7 @@ pub mod tests {
 
     use super::*;
 
-    fn _code() -> u32 { 0 }
+    fn _code() -> String { String::from("0") }
 
     fn _comment() -> String {
         String::from("test comment")
@@ -135,8 +173,25 @@ pub mod tests {
         let report: ProblemReport = ProblemReport::default()
             .set_comment(_comment())
             .set_thread_id(&_thread_id())
-            .set_description(_code());
+            .set_description(&_code());
 
         assert_eq!(_problem_report(), report);
     }
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_problem_report_deserializes_numeric_and_string_codes() {
+        let mut report = json!(_problem_report());
+
+        report["description"]["code"] = json!(0);
+        let parsed: ProblemReport = serde_json::from_value(report.clone()).unwrap();
+        assert_eq!(Some(_code()), parsed.code());
+
+        report["description"]["code"] = json!(REQUEST_DECLINED);
+        let parsed: ProblemReport = serde_json::from_value(report.clone()).unwrap();
+        assert_eq!(Some(REQUEST_DECLINED.to_string()), parsed.code());
+        assert_eq!(json!(parsed)["description"]["code"], json!(REQUEST_DECLINED));
+
+        report["description"]["code"] = json!(["request_declined"]);
+        assert!(serde_json::from_value::<ProblemReport>(report).is_err());
+    }
 }
@@ -105,6 +105,13 @@ pub mod tests {
         }
     }
 
+    /**
+    Offer starting a new issuance, so its id is the thread id of the protocol.
+     */
+    pub fn _credential_offer_starting_thread() -> CredentialOffer {
+        CredentialOffer { thread: None, .._credential_offer() }
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_credential_offer_build_works() {
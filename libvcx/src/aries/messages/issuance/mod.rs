@@ -21,21 +21,27 @@ impl CredentialPreviewData {
         CredentialPreviewData::default()
     }
 
+    /**
+    Adds attribute to the preview, values of other than text types are expected to be base64 encoded.
+     */
     pub fn add_value(mut self, name: &str, value: &str, mime_type: MimeType) -> VcxResult<CredentialPreviewData> {
-        let data_value = match mime_type {
-            MimeType::Plain => {
-                CredentialValue {
-                    name: name.to_string(),
-                    value: value.to_string(),
-                    _type: None,
-                }
-            }
+        let data_value = CredentialValue {
+            name: name.to_string(),
+            value: value.to_string(),
+            // text/plain is the default, it's omitted for compatibility with agents not expecting mime-type
+            _type: if mime_type.is_plain() { None } else { Some(mime_type) },
         };
         self.attributes.push(data_value);
         Ok(self)
     }
 }
 
+impl CredentialValue {
+    pub fn mime_type(&self) -> MimeType {
+        self._type.clone().unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
 pub struct CredentialValue {
     pub name: String,
@@ -1,7 +1,45 @@
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+/**
+Mime type of attribute values, values of non-text types (eg. images) are base64 encoded.
+Types without a dedicated variant are kept as `Other`, so messages using them can still be processed.
+ */
+#[derive(Debug, PartialEq, Clone)]
 pub enum MimeType {
-    #[serde(rename = "text/plain")]
-    Plain
+    Plain,
+    Json,
+    Png,
+    Jpeg,
+    Other(String),
+}
+
+impl MimeType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            MimeType::Plain => "text/plain",
+            MimeType::Json => "application/json",
+            MimeType::Png => "image/png",
+            MimeType::Jpeg => "image/jpeg",
+            MimeType::Other(mime_type) => mime_type,
+        }
+    }
+
+    pub fn is_plain(&self) -> bool {
+        *self == MimeType::Plain
+    }
+}
+
+impl From<&str> for MimeType {
+    fn from(mime_type: &str) -> MimeType {
+        match mime_type {
+            "text/plain" => MimeType::Plain,
+            "application/json" => MimeType::Json,
+            "image/png" => MimeType::Png,
+            "image/jpeg" => MimeType::Jpeg,
+            other => MimeType::Other(other.to_string()),
+        }
+    }
 }
 
 impl Default for MimeType {
@@ -9,3 +47,19 @@ impl Default for MimeType {
         MimeType::Plain
     }
 }
+
+impl<'de> Deserialize<'de> for MimeType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        let value = Value::deserialize(deserializer).map_err(de::Error::custom)?;
+        match value.as_str() {
+            Some(mime_type) => Ok(MimeType::from(mime_type)),
+            _ => Err(de::Error::custom("Unexpected mime-type field structure."))
+        }
+    }
+}
+
+impl Serialize for MimeType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_str(self.as_str())
+    }
+}
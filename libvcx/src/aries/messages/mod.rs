@@ -15,4 +15,7 @@ pub mod proof_presentation;
 pub mod discovery;
 pub mod trust_ping;
 pub mod basic_message;
+pub mod revocation_notification;
 pub mod localization;
+pub mod timing;
+pub mod out_of_band;
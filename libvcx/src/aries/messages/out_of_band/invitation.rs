@@ -0,0 +1,313 @@
+use serde_json::Value;
+
+use crate::aries::messages::a2a::{A2AMessage, MessageId};
+use crate::aries::messages::a2a::message_family::MessageFamilies;
+use crate::aries::messages::connection::did_doc::DidDoc;
+use agency_client::message_type::parse_message_type;
+
+use crate::aries::messages::connection::invite::Invitation;
+use crate::aries::messages::proof_presentation::v2::DIDCOMM_PREFIX;
+use crate::error::prelude::*;
+
+/**
+Out-of-band invitation (RFC 0434). Only inline services are supported, the first of them is used
+to establish the connection. Request attached in `requests~attach` is kept so it can be processed
+once the connection is established.
+ */
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Default)]
+pub struct OutOfBandInvitation {
+    #[serde(rename = "@type")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub type_: String,
+    #[serde(rename = "@id")]
+    pub id: MessageId,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub handshake_protocols: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accept: Vec<String>,
+    #[serde(rename = "requests~attach")]
+    #[serde(default)]
+    pub requests_attach: Vec<RequestAttachment>,
+    pub services: Vec<OutOfBandService>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum OutOfBandService {
+    Inline(InlineService),
+    Did(String),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Default)]
+pub struct InlineService {
+    #[serde(default)]
+    pub id: String,
+    #[serde(rename = "type")]
+    #[serde(default)]
+    pub type_: String,
+    #[serde(rename = "recipientKeys")]
+    pub recipient_keys: Vec<String>,
+    #[serde(rename = "routingKeys")]
+    #[serde(default)]
+    pub routing_keys: Vec<String>,
+    #[serde(rename = "serviceEndpoint")]
+    pub service_endpoint: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Default)]
+pub struct RequestAttachment {
+    #[serde(rename = "@id")]
+    #[serde(default)]
+    pub id: String,
+    #[serde(rename = "mime-type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub data: RequestAttachmentData,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Default)]
+pub struct RequestAttachmentData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base64: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json: Option<Value>,
+}
+
+impl RequestAttachment {
+    pub fn content(&self) -> VcxResult<A2AMessage> {
+        let json: Value = match (&self.data.json, &self.data.base64) {
+            (Some(json), _) => json.clone(),
+            (None, Some(base64)) => {
+                let bytes = base64::decode(base64)
+                    .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot decode attachment {}: {:?}", self.id, err)))?;
+                serde_json::from_slice(&bytes)
+                    .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Attachment {} is not valid JSON: {:?}", self.id, err)))?
+            }
+            (None, None) => return Err(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Attachment {} has no data", self.id)))
+        };
+
+        serde_json::from_value(json)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Attachment {} does not contain a message: {:?}", self.id, err)))
+    }
+}
+
+impl OutOfBandInvitation {
+    pub fn type_() -> String {
+        format!("{};spec/out-of-band/1.0/invitation", MessageFamilies::DID)
+    }
+
+    /**
+    Builds invitation to the connections protocol carrying every service of `did_doc` inline, the primary one first,
+    so services RFC 0160 invitation has no room for are published as well. Its id is the id of `invitation`.
+     */
+    pub fn from_connection_invitation(invitation: &Invitation, did_doc: &DidDoc) -> OutOfBandInvitation {
+        let primary_service = InlineService {
+            id: did_doc.service.get(0).map(|service| service.id.clone()).unwrap_or_default(),
+            type_: did_doc.service.get(0).map(|service| service.type_.clone()).unwrap_or_default(),
+            recipient_keys: invitation.recipient_keys.clone(),
+            routing_keys: invitation.routing_keys.clone(),
+            service_endpoint: invitation.service_endpoint.clone(),
+        };
+        let additional_services = did_doc.resolve_additional_services().into_iter()
+            .map(|service| OutOfBandService::Inline(InlineService {
+                id: service.id,
+                type_: service.type_,
+                recipient_keys: service.recipient_keys,
+                routing_keys: service.routing_keys,
+                service_endpoint: service.service_endpoint,
+            }));
+
+        OutOfBandInvitation {
+            type_: OutOfBandInvitation::type_(),
+            id: invitation.id.clone(),
+            label: invitation.label.clone(),
+            handshake_protocols: vec![MessageFamilies::Connections.id()],
+            accept: vec![],
+            requests_attach: vec![],
+            services: vec![OutOfBandService::Inline(primary_service)].into_iter().chain(additional_services).collect(),
+        }
+    }
+
+    /**
+    Checks the invitation is out-of-band invitation proposing the connections protocol, the only handshake
+    protocol supported. Protocol ids are accepted in both `did:sov:...;spec/` and `https://didcomm.org/` form.
+     */
+    pub fn validate(&self) -> VcxResult<()> {
+        match parse_message_type(&self.type_) {
+            Ok((_, ref family, _, ref type_)) if family == "out-of-band" && type_ == "invitation" => {}
+            _ => return Err(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Unexpected out-of-band invitation @type: {:?}", self.type_)))
+        }
+
+        let connections = MessageFamilies::Connections;
+        let supported_protocols = vec![connections.id(), format!("{}/{}/{}", DIDCOMM_PREFIX, connections.to_string(), connections.version())];
+        if !self.handshake_protocols.iter().any(|protocol| supported_protocols.contains(protocol)) {
+            return Err(VcxError::from_msg(VcxErrorKind::ActionNotSupported,
+                                          format!("None of out-of-band invitation handshake protocols is supported: {:?}, supported protocols are {:?}",
+                                                  self.handshake_protocols, supported_protocols)));
+        }
+        Ok(())
+    }
+
+    pub fn to_connection_invitation(&self) -> VcxResult<Invitation> {
+        let service = self.services.iter()
+            .filter_map(|service| match service {
+                OutOfBandService::Inline(service) => Some(service),
+                OutOfBandService::Did(_) => None
+            })
+            .next()
+            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidJson, "Out-of-band invitation does not contain inline service"))?;
+
+        Ok(Invitation::create()
+            .set_id(self.id.0.clone())
+            .set_label(self.label.clone())
+            .set_recipient_keys(service.recipient_keys.clone())
+            .set_routing_keys(service.routing_keys.clone())
+            .set_service_endpoint(service.service_endpoint.clone()))
+    }
+
+    /**
+    Returns the first attached credential offer or presentation request, other requests are ignored.
+     */
+    pub fn attached_request(&self) -> VcxResult<Option<A2AMessage>> {
+        for attachment in self.requests_attach.iter() {
+            match attachment.content()? {
+                message @ A2AMessage::CredentialOffer(_) |
+                message @ A2AMessage::PresentationRequest(_) => return Ok(Some(message)),
+                message => warn!("OutOfBandInvitation::attached_request >>> ignoring unsupported attached request: {:?}", message)
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::aries::messages::connection::did_doc::tests::*;
+    use crate::aries::messages::issuance::credential_offer::tests::_credential_offer;
+    use crate::aries::messages::proof_presentation::presentation_request::tests::_presentation_request;
+
+    use super::*;
+
+    fn _inline_service() -> OutOfBandService {
+        OutOfBandService::Inline(InlineService {
+            id: String::from("#inline"),
+            type_: String::from("did-communication"),
+            recipient_keys: _recipient_keys(),
+            routing_keys: _routing_keys(),
+            service_endpoint: _service_endpoint(),
+        })
+    }
+
+    pub fn _request_attachment(message: &A2AMessage) -> RequestAttachment {
+        RequestAttachment {
+            id: String::from("request-0"),
+            mime_type: Some(String::from("application/json")),
+            data: RequestAttachmentData {
+                base64: Some(base64::encode(&serde_json::to_string(message).unwrap())),
+                json: None,
+            },
+        }
+    }
+
+    pub fn _oob_invitation_with_offer() -> OutOfBandInvitation {
+        OutOfBandInvitation {
+            type_: OutOfBandInvitation::type_(),
+            id: MessageId::id(),
+            label: _label(),
+            handshake_protocols: vec![String::from("did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/connections/1.0")],
+            accept: vec![],
+            requests_attach: vec![_request_attachment(&A2AMessage::CredentialOffer(_credential_offer()))],
+            services: vec![OutOfBandService::Did(String::from("did:sov:LjgpST2rjsoxYegQDRm7EL")), _inline_service()],
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_oob_invitation_to_connection_invitation() {
+        let invitation = _oob_invitation_with_offer().to_connection_invitation().unwrap();
+
+        assert_eq!(_recipient_keys(), invitation.recipient_keys);
+        assert_eq!(_routing_keys(), invitation.routing_keys);
+        assert_eq!(_service_endpoint(), invitation.service_endpoint);
+        assert_eq!(_label(), invitation.label);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_oob_invitation_without_inline_service_fails() {
+        let mut oob_invitation = _oob_invitation_with_offer();
+        oob_invitation.services = vec![OutOfBandService::Did(String::from("did:sov:LjgpST2rjsoxYegQDRm7EL"))];
+
+        assert_eq!(VcxErrorKind::InvalidJson, oob_invitation.to_connection_invitation().unwrap_err().kind());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_oob_invitation_attached_request() {
+        let oob_invitation = _oob_invitation_with_offer();
+        assert_eq!(Some(A2AMessage::CredentialOffer(_credential_offer())), oob_invitation.attached_request().unwrap());
+
+        let mut oob_invitation = _oob_invitation_with_offer();
+        oob_invitation.requests_attach = vec![RequestAttachment {
+            id: String::from("request-0"),
+            mime_type: None,
+            data: RequestAttachmentData { base64: None, json: Some(json!(A2AMessage::PresentationRequest(_presentation_request()))) },
+        }];
+        assert_eq!(Some(A2AMessage::PresentationRequest(_presentation_request())), oob_invitation.attached_request().unwrap());
+
+        oob_invitation.requests_attach = vec![];
+        assert_eq!(None, oob_invitation.attached_request().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_oob_invitation_deserialization() {
+        let oob_invitation: OutOfBandInvitation = serde_json::from_value(json!({
+            "@type": "https://didcomm.org/out-of-band/1.0/invitation",
+            "@id": "testid",
+            "label": _label(),
+            "handshake_protocols": ["did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/connections/1.0"],
+            "requests~attach": [serde_json::to_value(_request_attachment(&A2AMessage::CredentialOffer(_credential_offer()))).unwrap()],
+            "services": ["did:sov:LjgpST2rjsoxYegQDRm7EL", {
+                "id": "#inline",
+                "type": "did-communication",
+                "recipientKeys": _recipient_keys(),
+                "routingKeys": _routing_keys(),
+                "serviceEndpoint": _service_endpoint()
+            }]
+        })).unwrap();
+
+        assert_eq!(_oob_invitation_with_offer(), oob_invitation);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_oob_invitation_validates_type() {
+        _oob_invitation_with_offer().validate().unwrap();
+
+        let mut oob_invitation = _oob_invitation_with_offer();
+        oob_invitation.type_ = String::from("https://didcomm.org/out-of-band/1.0/invitation");
+        oob_invitation.validate().unwrap();
+
+        for type_ in vec!["", "invitation", "did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/connections/1.0/invitation", "https://didcomm.org/out-of-band/1.0/handshake-reuse"] {
+            oob_invitation.type_ = String::from(type_);
+            assert_eq!(VcxErrorKind::InvalidJson, oob_invitation.validate().unwrap_err().kind());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_oob_invitation_validates_handshake_protocols() {
+        let mut oob_invitation = _oob_invitation_with_offer();
+        oob_invitation.handshake_protocols = vec![String::from("https://didcomm.org/didexchange/1.0"), String::from("https://didcomm.org/connections/1.0")];
+        oob_invitation.validate().unwrap();
+
+        oob_invitation.handshake_protocols = vec![String::from("https://didcomm.org/didexchange/1.0")];
+        assert_eq!(VcxErrorKind::ActionNotSupported, oob_invitation.validate().unwrap_err().kind());
+
+        oob_invitation.handshake_protocols = vec![];
+        assert_eq!(VcxErrorKind::ActionNotSupported, oob_invitation.validate().unwrap_err().kind());
+    }
+}
@@ -63,7 +63,7 @@ pub mod tests {
             comment: Some(_comment()),
             presentations_attach: attachment,
             thread: thread(),
-            please_ack: Some(PleaseAck {}),
+            please_ack: Some(PleaseAck::default()),
         }
     }
 
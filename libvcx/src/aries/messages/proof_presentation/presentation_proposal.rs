@@ -47,6 +47,15 @@ fn default_presentation_preview_type() -> MessageType {
     MessageType::build(MessageFamilies::CredentialIssuance, "presentation-preview")
 }
 
+impl PresentationPreview {
+    pub fn create() -> Self {
+        PresentationPreview {
+            _type: default_presentation_preview_type(),
+            ..PresentationPreview::default()
+        }
+    }
+}
+
 impl PresentationProposal {
     pub fn create() -> Self {
         PresentationProposal::default()
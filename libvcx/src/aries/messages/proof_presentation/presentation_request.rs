@@ -2,6 +2,8 @@ use crate::error::prelude::*;
 use crate::aries::messages::a2a::{A2AMessage, MessageId};
 use crate::aries::messages::attachment::{AttachmentId, Attachments};
 use crate::aries::messages::connection::service::Service;
+use crate::aries::messages::proof_presentation::v2::PRESENT_PROOF_V1;
+use crate::aries::messages::thread::Thread;
 use crate::libindy::proofs::proof_request::ProofRequestData;
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
@@ -14,7 +16,14 @@ pub struct PresentationRequest {
     pub request_presentations_attach: Attachments,
     #[serde(rename = "~service")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub service: Option<Service>
+    pub service: Option<Service>,
+    #[serde(rename = "~thread")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread: Option<Thread>,
+    // present-proof version the request was exchanged in, kept so the answers are sent in the same version
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub present_proof_version: Option<String>,
 }
 
 impl PresentationRequest {
@@ -41,6 +50,31 @@ impl PresentationRequest {
         self.service = service;
         self
     }
+
+    pub fn set_parent_thread_id(mut self, id: &str) -> Self {
+        self.thread = Some(self.thread.unwrap_or_default().set_pthid(id.to_string()));
+        self
+    }
+
+    pub fn set_present_proof_version(mut self, version: &str) -> Self {
+        self.present_proof_version = match version {
+            PRESENT_PROOF_V1 => None,
+            version => Some(version.to_string())
+        };
+        self
+    }
+
+    /**
+    Returns present-proof version the request was exchanged in, "1.0" unless it was received or sent as "2.0".
+     */
+    pub fn present_proof_version(&self) -> &str {
+        self.present_proof_version.as_deref().unwrap_or(PRESENT_PROOF_V1)
+    }
+
+    pub fn parent_thread_id(&self) -> Option<String> {
+        self.thread.as_ref().and_then(|thread| thread.pthid.clone())
+    }
+
     pub fn to_json(&self) -> VcxResult<String> {
         serde_json::to_string(self)
             .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot serialize PresentationRequest: {}", err)))
@@ -54,7 +88,6 @@ pub type PresentationRequestData = ProofRequestData;
 
 #[cfg(test)]
 pub mod tests {
-    use crate::aries::messages::thread::Thread;
     use crate::aries::messages::connection::service::tests::_service;
 
     use super::*;
@@ -88,6 +121,8 @@ pub mod tests {
             comment: Some(_comment()),
             request_presentations_attach: _attachment(),
             service: None,
+            thread: None,
+            present_proof_version: None,
         }
     }
 
@@ -97,6 +132,8 @@ pub mod tests {
             comment: Some(_comment()),
             request_presentations_attach: _attachment(),
             service: Some(_service()),
+            thread: None,
+            present_proof_version: None,
         }
     }
 
@@ -120,4 +157,36 @@ pub mod tests {
 
         assert_eq!(_presentation_request_with_service(), presentation_request);
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_presentation_request_build_works_for_parent_thread() {
+        let presentation_request: PresentationRequest = PresentationRequest::default()
+            .set_parent_thread_id("parent-thread-id");
+
+        assert_eq!(Some("parent-thread-id".to_string()), presentation_request.parent_thread_id());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_presentation_request_parses_thread_decorator() {
+        let mut message = json!(_presentation_request());
+        message["~thread"] = json!({"thid": "thread-id", "pthid": "parent-thread-id"});
+
+        let presentation_request: PresentationRequest = serde_json::from_value(message).unwrap();
+
+        let thread = presentation_request.thread.clone().unwrap();
+        assert_eq!(Some("thread-id".to_string()), thread.thid);
+        assert_eq!(Some("parent-thread-id".to_string()), thread.pthid);
+        assert_eq!(Some("parent-thread-id".to_string()), presentation_request.parent_thread_id());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_presentation_request_without_thread_decorator_has_no_parent_thread() {
+        let presentation_request: PresentationRequest = serde_json::from_value(json!(_presentation_request())).unwrap();
+
+        assert_eq!(None, presentation_request.thread);
+        assert_eq!(None, presentation_request.parent_thread_id());
+    }
 }
@@ -0,0 +1,311 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::aries::messages::a2a::{A2AMessage, MessageId};
+use crate::aries::messages::a2a::message_family::MessageFamilies;
+use crate::aries::messages::a2a::message_type::MessageType;
+use crate::aries::messages::ack::PleaseAck;
+use crate::aries::messages::attachment::{AttachmentId, Attachments};
+use crate::aries::messages::connection::service::Service;
+use crate::aries::messages::discovery::disclose::ProtocolDescriptor;
+use crate::aries::messages::proof_presentation::presentation::Presentation;
+use crate::aries::messages::proof_presentation::presentation_request::PresentationRequest;
+use crate::aries::messages::thread::Thread;
+use crate::error::prelude::*;
+
+/*
+Present proof 2.0 (RFC 0454) support. Messages are converted from/to their 1.0 counterparts on the wire,
+so the prover and verifier state machines work with 1.0 messages regardless of the negotiated version.
+ */
+
+pub const PRESENT_PROOF_V1: &str = "1.0";
+pub const PRESENT_PROOF_V2: &str = "2.0";
+
+pub const DIDCOMM_PREFIX: &str = "https://didcomm.org";
+
+pub const PROOF_REQUEST_FORMAT: &str = "hlindy/proof-req@v2.0";
+pub const PROOF_FORMAT: &str = "hlindy/proof@v2.0";
+
+pub const REQUEST_PRESENTATION: &str = "request-presentation";
+pub const PRESENTATION: &str = "presentation";
+pub const ACK: &str = "ack";
+pub const PROBLEM_REPORT: &str = "problem-report";
+
+const REQUEST_ATTACH_ID: &str = "libindy-request-presentation-0";
+const PRESENTATION_ATTACH_ID: &str = "libindy-presentation-0";
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct AttachmentFormat {
+    pub attach_id: String,
+    pub format: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct AttachmentV2 {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "mime-type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub data: AttachmentDataV2,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct AttachmentDataV2 {
+    pub base64: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct RequestPresentationV2 {
+    #[serde(rename = "@id")]
+    pub id: MessageId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub will_confirm: bool,
+    pub formats: Vec<AttachmentFormat>,
+    #[serde(rename = "request_presentations~attach")]
+    pub request_presentations_attach: Vec<AttachmentV2>,
+    #[serde(rename = "~service")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<Service>,
+    #[serde(rename = "~thread")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread: Option<Thread>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct PresentationV2 {
+    #[serde(rename = "@id")]
+    pub id: MessageId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    pub formats: Vec<AttachmentFormat>,
+    #[serde(rename = "presentations~attach")]
+    pub presentations_attach: Vec<AttachmentV2>,
+    #[serde(rename = "~thread")]
+    pub thread: Thread,
+    #[serde(rename = "~please_ack")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub please_ack: Option<PleaseAck>,
+}
+
+fn _attach(id: &str, format: &str, content: &str) -> (Vec<AttachmentFormat>, Vec<AttachmentV2>) {
+    let format = AttachmentFormat { attach_id: id.to_string(), format: format.to_string() };
+    let attachment = AttachmentV2 {
+        id: id.to_string(),
+        mime_type: Some(String::from("application/json")),
+        data: AttachmentDataV2 { base64: base64::encode(content) },
+    };
+    (vec![format], vec![attachment])
+}
+
+fn _attachment_content(formats: &[AttachmentFormat], attachments: &[AttachmentV2], format: &str) -> VcxResult<Value> {
+    let attach_id = formats.iter()
+        .find(|attachment_format| attachment_format.format == format)
+        .map(|attachment_format| attachment_format.attach_id.as_str())
+        .ok_or(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Message does not contain attachment of {} format", format)))?;
+
+    let attachment = attachments.iter()
+        .find(|attachment| attachment.id == attach_id)
+        .ok_or(VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Attachment {} not found", attach_id)))?;
+
+    let bytes = base64::decode(&attachment.data.base64)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot decode attachment {}: {:?}", attach_id, err)))?;
+
+    serde_json::from_slice(&bytes)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Attachment {} is not valid JSON: {:?}", attach_id, err)))
+}
+
+impl RequestPresentationV2 {
+    pub fn from_v1(request: &PresentationRequest) -> VcxResult<RequestPresentationV2> {
+        let (formats, request_presentations_attach) = _attach(REQUEST_ATTACH_ID, PROOF_REQUEST_FORMAT,
+                                                               &request.request_presentations_attach.content()?);
+        Ok(RequestPresentationV2 {
+            id: request.id.clone(),
+            comment: request.comment.clone(),
+            will_confirm: true,
+            formats,
+            request_presentations_attach,
+            service: request.service.clone(),
+            thread: request.thread.clone(),
+        })
+    }
+
+    pub fn into_v1(self) -> VcxResult<PresentationRequest> {
+        let content = _attachment_content(&self.formats, &self.request_presentations_attach, PROOF_REQUEST_FORMAT)?;
+
+        let mut request_presentations_attach = Attachments::new();
+        request_presentations_attach.add_base64_encoded_json_attachment(AttachmentId::PresentationRequest, content)?;
+
+        Ok(PresentationRequest {
+            id: self.id,
+            comment: self.comment,
+            request_presentations_attach,
+            service: self.service,
+            thread: self.thread,
+            present_proof_version: Some(PRESENT_PROOF_V2.to_string()),
+        })
+    }
+}
+
+impl PresentationV2 {
+    pub fn from_v1(presentation: &Presentation) -> VcxResult<PresentationV2> {
+        let (formats, presentations_attach) = _attach(PRESENTATION_ATTACH_ID, PROOF_FORMAT,
+                                                      &presentation.presentations_attach.content()?);
+        Ok(PresentationV2 {
+            id: presentation.id.clone(),
+            comment: presentation.comment.clone(),
+            formats,
+            presentations_attach,
+            thread: presentation.thread.clone(),
+            please_ack: presentation.please_ack.clone(),
+        })
+    }
+
+    pub fn into_v1(self) -> VcxResult<Presentation> {
+        let content = _attachment_content(&self.formats, &self.presentations_attach, PROOF_FORMAT)?;
+
+        let mut presentations_attach = Attachments::new();
+        presentations_attach.add_base64_encoded_json_attachment(AttachmentId::Presentation, content)?;
+
+        Ok(Presentation {
+            id: self.id,
+            comment: self.comment,
+            presentations_attach,
+            thread: self.thread,
+            please_ack: self.please_ack,
+        })
+    }
+}
+
+fn _v2_message<T: Serialize>(message: &T, name: &str) -> VcxResult<A2AMessage> {
+    let message_type = MessageType {
+        did: DIDCOMM_PREFIX.to_string(),
+        family: MessageFamilies::PresentProof,
+        version: PRESENT_PROOF_V2.to_string(),
+        type_: name.to_string(),
+    };
+
+    let mut value = serde_json::to_value(message)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize present-proof 2.0 message: {:?}", err)))?;
+    value.as_object_mut()
+        .ok_or(VcxError::from_msg(VcxErrorKind::SerializationError, "Present-proof 2.0 message is not JSON object"))?
+        .insert(String::from("@type"), Value::String(message_type.to_string()));
+
+    Ok(A2AMessage::Generic(value))
+}
+
+/**
+Converts present-proof 1.0 message into its 2.0 wire form, other messages are returned unchanged.
+ */
+pub fn to_v2_message(message: A2AMessage) -> VcxResult<A2AMessage> {
+    match message {
+        A2AMessage::PresentationRequest(request) => _v2_message(&RequestPresentationV2::from_v1(&request)?, REQUEST_PRESENTATION),
+        A2AMessage::Presentation(presentation) => _v2_message(&PresentationV2::from_v1(&presentation)?, PRESENTATION),
+        A2AMessage::PresentationAck(ack) => _v2_message(&ack, ACK),
+        A2AMessage::CommonProblemReport(problem_report) => _v2_message(&problem_report, PROBLEM_REPORT),
+        message => Ok(message)
+    }
+}
+
+/**
+Converts present-proof message for the given protocol version.
+ */
+pub fn for_version(message: A2AMessage, version: &str) -> VcxResult<A2AMessage> {
+    match version {
+        PRESENT_PROOF_V2 => to_v2_message(message),
+        _ => Ok(message)
+    }
+}
+
+/**
+Picks present-proof version to use with counterparty based on protocols it disclosed.
+Falls back to 1.0 when the counterparty's protocols are not known.
+ */
+pub fn negotiate_version(remote_protocols: Option<&Vec<ProtocolDescriptor>>) -> &'static str {
+    let supports_v2 = remote_protocols
+        .map(|protocols| protocols.iter().any(|protocol| protocol.pid.trim_end_matches('/').ends_with("present-proof/2.0")))
+        .unwrap_or(false);
+
+    if supports_v2 { PRESENT_PROOF_V2 } else { PRESENT_PROOF_V1 }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::aries::messages::proof_presentation::presentation::tests::_presentation;
+    use crate::aries::messages::proof_presentation::presentation_request::tests::_presentation_request;
+    use crate::aries::messages::proof_presentation::test::{_ack, _problem_report};
+
+    use super::*;
+
+    fn _round_trip(message: A2AMessage) -> (Value, A2AMessage) {
+        let json = serde_json::to_value(&to_v2_message(message).unwrap()).unwrap();
+        let parsed: A2AMessage = serde_json::from_value(json.clone()).unwrap();
+        (json, parsed)
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_request_presentation_v2_round_trip() {
+        let (json, parsed) = _round_trip(A2AMessage::PresentationRequest(_presentation_request()));
+
+        assert_eq!(json["@type"], "https://didcomm.org/present-proof/2.0/request-presentation");
+        assert_eq!(json["formats"][0]["format"], PROOF_REQUEST_FORMAT);
+        assert_eq!(json["formats"][0]["attach_id"], json["request_presentations~attach"][0]["@id"]);
+        assert_eq!(A2AMessage::PresentationRequest(_presentation_request().set_present_proof_version(PRESENT_PROOF_V2)), parsed);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_presentation_v2_round_trip() {
+        let (json, parsed) = _round_trip(A2AMessage::Presentation(_presentation()));
+
+        assert_eq!(json["@type"], "https://didcomm.org/present-proof/2.0/presentation");
+        assert_eq!(json["formats"][0]["format"], PROOF_FORMAT);
+        assert_eq!(A2AMessage::Presentation(_presentation()), parsed);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_ack_and_problem_report_v2_round_trip() {
+        let (json, parsed) = _round_trip(A2AMessage::PresentationAck(_ack()));
+        assert_eq!(json["@type"], "https://didcomm.org/present-proof/2.0/ack");
+        assert_eq!(A2AMessage::PresentationAck(_ack()), parsed);
+
+        let (json, parsed) = _round_trip(A2AMessage::CommonProblemReport(_problem_report()));
+        assert_eq!(json["@type"], "https://didcomm.org/present-proof/2.0/problem-report");
+        assert_eq!(A2AMessage::CommonProblemReport(_problem_report()), parsed);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_request_presentation_v2_without_known_format_fails() {
+        let mut request = RequestPresentationV2::from_v1(&_presentation_request()).unwrap();
+        request.formats[0].format = String::from("dif/presentation-exchange/definitions@v1.0");
+
+        assert_eq!(VcxErrorKind::InvalidJson, request.into_v1().unwrap_err().kind());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_negotiate_version() {
+        assert_eq!(PRESENT_PROOF_V1, negotiate_version(None));
+
+        let protocols = vec![ProtocolDescriptor { pid: MessageFamilies::PresentProof.id(), roles: None }];
+        assert_eq!(PRESENT_PROOF_V1, negotiate_version(Some(&protocols)));
+
+        let protocols = vec![ProtocolDescriptor { pid: String::from("https://didcomm.org/present-proof/2.0"), roles: None }];
+        assert_eq!(PRESENT_PROOF_V2, negotiate_version(Some(&protocols)));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_for_version_keeps_other_messages() {
+        let message = A2AMessage::PresentationRequest(_presentation_request());
+        assert_eq!(message.clone(), for_version(message, PRESENT_PROOF_V1).unwrap());
+
+        let message = A2AMessage::Generic(json!({"foo": "bar"}));
+        assert_eq!(message.clone(), for_version(message, PRESENT_PROOF_V2).unwrap());
+    }
+}
@@ -0,0 +1 @@
+pub mod revocation_notification;
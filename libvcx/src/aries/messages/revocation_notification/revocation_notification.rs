@@ -0,0 +1,76 @@
+use crate::aries::messages::a2a::{A2AMessage, MessageId};
+use crate::aries::messages::ack::PleaseAck;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct RevocationNotification {
+    #[serde(rename = "@id")]
+    pub id: MessageId,
+    pub thread_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(rename = "~please_ack")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub please_ack: Option<PleaseAck>,
+}
+
+impl RevocationNotification {
+    pub fn create() -> RevocationNotification {
+        RevocationNotification::default()
+    }
+
+    pub fn set_credential_thread_id(mut self, thread_id: &str) -> Self {
+        self.thread_id = thread_id.to_string();
+        self
+    }
+
+    pub fn set_comment(mut self, comment: Option<String>) -> Self {
+        self.comment = comment;
+        self
+    }
+}
+
+please_ack!(RevocationNotification);
+a2a_message!(RevocationNotification);
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn _thread_id() -> String {
+        String::from("cred_thread_id")
+    }
+
+    fn _comment() -> String {
+        String::from("Your credential has been revoked")
+    }
+
+    pub fn _revocation_notification() -> RevocationNotification {
+        RevocationNotification {
+            id: MessageId::id(),
+            thread_id: _thread_id(),
+            comment: Some(_comment()),
+            please_ack: None,
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_revocation_notification_build_works() {
+        let notification: RevocationNotification = RevocationNotification::default()
+            .set_credential_thread_id(&_thread_id())
+            .set_comment(Some(_comment()));
+
+        assert_eq!(_revocation_notification(), notification);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_revocation_notification_serialization_roundtrip() {
+        let message = _revocation_notification().to_a2a_message();
+        let json = ::serde_json::to_string(&message).unwrap();
+        assert!(json.contains("revocation_notification/1.0/revoke"));
+
+        let deserialized: A2AMessage = ::serde_json::from_str(&json).unwrap();
+        assert_eq!(message, deserialized);
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct Thread {
@@ -8,8 +8,9 @@ pub struct Thread {
     pub pthid: Option<String>,
     #[serde(default)]
     pub sender_order: u32,
+    // ordered map, so serialized threads (and objects holding them) are byte-stable
     #[serde(default)]
-    pub received_orders: HashMap<String, u32>,
+    pub received_orders: BTreeMap<String, u32>,
 }
 
 impl Thread {
@@ -22,6 +23,11 @@ impl Thread {
         self
     }
 
+    pub fn set_pthid(mut self, pthid: String) -> Thread {
+        self.pthid = Some(pthid);
+        self
+    }
+
     pub fn increment_receiver(&mut self, did: &str) {
         self.received_orders.entry(did.to_string())
             .and_modify(|e| *e += 1)
@@ -39,7 +45,7 @@ impl Default for Thread {
             thid: None,
             pthid: None,
             sender_order: 0,
-            received_orders: HashMap::new(),
+            received_orders: BTreeMap::new(),
         }
     }
 }
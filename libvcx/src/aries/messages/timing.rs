@@ -0,0 +1,74 @@
+use std::time::{Duration, SystemTime};
+
+use chrono::prelude::*;
+
+use crate::error::prelude::*;
+use crate::utils::clock;
+
+/**
+`~timing` decorator (RFC 0032), times are ISO 8601 strings.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Timing {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub out_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_time: Option<String>,
+}
+
+impl Timing {
+    /**
+    Stamps the current time as `out_time`, the message expires `ttl` later.
+     */
+    pub fn expiring_in(ttl: Duration) -> Timing {
+        let now = clock::now();
+        Timing {
+            out_time: Some(_format_time(now)),
+            expires_time: Some(_format_time(now + ttl)),
+        }
+    }
+
+    pub fn check_expiration(&self) -> VcxResult<()> {
+        let expires_time = match self.expires_time {
+            Some(ref expires_time) => expires_time,
+            None => return Ok(())
+        };
+        let expires_at = DateTime::parse_from_rfc3339(expires_time)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot parse ~timing.expires_time {:?}: {}", expires_time, err)))?;
+        if DateTime::<Utc>::from(clock::now()) > expires_at {
+            return Err(VcxError::from_msg(VcxErrorKind::MessageExpired, format!("Message expired at {}", expires_time)));
+        }
+        Ok(())
+    }
+}
+
+fn _format_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::utils::clock::SetupFakeClock;
+
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_timing_expires_after_ttl() {
+        let setup = SetupFakeClock::init(1_600_000_000);
+
+        let timing = Timing::expiring_in(Duration::from_secs(60));
+        assert_eq!(timing.out_time, Some(String::from("2020-09-13T12:26:40Z")));
+        assert_eq!(timing.expires_time, Some(String::from("2020-09-13T12:27:40Z")));
+
+        setup.clock.advance(Duration::from_secs(60));
+        timing.check_expiration().unwrap();
+
+        setup.clock.advance(Duration::from_secs(1));
+        assert_eq!(timing.check_expiration().unwrap_err().kind(), VcxErrorKind::MessageExpired);
+
+        Timing::default().check_expiration().unwrap();
+        let invalid = Timing { out_time: None, expires_time: Some(String::from("tomorrow")) };
+        assert_eq!(invalid.check_expiration().unwrap_err().kind(), VcxErrorKind::InvalidJson);
+    }
+}
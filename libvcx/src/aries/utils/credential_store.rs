@@ -0,0 +1,104 @@
+use std::sync::{Arc, RwLock};
+
+use indy::WalletHandle;
+
+use crate::error::prelude::*;
+use crate::libindy::utils::{anoncreds, wallet};
+
+/**
+Storage of holder's credentials. By default credentials are kept in the libindy wallet (`WalletCredentialStore`),
+deployments keeping secrets elsewhere can register their own implementation via `set_credential_store`.
+
+Implementations must provide:
+  - `get_credentials_for_proof_req` - finds credentials matching proof request, returns JSON in the format of
+    libindy `prover_get_credentials_for_proof_req`: `{"attrs": {<attr_referent>: [{"cred_info": ..., "interval": ...}]}, "predicates": {...}}`,
+  - `store_credential` - stores received credential, returns id of stored credential,
+  - `delete_credential` - deletes credential with given id.
+ */
+pub trait CredentialStore: Send + Sync {
+    fn get_credentials_for_proof_req(&self, proof_req: &str) -> VcxResult<String>;
+
+    fn store_credential(&self,
+                        cred_id: Option<&str>,
+                        cred_req_meta: &str,
+                        cred_json: &str,
+                        cred_def_json: &str,
+                        rev_reg_def_json: Option<&str>) -> VcxResult<String>;
+
+    fn delete_credential(&self, cred_id: &str) -> VcxResult<()>;
+}
+
+pub struct WalletCredentialStore;
+
+impl CredentialStore for WalletCredentialStore {
+    fn get_credentials_for_proof_req(&self, proof_req: &str) -> VcxResult<String> {
+        anoncreds::libindy_prover_get_credentials_for_proof_req(proof_req)
+    }
+
+    fn store_credential(&self,
+                        cred_id: Option<&str>,
+                        cred_req_meta: &str,
+                        cred_json: &str,
+                        cred_def_json: &str,
+                        rev_reg_def_json: Option<&str>) -> VcxResult<String> {
+        anoncreds::libindy_prover_store_credential(cred_id, cred_req_meta, cred_json, cred_def_json, rev_reg_def_json)
+    }
+
+    fn delete_credential(&self, cred_id: &str) -> VcxResult<()> {
+        anoncreds::libindy_prover_delete_credential(cred_id)
+    }
+}
+
+/**
+Credentials kept in the wallet of a registered wallet profile, see `wallet::register_wallet_profile`.
+ */
+pub struct WalletProfileCredentialStore {
+    wallet_handle: WalletHandle,
+}
+
+impl WalletProfileCredentialStore {
+    pub fn new(wallet_profile: &str) -> VcxResult<WalletProfileCredentialStore> {
+        let wallet_handle = wallet::get_profile_wallet_handle(Some(wallet_profile))?;
+        Ok(WalletProfileCredentialStore { wallet_handle })
+    }
+}
+
+impl CredentialStore for WalletProfileCredentialStore {
+    fn get_credentials_for_proof_req(&self, proof_req: &str) -> VcxResult<String> {
+        anoncreds::libindy_prover_get_credentials_for_proof_req_in_wallet(self.wallet_handle, proof_req)
+    }
+
+    fn store_credential(&self,
+                        _cred_id: Option<&str>,
+                        _cred_req_meta: &str,
+                        _cred_json: &str,
+                        _cred_def_json: &str,
+                        _rev_reg_def_json: Option<&str>) -> VcxResult<String> {
+        Err(VcxError::from_msg(VcxErrorKind::ActionNotSupported, "Credentials are stored only through the main credential store"))
+    }
+
+    fn delete_credential(&self, _cred_id: &str) -> VcxResult<()> {
+        Err(VcxError::from_msg(VcxErrorKind::ActionNotSupported, "Credentials are deleted only through the main credential store"))
+    }
+}
+
+lazy_static! {
+    static ref CREDENTIAL_STORE: RwLock<Arc<dyn CredentialStore>> = RwLock::new(Arc::new(WalletCredentialStore));
+}
+
+pub fn get_credential_store() -> VcxResult<Arc<dyn CredentialStore>> {
+    CREDENTIAL_STORE.read()
+        .map(|store| store.clone())
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::PoisonedLock, "Cannot read credential store"))
+}
+
+pub fn set_credential_store(store: Arc<dyn CredentialStore>) -> VcxResult<()> {
+    let mut current = CREDENTIAL_STORE.write()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::PoisonedLock, "Cannot set credential store"))?;
+    *current = store;
+    Ok(())
+}
+
+pub fn reset_credential_store() -> VcxResult<()> {
+    set_credential_store(Arc::new(WalletCredentialStore))
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use serde_json::Value;
+
+use crate::aries::messages::a2a::A2AMessage;
+use crate::error::prelude::*;
+
+lazy_static! {
+    static ref GLOBAL_DECORATORS: RwLock<BTreeMap<String, Value>> = RwLock::new(BTreeMap::new());
+}
+
+pub fn validate_decorator_name(name: &str) -> VcxResult<()> {
+    if name.len() < 2 || !name.starts_with('~') {
+        return Err(VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Decorator name must start with '~', got: {:?}", name)));
+    }
+    Ok(())
+}
+
+/**
+Application defined decorators (e.g. `~com.ourcorp/trace`) merged into every outgoing message before it's packed.
+Decorators set on a connection take precedence over the global ones, decorators set by the protocol itself
+(e.g. `~thread`) are never overwritten.
+ */
+pub fn register_global_decorator(name: &str, value: Value) -> VcxResult<()> {
+    validate_decorator_name(name)?;
+    GLOBAL_DECORATORS.write()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::PoisonedLock, "Cannot register decorator"))?
+        .insert(name.to_string(), value);
+    Ok(())
+}
+
+pub fn unregister_global_decorator(name: &str) -> VcxResult<()> {
+    GLOBAL_DECORATORS.write()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::PoisonedLock, "Cannot unregister decorator"))?
+        .remove(name);
+    Ok(())
+}
+
+/**
+Forgets all registered global decorators, they are process wide so they are cleared on shutdown.
+ */
+pub fn clear_global_decorators() {
+    match GLOBAL_DECORATORS.write() {
+        Ok(mut decorators) => decorators.clear(),
+        Err(_) => warn!("Cannot clear global decorators, lock is poisoned")
+    }
+}
+
+/**
+Returns the message with global and given connection decorators merged in. The message is returned untouched when
+there is nothing to merge, otherwise it's turned into generic json message so the decorators survive packing.
+ */
+pub fn decorate(message: &A2AMessage, connection_decorators: &BTreeMap<String, Value>) -> VcxResult<A2AMessage> {
+    let mut decorators = GLOBAL_DECORATORS.read()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::PoisonedLock, "Cannot read decorators"))?
+        .clone();
+    decorators.extend(connection_decorators.iter().map(|(name, value)| (name.clone(), value.clone())));
+
+    if decorators.is_empty() {
+        return Ok(message.clone());
+    }
+
+    let mut message_json = match message {
+        A2AMessage::Generic(value) => value.clone(),
+        A2AMessage::Unknown { raw, .. } => raw.clone(),
+        message => json!(message)
+    };
+    let fields = message_json.as_object_mut()
+        .ok_or(VcxError::from_msg(VcxErrorKind::InvalidJson, "Only json object messages can be decorated"))?;
+    for (name, value) in decorators {
+        fields.entry(name).or_insert(value);
+    }
+    Ok(A2AMessage::Generic(message_json))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::aries::messages::ack::tests::_ack;
+    use crate::aries::messages::connection::did_doc::DidDoc;
+    use crate::aries::utils::encryption_envelope::EncryptionEnvelope;
+    use crate::libindy::utils::crypto;
+    use crate::libindy::utils::tests::test_setup;
+    use crate::settings;
+    use crate::utils::devsetup::SetupMocks;
+
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_decorator_name_must_start_with_tilde() {
+        let _setup = SetupMocks::init();
+
+        assert_eq!(register_global_decorator("com.ourcorp/trace", json!({})).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+        assert_eq!(register_global_decorator("~", json!({})).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_decorate_merges_connection_decorators_without_overwriting_message() {
+        let _setup = SetupMocks::init();
+
+        let mut decorators = BTreeMap::new();
+        decorators.insert("~com.ourcorp/trace".to_string(), json!({"trace_id": "abc"}));
+        decorators.insert("~thread".to_string(), json!({"thid": "overwritten"}));
+
+        let message = A2AMessage::Ack(_ack());
+        let decorated = match decorate(&message, &decorators).unwrap() {
+            A2AMessage::Generic(value) => value,
+            other => panic!("Expected generic message, got {:?}", other)
+        };
+        assert_eq!(decorated["~com.ourcorp/trace"], json!({"trace_id": "abc"}));
+        assert_eq!(decorated["~thread"], json!(message)["~thread"]);
+        assert_eq!(decorated["@type"], json!(message)["@type"]);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_custom_decorator_is_present_in_packed_message() {
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "false");
+        let setup = test_setup::setup_wallet();
+        let sender_key = test_setup::create_key(setup.wh);
+        let recipient_key = test_setup::create_key(setup.wh);
+
+        let mut did_doc = DidDoc::default();
+        did_doc.set_keys(vec![recipient_key], vec![]);
+
+        register_global_decorator("~com.ourcorp/packed", json!({"origin": "global"})).unwrap();
+        let mut decorators = BTreeMap::new();
+        decorators.insert("~com.ourcorp/trace".to_string(), json!({"trace_id": "abc"}));
+        let message = decorate(&A2AMessage::Ack(_ack()), &decorators).unwrap();
+        unregister_global_decorator("~com.ourcorp/packed").unwrap();
+
+        let envelope = EncryptionEnvelope::create(&message, Some(&sender_key), &did_doc).unwrap();
+
+        let unpacked: Value = serde_json::from_slice(&crypto::unpack_message(&envelope.0).unwrap()).unwrap();
+        let packed: Value = serde_json::from_str(unpacked["message"].as_str().unwrap()).unwrap();
+        assert_eq!(packed["~com.ourcorp/trace"], json!({"trace_id": "abc"}));
+        assert_eq!(packed["~com.ourcorp/packed"], json!({"origin": "global"}));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_global_decorators_are_cleared_on_test_tear_down() {
+        {
+            let _setup = SetupMocks::init();
+            register_global_decorator("~com.ourcorp/leaked", json!({"origin": "global"})).unwrap();
+        }
+        let _setup = SetupMocks::init();
+
+        let message = A2AMessage::Ack(_ack());
+        assert_eq!(decorate(&message, &BTreeMap::new()).unwrap(), message);
+    }
+}
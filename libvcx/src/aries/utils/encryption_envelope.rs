@@ -3,10 +3,58 @@ use agency_client::mocking::AgencyMockDecrypted;
 use crate::aries::messages::a2a::A2AMessage;
 use crate::aries::messages::connection::did_doc::DidDoc;
 use crate::aries::messages::forward::Forward;
+use crate::aries::utils::wire_observer;
 use crate::error::prelude::*;
 use crate::libindy::utils::crypto;
 use crate::settings;
 
+pub const MEDIA_TYPE_AIP1: &str = "didcomm/aip1";
+pub const MEDIA_TYPE_AIP2_RFC19: &str = "didcomm/aip2;env=rfc19";
+pub const MEDIA_TYPE_DIDCOMM_V2: &str = "didcomm/v2";
+
+/**
+Envelope format accepted by the counterparty. V1 is the RFC 0019 envelope messages are packed into, V2 is DIDComm v2
+which can't be packed yet, connections with counterparties accepting nothing else are refused.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvelopeVersion {
+    V1,
+    V2,
+}
+
+impl Default for EnvelopeVersion {
+    fn default() -> EnvelopeVersion {
+        EnvelopeVersion::V1
+    }
+}
+
+impl EnvelopeVersion {
+    /**
+    Picks envelope version based on media types from `accept` field of an out-of-band invitation.
+    V1 is used when it's accepted or nothing is specified.
+     */
+    pub fn negotiate(accept: &[String]) -> EnvelopeVersion {
+        let accepts = |media_type: &str| accept.iter().any(|accepted| accepted == media_type);
+        if accepts(MEDIA_TYPE_DIDCOMM_V2) && !accepts(MEDIA_TYPE_AIP1) && !accepts(MEDIA_TYPE_AIP2_RFC19) {
+            EnvelopeVersion::V2
+        } else {
+            EnvelopeVersion::V1
+        }
+    }
+
+    pub fn is_v1(&self) -> bool {
+        *self == EnvelopeVersion::V1
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnvelopeVersion::V1 => "v1",
+            EnvelopeVersion::V2 => "v2",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct EncryptionEnvelope(pub Vec<u8>);
 
@@ -16,30 +64,77 @@ impl EncryptionEnvelope {
                   did_doc: &DidDoc) -> VcxResult<EncryptionEnvelope> {
         trace!("EncryptionEnvelope::create >>> message: {:?}, pw_verkey: {:?}, did_doc: {:?}", message, pw_verkey, did_doc);
 
-        if settings::indy_mocks_enabled() { return Ok(EncryptionEnvelope(vec![])); }
+        EncryptionEnvelope::create_for_recipient_key(message, pw_verkey, did_doc, None)
+    }
+
+    /**
+    Packs message for every recipient key of the DidDoc, or only for `recipient_key` if it's specified.
+    The key must be one of recipient keys of the DidDoc.
+     */
+    pub fn create_for_recipient_key(message: &A2AMessage,
+                                    pw_verkey: Option<&str>,
+                                    did_doc: &DidDoc,
+                                    recipient_key: Option<&str>) -> VcxResult<EncryptionEnvelope> {
+        trace!("EncryptionEnvelope::create_for_recipient_key >>> message: {:?}, pw_verkey: {:?}, did_doc: {:?}, recipient_key: {:?}",
+               message, pw_verkey, did_doc, recipient_key);
+
+        let recipient_keys = EncryptionEnvelope::select_recipient_keys(did_doc, recipient_key)?;
+        EncryptionEnvelope::validate_routing_depth(did_doc)?;
+
+        // messages are not encrypted in test mode, so the sent messages can be inspected
+        if settings::indy_mocks_enabled() {
+            let message = serde_json::to_vec(message)
+                .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize A2A message: {}", err)))?;
+            return Ok(EncryptionEnvelope(message));
+        }
+
+        let packed = EncryptionEnvelope::encrypt_for_pairwise(message, pw_verkey, &recipient_keys)
+            .and_then(|message| EncryptionEnvelope::wrap_into_forward_messages(message, &recipient_keys, did_doc))?;
+        wire_observer::notify_packed(&packed);
+        Ok(EncryptionEnvelope(packed))
+    }
 
-        EncryptionEnvelope::encrypt_for_pairwise(message, pw_verkey, did_doc)
-            .and_then(|message| EncryptionEnvelope::wrap_into_forward_messages(message, did_doc))
-            .map(|message| EncryptionEnvelope(message))
+    /**
+    Every routing key adds a forward envelope, so a DidDoc with long chain of routing keys would blow up sent messages.
+     */
+    fn validate_routing_depth(did_doc: &DidDoc) -> VcxResult<()> {
+        let routing_depth = did_doc.routing_keys().len();
+        let max_routing_depth = settings::get_max_routing_depth();
+        if routing_depth > max_routing_depth {
+            return Err(VcxError::from_msg(VcxErrorKind::InvalidJson,
+                                          format!("DIDDoc has {} routing keys, at most {} are allowed", routing_depth, max_routing_depth)));
+        }
+        Ok(())
+    }
+
+    fn select_recipient_keys(did_doc: &DidDoc, recipient_key: Option<&str>) -> VcxResult<Vec<String>> {
+        let recipient_keys = did_doc.recipient_keys();
+        match recipient_key {
+            None => Ok(recipient_keys),
+            Some(key) if recipient_keys.iter().any(|recipient_key| recipient_key == key) => Ok(vec![key.to_string()]),
+            Some(key) => Err(VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Key {} is not a recipient key of DIDDoc: {:?}", key, did_doc)))
+        }
     }
 
     fn encrypt_for_pairwise(message: &A2AMessage,
                             pw_verkey: Option<&str>,
-                            did_doc: &DidDoc) -> VcxResult<Vec<u8>> {
+                            recipient_keys: &Vec<String>) -> VcxResult<Vec<u8>> {
         let message = match message {
             A2AMessage::Generic(message_) => message_.to_string(),
+            A2AMessage::Unknown { raw, .. } => raw.to_string(),
             message => json!(message).to_string()
         };
 
-        let receiver_keys = json!(did_doc.recipient_keys()).to_string();
+        let receiver_keys = json!(recipient_keys).to_string();
 
         warn!("Encrypting for pairwise; pw_verkey={:?}, receiver_keys={:?}", pw_verkey, receiver_keys);
         crypto::pack_message(pw_verkey, &receiver_keys, message.as_bytes())
     }
 
     fn wrap_into_forward_messages(mut message: Vec<u8>,
+                                  recipient_keys: &Vec<String>,
                                   did_doc: &DidDoc) -> VcxResult<Vec<u8>> {
-        let (recipient_keys, routing_keys) = did_doc.resolve_keys();
+        let routing_keys = did_doc.routing_keys();
 
         let mut to = recipient_keys.get(0)
             .map(String::from)
@@ -90,6 +185,7 @@ impl EncryptionEnvelope {
             let (a2a_message, _sender_vk) = Self::_unpack_a2a_message(payload)?;
             a2a_message
         };
+        wire_observer::notify_unpacked(&message);
         let a2a_message = serde_json::from_str(&message)
             .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize A2A message: {}", err)))?;
         Ok(a2a_message)
@@ -121,6 +217,7 @@ impl EncryptionEnvelope {
             }
             a2a_message
         };
+        wire_observer::notify_unpacked(&message);
         let a2a_message = serde_json::from_str(&message)
             .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize A2A message: {}", err)))?;
         Ok(a2a_message)
@@ -135,7 +232,7 @@ pub mod tests {
     use crate::libindy::utils::tests::test_setup;
     use crate::libindy::utils::tests::test_setup::create_trustee_key;
     use crate::libindy::utils::wallet;
-    use crate::utils::devsetup::SetupEmpty;
+    use crate::utils::devsetup::{SetupEmpty, SetupMocks};
 
     use super::*;
 
@@ -210,6 +307,39 @@ pub mod tests {
         assert_eq!(ack, EncryptionEnvelope::anon_unpack(message_2).unwrap());
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_anonymous_encryption_envelope_wraps_through_all_routing_keys() {
+        _setup();
+        let _wallet = test_setup::setup_wallet();
+
+        let key_1 = create_key(None).unwrap();
+        let key_2 = create_key(None).unwrap();
+
+        let mut did_doc = DidDoc::default();
+        did_doc.set_service_endpoint(_service_endpoint());
+        did_doc.set_keys(_recipient_keys(), vec![key_1.clone(), key_2.clone()]);
+
+        let ack = A2AMessage::Ack(_ack());
+
+        let envelope = EncryptionEnvelope::create(&ack, None, &did_doc).unwrap();
+
+        // outermost forward is encrypted for the last routing key and addressed to the previous one
+        let forward_1 = match EncryptionEnvelope::anon_unpack(envelope.0).unwrap() {
+            A2AMessage::Forward(forward) => forward,
+            other => panic!("Expected forward message, got {:?}", other)
+        };
+        assert_eq!(key_1, forward_1.to);
+
+        let forward_2 = match EncryptionEnvelope::anon_unpack(serde_json::to_vec(&forward_1.msg).unwrap()).unwrap() {
+            A2AMessage::Forward(forward) => forward,
+            other => panic!("Expected forward message, got {:?}", other)
+        };
+        assert_eq!(_key_1(), forward_2.to);
+
+        assert_eq!(ack, EncryptionEnvelope::anon_unpack(serde_json::to_vec(&forward_2.msg).unwrap()).unwrap());
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_auth_unpack_message_should_succeed_if_sender_key_matches_expectation() {
@@ -257,4 +387,73 @@ pub mod tests {
         let result = EncryptionEnvelope::auth_unpack(envelope.0, &sender_key_1);
         assert!(result.is_err());
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_encryption_envelope_packs_for_all_or_selected_recipient_keys() {
+        SetupEmpty::init();
+        _setup();
+        let device_1_wallet = test_setup::setup_wallet();
+        let device_1_key = test_setup::create_key(device_1_wallet.wh);
+
+        let device_2_wallet = test_setup::setup_wallet();
+        let device_2_key = test_setup::create_key(device_2_wallet.wh);
+
+        let sender_wallet = test_setup::setup_wallet();
+        let sender_key = test_setup::create_key(sender_wallet.wh);
+
+        let mut did_doc = DidDoc::default();
+        did_doc.set_keys(vec![device_1_key.clone(), device_2_key.clone()], vec![]);
+
+        let ack = A2AMessage::Ack(_ack());
+
+        wallet::set_wallet_handle(sender_wallet.wh);
+        let envelope = EncryptionEnvelope::create(&ack, Some(&sender_key), &did_doc).unwrap();
+        let targeted_envelope = EncryptionEnvelope::create_for_recipient_key(&ack, Some(&sender_key), &did_doc, Some(&device_2_key)).unwrap();
+        let res = EncryptionEnvelope::create_for_recipient_key(&ack, Some(&sender_key), &did_doc, Some(&sender_key));
+        assert_eq!(res.unwrap_err().kind(), VcxErrorKind::InvalidOption);
+
+        wallet::set_wallet_handle(device_1_wallet.wh);
+        assert_eq!(ack, EncryptionEnvelope::auth_unpack(envelope.0.clone(), &sender_key).unwrap());
+        assert!(EncryptionEnvelope::auth_unpack(targeted_envelope.0.clone(), &sender_key).is_err());
+
+        wallet::set_wallet_handle(device_2_wallet.wh);
+        assert_eq!(ack, EncryptionEnvelope::auth_unpack(envelope.0, &sender_key).unwrap());
+        assert_eq!(ack, EncryptionEnvelope::auth_unpack(targeted_envelope.0, &sender_key).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_envelope_version_negotiation() {
+        let media_types = |media_types: &[&str]| media_types.iter().map(|media_type| media_type.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(EnvelopeVersion::V1, EnvelopeVersion::negotiate(&[]));
+        assert_eq!(EnvelopeVersion::V1, EnvelopeVersion::negotiate(&media_types(&[MEDIA_TYPE_AIP1])));
+        assert_eq!(EnvelopeVersion::V1, EnvelopeVersion::negotiate(&media_types(&[MEDIA_TYPE_DIDCOMM_V2, MEDIA_TYPE_AIP2_RFC19])));
+        assert_eq!(EnvelopeVersion::V2, EnvelopeVersion::negotiate(&media_types(&[MEDIA_TYPE_DIDCOMM_V2])));
+        assert_eq!(EnvelopeVersion::V1, EnvelopeVersion::negotiate(&media_types(&["didcomm/v3"])));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_encryption_envelope_enforces_max_routing_depth() {
+        let _setup = SetupMocks::init();
+
+        let did_doc_with_routing_depth = |routing_depth: usize| {
+            let mut did_doc = DidDoc::default();
+            did_doc.set_service_endpoint(_service_endpoint());
+            did_doc.set_keys(_recipient_keys(), (0..routing_depth).map(|_| _key_2()).collect());
+            did_doc
+        };
+        let ack = A2AMessage::Ack(_ack());
+
+        EncryptionEnvelope::create(&ack, None, &did_doc_with_routing_depth(settings::DEFAULT_MAX_ROUTING_DEPTH)).unwrap();
+        let res = EncryptionEnvelope::create(&ack, None, &did_doc_with_routing_depth(settings::DEFAULT_MAX_ROUTING_DEPTH + 1));
+        assert_eq!(res.unwrap_err().kind(), VcxErrorKind::InvalidJson);
+
+        settings::set_config_value(settings::CONFIG_MAX_ROUTING_DEPTH, "2");
+        EncryptionEnvelope::create(&ack, None, &did_doc_with_routing_depth(2)).unwrap();
+        let res = EncryptionEnvelope::create(&ack, None, &did_doc_with_routing_depth(3));
+        assert_eq!(res.unwrap_err().kind(), VcxErrorKind::InvalidJson);
+    }
 }
@@ -0,0 +1,213 @@
+use std::fmt;
+
+use rust_base58::{FromBase58, ToBase58};
+use sodiumoxide::crypto::aead::xchacha20poly1305_ietf;
+use sodiumoxide::crypto::box_;
+use sodiumoxide::crypto::sealedbox;
+use sodiumoxide::crypto::sign::ed25519;
+
+use crate::error::prelude::*;
+
+/*
+Key pair living in memory only, used to receive messages without touching the wallet. Messages packed for its
+verkey (anoncrypt or authcrypt envelope of Aries RFC 0019, as produced by indy `pack_message`) are unpacked here
+instead of by libindy. The pair is serializable, so whoever created it can hold it until the answer arrives.
+ */
+
+const ANONCRYPT: &str = "Anoncrypt";
+const AUTHCRYPT: &str = "Authcrypt";
+const CONTENT_ENCRYPTION: &str = "xchacha20poly1305_ietf";
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct EphemeralKey {
+    pub verkey: String,
+    secret_key: String,
+}
+
+impl fmt::Debug for EphemeralKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EphemeralKey").field("verkey", &self.verkey).finish()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Jwe {
+    protected: String,
+    iv: String,
+    ciphertext: String,
+    tag: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProtectedHeader {
+    enc: String,
+    alg: String,
+    recipients: Vec<Recipient>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Recipient {
+    encrypted_key: String,
+    header: RecipientHeader,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecipientHeader {
+    kid: String,
+    sender: Option<String>,
+    iv: Option<String>,
+}
+
+impl EphemeralKey {
+    pub fn generate() -> VcxResult<EphemeralKey> {
+        _init()?;
+        let (public_key, secret_key) = ed25519::gen_keypair();
+        Ok(EphemeralKey {
+            verkey: public_key.0.to_base58(),
+            secret_key: secret_key.0.to_base58(),
+        })
+    }
+
+    /**
+    Unpacks message packed for this key, returns the message and the verkey of its sender if it was authcrypted.
+     */
+    pub fn unpack(&self, payload: &[u8]) -> VcxResult<(String, Option<String>)> {
+        trace!("EphemeralKey::unpack >>> processing payload of {} bytes for {}", payload.len(), self.verkey);
+        _init()?;
+
+        let jwe: Jwe = serde_json::from_slice(payload)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize packed message: {}", err)))?;
+        let header: ProtectedHeader = serde_json::from_slice(&_decode(&jwe.protected)?)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize protected header: {}", err)))?;
+        if header.enc != CONTENT_ENCRYPTION {
+            return Err(VcxError::from_msg(VcxErrorKind::InvalidMessagePack, format!("Unsupported content encryption: {}", header.enc)));
+        }
+        let recipient = header.recipients.iter()
+            .find(|recipient| recipient.header.kid == self.verkey)
+            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidMessagePack, format!("Message is not packed for key {}", self.verkey)))?;
+
+        let (public_key, secret_key) = self._curve_key_pair()?;
+        let encrypted_key = _decode(&recipient.encrypted_key)?;
+        let (cek, sender_vk) = match (header.alg.as_str(), &recipient.header.sender, &recipient.header.iv) {
+            (ANONCRYPT, _, _) => {
+                let cek = sealedbox::open(&encrypted_key, &public_key, &secret_key)
+                    .map_err(|_| VcxError::from_msg(VcxErrorKind::InvalidMessagePack, "Cannot decrypt content encryption key"))?;
+                (cek, None)
+            }
+            (AUTHCRYPT, Some(sender), Some(iv)) => {
+                let sender_vk = sealedbox::open(&_decode(sender)?, &public_key, &secret_key)
+                    .map_err(|_| VcxError::from_msg(VcxErrorKind::InvalidMessagePack, "Cannot decrypt sender verkey"))?;
+                let sender_vk = String::from_utf8(sender_vk)
+                    .map_err(|_| VcxError::from_msg(VcxErrorKind::InvalidMessagePack, "Sender verkey is not valid UTF-8"))?;
+                let nonce = box_::Nonce::from_slice(&_decode(iv)?)
+                    .ok_or(VcxError::from_msg(VcxErrorKind::InvalidMessagePack, "Invalid content encryption key nonce"))?;
+                let cek = box_::open(&encrypted_key, &nonce, &_curve_public_key(&sender_vk)?, &secret_key)
+                    .map_err(|_| VcxError::from_msg(VcxErrorKind::InvalidMessagePack, "Cannot decrypt content encryption key"))?;
+                (cek, Some(sender_vk))
+            }
+            (alg, _, _) => return Err(VcxError::from_msg(VcxErrorKind::InvalidMessagePack, format!("Unsupported packing algorithm: {}", alg)))
+        };
+
+        let cek = xchacha20poly1305_ietf::Key::from_slice(&cek)
+            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidMessagePack, "Invalid content encryption key"))?;
+        let nonce = xchacha20poly1305_ietf::Nonce::from_slice(&_decode(&jwe.iv)?)
+            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidMessagePack, "Invalid content nonce"))?;
+        let mut ciphertext = _decode(&jwe.ciphertext)?;
+        ciphertext.extend(_decode(&jwe.tag)?);
+        let message = xchacha20poly1305_ietf::open(&ciphertext, Some(jwe.protected.as_bytes()), &nonce, &cek)
+            .map_err(|_| VcxError::from_msg(VcxErrorKind::InvalidMessagePack, "Cannot decrypt message"))?;
+        let message = String::from_utf8(message)
+            .map_err(|_| VcxError::from_msg(VcxErrorKind::InvalidMessagePack, "Message is not valid UTF-8"))?;
+
+        Ok((message, sender_vk))
+    }
+
+    fn _curve_key_pair(&self) -> VcxResult<(box_::PublicKey, box_::SecretKey)> {
+        let secret_key = self.secret_key.from_base58()
+            .map_err(|_| VcxError::from_msg(VcxErrorKind::NotBase58, "Ephemeral secret key is not base58"))?;
+        let secret_key = ed25519::SecretKey::from_slice(&secret_key)
+            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidMessagePack, "Invalid ephemeral secret key"))?;
+        let secret_key = ed25519::to_curve25519_sk(&secret_key)
+            .map_err(|_| VcxError::from_msg(VcxErrorKind::InvalidMessagePack, "Cannot convert ephemeral secret key"))?;
+        Ok((_curve_public_key(&self.verkey)?, box_::SecretKey(secret_key.0)))
+    }
+}
+
+fn _init() -> VcxResult<()> {
+    sodiumoxide::init()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::UnknownError, "Cannot initialize sodium"))
+}
+
+fn _curve_public_key(verkey: &str) -> VcxResult<box_::PublicKey> {
+    let verkey = verkey.from_base58()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::NotBase58, format!("Verkey {} is not base58", verkey)))?;
+    let verkey = ed25519::PublicKey::from_slice(&verkey)
+        .ok_or(VcxError::from_msg(VcxErrorKind::InvalidVerkey, "Invalid verkey"))?;
+    let public_key = ed25519::to_curve25519_pk(&verkey)
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::InvalidVerkey, "Cannot convert verkey"))?;
+    Ok(box_::PublicKey(public_key.0))
+}
+
+fn _decode(value: &str) -> VcxResult<Vec<u8>> {
+    base64::decode_config(value.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::InvalidMessagePack, format!("Invalid base64 value: {}", value)))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // anoncrypts message for the verkey the same way as indy `pack_message` does
+    pub fn anon_pack(message: &str, verkey: &str) -> Vec<u8> {
+        _init().unwrap();
+        let cek = xchacha20poly1305_ietf::gen_key();
+        let encrypted_key = sealedbox::seal(&cek.0, &_curve_public_key(verkey).unwrap());
+        let protected = json!({
+            "enc": CONTENT_ENCRYPTION,
+            "typ": "JWM/1.0",
+            "alg": ANONCRYPT,
+            "recipients": [{"encrypted_key": base64::encode_config(&encrypted_key, base64::URL_SAFE), "header": {"kid": verkey}}]
+        }).to_string();
+        let protected = base64::encode_config(protected.as_bytes(), base64::URL_SAFE);
+        let nonce = xchacha20poly1305_ietf::gen_nonce();
+        let sealed = xchacha20poly1305_ietf::seal(message.as_bytes(), Some(protected.as_bytes()), &nonce, &cek);
+        let (ciphertext, tag) = sealed.split_at(sealed.len() - xchacha20poly1305_ietf::TAGBYTES);
+        json!({
+            "protected": protected,
+            "iv": base64::encode_config(&nonce.0, base64::URL_SAFE),
+            "ciphertext": base64::encode_config(ciphertext, base64::URL_SAFE),
+            "tag": base64::encode_config(tag, base64::URL_SAFE)
+        }).to_string().into_bytes()
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_unpack_anoncrypted_message() {
+        let key = EphemeralKey::generate().unwrap();
+
+        let (message, sender_vk) = key.unpack(&anon_pack("{\"hello\": \"world\"}", &key.verkey)).unwrap();
+        assert_eq!(message, "{\"hello\": \"world\"}");
+        assert_eq!(sender_vk, None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_unpack_fails_for_message_packed_for_other_key() {
+        let key = EphemeralKey::generate().unwrap();
+        let other_key = EphemeralKey::generate().unwrap();
+
+        let packed = anon_pack("{}", &other_key.verkey);
+        assert_eq!(key.unpack(&packed).unwrap_err().kind(), VcxErrorKind::InvalidMessagePack);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_ephemeral_key_survives_serialization() {
+        let key = EphemeralKey::generate().unwrap();
+        let restored: EphemeralKey = serde_json::from_str(&serde_json::to_string(&key).unwrap()).unwrap();
+        assert_eq!(restored, key);
+        assert!(!format!("{:?}", key).contains(&key.secret_key));
+
+        restored.unpack(&anon_pack("{}", &key.verkey)).unwrap();
+    }
+}
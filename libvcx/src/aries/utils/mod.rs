@@ -1 +1,5 @@
-pub mod encryption_envelope;
\ No newline at end of file
+pub mod encryption_envelope;
+pub mod ephemeral_key;
+pub mod credential_store;
+pub mod decorators;
+pub mod wire_observer;
@@ -0,0 +1,100 @@
+use std::sync::{Arc, RwLock};
+
+/**
+Observer of messages on the wire, for debugging interoperability. It sees packed envelopes right before they are
+sent and plaintext of unpacked messages right after they are received. Keys are never passed to the observer,
+unpacked messages are passed without sender and recipient keys of the envelope.
+Observer is invoked synchronously while sending or receiving, so it should return quickly.
+ */
+pub trait WireObserver: Send + Sync {
+    fn on_packed(&self, packed: &[u8]);
+    fn on_unpacked(&self, message: &str);
+}
+
+lazy_static! {
+    static ref WIRE_OBSERVER: RwLock<Option<Arc<dyn WireObserver>>> = RwLock::new(None);
+}
+
+/**
+Registers the observer globally, replacing any previously set. Messages are observed only while an observer is set,
+`None` disables observing.
+ */
+pub fn set_wire_observer(observer: Option<Arc<dyn WireObserver>>) {
+    *WIRE_OBSERVER.write().unwrap() = observer;
+}
+
+fn observer() -> Option<Arc<dyn WireObserver>> {
+    WIRE_OBSERVER.read().ok().and_then(|observer| observer.clone())
+}
+
+pub(crate) fn notify_packed(packed: &[u8]) {
+    if let Some(observer) = observer() {
+        observer.on_packed(packed);
+    }
+}
+
+pub(crate) fn notify_unpacked(message: &str) {
+    if let Some(observer) = observer() {
+        observer.on_unpacked(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::aries::messages::a2a::A2AMessage;
+    use crate::aries::messages::ack::tests::_ack;
+    use crate::aries::messages::connection::did_doc::tests::*;
+    use crate::aries::utils::encryption_envelope::EncryptionEnvelope;
+    use crate::libindy::utils::tests::test_setup;
+    use crate::libindy::utils::tests::test_setup::create_trustee_key;
+    use crate::settings;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        packed: Mutex<Vec<Vec<u8>>>,
+        unpacked: Mutex<Vec<String>>,
+    }
+
+    impl WireObserver for RecordingObserver {
+        fn on_packed(&self, packed: &[u8]) {
+            self.packed.lock().unwrap().push(packed.to_vec());
+        }
+
+        fn on_unpacked(&self, message: &str) {
+            self.unpacked.lock().unwrap().push(message.to_string());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_wire_observer_sees_packed_and_unpacked_messages() {
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "false");
+        let setup = test_setup::setup_wallet();
+        let trustee_key = create_trustee_key(setup.wh);
+        let observer = Arc::new(RecordingObserver::default());
+        set_wire_observer(Some(observer.clone()));
+
+        // observer is global, messages packed by concurrently running tests are recorded too
+        let thread_id = "test_wire_observer_sees_packed_and_unpacked_messages";
+        let message = A2AMessage::Ack(_ack().set_thread_id(thread_id));
+        let envelope = EncryptionEnvelope::create(&message, Some(&trustee_key), &_did_doc_4()).unwrap();
+        assert_eq!(observer.packed.lock().unwrap().iter().filter(|packed| **packed == envelope.0).count(), 1);
+
+        assert_eq!(message, EncryptionEnvelope::anon_unpack(envelope.0).unwrap());
+        let unpacked: Vec<String> = observer.unpacked.lock().unwrap().iter()
+            .filter(|unpacked| unpacked.contains(thread_id))
+            .cloned()
+            .collect();
+        assert_eq!(unpacked.len(), 1);
+        assert_eq!(serde_json::from_str::<A2AMessage>(&unpacked[0]).unwrap(), message);
+        assert!(!unpacked[0].contains(&_key_1()));
+
+        set_wire_observer(None);
+        let unobserved = EncryptionEnvelope::create(&message, Some(&trustee_key), &_did_doc_4()).unwrap();
+        assert!(observer.packed.lock().unwrap().iter().all(|packed| *packed != unobserved.0));
+    }
+}
@@ -1,24 +1,48 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use serde_json;
+use serde_json::Value;
 
 use agency_client;
 use agency_client::{MessageStatusCode, SerializableObjectWithState};
+use agency_client::cancellation::{self, CancellationToken};
+use agency_client::circuit_breaker;
+use agency_client::delivery_receipt::DeliveryReceipt;
 use agency_client::get_message::{Message, MessageByConnection};
 
-use crate::aries::handlers::connection::agent_info::AgentInfo;
-use crate::aries::handlers::connection::connection::{Connection, SmConnectionState};
+use crate::api::VcxStateType;
+use crate::aries::handlers::connection::agent_info::{AgentInfo, InvitationType};
+use crate::aries::handlers::connection::connection::{Connection, ConnectionData, ConnectionInfo, SmConnectionState};
+use crate::aries::handlers::connection::deletion_observer::{self, DeletionReason};
+use crate::aries::handlers::connection::legacy;
+use crate::aries::handlers::connection::message_handler;
+use crate::aries::handlers::connection::send_rate_limiter;
 use crate::aries::messages::a2a::A2AMessage;
+use crate::aries::messages::a2a::message_family::MessageFamilies;
+use crate::aries::messages::connection::problem_report::{ProblemCode as ConnectionProblemCode, ProblemReport as ConnectionProblemReport};
+use crate::aries::messages::error::ProblemReport as CommonProblemReport;
 use crate::aries::messages::connection::did_doc::DidDoc;
 use crate::aries::messages::connection::invite::Invitation as InvitationV3;
+use crate::aries::messages::discovery::query::ProtocolQuery;
+use crate::aries::messages::out_of_band::invitation::OutOfBandInvitation;
+use crate::aries::utils::ephemeral_key::EphemeralKey;
+use crate::disclosed_proof;
 use crate::error::prelude::*;
+use crate::settings;
+use crate::settings::Actors;
+use crate::utils::clock;
 use crate::utils::error;
+use crate::utils::json;
 use crate::utils::object_cache::ObjectCache;
+use crate::utils::polling;
+use crate::utils::serialization;
 
 lazy_static! {
     static ref CONNECTION_MAP: ObjectCache<Connection> = ObjectCache::<Connection>::new("connections-cache");
 }
 
+
 pub fn create_agent_keys(source_id: &str, pw_did: &str, pw_verkey: &str) -> VcxResult<(String, String)> {
     debug!("creating pairwise keys on agent for connection {}", source_id);
     trace!("create_agent_keys >>> source_id: {}, pw_did: {}, pw_verkey: {}", source_id, pw_did, pw_verkey);
@@ -61,6 +85,19 @@ pub fn get_their_pw_did(handle: u32) -> VcxResult<String> {
     })
 }
 
+/**
+Returns routing keys of the counterparty DidDoc, in the order the outgoing messages are wrapped into forwards:
+the first key wraps the message addressed to the recipient key (innermost), the last one produces the
+outermost forward delivered to the service endpoint.
+ */
+pub fn get_routing_keys(handle: u32) -> VcxResult<Vec<String>> {
+    CONNECTION_MAP.get(handle, |connection| {
+        connection.their_did_doc()
+            .map(|did_doc| did_doc.routing_keys())
+            .ok_or(VcxError::from_msg(VcxErrorKind::NotReady, "Remote Connection DidDoc is not set"))
+    })
+}
+
 pub fn get_agent_verkey(handle: u32) -> VcxResult<String> {
     CONNECTION_MAP.get(handle, |connection| {
         Ok(connection.agent_info().agent_vk.clone())
@@ -97,19 +134,133 @@ pub fn create_connection(source_id: &str) -> VcxResult<u32> {
     return store_connection(connection);
 }
 
+/**
+Creates listen-only connection with agent ready to receive messages, see `Connection::create_receiver`.
+ */
+pub fn create_receiver(source_id: &str) -> VcxResult<u32> {
+    trace!("create_receiver >>> source_id: {}", source_id);
+    let connection = Connection::create_receiver(source_id)?;
+    store_connection(connection)
+}
+
+/**
+Returns DidDoc of the service of listen-only connection as JSON, to be published so peers can send messages to it.
+ */
+pub fn get_service_did_doc(handle: u32) -> VcxResult<String> {
+    CONNECTION_MAP.get(handle, |connection| {
+        let did_doc = connection.get_service_did_doc()?;
+        serde_json::to_string(&did_doc)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize DidDoc: {}", err)))
+    })
+}
+
+/**
+Returns DidDoc Inviter publishes to the invitee as JSON, available only before the connection is connected.
+ */
+pub fn get_local_did_doc(handle: u32) -> VcxResult<String> {
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        let did_doc = connection.get_local_did_doc()?;
+        serde_json::to_string(&did_doc)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize DidDoc: {}", err)))
+    })
+}
+
+/**
+Replaces DidDoc Inviter publishes to the invitee by customized one, e.g. with services of custom protocols.
+The invitation generated by `connect` reflects the customized DidDoc, it's out-of-band invitation if services
+were added, as RFC 0160 invitation carries only one.
+ */
+pub fn set_local_did_doc(handle: u32, did_doc: &str) -> VcxResult<u32> {
+    let did_doc: DidDoc = serde_json::from_str(did_doc)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize DidDoc: {}", err)))?;
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection.set_local_did_doc(did_doc.clone())?;
+        Ok(error::SUCCESS.code_num)
+    })
+}
+
+/**
+Checks the endpoint the connection advertises is reachable, by sending test message through the whole routing path
+to it and waiting up to `timeout` until it comes back to the connection agent. Returns false on timeout.
+ */
+pub fn verify_endpoint_reachable(handle: u32, timeout: Duration) -> VcxResult<bool> {
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection.verify_endpoint_reachable(timeout)
+    })
+}
+
 pub fn create_connection_with_invite(source_id: &str, details: &str) -> VcxResult<u32> {
     debug!("create connection {} with invite {}", source_id, details);
     if let Some(invitation) = serde_json::from_str::<InvitationV3>(details).ok() {
         let connection = Connection::create_with_invite(source_id, invitation)?;
         store_connection(connection)
+    } else if let Some(oob_invitation) = serde_json::from_str::<OutOfBandInvitation>(details).ok() {
+        let connection = Connection::create_with_oob_invite(source_id, oob_invitation)?;
+        store_connection(connection)
     } else {
         Err(VcxError::from_msg(VcxErrorKind::InvalidJson, "Used invite has invalid structure")) // TODO: Specific error type
     }
 }
 
-pub fn send_generic_message(connection_handle: u32, msg: &str) -> VcxResult<String> {
+/**
+Creates connection which skips agent provisioning in the agency, the counterparty sends messages directly to `endpoint`.
+Received messages have to be passed to the connection by `update_state_with_message`.
+ */
+pub fn create_agentless(source_id: &str, endpoint: &str) -> VcxResult<u32> {
+    trace!("create_agentless >>> source_id: {}, endpoint: {}", source_id, endpoint);
+    let connection = Connection::create_agentless(source_id, endpoint)?;
+    store_connection(connection)
+}
+
+/**
+Same as `create_agentless`, but the connection is established from the counterparty's invitation.
+ */
+pub fn create_agentless_with_invite(source_id: &str, endpoint: &str, details: &str) -> VcxResult<u32> {
+    debug!("create agentless connection {} with invite {}", source_id, details);
+    let invitation = serde_json::from_str::<InvitationV3>(details)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Used invite has invalid structure: {:?}", err)))?;
+    let connection = Connection::create_agentless_with_invite(source_id, endpoint, invitation)?;
+    store_connection(connection)
+}
+
+/**
+Same as `create_connection_with_invite`, but the connection fails if the counterparty doesn't establish it
+with `expected_did`: the Response is rejected with `response_not_accepted` problem report, the connection
+moves to None state and `update_state` returns `ConnectionDidMismatch` error. The problem report is available
+through `get_last_problem_report`.
+ */
+pub fn create_with_invite_expecting(source_id: &str, details: &str, expected_did: &str) -> VcxResult<u32> {
+    debug!("create connection {} with invite {} expecting DID {}", source_id, details, expected_did);
+    let invitation = serde_json::from_str::<InvitationV3>(details)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Used invite has invalid structure: {:?}", err)))?;
+    let connection = Connection::create_with_invite_expecting(source_id, invitation, expected_did)?;
+    store_connection(connection)
+}
+
+/**
+Returns credential offer or presentation request attached to the out-of-band invitation the connection was created with.
+ */
+pub fn get_attached_request(handle: u32) -> VcxResult<Option<A2AMessage>> {
+    CONNECTION_MAP.get(handle, |connection| {
+        Ok(connection.get_attached_request())
+    })
+}
+
+pub fn get_invitation_type(handle: u32) -> VcxResult<InvitationType> {
+    CONNECTION_MAP.get(handle, |connection| {
+        Ok(connection.get_invitation_type())
+    })
+}
+
+pub fn get_message_counts(handle: u32) -> VcxResult<HashMap<MessageStatusCode, u32>> {
+    CONNECTION_MAP.get(handle, |connection| {
+        connection.get_message_counts()
+    })
+}
+
+pub fn send_generic_message(connection_handle: u32, msg: &str, thread_id: Option<&str>) -> VcxResult<String> {
     CONNECTION_MAP.get(connection_handle, |connection| {
-        connection.send_generic_message(msg)
+        send_rate_limiter::send_limited(&connection.agent_info().pw_did, || connection.send_generic_message(msg, thread_id))
     })
 }
 
@@ -139,22 +290,108 @@ Tries to update state of connection state machine in 3 steps:
   3. update state of used message in agency to "Reviewed".
  */
 pub fn update_state(handle: u32) -> VcxResult<u32> {
+    update_state_cancellable(handle, None)
+}
+
+/**
+Same as `update_state`, agency requests fail with `Cancelled` once the given token is cancelled.
+ */
+pub fn update_state_cancellable(handle: u32, cancellation: Option<&CancellationToken>) -> VcxResult<u32> {
+    cancellation::with_cancellation(cancellation, || _update_state(handle, vec![MessageStatusCode::Received]))?;
+    Ok(error::SUCCESS.code_num)
+}
+
+/**
+Same as `update_state`, but returns number of downloaded messages the connection protocol could still handle in
+the new state. If it's not zero, `update_state` should be called again right away instead of at the next poll.
+Messages of other protocols, eg. credential offers, are not counted.
+ */
+pub fn update_state_with_remaining(handle: u32) -> VcxResult<u32> {
+    _update_state(handle, vec![MessageStatusCode::Received])
+}
+
+/**
+Same as `update_state`, but only messages in one of the given statuses are downloaded and processed.
+`update_state` processes Received messages only. Including Reviewed lets operators reprocess messages
+during incident recovery, messages in any status are marked Reviewed once handled.
+ */
+pub fn update_state_for_statuses(handle: u32, statuses: Vec<MessageStatusCode>) -> VcxResult<u32> {
+    if statuses.is_empty() {
+        return Err(VcxError::from_msg(VcxErrorKind::InvalidOption, "At least one message status must be given"));
+    }
+    _update_state(handle, statuses)?;
+    Ok(error::SUCCESS.code_num)
+}
+
+fn _update_state(handle: u32, statuses: Vec<MessageStatusCode>) -> VcxResult<u32> {
     CONNECTION_MAP.get_mut(handle, |connection| {
-        trace!("Connection::update_state >>>");
+        connection_span!("update_state", handle, connection);
 
-        if connection.is_in_null_state() {
+        if connection.is_in_null_state() && !connection.is_receiver() {
             warn!("Connection::update_state :: update state on connection in null state is ignored");
-            return Ok(error::SUCCESS.code_num);
+            return Ok(0);
         }
 
         // connection protocol itself handles message authentication where it makes sense
-        let messages = connection.get_messages_noauth()?;
+        let messages = connection.get_messages_noauth_with_statuses(statuses)?;
         trace!("Connection::update_state >>> retrieved messages {:?}", messages);
 
-        if let Some((uid, message)) = connection.find_message_to_handle(messages) {
-            trace!("Connection::update_state >>> handling message uid: {:?}", uid);
-            connection.update_state_with_message(&message)?;
+        // application handlers get first refusal, connection protocol is the last handler
+        let (handled, messages) = message_handler::dispatch(handle, messages)?;
+        for uid in handled {
             connection.agent_info().clone().update_message_status(uid)?;
+        }
+
+        // presentation requests are recorded for the application to answer, see `disclosed_proof::list_pending_requests`,
+        // their messages stay unprocessed until answered, so requests recorded in memory only are recorded again after restart
+        let mut messages = messages;
+        for (uid, message) in messages.iter() {
+            if let A2AMessage::PresentationRequest(presentation_request) = message {
+                if let Err(err) = disclosed_proof::record_received_request(handle, uid, presentation_request) {
+                    warn!("Connection::update_state >>> cannot record presentation request {}: {}", presentation_request.id.0, err);
+                }
+            }
+        }
+
+        // messages of unsupported types are downloaded on every update unless the application opted in to mark them,
+        // otherwise they stay unprocessed for the application to pick up by get_messages
+        if settings::mark_unknown_messages_processed() {
+            let unknown: Vec<String> = messages.iter()
+                .filter(|(_, message)| match message {
+                    A2AMessage::Unknown { .. } => true,
+                    _ => false
+                })
+                .map(|(uid, _)| uid.clone())
+                .collect();
+            for uid in unknown {
+                warn!("Connection::update_state >>> message {} of unsupported type is marked as processed", uid);
+                connection.agent_info().clone().update_message_status(uid.clone())?;
+                messages.remove(&uid);
+            }
+        }
+
+        // listen-only connection runs no connection protocol, remaining messages are left for the application
+        if connection.is_receiver() {
+            trace!("Connection::update_state >>> done");
+            return Ok(0);
+        }
+
+        if let Some((uid, message)) = connection.find_message_to_handle(messages.clone()) {
+            trace!("Connection::update_state >>> handling message uid: {:?}", uid);
+            if let Err(err) = connection.update_state_with_message(&message) {
+                // rejected Response of unexpected counterparty failed the connection, it must not be handled again
+                if err.kind() == VcxErrorKind::ConnectionDidMismatch {
+                    connection.agent_info().clone().update_message_status(uid.clone())?;
+                }
+                return Err(err);
+            }
+            connection.agent_info().clone().update_message_status(uid.clone())?;
+
+            let mut remaining = messages;
+            remaining.remove(&uid);
+            let remaining = connection.count_messages_to_handle(&remaining);
+            trace!("Connection::update_state >>> done, {} messages remaining", remaining);
+            return Ok(remaining);
         } else if let SmConnectionState::Inviter(_) = connection.state_object() {
             trace!("Connection::update_state >>> Inviter found no message to handle on main connection agent. Will check bootstrap agent.");
             if let Some((messages, bootstrap_agent_info)) = get_bootstrap_agent_messages(connection.remote_vk(), connection.bootstrap_agent_info())? {
@@ -167,41 +404,187 @@ pub fn update_state(handle: u32) -> VcxResult<u32> {
         }
 
         trace!("Connection::update_state >>> done");
-        Ok(error::SUCCESS.code_num)
+        Ok(0)
     })
 }
 
+/**
+Creates connection from the invitation, connects and keeps updating its state until the connection is established.
+State is polled with exponential backoff starting at 100ms and capped at 5s, transient agency errors are retried.
+If the connection is not established within `timeout` or the inviter rejects it, the connection is deleted and
+the error returned.
+ */
+pub fn establish_from_invite(source_id: &str, invite: &str, timeout: Duration) -> VcxResult<u32> {
+    trace!("establish_from_invite >>> source_id: {}, timeout: {:?}", source_id, timeout);
+    let handle = create_connection_with_invite(source_id, invite)?;
+
+    match _establish(handle, timeout) {
+        Ok(()) => Ok(handle),
+        Err(err) => {
+            let reason = match err.kind() {
+                VcxErrorKind::Timeout => DeletionReason::Expired,
+                _ => DeletionReason::Error
+            };
+            if let Err(delete_err) = delete_with_reason(handle, reason) {
+                warn!("establish_from_invite >>> failed to delete connection {} which was not established: {}", handle, delete_err);
+                release(handle).ok();
+            }
+            Err(err)
+        }
+    }
+}
+
+fn _establish(handle: u32, timeout: Duration) -> VcxResult<()> {
+    connect(handle)?;
+    polling::poll_until(timeout, || {
+        update_state(handle)?;
+        match get_state(handle) {
+            state if state == VcxStateType::VcxStateAccepted as u32 => Ok(Some(())),
+            state if state == VcxStateType::VcxStateInitialized as u32 => {
+                Err(VcxError::from_msg(VcxErrorKind::GeneralConnectionError,
+                                       format!("Connection was not established: {}", get_last_problem_report(handle).unwrap_or_default())))
+            }
+            _ => Ok(None)
+        }
+    })?.ok_or(VcxError::from_msg(VcxErrorKind::Timeout, format!("Connection was not established within {:?}", timeout)))
+}
+
 pub fn delete_connection(handle: u32) -> VcxResult<u32> {
-    CONNECTION_MAP.get_mut(handle, |connection| {
+    delete_with_reason(handle, DeletionReason::UserInitiated)
+}
+
+/**
+Deletes the connection like `delete_connection`, reporting the reason to the deletion observer before
+the connection is deleted and the outcome of the deletion once it's attempted.
+ */
+pub fn delete_with_reason(handle: u32, reason: DeletionReason) -> VcxResult<u32> {
+    trace!("delete_with_reason >>> handle: {}, reason: {:?}", handle, reason);
+    let source_id = get_source_id(handle)
+        .or(Err(VcxError::from(VcxErrorKind::DeleteConnection)))?;
+
+    deletion_observer::notify_delete(handle, &source_id, reason);
+    let result = CONNECTION_MAP.get_mut(handle, |connection| {
+        let pw_did = connection.agent_info().pw_did.clone();
         connection.delete()?;
+        send_rate_limiter::release(&pw_did)?;
         Ok(error::SUCCESS.code_num)
     })
         .map(|_| error::SUCCESS.code_num)
         .or(Err(VcxError::from(VcxErrorKind::DeleteConnection)))
-        .and(release(handle))
-        .and_then(|_| Ok(error::SUCCESS.code_num))
+        .and(release(handle));
+    deletion_observer::notify_delete_completed(handle, &source_id, reason, result.is_ok());
+
+    result
+}
+
+/**
+Deletes the pairwise agent in agency, so no more messages are received on this connection, but keeps
+the local connection record. Deactivated connection can still be serialized and queried, but sending
+messages or updating its state fails with ConnectionDeactivated error.
+ */
+pub fn deactivate(handle: u32) -> VcxResult<()> {
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection.deactivate()
+    })
+}
+
+/**
+Re-sends the connection request of invitee in Requested state, eg. when inviter's response didn't arrive in time.
+The request is the same as the original one, returns its thread id.
+ */
+pub fn resend_request(handle: u32) -> VcxResult<String> {
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection.resend_request()
+    })
+}
+
+pub fn cancel(handle: u32) -> VcxResult<()> {
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection.cancel()
+    })
 }
 
 pub fn connect(handle: u32) -> VcxResult<Option<String>> {
     CONNECTION_MAP.get_mut(handle, |connection| {
+        connection_span!("connect", handle, connection);
         connection.connect()?;
         Ok(connection.get_invite_details())
     })
 }
 
+/**
+Builds an invitation the same way `connect` does, but from pairwise and agent keys generated in memory only. Nothing
+is written to the wallet, no agent is created in the agency and nothing is stored in the connection cache.
+The invitation is labeled by `label`, or by `source_id` if it's empty, as `connect` labels invitations by source id.
+
+The returned invitation is only good for previews and tests: since no pairwise agent exists, the
+invitee has no way to reach us until a real connection is created and provisioned with `connect`.
+ */
+pub fn preview_invitation(source_id: &str, label: &str) -> VcxResult<InvitationV3> {
+    trace!("preview_invitation >>> source_id: {}, label: {}", source_id, label);
+
+    let agent_info = AgentInfo {
+        pw_vk: EphemeralKey::generate()?.verkey,
+        agent_vk: EphemeralKey::generate()?.verkey,
+        ..AgentInfo::default()
+    };
+    let label = if label.is_empty() { source_id } else { label };
+
+    Ok(InvitationV3::from_did_doc(label.to_string(), &agent_info.did_doc()?))
+}
+
+/**
+Serializes the connection. The output is deterministic, the same connection always serializes to the same string,
+so it can be hashed to detect modifications of the stored connection.
+ */
 pub fn to_string(handle: u32) -> VcxResult<String> {
     CONNECTION_MAP.get(handle, |connection| {
         let (state, data, source_id) = connection.to_owned().into();
         let object = SerializableObjectWithState::V1 { data, state, source_id };
 
-        serde_json::to_string(&object)
-            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidState, format!("Cannot serialize Connection: {:?}", err)))
+        serialization::serialize(&object)
+            .map_err(|err| err.extend("Cannot serialize Connection"))
     })
 }
 
+/**
+Deserializes the connection. If `validate_connection_keys` is enabled, the connection is rejected with
+WalletRecordNotFound if its pairwise keys are not stored in the current wallet, eg. after the wallet was restored
+from a different backup. See `validate_keys`.
+ */
 pub fn from_string(connection_data: &str) -> VcxResult<u32> {
-    let object: SerializableObjectWithState<AgentInfo, SmConnectionState> = serde_json::from_str(connection_data)
-        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize Connection: {:?}", err)))?;
+    let object: SerializableObjectWithState<ConnectionData, SmConnectionState> = serialization::deserialize(connection_data)
+        .map_err(|err| err.extend("Cannot deserialize Connection"))?;
+
+    let handle = match object {
+        SerializableObjectWithState::V1 { data, state, source_id } => {
+            if settings::validate_connection_keys() && !data.agent_info.validate_keys()? {
+                return Err(VcxError::from_msg(VcxErrorKind::WalletRecordNotFound,
+                                              format!("Pairwise keys of connection {} (DID: {}, verkey: {}) are not stored in the wallet", source_id, data.agent_info.pw_did, data.agent_info.pw_vk)));
+            }
+            CONNECTION_MAP.add((state, data, source_id).into())?
+        }
+    };
+    Ok(handle)
+}
+
+/**
+Checks pairwise keys of the connection are stored in the current wallet. Returns false if they are not, so sending
+or receiving messages over the connection would fail.
+ */
+pub fn validate_keys(handle: u32) -> VcxResult<bool> {
+    CONNECTION_MAP.get(handle, |connection| {
+        connection.agent_info().validate_keys()
+    })
+}
+
+/**
+Lenient variant of `from_string` accepting connections serialized by older releases, see `legacy::upgrade_connection`
+for the supported schemas.
+ */
+pub fn from_legacy_string(connection_data: &str) -> VcxResult<u32> {
+    let object: SerializableObjectWithState<ConnectionData, SmConnectionState> = serde_json::from_value(legacy::upgrade_connection(connection_data)?)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize legacy Connection: {}", err)))?;
 
     let handle = match object {
         SerializableObjectWithState::V1 { data, state, source_id } => {
@@ -211,6 +594,43 @@ pub fn from_string(connection_data: &str) -> VcxResult<u32> {
     Ok(handle)
 }
 
+/**
+Portable backup of a connection, holding its serialized state alongside arbitrary application metadata.
+ */
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "version")]
+enum ConnectionBundle {
+    #[serde(rename = "1.0")]
+    V1 { connection: String, extra: Value },
+}
+
+pub fn export_bundle(handle: u32, extra: Value) -> VcxResult<String> {
+    let connection = to_string(handle)?;
+    let bundle = ConnectionBundle::V1 { connection, extra };
+
+    serde_json::to_string(&bundle)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize connection bundle: {:?}", err)))
+}
+
+/**
+Restores connection from bundle created by `export_bundle`, the connection is recorded as `ContactImport`.
+ */
+pub fn import_bundle(bundle: &str) -> VcxResult<(u32, Value)> {
+    let bundle: ConnectionBundle = serde_json::from_str(bundle)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize connection bundle: {:?}", err)))?;
+
+    match bundle {
+        ConnectionBundle::V1 { connection, extra } => {
+            let handle = from_string(&connection)?;
+            CONNECTION_MAP.get_mut(handle, |connection| {
+                connection.set_invitation_type(InvitationType::ContactImport);
+                Ok(())
+            })?;
+            Ok((handle, extra))
+        }
+    }
+}
+
 pub fn release(handle: u32) -> VcxResult<()> {
     CONNECTION_MAP.release(handle)
         .or(Err(VcxError::from(VcxErrorKind::InvalidConnectionHandle)))
@@ -227,198 +647,2478 @@ pub fn get_invite_details(handle: u32) -> VcxResult<String> {
     }).or(Err(VcxError::from(VcxErrorKind::InvalidConnectionHandle)))
 }
 
-impl Into<(SmConnectionState, AgentInfo, String)> for Connection {
-    fn into(self) -> (SmConnectionState, AgentInfo, String) {
-        (self.state_object(), self.agent_info().to_owned(), self.source_id())
-    }
-}
+/**
+Builds fresh single-use invitation for the counterparty of completed connection, so it can re-onboard after losing its end.
+Completed connection can't accept another connection request, hence the invitation always belongs to a new inviter
+connection with new pairwise keys, the original connection is left untouched. Returns handle of the new connection
+and the invitation.
+ */
+pub fn regenerate_invitation(handle: u32) -> VcxResult<(u32, String)> {
+    let source_id = CONNECTION_MAP.get(handle, |connection| {
+        if connection.state() != VcxStateType::VcxStateAccepted as u32 {
+            return Err(VcxError::from_msg(VcxErrorKind::ActionNotSupported, "Invitation can be regenerated only for completed connection"));
+        }
+        Ok(connection.get_source_id())
+    })?;
+
+    let new_handle = create_connection(&source_id)?;
+    let invitation = connect(new_handle)
+        .and_then(|invitation| invitation.ok_or(VcxError::from_msg(VcxErrorKind::InvalidState, "Connection has no invitation after connect")));
+    match invitation {
+        Ok(invitation) => Ok((new_handle, invitation)),
+        Err(err) => {
+            release(new_handle).ok();
+            Err(err)
+        }
+    }
+}
+
+impl Into<(SmConnectionState, ConnectionData, String)> for Connection {
+    fn into(self) -> (SmConnectionState, ConnectionData, String) {
+        (self.state_object(), self.data(), self.source_id())
+    }
+}
+
+impl From<(SmConnectionState, ConnectionData, String)> for Connection {
+    fn from((state, data, source_id): (SmConnectionState, ConnectionData, String)) -> Connection {
+        Connection::from_parts(source_id, data, state)
+    }
+}
+
+pub fn get_messages(handle: u32) -> VcxResult<HashMap<String, A2AMessage>> {
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection.get_messages()
+    })
+}
+
+/**
+Synchronously waits up to `timeout` for new messages received from the counterparty of the connection. The agency
+is polled with exponential backoff like in `establish_from_invite` until some messages arrive, no messages are
+returned if none arrive in time.
+
+Downloaded messages are passed through registered message handlers and the connection protocol first, each of them
+is marked Reviewed once it was handled there. The remaining messages are returned by their uids in Received status,
+the caller marks them by `update_message_status` once it processed them. If handling fails, the error is returned
+and messages which were not handled yet stay in Received status, so they are downloaded again by the next call.
+ */
+pub fn subscribe(handle: u32, timeout: Duration) -> VcxResult<HashMap<String, A2AMessage>> {
+    trace!("subscribe >>> handle: {}, timeout: {:?}", handle, timeout);
+    // connection is locked only while downloading, not while waiting for the next poll
+    let messages = polling::poll_until(timeout, || {
+        let messages = get_messages(handle)?;
+        Ok(if messages.is_empty() { None } else { Some(messages) })
+    })?.unwrap_or_default();
+
+    let (handled, mut messages) = message_handler::dispatch(handle, messages)?;
+    for uid in handled {
+        update_message_status(handle, uid)?;
+    }
+
+    let mut uids: Vec<String> = messages.keys().cloned().collect();
+    uids.sort();
+    for uid in uids {
+        let message = messages[&uid].clone();
+        let handled = CONNECTION_MAP.get_mut(handle, |connection| {
+            // listen-only connection runs no connection protocol
+            if connection.is_receiver() || !connection.can_handle_message(&message) {
+                return Ok(false);
+            }
+            connection.update_state_with_message(&message)?;
+            connection.update_message_status(uid.clone())?;
+            Ok(true)
+        })?;
+        if handled {
+            messages.remove(&uid);
+        }
+    }
+    Ok(messages)
+}
+
+pub fn update_message_status(handle: u32, uid: String) -> VcxResult<()> {
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection.update_message_status(uid.clone())
+    })
+}
+
+pub fn get_message_by_id(handle: u32, msg_id: String) -> VcxResult<A2AMessage> {
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection.get_message_by_id(&msg_id)
+    })
+}
+
+pub fn send_message(handle: u32, message: A2AMessage) -> VcxResult<()> {
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection_span!("send_message", handle, connection);
+        send_rate_limiter::send_limited(&connection.agent_info().pw_did, || connection.send_message(&message))
+    })
+}
+
+/**
+Sends problem report of the given protocol family, eg. `connections`, `issue-credential` or `present-proof`, in reply
+to the thread `thread_id`. Connection protocol problem codes are restricted to the codes defined by the protocol,
+problem reports of other families carry any code, eg. `invalid_presentation` of RFC 0035.
+ */
+pub fn send_problem_report(handle: u32, protocol: &str, code: &str, comment: Option<String>, thread_id: &str) -> VcxResult<()> {
+    let problem_report = _build_problem_report(protocol, code, comment, thread_id)?;
+    send_message(handle, problem_report)
+}
+
+fn _build_problem_report(protocol: &str, code: &str, comment: Option<String>, thread_id: &str) -> VcxResult<A2AMessage> {
+    match MessageFamilies::from(protocol.to_string()) {
+        MessageFamilies::Connections => {
+            let problem_code: ConnectionProblemCode = serde_json::from_value(json!(code))
+                .map_err(|_| VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Unknown problem code of connection protocol: {}", code)))?;
+            let problem_report = ConnectionProblemReport::create()
+                .set_problem_code(problem_code)
+                .set_thread_id(thread_id);
+            Ok(match comment {
+                Some(comment) => problem_report.set_explain(comment),
+                None => problem_report
+            }.to_a2a_message())
+        }
+        MessageFamilies::Unknown(family) => {
+            Err(VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Unknown protocol family: {}", family)))
+        }
+        family => {
+            if code.is_empty() {
+                return Err(VcxError::from_msg(VcxErrorKind::InvalidOption, "Problem code must not be empty"));
+            }
+            let problem_report = CommonProblemReport::create()
+                .set_description(code)
+                .set_thread_id(thread_id);
+            match comment {
+                Some(comment) => problem_report.set_comment(comment),
+                None => problem_report
+            }.to_a2a_message_for_family(family)
+        }
+    }
+}
+
+pub fn send_message_with_receipt(handle: u32, message: A2AMessage) -> VcxResult<DeliveryReceipt> {
+    CONNECTION_MAP.get(handle, |connection| {
+        connection_span!("send_message_with_receipt", handle, connection);
+        send_rate_limiter::send_limited(&connection.agent_info().pw_did, || connection.send_message_with_receipt(&message))
+    })
+}
+
+pub fn send_message_to_recipient_key(handle: u32, message: A2AMessage, recipient_key: &str) -> VcxResult<()> {
+    CONNECTION_MAP.get(handle, |connection| {
+        connection_span!("send_message_to_recipient_key", handle, connection);
+        send_rate_limiter::send_limited(&connection.agent_info().pw_did, || connection.send_message_to_recipient_key(&message, recipient_key))
+    })
+}
+
+pub fn send_present_proof_message(handle: u32, message: A2AMessage, version: &str) -> VcxResult<()> {
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection_span!("send_present_proof_message", handle, connection);
+        send_rate_limiter::send_limited(&connection.agent_info().pw_did, || connection.send_present_proof_message(&message, version))
+    })
+}
+
+pub fn get_present_proof_version(handle: u32) -> VcxResult<String> {
+    CONNECTION_MAP.get(handle, |connection| {
+        Ok(connection.present_proof_version().to_string())
+    })
+}
+
+pub fn get_last_problem_report(handle: u32) -> VcxResult<String> {
+    CONNECTION_MAP.get(handle, |connection| {
+        connection.get_last_problem_report()
+    })
+}
+
+/**
+Returns envelope version negotiated with the counterparty, "v1" (RFC 0019 envelope) or "v2" (DIDComm v2).
+ */
+pub fn get_envelope_version(handle: u32) -> VcxResult<String> {
+    CONNECTION_MAP.get(handle, |connection| {
+        Ok(connection.envelope_version().as_str().to_string())
+    })
+}
+
+pub fn send_message_to_self_endpoint(message: A2AMessage, did_doc: &DidDoc) -> VcxResult<()> {
+    Connection::send_message_to_self_endpoint(&message, did_doc)
+}
+
+/**
+Returns version of the protocol used to communicate with the agency, pinned by `agency_protocol_version` setting.
+ */
+pub fn get_agency_protocol_version() -> VcxResult<String> {
+    Ok(settings::get_agency_client()?.get_protocol_version())
+}
+
+/**
+Returns state of the circuit breaker guarding agency requests as JSON `{"enabled": bool, "state": "Closed"|"Open"|"HalfOpen", "consecutive_failures": u32}`.
+The breaker is enabled by `agency_circuit_breaker_threshold` setting, see `agency_client::circuit_breaker`.
+ */
+pub fn get_agency_circuit_breaker_status() -> VcxResult<String> {
+    serde_json::to_string(&circuit_breaker::status())
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize circuit breaker status: {}", err)))
+}
+
+pub fn is_v3_connection(connection_handle: u32) -> VcxResult<bool> {
+    CONNECTION_MAP.get(connection_handle, |_| {
+        Ok(true)
+    }).or(Err(VcxError::from(VcxErrorKind::InvalidConnectionHandle)))
+}
+
+pub fn send_ping(connection_handle: u32, comment: Option<String>) -> VcxResult<Option<String>> {
+    CONNECTION_MAP.get_mut(connection_handle, |connection| {
+        connection.send_ping(comment.clone())
+    })
+}
+
+/**
+Actively probes whether the counterparty also considers the connection completed. Sends trust ping requesting
+response and waits up to `timeout` for the ping response, which is taken as confirmation that the peer's end
+of the connection is live and completed. Messages are polled with the same backoff as `establish_from_invite`,
+transient agency errors are retried.
+Returns false if the connection is not completed on our side or the ping response doesn't arrive in time.
+ */
+pub fn confirm_mutual_completion(connection_handle: u32, timeout: Duration) -> VcxResult<bool> {
+    trace!("confirm_mutual_completion >>> connection_handle: {}, timeout: {:?}", connection_handle, timeout);
+    if !is_valid_handle(connection_handle) {
+        return Err(VcxError::from(VcxErrorKind::InvalidConnectionHandle));
+    }
+    if get_state(connection_handle) != VcxStateType::VcxStateAccepted as u32 {
+        debug!("confirm_mutual_completion >>> connection {} is not completed on our side", connection_handle);
+        return Ok(false);
+    }
+
+    let ping_id = match send_ping(connection_handle, None)? {
+        Some(ping_id) => ping_id,
+        None => return Ok(false)
+    };
+    let confirmed = polling::poll_until(timeout, || {
+        let ping_response = get_messages(connection_handle)?.into_iter()
+            .find(|(_, message)| match message {
+                A2AMessage::PingResponse(ping_response) => ping_response.thread.thid.as_ref() == Some(&ping_id),
+                _ => false
+            });
+        match ping_response {
+            Some((uid, _)) => {
+                update_message_status(connection_handle, uid)?;
+                Ok(Some(()))
+            }
+            None => Ok(None)
+        }
+    })?;
+
+    if confirmed.is_none() {
+        debug!("confirm_mutual_completion >>> no ping response received on connection {} within {:?}", connection_handle, timeout);
+    }
+    Ok(confirmed.is_some())
+}
+
+pub fn send_discovery_features(connection_handle: u32, query: Option<String>, comment: Option<String>) -> VcxResult<String> {
+    CONNECTION_MAP.get_mut(connection_handle, |connection| {
+        connection.send_discovery_features(query.clone(), comment.clone())
+    })
+}
+
+pub fn send_discovery_features_query(connection_handle: u32, query: ProtocolQuery, comment: Option<String>) -> VcxResult<String> {
+    CONNECTION_MAP.get_mut(connection_handle, |connection| {
+        connection.send_discovery_features_query(&query, comment.clone())
+    })
+}
+
+pub fn get_disclosed_protocols(connection_handle: u32) -> VcxResult<Vec<(String, Vec<Actors>)>> {
+    CONNECTION_MAP.get(connection_handle, |connection| {
+        connection.get_disclosed_protocols()
+    })
+}
+
+/**
+Renames the connection, the source id is also the label presented to the counterparty in invitations and requests
+created afterwards.
+ */
+pub fn set_label(handle: u32, label: &str) -> VcxResult<()> {
+    if label.is_empty() {
+        return Err(VcxError::from_msg(VcxErrorKind::InvalidOption, "Label can not be empty"));
+    }
+    patch(handle, &json!({"source_id": label}).to_string())
+}
+
+/**
+Applies JSON merge patch (RFC 7386) to the serialized form of the connection, as returned by `to_string`, in place.
+The handle stays valid. Patched connection must deserialize and have valid counterparty DidDoc, otherwise it's left
+unchanged. Applications storing serialized connections can apply the same patch to the stored object.
+ */
+pub fn patch(handle: u32, patch_json: &str) -> VcxResult<()> {
+    let patch: Value = serde_json::from_str(patch_json)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize connection patch: {}", err)))?;
+
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        let (state, data, source_id) = connection.to_owned().into();
+        let mut object = serde_json::to_value(SerializableObjectWithState::V1 { data, state, source_id })
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize Connection: {}", err)))?;
+        json::merge_patch(&mut object, &patch);
+
+        let object: SerializableObjectWithState<ConnectionData, SmConnectionState> = serde_json::from_value(object)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Patched connection is not valid: {}", err)))?;
+        let patched: Connection = match object {
+            SerializableObjectWithState::V1 { data, state, source_id } => (state, data, source_id).into()
+        };
+        if let Some(did_doc) = patched.their_did_doc() {
+            did_doc.validate()?;
+        }
+
+        *connection = patched;
+        Ok(())
+    })
+}
+
+pub fn add_tag(handle: u32, tag: &str) -> VcxResult<()> {
+    if tag.is_empty() {
+        return Err(VcxError::from_msg(VcxErrorKind::InvalidOption, "Tag can not be empty"));
+    }
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection.add_tag(tag);
+        Ok(())
+    })
+}
+
+pub fn remove_tag(handle: u32, tag: &str) -> VcxResult<()> {
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection.remove_tag(tag);
+        Ok(())
+    })
+}
+
+/**
+Sets decorator merged into every message sent over the connection, `None` removes it.
+ */
+pub fn set_decorator(handle: u32, name: &str, value: Option<&str>) -> VcxResult<()> {
+    let value = match value {
+        Some(value) => Some(serde_json::from_str(value)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot parse decorator value: {}", err)))?),
+        None => None
+    };
+    CONNECTION_MAP.get_mut(handle, |connection| {
+        connection.set_decorator(name, value.clone())
+    })
+}
+
+pub fn get_tags(handle: u32) -> VcxResult<Vec<String>> {
+    CONNECTION_MAP.get(handle, |connection| {
+        Ok(connection.get_tags())
+    })
+}
+
+/**
+Returns handles of connections having the given tag, in ascending order.
+ */
+pub fn find_by_tag(tag: &str) -> VcxResult<Vec<u32>> {
+    let mut handles = Vec::new();
+    for handle in CONNECTION_MAP.handles()? {
+        match CONNECTION_MAP.get(handle, |connection| Ok(connection.has_tag(tag))) {
+            Ok(true) => handles.push(handle),
+            Ok(false) => {}
+            // connection was released while iterating
+            Err(ref err) if err.kind() == VcxErrorKind::InvalidHandle => continue,
+            Err(err) => return Err(err)
+        }
+    }
+    handles.sort();
+    Ok(handles)
+}
+
+/**
+Returns (handle, source_id, state) of every connection in memory, in ascending order of handles.
+ */
+pub fn list() -> VcxResult<Vec<(u32, String, u32)>> {
+    let connections = CONNECTION_MAP.map(|connection| Ok((connection.get_source_id(), connection.state())))?;
+    Ok(connections.into_iter().map(|(handle, (source_id, state))| (handle, source_id, state)).collect())
+}
+
+/**
+Returns handle of the connection with the given pairwise DID, which stays the same when the connection is
+serialized and loaded again, unlike its handle.
+ */
+pub fn find_by_pw_did(pw_did: &str) -> VcxResult<Option<u32>> {
+    let mut handles = CONNECTION_MAP.handles()?;
+    handles.sort();
+    for handle in handles {
+        match CONNECTION_MAP.get(handle, |connection| Ok(connection.agent_info().pw_did == pw_did)) {
+            Ok(true) => return Ok(Some(handle)),
+            Ok(false) => {}
+            // connection was released while iterating
+            Err(ref err) if err.kind() == VcxErrorKind::InvalidHandle => continue,
+            Err(err) => return Err(err)
+        }
+    }
+    Ok(None)
+}
+
+pub fn get_connection_info(handle: u32) -> VcxResult<String> {
+    CONNECTION_MAP.get(handle, |connection| {
+        connection.get_connection_info()
+    })
+}
+
+/**
+Returns redacted JSON report of the connection for support tickets, optionally including counterparty's DidDoc
+and the signature of the connection response.
+ */
+pub fn diagnostics(handle: u32, include_did_doc: bool, include_signed_response: bool) -> VcxResult<String> {
+    CONNECTION_MAP.get(handle, |connection| {
+        serde_json::to_string(&connection.get_diagnostics(include_did_doc, include_signed_response))
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize connection diagnostics: {:?}", err)))
+    })
+}
+
+/**
+Returns JSON of the `connection~sig` structure (base64 signature, signed data and signer) of the connection response
+sent as inviter or received as invitee, `None` until the response is sent or accepted.
+ */
+pub fn get_signed_response(handle: u32) -> VcxResult<Option<String>> {
+    CONNECTION_MAP.get(handle, |connection| {
+        connection.get_signed_response()
+    })
+}
+
+pub fn get_invitation_recipient_key(handle: u32) -> VcxResult<String> {
+    CONNECTION_MAP.get(handle, |connection| {
+        connection.get_invitation_recipient_key()
+    })
+}
+
+pub fn get_establishment_latency(handle: u32) -> VcxResult<Option<Duration>> {
+    CONNECTION_MAP.get(handle, |connection| {
+        Ok(connection.get_establishment_latency())
+    })
+}
+
+pub fn get_connection_info_typed(handle: u32) -> VcxResult<ConnectionInfo> {
+    CONNECTION_MAP.get(handle, |connection| {
+        connection.get_connection_info_typed()
+    })
+}
+
+/**
+Downloads and decrypts messages of the given connections. If `received_within` is set as (from, to) in seconds since epoch,
+only messages received within that inclusive window are returned. The agency doesn't support filtering by time,
+so the window is applied after the download, messages the agency reports no received time for are left out, as it's
+unknown whether they fall within the window.
+Once the `cancellation` token is cancelled, the download is aborted with `Cancelled`.
+ */
+pub fn download_messages(conn_handles: Vec<u32>, status_codes: Option<Vec<MessageStatusCode>>, uids: Option<Vec<String>>, received_within: Option<(u64, u64)>, cancellation: Option<&CancellationToken>) -> VcxResult<Vec<MessageByConnection>> {
+    trace!("download_messages >>> cann_handles: {:?}, status_codes: {:?}, uids: {:?}, received_within: {:?}", conn_handles, status_codes, uids, received_within);
+    cancellation::with_cancellation(cancellation, || _download_messages(conn_handles, status_codes, uids, received_within))
+}
+
+fn _download_messages(conn_handles: Vec<u32>, status_codes: Option<Vec<MessageStatusCode>>, uids: Option<Vec<String>>, received_within: Option<(u64, u64)>) -> VcxResult<Vec<MessageByConnection>> {
+    let mut res = Vec::new();
+    for conn_handle in conn_handles {
+        cancellation::check_current()?;
+        let msg_by_conn = CONNECTION_MAP.get(
+            conn_handle, |connection| {
+                connection_span!("download_messages", conn_handle, connection);
+                let expected_sender_vk = connection.remote_vk()?;
+                let msgs = connection
+                    .agent_info()
+                    .download_encrypted_messages(uids.clone(), status_codes.clone())?
+                    .iter()
+                    .filter(|msg| is_received_within(msg, received_within))
+                    .map(|msg| msg.decrypt_auth(&expected_sender_vk).map_err(|err| err.into()))
+                    .collect::<VcxResult<Vec<Message>>>()?;
+                Ok(MessageByConnection { pairwise_did: connection.agent_info().clone().pw_did, msgs })
+            },
+        )?;
+        res.push(msg_by_conn);
+    };
+    trace!("download_messages <<< res: {:?}", res);
+    Ok(res)
+}
+
+fn is_received_within(message: &Message, received_within: Option<(u64, u64)>) -> bool {
+    match (received_within, message.created_at) {
+        (Some((from, to)), Some(received_time)) => from <= received_time && received_time <= to,
+        (Some(_), None) => false,
+        (None, _) => true
+    }
+}
+
+/**
+Downloads and decrypts messages of every established connection, returned as (connection handle, message, received time)
+ordered from the oldest to the newest. Received time is in seconds since epoch as reported by the agency, falling back
+to the time of the download when the agency doesn't report it. If `since` is set, older messages are skipped.
+Connections whose messages can't be downloaded or decrypted don't stop the others, their errors are returned
+alongside the messages as (connection handle, error).
+ */
+pub fn download_all_messages(status_codes: Option<Vec<MessageStatusCode>>, since: Option<u64>) -> VcxResult<(Vec<(u32, A2AMessage, u64)>, Vec<(u32, VcxError)>)> {
+    trace!("download_all_messages >>> status_codes: {:?}, since: {:?}", status_codes, since);
+    let now = clock::now_secs();
+    let mut res = Vec::new();
+    let mut errors = Vec::new();
+    for conn_handle in CONNECTION_MAP.handles()? {
+        let msgs = CONNECTION_MAP.get(
+            conn_handle, |connection| {
+                let expected_sender_vk = match connection.remote_vk() {
+                    Ok(vk) => vk,
+                    Err(_) => return Ok(Vec::new())
+                };
+                connection
+                    .agent_info()
+                    .download_encrypted_messages(None, status_codes.clone())?
+                    .iter()
+                    .map(|msg| {
+                        let message = connection.agent_info().decrypt_decode_message(msg, &expected_sender_vk)?;
+                        Ok((conn_handle, message, msg.created_at.unwrap_or(now)))
+                    })
+                    .collect::<VcxResult<Vec<(u32, A2AMessage, u64)>>>()
+            },
+        );
+        match msgs {
+            Ok(msgs) => res.extend(msgs.into_iter().filter(|(_, _, received_time)| since.map_or(true, |since| *received_time >= since))),
+            // connection was released while iterating
+            Err(ref err) if err.kind() == VcxErrorKind::InvalidHandle => continue,
+            Err(err) => {
+                warn!("download_all_messages >>> failed to download messages of connection {}: {}", conn_handle, err);
+                errors.push((conn_handle, err))
+            }
+        }
+    };
+    res.sort_by_key(|(_, _, received_time)| *received_time);
+    trace!("download_all_messages <<< res: {:?}, errors: {:?}", res, errors);
+    Ok((res, errors))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use serde_json::Value;
+
+    use agency_client::agency_settings;
+    use agency_client::error::AgencyClientResult;
+    use agency_client::get_message::download_messages_noauth;
+    use agency_client::MessageStatusCode;
+    use agency_client::httpclient::{self, Transport};
+    use agency_client::mocking::{AgencyMockDecrypted, AgencyMockUpdatedMessages, HttpClientMockResponse, MockTransport};
+    use agency_client::update_message::{UIDsByConn, update_agency_messages};
+
+    use crate::{connection, utils, settings};
+    use crate::aries::handlers::connection::message_handler::{HandlerResult, MessageHandler};
+    use crate::aries::messages::basic_message::message::BasicMessage;
+    use crate::aries::messages::connection::problem_report::{ProblemCode, ProblemReport};
+    use crate::aries::messages::connection::problem_report::tests::_problem_report;
+    use crate::aries::messages::connection::response::Response;
+    use crate::aries::messages::discovery::query::Query;
+    use crate::api::VcxStateType;
+    use crate::aries::messages::a2a::MessageId;
+    use crate::aries::messages::issuance::credential_offer::tests::_credential_offer;
+    use crate::aries::messages::out_of_band::invitation::OutOfBandService;
+    use crate::aries::messages::out_of_band::invitation::tests::_oob_invitation_with_offer;
+    use crate::aries::messages::proof_presentation::presentation::tests::_presentation;
+    use crate::aries::messages::proof_presentation::presentation_request::tests::_presentation_request;
+    use crate::aries::messages::proof_presentation::v2;
+    use crate::aries::messages::trust_ping::ping::Ping;
+    use crate::aries::messages::trust_ping::ping_response::PingResponse;
+    use crate::aries::messages::revocation_notification::revocation_notification::RevocationNotification;
+    use crate::aries::messages::a2a::message_family::MessageFamilies;
+    use crate::libindy::utils::signus::create_and_store_my_did;
+    use crate::utils::clock::SetupFakeClock;
+    use crate::utils::constants;
+    use crate::utils::devsetup::*;
+    use crate::utils::mockdata::mockdata_connection::{ARIES_CONNECTION_ACK, ARIES_CONNECTION_INVITATION, ARIES_CONNECTION_REQUEST, CONNECTION_SM_INVITEE_COMPLETED, CONNECTION_SM_INVITEE_INVITED, CONNECTION_SM_INVITEE_REQUESTED, CONNECTION_SM_INVITER_COMPLETED, DEFAULT_SERIALIZED_CONNECTION};
+
+    use super::*;
+
+    pub fn build_test_connection_inviter_null() -> u32 {
+        let handle = create_connection("faber_to_alice").unwrap();
+        handle
+    }
+
+    pub fn build_test_connection_inviter_invited() -> u32 {
+        let handle = create_connection("faber_to_alice").unwrap();
+        connect(handle).unwrap();
+        handle
+    }
+
+    pub fn build_test_connection_invitee_completed() -> u32 {
+        let handle = from_string(CONNECTION_SM_INVITEE_COMPLETED).unwrap();
+        handle
+    }
+
+    pub fn build_test_connection_inviter_requested() -> u32 {
+        let handle = build_test_connection_inviter_invited();
+        let msg: A2AMessage = serde_json::from_str(ARIES_CONNECTION_REQUEST).unwrap();
+        update_state_with_message(handle, msg).unwrap();
+        handle
+    }
+
+    pub fn create_connected_connections(consumer_handle: Option<u32>, institution_handle: Option<u32>) -> (u32, u32) {
+        debug!("Institution is going to create connection.");
+        utils::devsetup::set_institution(institution_handle);
+        let institution_to_consumer = create_connection("consumer").unwrap();
+        let _my_public_did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap();
+        let details = connect(institution_to_consumer).unwrap().unwrap();
+        // update_state(institution_to_consumer).unwrap();
+
+        utils::devsetup::set_consumer(consumer_handle);
+        debug!("Consumer is going to accept connection invitation.");
+        let consumer_to_institution = create_connection_with_invite("institution", &details).unwrap();
+        connect(consumer_to_institution).unwrap();
+        update_state(consumer_to_institution).unwrap();
+
+        debug!("Institution is going to process connection request.");
+        utils::devsetup::set_institution(institution_handle);
+        thread::sleep(Duration::from_millis(500));
+        update_state(institution_to_consumer).unwrap();
+        assert_eq!(VcxStateType::VcxStateRequestReceived as u32, get_state(institution_to_consumer));
+
+        debug!("Consumer is going to complete the connection protocol.");
+        utils::devsetup::set_consumer(consumer_handle);
+        update_state(consumer_to_institution).unwrap();
+        assert_eq!(VcxStateType::VcxStateAccepted as u32, get_state(consumer_to_institution));
+
+        debug!("Institution is going to complete the connection protocol.");
+        utils::devsetup::set_institution(institution_handle);
+        thread::sleep(Duration::from_millis(500));
+        update_state(institution_to_consumer).unwrap();
+        assert_eq!(VcxStateType::VcxStateAccepted as u32, get_state(institution_to_consumer));
+
+        (consumer_to_institution, institution_to_consumer)
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_preview_invitation() {
+        let _setup = SetupMocks::init();
+
+        let invitation = preview_invitation("test_preview_invitation", "Faber College").unwrap();
+        assert_eq!(invitation.label, "Faber College");
+        assert_eq!(preview_invitation("test_preview_invitation", "").unwrap().label, "test_preview_invitation");
+
+        // keys are generated in memory, not taken from the wallet
+        assert_eq!(invitation.recipient_keys.len(), 1);
+        assert_ne!(invitation.recipient_keys[0], constants::VERKEY);
+
+        // shaped as the invitation published by connect
+        let handle = create_connection("test_preview_invitation").unwrap();
+        connect(handle).unwrap();
+        let published: InvitationV3 = serde_json::from_str(&get_invite_details(handle).unwrap()).unwrap();
+        assert_eq!(invitation.service_endpoint, published.service_endpoint);
+        assert_eq!(invitation.routing_keys.len(), published.routing_keys.len());
+        assert_eq!(invitation.routing_keys.last(), published.routing_keys.last());
+        assert_ne!(invitation.routing_keys[0], published.routing_keys[0]);
+
+        let json = serde_json::to_value(&invitation.to_a2a_message()).unwrap();
+        assert!(json["@type"].as_str().unwrap().ends_with("connections/1.0/invitation"));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_create_connection() {
+        let _setup = SetupMocks::init();
+
+        let handle = create_connection("test_create_connection").unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateInitialized as u32);
+
+
+        connect(handle).unwrap();
+        assert_eq!(get_pw_did(handle).unwrap(), constants::DID);
+        assert_eq!(get_pw_verkey(handle).unwrap(), constants::VERKEY);
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(ARIES_CONNECTION_REQUEST);
+        update_state(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateRequestReceived as u32);
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(ARIES_CONNECTION_ACK);
+        update_state(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::DELETE_CONNECTION_DECRYPTED_RESPONSE);
+        assert_eq!(delete_connection(handle).unwrap(), 0);
+
+        // This errors b/c we release handle in delete connection
+        assert!(release(handle).is_err());
+    }
+
+    struct ChatHandler {
+        received: Mutex<Vec<(u32, String)>>,
+    }
+
+    impl MessageHandler for ChatHandler {
+        fn handle(&self, connection_handle: u32, message: &A2AMessage) -> VcxResult<HandlerResult> {
+            match message {
+                A2AMessage::BasicMessage(message) if message.content == "chat: hello" => {
+                    self.received.lock().unwrap().push((connection_handle, message.content.clone()));
+                    Ok(HandlerResult::Handled)
+                }
+                _ => Ok(HandlerResult::Passed)
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_dispatches_message_to_registered_handler() {
+        let _setup = SetupMocks::init();
+
+        let chat = Arc::new(ChatHandler { received: Mutex::new(vec![]) });
+        let handler_id = message_handler::register_message_handler(chat.clone()).unwrap();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let basic_message = BasicMessage::create().set_content(String::from("chat: hello"));
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&serde_json::to_string(&basic_message.to_a2a_message()).unwrap());
+        update_state(handle).unwrap();
+
+        assert_eq!(*chat.received.lock().unwrap(), vec![(handle, String::from("chat: hello"))]);
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+
+        message_handler::unregister_message_handler(handler_id).unwrap();
+        assert_eq!(message_handler::unregister_message_handler(handler_id).unwrap_err().kind(), VcxErrorKind::InvalidHandle);
+    }
+
+    struct FailingHandler;
+
+    impl MessageHandler for FailingHandler {
+        fn handle(&self, _connection_handle: u32, message: &A2AMessage) -> VcxResult<HandlerResult> {
+            match message {
+                A2AMessage::BasicMessage(message) if message.content.starts_with("chat:") => {
+                    Err(VcxError::from_msg(VcxErrorKind::IOError, "Chat subsystem is down"))
+                }
+                _ => Ok(HandlerResult::Passed)
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_isolates_failing_handler() {
+        let _setup = SetupMocks::init();
+
+        let failing_id = message_handler::register_message_handler(Arc::new(FailingHandler)).unwrap();
+        let chat = Arc::new(ChatHandler { received: Mutex::new(vec![]) });
+        let chat_id = message_handler::register_message_handler(chat.clone()).unwrap();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let basic_message = BasicMessage::create().set_content(String::from("chat: hello"));
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&serde_json::to_string(&basic_message.to_a2a_message()).unwrap());
+        update_state(handle).unwrap();
+        message_handler::unregister_message_handler(failing_id).unwrap();
+        message_handler::unregister_message_handler(chat_id).unwrap();
+
+        // handlers are global, messages of concurrently running tests may be offered to them too
+        let received: Vec<_> = chat.received.lock().unwrap().iter().filter(|(received_on, _)| *received_on == handle).cloned().collect();
+        assert_eq!(received, vec![(handle, String::from("chat: hello"))]);
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_subscribe_returns_received_messages() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let basic_message = BasicMessage::create().set_content(String::from("hello"));
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&serde_json::to_string(&basic_message.to_a2a_message()).unwrap());
+        AgencyMockUpdatedMessages::take();
+        let messages = subscribe(handle, Duration::from_secs(10)).unwrap();
+
+        assert_eq!(messages.values().collect::<Vec<_>>(), vec![&basic_message.to_a2a_message()]);
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+
+        // returned messages stay in Received status until the caller processes them
+        assert!(AgencyMockUpdatedMessages::take().is_empty());
+        let uid = messages.keys().next().unwrap().clone();
+        update_message_status(handle, uid.clone()).unwrap();
+        assert_eq!(AgencyMockUpdatedMessages::take(), vec![(MessageStatusCode::Reviewed, uid)]);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_subscribe_passes_messages_through_connection_protocol() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let ping = Ping::create().request_response();
+
+        transport.queue_messages(&get_their_pw_verkey(handle).unwrap(), &[&serde_json::to_string(&ping.to_a2a_message()).unwrap()]);
+        AgencyMockUpdatedMessages::take();
+        let messages = subscribe(handle, Duration::from_secs(10)).unwrap();
+
+        // ping was answered by the connection protocol and marked Reviewed
+        assert!(messages.is_empty());
+        let sent: Value = serde_json::from_slice(&transport.sent_messages().last().unwrap().body).unwrap();
+        assert_eq!(sent["~thread"]["thid"], ping.id.0);
+        let updated = AgencyMockUpdatedMessages::take();
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].0, MessageStatusCode::Reviewed);
+
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_ignores_message_of_unknown_type() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(r#"{"@id":"abc","@type":"did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/some-future-protocol/2.0/do-something"}"#);
+        AgencyMockUpdatedMessages::take();
+        update_state(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+        assert!(AgencyMockUpdatedMessages::take().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_marks_message_of_unknown_type_processed_if_enabled() {
+        let _setup = SetupMocks::init();
+        settings::set_config_value(settings::CONFIG_MARK_UNKNOWN_MESSAGES_PROCESSED, "true");
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(r#"{"@id":"abc","@type":"did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/some-future-protocol/2.0/do-something"}"#);
+        AgencyMockUpdatedMessages::take();
+        update_state(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+
+        let updated = AgencyMockUpdatedMessages::take();
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].0, settings::get_processed_message_status());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_serialization_is_deterministic() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let serialized = to_string(handle).unwrap();
+        assert_eq!(serialized.as_bytes(), to_string(handle).unwrap().as_bytes());
+
+        // deserialized copy of the connection serializes identically
+        let copy = from_string(&serialized).unwrap();
+        assert_eq!(serialized.as_bytes(), to_string(copy).unwrap().as_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_patch_changes_only_source_id() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let mut expected: Value = serde_json::from_str(&to_string(handle).unwrap()).unwrap();
+
+        patch(handle, &json!({"source_id": "renamed"}).to_string()).unwrap();
+        expected["source_id"] = json!("renamed");
+        assert_eq!(serde_json::from_str::<Value>(&to_string(handle).unwrap()).unwrap(), expected);
+        assert_eq!(get_source_id(handle).unwrap(), "renamed");
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+
+        set_label(handle, "relabeled").unwrap();
+        assert_eq!(get_source_id(handle).unwrap(), "relabeled");
+
+        // invalid patch leaves the connection unchanged
+        let serialized = to_string(handle).unwrap();
+        assert_eq!(patch(handle, &json!({"version": "2.0"}).to_string()).unwrap_err().kind(), VcxErrorKind::InvalidJson);
+        assert_eq!(patch(handle, "not json").unwrap_err().kind(), VcxErrorKind::InvalidJson);
+        assert_eq!(to_string(handle).unwrap(), serialized);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_export_import_bundle() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let extra = json!({"alias": "Faber", "tags": ["work"]});
+
+        let bundle = export_bundle(handle, extra.clone()).unwrap();
+        let bundle_json: Value = serde_json::from_str(&bundle).unwrap();
+        assert_eq!(bundle_json["version"], "1.0");
+
+        let (imported_handle, imported_extra) = import_bundle(&bundle).unwrap();
+        assert_eq!(imported_extra, extra);
+        assert_eq!(get_invitation_type(imported_handle).unwrap(), InvitationType::ContactImport);
+
+        // apart from its origin, imported connection is the same as the exported one
+        let mut imported: Value = serde_json::from_str(&to_string(imported_handle).unwrap()).unwrap();
+        imported["data"].as_object_mut().unwrap().remove("invitation_type");
+        let exported: Value = serde_json::from_str(&to_string(handle).unwrap()).unwrap();
+        assert_eq!(imported, exported);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_import_bundle_fails_for_unknown_version() {
+        let _setup = SetupMocks::init();
+
+        let bundle = json!({"version": "99.0", "connection": "", "extra": null}).to_string();
+        assert_eq!(import_bundle(&bundle).unwrap_err().kind(), VcxErrorKind::InvalidJson);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_send_ping_and_discovery_features_return_message_id() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+
+        assert_eq!(send_ping(handle, None).unwrap(), Some(MessageId::id().0));
+        assert_eq!(send_discovery_features(handle, None, None).unwrap(), MessageId::id().0);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_send_ping_returns_none_if_ping_was_ignored() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITEE_REQUESTED).unwrap();
+        assert_eq!(send_ping(handle, None).unwrap(), None);
+
+        let handle = from_string(CONNECTION_SM_INVITEE_COMPLETED).unwrap();
+        assert_eq!(send_ping(handle, None).unwrap(), Some(MessageId::id().0));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_confirm_mutual_completion() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let ping_response = PingResponse::create().set_thread_id(&MessageId::id().0);
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&serde_json::to_string(&ping_response.to_a2a_message()).unwrap());
+        assert!(confirm_mutual_completion(handle, Duration::from_secs(10)).unwrap());
+
+        assert!(!confirm_mutual_completion(handle, Duration::from_millis(300)).unwrap());
+
+        let not_completed = create_connection("test_confirm_mutual_completion").unwrap();
+        assert!(!confirm_mutual_completion(not_completed, Duration::from_secs(10)).unwrap());
+
+        assert_eq!(confirm_mutual_completion(0, Duration::from_secs(10)).unwrap_err().kind(), VcxErrorKind::InvalidConnectionHandle);
+    }
+
+    fn _get_messages_response(count: usize) -> String {
+        let mut response: Value = serde_json::from_str(constants::GET_MESSAGES_DECRYPTED_RESPONSE).unwrap();
+        let message = response["msgs"][0].clone();
+        response["msgs"] = json!((0..count).map(|index| {
+            let mut message = message.clone();
+            message["uid"] = json!(format!("uid-{}", index));
+            message
+        }).collect::<Vec<Value>>());
+        response.to_string()
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_reports_remaining_messages() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let ping = json!(Ping::create().set_comment(Some(String::from("burst"))).to_a2a_message()).to_string();
+
+        // agency keeps returning messages which were not handled yet
+        for queued in (1..=3).rev() {
+            AgencyMockDecrypted::set_next_decrypted_response(&_get_messages_response(queued));
+            for _ in 0..queued {
+                AgencyMockDecrypted::set_next_decrypted_message(&ping);
+            }
+            assert_eq!(update_state_with_remaining(handle).unwrap(), queued as u32 - 1);
+        }
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_send_problem_report_for_protocol_families() {
+        let _setup = SetupMocks::init();
+
+        let present_proof = json!(_build_problem_report("present-proof", "invalid_presentation", Some(String::from("bad proof")), "thread-1").unwrap());
+        assert_eq!(present_proof["@type"], "did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/present-proof/1.0/problem-report");
+        assert_eq!(present_proof["description"]["code"], "invalid_presentation");
+        assert_eq!(present_proof["comment"], "bad proof");
+        assert_eq!(present_proof["~thread"]["thid"], "thread-1");
+
+        let connections = json!(_build_problem_report("connections", "request_not_accepted", None, "thread-2").unwrap());
+        assert_eq!(connections["@type"], "did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/connections/1.0/problem_report");
+        assert_eq!(connections["problem-code"], "request_not_accepted");
+        assert_eq!(connections["~thread"]["thid"], "thread-2");
+
+        assert_eq!(_build_problem_report("connections", "no_such_code", None, "thread-2").unwrap_err().kind(), VcxErrorKind::InvalidOption);
+        assert_eq!(_build_problem_report("present-proof", "", None, "thread-1").unwrap_err().kind(), VcxErrorKind::InvalidOption);
+        assert_eq!(_build_problem_report("no-such-protocol", "code", None, "thread-3").unwrap_err().kind(), VcxErrorKind::InvalidOption);
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        send_problem_report(handle, "issue-credential", "issuance_abandoned", None, "thread-4").unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_validate_keys_detects_keys_missing_in_wallet() {
+        let _setup = SetupLibraryWallet::init();
+
+        // keys of the serialized connection were created in another wallet
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        assert_eq!(validate_keys(handle).unwrap(), false);
+        settings::set_config_value(settings::CONFIG_VALIDATE_CONNECTION_KEYS, "true");
+        assert_eq!(from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap_err().kind(), VcxErrorKind::WalletRecordNotFound);
+
+        let (pw_did, pw_vk) = create_and_store_my_did(None, None).unwrap();
+        let restored = CONNECTION_SM_INVITER_COMPLETED
+            .replace("2ZHFFhzA2XtTD6hJqzL7ux", &pw_did)
+            .replace("rCw3x5h1jS6gPo7rRrt3EYbXXe5nNjnGbdf1jAwUxuj", &pw_vk);
+        let handle = from_string(&restored).unwrap();
+        assert_eq!(validate_keys(handle).unwrap(), true);
+        settings::set_config_value(settings::CONFIG_VALIDATE_CONNECTION_KEYS, "false");
+
+        assert_eq!(validate_keys(create_connection("no_keys_yet").unwrap()).unwrap(), true);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_for_received_statuses_only() {
+        let _setup = SetupMocks::init();
+
+        let handle = create_connection("test_update_state_for_received_statuses_only").unwrap();
+        connect(handle).unwrap();
+
+        assert_eq!(update_state_for_statuses(handle, vec![]).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(ARIES_CONNECTION_REQUEST);
+        update_state_for_statuses(handle, vec![MessageStatusCode::Received]).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateRequestReceived as u32);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_for_statuses_reprocesses_reviewed_messages() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        let handle = create_connection("test_update_state_for_statuses_reprocesses_reviewed_messages").unwrap();
+        connect(handle).unwrap();
+        // agency requests are not encrypted in test mode
+        let status_queries = || -> Vec<String> {
+            transport.sent_messages().iter()
+                .map(|message| String::from_utf8(message.body.clone()).unwrap())
+                .filter(|query| query.contains("statusCodes"))
+                .collect()
+        };
+
+        transport.queue_messages("", &[]);
+        update_state(handle).unwrap();
+        assert!(status_queries().last().unwrap().contains(r#""statusCodes":["MS-103"]"#));
+        assert_eq!(get_state(handle), VcxStateType::VcxStateOfferSent as u32);
+
+        // request was already reviewed, it's picked up only once Reviewed status is asked for
+        transport.queue_agency_response(&json!({
+            "@type": "did:sov:123456789abcdefghi1234;spec/pairwise/1.0/MSGS",
+            "msgs": [{
+                "statusCode": "MS-106",
+                "uid": "reviewed-uid",
+                "senderDID": "",
+                "type": "aries",
+                "payload": { "message": ARIES_CONNECTION_REQUEST, "sender_verkey": "" }
+            }]
+        }).to_string());
+        update_state_for_statuses(handle, vec![MessageStatusCode::Reviewed]).unwrap();
+        assert!(status_queries().last().unwrap().contains(r#""statusCodes":["MS-106"]"#));
+        assert_eq!(get_state(handle), VcxStateType::VcxStateRequestReceived as u32);
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_applies_processed_message_status() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        let handle = create_connection("test_update_state_applies_processed_message_status").unwrap();
+        connect(handle).unwrap();
+        // agency requests are not encrypted in test mode
+        let last_status_update = || -> String {
+            transport.sent_messages().iter()
+                .map(|message| String::from_utf8(message.body.clone()).unwrap())
+                .filter(|update| update.contains("uidsByConns"))
+                .last()
+                .unwrap()
+        };
+
+        let uids = transport.queue_messages("", &[ARIES_CONNECTION_REQUEST]);
+        update_state(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateRequestReceived as u32);
+        assert!(last_status_update().contains(r#""statusCode":"MS-106""#));
+        assert!(last_status_update().contains(&uids[0]));
+
+        settings::set_config_value(settings::CONFIG_PROCESSED_MESSAGE_STATUS, "MS-105");
+        let uids = transport.queue_messages(&get_their_pw_verkey(handle).unwrap(), &[ARIES_CONNECTION_ACK]);
+        update_state(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+        assert!(last_status_update().contains(r#""statusCode":"MS-105""#));
+        assert!(last_status_update().contains(&uids[0]));
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_receiver_connection_receives_messages() {
+        let _setup = SetupMocks::init();
+
+        let handle = create_receiver("test_receiver_connection_receives_messages").unwrap();
+        let did_doc: DidDoc = serde_json::from_str(&get_service_did_doc(handle).unwrap()).unwrap();
+        assert_eq!(did_doc.id, get_pw_did(handle).unwrap());
+        assert_eq!(did_doc.recipient_keys(), vec![get_pw_verkey(handle).unwrap()]);
+        assert_eq!(connect(handle).unwrap_err().kind(), VcxErrorKind::ActionNotSupported);
+
+        let notification = RevocationNotification::create().set_credential_thread_id("cred_thread_id").to_a2a_message();
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&serde_json::to_string(&notification).unwrap());
+        update_state(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateInitialized as u32);
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&serde_json::to_string(&notification).unwrap());
+        let messages = get_messages(handle).unwrap();
+        assert_eq!(messages.into_iter().map(|(_, message)| message).collect::<Vec<A2AMessage>>(), vec![notification]);
+
+        let inviter = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        assert_eq!(get_service_did_doc(inviter).unwrap_err().kind(), VcxErrorKind::ActionNotSupported);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_local_did_doc_with_custom_service_is_published_in_invitation() {
+        let _setup = SetupMocks::init();
+
+        let handle = create_connection("test_local_did_doc").unwrap();
+        let mut did_doc: DidDoc = serde_json::from_str(&get_local_did_doc(handle).unwrap()).unwrap();
+        assert_eq!(did_doc.id, get_pw_did(handle).unwrap());
+        assert_eq!(did_doc.recipient_keys(), vec![get_pw_verkey(handle).unwrap()]);
+
+        let mut custom_service = did_doc.service[0].clone();
+        custom_service.id = String::from("did:example:123456789abcdefghi;custom");
+        custom_service.type_ = String::from("CustomProtocol");
+        custom_service.service_endpoint = String::from("https://custom.example.org/endpoint");
+        did_doc.service.push(custom_service);
+        set_local_did_doc(handle, &serde_json::to_string(&did_doc).unwrap()).unwrap();
+
+        let mut invalid_did_doc = did_doc.clone();
+        invalid_did_doc.service[1].service_endpoint = String::from("not an url");
+        assert_eq!(set_local_did_doc(handle, &serde_json::to_string(&invalid_did_doc).unwrap()).unwrap_err().kind(), VcxErrorKind::InvalidJson);
+
+        // RFC 0160 invitation has no room for other services, they are published in out-of-band invitation
+        let invitation = connect(handle).unwrap().unwrap();
+        assert_eq!(invitation, get_invite_details(handle).unwrap());
+        let oob_invitation: OutOfBandInvitation = serde_json::from_str(&invitation).unwrap();
+        assert_eq!(oob_invitation.type_, OutOfBandInvitation::type_());
+        assert_eq!(oob_invitation.services.len(), 2);
+        match (&oob_invitation.services[0], &oob_invitation.services[1]) {
+            (OutOfBandService::Inline(primary_service), OutOfBandService::Inline(custom_service)) => {
+                assert_eq!(primary_service.recipient_keys, vec![get_pw_verkey(handle).unwrap()]);
+                assert_eq!(primary_service.service_endpoint, did_doc.get_endpoint());
+                assert_eq!(custom_service.type_, "CustomProtocol");
+                assert_eq!(custom_service.service_endpoint, "https://custom.example.org/endpoint");
+                assert_eq!(custom_service.recipient_keys, vec![get_pw_verkey(handle).unwrap()]);
+            }
+            services => panic!("Unexpected services {:?}", services)
+        }
+
+        // invitee connects through the primary service
+        let invitee = create_connection_with_invite("invitee", &invitation).unwrap();
+        assert_eq!(get_state(invitee), VcxStateType::VcxStateOfferSent as u32);
+
+        assert_eq!(get_local_did_doc(handle).unwrap_err().kind(), VcxErrorKind::InvalidState);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_local_did_doc_with_custom_primary_service_is_published_in_invitation() {
+        let _setup = SetupMocks::init();
+
+        let handle = create_connection("test_local_did_doc").unwrap();
+        let mut did_doc: DidDoc = serde_json::from_str(&get_local_did_doc(handle).unwrap()).unwrap();
+        did_doc.service[0].service_endpoint = String::from("https://mediator.example.org/endpoint");
+        did_doc.service[0].routing_keys = vec![String::from("9NnKFUZoYcCqYC2PcaXH3cnaGsoRfyGgyEHbvbLJYh8j")];
+        set_local_did_doc(handle, &serde_json::to_string(&did_doc).unwrap()).unwrap();
+
+        let invitation: InvitationV3 = serde_json::from_str(&connect(handle).unwrap().unwrap()).unwrap();
+        assert_eq!(invitation.service_endpoint, "https://mediator.example.org/endpoint");
+        assert_eq!(invitation.recipient_keys, vec![get_pw_verkey(handle).unwrap()]);
+        assert_eq!(invitation.routing_keys, vec![String::from("9NnKFUZoYcCqYC2PcaXH3cnaGsoRfyGgyEHbvbLJYh8j")]);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_create_with_oob_invite_with_attached_offer() {
+        let _setup = SetupMocks::init();
+
+        let oob_invitation = _oob_invitation_with_offer();
+        let handle = create_connection_with_invite("alice", &serde_json::to_string(&oob_invitation).unwrap()).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateOfferSent as u32);
+
+        let attached_request = get_attached_request(handle).unwrap();
+        assert_eq!(attached_request, Some(A2AMessage::CredentialOffer(_credential_offer())));
+
+        let handle = create_connection_with_invite("alice", ARIES_CONNECTION_INVITATION).unwrap();
+        assert_eq!(get_attached_request(handle).unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_create_with_oob_invite_rejects_invalid_invitation() {
+        let _setup = SetupMocks::init();
+
+        let mut oob_invitation = _oob_invitation_with_offer();
+        oob_invitation.type_ = String::from("https://didcomm.org/out-of-band/1.0/handshake-reuse");
+        let res = create_connection_with_invite("alice", &json!(oob_invitation).to_string());
+        assert_eq!(res.unwrap_err().kind(), VcxErrorKind::InvalidJson);
+
+        let mut oob_invitation = _oob_invitation_with_offer();
+        oob_invitation.handshake_protocols = vec![String::from("https://didcomm.org/didexchange/1.0")];
+        let res = create_connection_with_invite("alice", &json!(oob_invitation).to_string());
+        assert_eq!(res.unwrap_err().kind(), VcxErrorKind::ActionNotSupported);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_message_counts() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        let counts = get_message_counts(handle).unwrap();
+        assert_eq!(counts.get(&MessageStatusCode::Received), Some(&1));
+        assert_eq!(counts.get(&MessageStatusCode::Reviewed), Some(&0));
+        assert_eq!(counts.get(&MessageStatusCode::Rejected), Some(&0));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_establishment_latency_with_fake_clock() {
+        let _setup = SetupMocks::init();
+        let clock = SetupFakeClock::init(1_600_000_000);
+
+        let handle = create_connection("test_establishment_latency_with_fake_clock").unwrap();
+        connect(handle).unwrap();
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(ARIES_CONNECTION_REQUEST);
+        update_state(handle).unwrap();
+
+        clock.clock.advance(Duration::from_millis(1500));
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(ARIES_CONNECTION_ACK);
+        update_state(handle).unwrap();
+
+        assert_eq!(get_establishment_latency(handle).unwrap(), Some(Duration::from_millis(1500)));
+
+        // timestamps are kept by the connection, they are serialized alongside its agent info as before
+        let serialized: Value = serde_json::from_str(&to_string(handle).unwrap()).unwrap();
+        let connect_time = serialized["data"]["connect_time"].as_u64().unwrap();
+        assert_eq!(serialized["data"]["established_time"].as_u64().unwrap(), connect_time + 1500);
+        assert_eq!(serialized["data"]["pw_did"], json!(get_pw_did(handle).unwrap()));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_connection_flow_through_mock_transport() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        let handle = create_connection("test_connection_flow_through_mock_transport").unwrap();
+        connect(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateOfferSent as u32);
+
+        // sender of the request is not known yet
+        transport.queue_messages("", &[ARIES_CONNECTION_REQUEST]);
+        update_state(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateRequestReceived as u32);
+
+        let their_endpoint = get_connection_info_typed(handle).unwrap().their.unwrap().service_endpoint;
+        assert_eq!(transport.sent_to(&their_endpoint).len(), 1);
+
+        transport.queue_messages(&get_their_pw_verkey(handle).unwrap(), &[ARIES_CONNECTION_ACK]);
+        update_state(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+
+        transport.queue_response(Ok((202, vec![])));
+        assert_eq!(send_message_with_receipt(handle, A2AMessage::CredentialOffer(_credential_offer())).unwrap(), DeliveryReceipt::Queued);
+        assert_eq!(transport.sent_to(&their_endpoint).len(), 2);
+
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_answers_discovery_query_with_supported_protocols() {
+        let _setup = SetupMocks::init();
+        settings::set_config_value(settings::CONFIG_SUPPORTED_PROTOCOLS, &json!(["connections", "trust_ping"]).to_string());
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let their_endpoint = get_connection_info_typed(handle).unwrap().their.unwrap().service_endpoint;
+
+        let query = Query::create().set_query(Some(String::from("*")));
+        transport.queue_messages(&get_their_pw_verkey(handle).unwrap(), &[&json!(query.to_a2a_message()).to_string()]);
+        update_state(handle).unwrap();
+        httpclient::set_mock_transport(None);
+
+        // messages are not encrypted in test mode
+        let sent = transport.sent_to(&their_endpoint);
+        assert_eq!(sent.len(), 1);
+        let disclose = String::from_utf8(sent[0].body.clone()).unwrap();
+        assert!(disclose.contains("discover-features/1.0/disclose"));
+        assert!(disclose.contains(&MessageFamilies::Connections.id()));
+        assert!(disclose.contains(&MessageFamilies::TrustPing.id()));
+        assert!(!disclose.contains(&MessageFamilies::Basicmessage.id()));
+        assert!(disclose.contains(&format!(r#""thid":"{}""#, query.id.0)));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_ping_left_for_application_with_auto_respond_disabled() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let their_endpoint = get_connection_info_typed(handle).unwrap().their.unwrap().service_endpoint;
+        let ping = json!(Ping::create().request_response().to_a2a_message()).to_string();
+
+        let their_vk = get_their_pw_verkey(handle).unwrap();
+
+        settings::set_config_value(settings::CONFIG_AUTO_RESPOND_TO_PING, "false");
+        transport.queue_messages(&their_vk, &[&ping]);
+        assert_eq!(update_state_with_remaining(handle).unwrap(), 0);
+        assert!(transport.sent_to(&their_endpoint).is_empty());
+
+        transport.queue_messages(&their_vk, &[&ping]);
+        let messages = get_messages(handle).unwrap();
+        assert!(messages.values().any(|message| match message {
+            A2AMessage::Ping(received) => received.response_requested,
+            _ => false
+        }));
+
+        // default answers the ping
+        settings::set_config_value(settings::CONFIG_AUTO_RESPOND_TO_PING, "true");
+        transport.queue_messages(&their_vk, &[&ping]);
+        update_state(handle).unwrap();
+        assert_eq!(transport.sent_to(&their_endpoint).len(), 1);
+
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_resend_request_keeps_request_thread() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        let handle = from_string(CONNECTION_SM_INVITEE_REQUESTED).unwrap();
+        let pw_did = get_pw_did(handle).unwrap();
+        let their_endpoint = get_connection_info_typed(handle).unwrap().their.unwrap().service_endpoint;
+
+        assert_eq!(resend_request(handle).unwrap(), "8b58c65b-a585-4976-99e1-f9570a4bd097");
+        assert_eq!(resend_request(handle).unwrap(), "8b58c65b-a585-4976-99e1-f9570a4bd097");
+        assert_eq!(transport.sent_to(&their_endpoint).len(), 2);
+        assert_eq!(get_pw_did(handle).unwrap(), pw_did);
+        assert_eq!(get_state(handle), VcxStateType::VcxStateRequestReceived as u32);
+
+        let completed = from_string(CONNECTION_SM_INVITEE_COMPLETED).unwrap();
+        assert_eq!(resend_request(completed).unwrap_err().kind(), VcxErrorKind::InvalidState);
+        let inviter = build_test_connection_inviter_invited();
+        assert_eq!(resend_request(inviter).unwrap_err().kind(), VcxErrorKind::InvalidState);
+
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_routing_keys_of_multi_hop_did_doc() {
+        let _setup = SetupMocks::init();
+
+        let handle = build_test_connection_inviter_invited();
+        assert_eq!(get_routing_keys(handle).unwrap_err().kind(), VcxErrorKind::NotReady);
+
+        let mut request = match serde_json::from_str(ARIES_CONNECTION_REQUEST).unwrap() {
+            A2AMessage::ConnectionRequest(request) => request,
+            _ => panic!("Expected connection request")
+        };
+        let routing_keys = vec![String::from("MediatorInnerKey111111111111111111111111111"),
+                                String::from("MediatorOuterKey111111111111111111111111111")];
+        request.connection.did_doc.service[0].routing_keys = routing_keys.clone();
+        update_state_with_message(handle, request.to_a2a_message()).unwrap();
+
+        assert_eq!(get_routing_keys(handle).unwrap(), routing_keys);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_download_uses_configured_page_size() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+        let handle = build_test_connection_inviter_requested();
+        let their_vk = get_their_pw_verkey(handle).unwrap();
+
+        transport.queue_messages(&their_vk, &[ARIES_CONNECTION_REQUEST]);
+        get_messages(handle).unwrap();
+        // agency requests are not encrypted in test mode
+        let query = String::from_utf8(transport.sent_messages().last().unwrap().body.clone()).unwrap();
+        assert!(!query.contains("pageSize"));
+
+        agency_settings::process_agency_config_string(&json!({"agency_download_page_size": "2"}).to_string(), true).unwrap();
+        // uids are listed first and then downloaded by pages
+        transport.queue_agency_response(&json!({
+            "@type": "did:sov:123456789abcdefghi1234;spec/pairwise/1.0/MSGS",
+            "msgs": [
+                {"statusCode": "MS-103", "uid": "uid-1", "senderDID": "", "type": "aries"},
+                {"statusCode": "MS-103", "uid": "uid-2", "senderDID": "", "type": "aries"},
+                {"statusCode": "MS-103", "uid": "uid-3", "senderDID": "", "type": "aries"}
+            ]
+        }).to_string());
+        transport.queue_messages(&their_vk, &[ARIES_CONNECTION_REQUEST, ARIES_CONNECTION_REQUEST]);
+        transport.queue_messages(&their_vk, &[ARIES_CONNECTION_REQUEST]);
+        assert_eq!(get_messages(handle).unwrap().len(), 3);
+        let queries: Vec<String> = transport.sent_messages().iter().rev().take(3).rev()
+            .map(|message| String::from_utf8(message.body.clone()).unwrap())
+            .collect();
+        assert!(queries[0].contains(r#""excludePayload":"Y""#));
+        assert!(queries[1].contains(r#""uids":["uid-1","uid-2"]"#));
+        assert!(queries[2].contains(r#""uids":["uid-3"]"#));
+        assert!(queries.iter().all(|query| !query.contains("pageSize")));
+
+        assert_eq!(agency_settings::process_agency_config_string(&json!({"agency_download_page_size": "0"}).to_string(), true).unwrap_err().kind(),
+                   agency_client::error::AgencyClientErrorKind::InvalidConfiguration);
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_verify_endpoint_reachable() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        let handle = create_connection("test_verify_endpoint_reachable").unwrap();
+        let did_doc: DidDoc = serde_json::from_str(&get_local_did_doc(handle).unwrap()).unwrap();
+        let endpoint = did_doc.get_endpoint();
+
+        // the ping comes back through the agency
+        let sent_before = transport.sent_messages().len();
+        // the first response answers posting of the ping to the endpoint
+        transport.queue_response(Ok((200, vec![])));
+        transport.queue_messages("", &[&json!(Ping::create().to_a2a_message()).to_string()]);
+        assert_eq!(verify_endpoint_reachable(handle, Duration::from_secs(5)).unwrap(), true);
+        assert_eq!(transport.sent_messages()[sent_before].url, endpoint);
+
+        // the ping doesn't come back in time
+        transport.queue_response(Ok((200, vec![])));
+        transport.queue_messages("", &[&json!(BasicMessage::create().set_content(String::from("other")).to_a2a_message()).to_string()]);
+        assert_eq!(verify_endpoint_reachable(handle, Duration::from_millis(0)).unwrap(), false);
+
+        // the endpoint rejects the ping
+        transport.queue_response(Ok((502, vec![])));
+        assert_eq!(verify_endpoint_reachable(handle, Duration::from_secs(5)).unwrap(), false);
+
+        // the check doesn't start connecting
+        assert_eq!(get_state(handle), VcxStateType::VcxStateInitialized as u32);
+
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_agentless_connection_flow_through_mock_transport() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+        let relay_endpoint = "http://relay.local/faber";
+
+        let handle = create_agentless("test_agentless_connection_flow", relay_endpoint).unwrap();
+        connect(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateOfferSent as u32);
+        assert!(transport.sent_messages().is_empty());
+
+        let invitation: InvitationV3 = serde_json::from_str(&get_invite_details(handle).unwrap()).unwrap();
+        assert_eq!(invitation.service_endpoint, relay_endpoint);
+        assert!(invitation.routing_keys.is_empty());
+
+        update_state(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateOfferSent as u32);
+        assert!(transport.sent_messages().is_empty());
+
+        update_state_with_message(handle, serde_json::from_str(ARIES_CONNECTION_REQUEST).unwrap()).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateRequestReceived as u32);
+
+        let info = get_connection_info_typed(handle).unwrap();
+        assert_eq!(info.my.service_endpoint, relay_endpoint);
+        assert!(info.my.routing_keys.is_empty());
+        let their_endpoint = info.their.unwrap().service_endpoint;
+        assert_eq!(transport.sent_messages().len(), 1);
+        assert_eq!(transport.sent_to(&their_endpoint).len(), 1);
+
+        update_state_with_message(handle, serde_json::from_str(ARIES_CONNECTION_ACK).unwrap()).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+
+        delete_connection(handle).unwrap();
+        assert_eq!(transport.sent_messages().len(), 1);
+
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_agentless_invitee_connection_flow_through_mock_transport() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+        let relay_endpoint = "http://relay.local/alice";
+        let inviter_endpoint = "http://localhost:8080/agency/msg";
+        let inviter_vk = "DEKbrMDX9LBGhCk4LBhH6t5B6Kh5iE7GvfepAJYXp7GX";
+
+        let handle = create_agentless_with_invite("test_agentless_invitee_connection_flow", relay_endpoint, ARIES_CONNECTION_INVITATION).unwrap();
+        connect(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateRequestReceived as u32);
+
+        // only the request was sent, straight to the inviter, and it asks for the response at the relay
+        let info = get_connection_info_typed(handle).unwrap();
+        assert_eq!(info.my.service_endpoint, relay_endpoint);
+        assert!(info.my.routing_keys.is_empty());
+        assert_eq!(transport.sent_messages().len(), 1);
+        let request = String::from_utf8(transport.sent_to(inviter_endpoint)[0].body.clone()).unwrap();
+        assert!(request.contains(relay_endpoint));
+
+        update_state(handle).unwrap();
+        assert_eq!(transport.sent_messages().len(), 1);
+
+        let response = Response::default()
+            .set_service_endpoint(inviter_endpoint.to_string())
+            .set_keys(vec![inviter_vk.to_string()], vec![])
+            .set_thread_id(&MessageId::id().0)
+            .encode(inviter_vk).unwrap();
+        update_state_with_message(handle, response.to_a2a_message()).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+        assert_eq!(transport.sent_messages().len(), 2);
+        assert_eq!(transport.sent_to(inviter_endpoint).len(), 2);
+
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_create_agentless_rejects_invalid_endpoint() {
+        let _setup = SetupMocks::init();
+
+        assert_eq!(create_agentless("test_create_agentless_rejects_invalid_endpoint", "not an url").unwrap_err().kind(), VcxErrorKind::InvalidUrl);
+        assert_eq!(create_agentless_with_invite("test_create_agentless_rejects_invalid_endpoint", "not an url", ARIES_CONNECTION_INVITATION).unwrap_err().kind(), VcxErrorKind::InvalidUrl);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_disclosed_protocols() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        assert_eq!(get_disclosed_protocols(handle).unwrap_err().kind(), VcxErrorKind::NotReady);
+
+        send_discovery_features_query(handle, ProtocolQuery::create(MessageFamilies::PresentProof).set_version("1.*"), None).unwrap();
+
+        let disclose = json!({
+            "@id": "abc",
+            "@type": "did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/discover-features/1.0/disclose",
+            "protocols": [{"pid": "did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/present-proof/1.0", "roles": ["verifier"]}],
+            "~thread": {"thid": "def"}
+        });
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&disclose.to_string());
+        update_state(handle).unwrap();
+
+        let protocols = get_disclosed_protocols(handle).unwrap();
+        assert_eq!(protocols, vec![(String::from("did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/present-proof/1.0"), vec![Actors::Verifier])]);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_present_proof_2_0_exchange_with_peer_disclosing_it() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        assert_eq!(get_present_proof_version(handle).unwrap(), v2::PRESENT_PROOF_V1);
+
+        send_discovery_features_query(handle, ProtocolQuery::create(MessageFamilies::PresentProof), None).unwrap();
+
+        let disclose = json!({
+            "@id": "abc",
+            "@type": "did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/discover-features/1.0/disclose",
+            "protocols": [{"pid": "https://didcomm.org/present-proof/2.0", "roles": ["prover"]}],
+            "~thread": {"thid": "def"}
+        });
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&disclose.to_string());
+        update_state(handle).unwrap();
+        assert_eq!(get_present_proof_version(handle).unwrap(), v2::PRESENT_PROOF_V2);
+
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+        send_present_proof_message(handle, A2AMessage::PresentationRequest(_presentation_request()), &get_present_proof_version(handle).unwrap()).unwrap();
+        let sent: Value = serde_json::from_slice(&transport.sent_messages().last().unwrap().body).unwrap();
+        assert_eq!(sent["@type"], "https://didcomm.org/present-proof/2.0/request-presentation");
+        httpclient::set_mock_transport(None);
+
+        let presentation = v2::to_v2_message(A2AMessage::Presentation(_presentation())).unwrap();
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&serde_json::to_string(&presentation).unwrap());
+        let messages = get_messages(handle).unwrap();
+        assert_eq!(messages.values().collect::<Vec<_>>(), vec![&A2AMessage::Presentation(_presentation())]);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_diagnostics() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+
+        let report: Value = serde_json::from_str(&diagnostics(handle, false, false).unwrap()).unwrap();
+        assert_eq!(report["state"], VcxStateType::VcxStateAccepted as u32);
+        assert_eq!(report["role"], "Inviter");
+        assert_eq!(report["protocolVersion"], "1.0");
+        assert_eq!(report["endpointHost"], "localhost");
+        assert!(report["lastError"].is_null());
+        assert!(report.get("didDoc").is_none());
+        assert!(!report.to_string().contains(&get_their_pw_verkey(handle).unwrap()));
+
+        let report: Value = serde_json::from_str(&diagnostics(handle, true, false).unwrap()).unwrap();
+        assert_eq!(report["didDoc"]["id"], get_their_pw_did(handle).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_signed_response() {
+        let _setup = SetupMocks::init();
+
+        let handle = build_test_connection_inviter_invited();
+        assert_eq!(get_signed_response(handle).unwrap(), None);
+
+        let msg: A2AMessage = serde_json::from_str(ARIES_CONNECTION_REQUEST).unwrap();
+        update_state_with_message(handle, msg).unwrap();
+        let signed_response: Value = serde_json::from_str(&get_signed_response(handle).unwrap().unwrap()).unwrap();
+        assert!(signed_response["signature"].is_string());
+        assert!(signed_response["sig_data"].is_string());
+        assert_eq!(signed_response["signer"], get_invitation_recipient_key(handle).unwrap());
+
+        let restored = from_string(&to_string(handle).unwrap()).unwrap();
+        assert_eq!(get_signed_response(restored).unwrap(), get_signed_response(handle).unwrap());
+
+        let report: Value = serde_json::from_str(&diagnostics(handle, false, false).unwrap()).unwrap();
+        assert!(report.get("signedResponse").is_none());
+        let report: Value = serde_json::from_str(&diagnostics(handle, false, true).unwrap()).unwrap();
+        assert_eq!(report["signedResponse"], signed_response);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_signed_response_of_invitee() {
+        let _setup = SetupMocks::init();
+        let inviter_vk = "DEKbrMDX9LBGhCk4LBhH6t5B6Kh5iE7GvfepAJYXp7GX";
+
+        let handle = create_connection_with_invite("test_get_signed_response_of_invitee", ARIES_CONNECTION_INVITATION).unwrap();
+        connect(handle).unwrap();
+        assert_eq!(get_signed_response(handle).unwrap(), None);
+
+        let response = Response::default()
+            .set_service_endpoint(String::from("http://localhost:8080/agency/msg"))
+            .set_keys(vec![inviter_vk.to_string()], vec![])
+            .set_thread_id(&MessageId::id().0)
+            .encode(inviter_vk).unwrap();
+        update_state_with_message(handle, response.to_a2a_message()).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+
+        // the response is kept as received from the inviter
+        let signed_response: Value = serde_json::from_str(&get_signed_response(handle).unwrap().unwrap()).unwrap();
+        assert_eq!(signed_response, json!(response.connection_sig));
+        assert_eq!(signed_response["signer"], inviter_vk);
+
+        let restored = from_string(&to_string(handle).unwrap()).unwrap();
+        assert_eq!(get_signed_response(restored).unwrap(), get_signed_response(handle).unwrap());
+
+        let report: Value = serde_json::from_str(&diagnostics(handle, false, false).unwrap()).unwrap();
+        assert!(report.get("signedResponse").is_none());
+        let report: Value = serde_json::from_str(&diagnostics(handle, false, true).unwrap()).unwrap();
+        assert_eq!(report["signedResponse"], signed_response);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_diagnostics_records_last_activity() {
+        let _setup = SetupMocks::init();
+        let _clock = SetupFakeClock::init(1_600_000_000);
+
+        let handle = create_connection("alice").unwrap();
+        connect(handle).unwrap();
+
+        let report: Value = serde_json::from_str(&diagnostics(handle, false, false).unwrap()).unwrap();
+        assert_eq!(report["lastActivity"], 1_600_000_000_000u64);
+        assert!(report["lastError"].is_null());
+        assert!(report["endpointHost"].is_null());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_diagnostics_last_error_is_reset_by_successful_step() {
+        let _setup = SetupMocks::init();
+
+        let handle = create_connection("alice").unwrap();
+        httpclient::set_mock_transport(Some(Rc::new(FailingTransport) as Rc<dyn Transport>));
+        connect(handle).unwrap_err();
+        httpclient::set_mock_transport(None);
+
+        let report: Value = serde_json::from_str(&diagnostics(handle, false, false).unwrap()).unwrap();
+        assert!(report["lastError"].is_string());
+
+        connect(handle).unwrap();
+        let report: Value = serde_json::from_str(&diagnostics(handle, false, false).unwrap()).unwrap();
+        assert_eq!(report["state"], VcxStateType::VcxStateOfferSent as u32);
+        assert!(report["lastError"].is_null());
+    }
+
+    fn _message_received_at(created_at: Option<u64>) -> Message {
+        Message {
+            status_code: MessageStatusCode::Received,
+            payload: None,
+            uid: String::from("uid"),
+            ref_msg_id: None,
+            delivery_details: vec![],
+            decrypted_msg: None,
+            created_at,
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_is_received_within() {
+        let window = Some((1000, 2000));
+
+        assert!(is_received_within(&_message_received_at(Some(1000)), window));
+        assert!(is_received_within(&_message_received_at(Some(2000)), window));
+        assert!(!is_received_within(&_message_received_at(Some(999)), window));
+        assert!(!is_received_within(&_message_received_at(Some(2001)), window));
+        assert!(is_received_within(&_message_received_at(Some(2001)), None));
+
+        // received time is unknown, so the message can't be said to fall within the window
+        assert!(!is_received_within(&_message_received_at(None), window));
+        assert!(is_received_within(&_message_received_at(None), None));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_download_messages_within_time_window() {
+        let _setup = SetupMocks::init();
+        let _clock = SetupFakeClock::init(1_600_000_000);
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let mut response: Value = serde_json::from_str(constants::GET_MESSAGES_DECRYPTED_RESPONSE).unwrap();
+        response["msgs"][0]["createdAt"] = json!(1_599_999_500);
+        let response = response.to_string();
+
+        AgencyMockDecrypted::set_next_decrypted_response(&response);
+        AgencyMockDecrypted::set_next_decrypted_message(ARIES_CONNECTION_ACK);
+        let messages = download_messages(vec![handle], None, None, Some((1_599_999_000, 1_600_000_000)), None).unwrap();
+        assert_eq!(messages[0].msgs.len(), 1);
+
+        AgencyMockDecrypted::set_next_decrypted_response(&response);
+        let messages = download_messages(vec![handle], None, None, Some((1_599_999_600, 1_600_000_000)), None).unwrap();
+        assert_eq!(messages[0].msgs.len(), 0);
+
+        // agency didn't report when the message was received, it's not counted as received now
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        let messages = download_messages(vec![handle], None, None, Some((1_599_999_000, 1_600_000_000)), None).unwrap();
+        assert_eq!(messages[0].msgs.len(), 0);
+    }
+
+    struct SlowTransport;
+
+    impl Transport for SlowTransport {
+        fn post(&self, _body_content: &[u8], _url: &str) -> AgencyClientResult<(u16, Vec<u8>)> {
+            thread::sleep(Duration::from_millis(200));
+            httpclient::mocked_response()
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_download_messages_is_cancelled() {
+        let _setup = SetupMocks::init();
+        httpclient::set_mock_transport(Some(Rc::new(SlowTransport) as Rc<dyn Transport>));
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+
+        let token = CancellationToken::new();
+        let ui_token = token.clone();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            ui_token.cancel();
+        });
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(ARIES_CONNECTION_ACK);
+        let err = download_messages(vec![handle], None, None, None, Some(&token)).unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::Cancelled);
+        canceller.join().unwrap();
+
+        assert_eq!(download_messages(vec![handle], None, None, None, Some(&token)).unwrap_err().kind(), VcxErrorKind::Cancelled);
+        assert_eq!(update_state_cancellable(handle, Some(&token)).unwrap_err().kind(), VcxErrorKind::Cancelled);
+
+        httpclient::set_mock_transport(None);
+    }
+
+    struct FailingTransport;
+
+    impl Transport for FailingTransport {
+        fn post(&self, _body_content: &[u8], _url: &str) -> AgencyClientResult<(u16, Vec<u8>)> {
+            Ok((500, vec![]))
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_download_all_messages_collects_errors_per_connection() {
+        let _setup = SetupMocks::init();
+        httpclient::set_mock_transport(Some(Rc::new(FailingTransport) as Rc<dyn Transport>));
+
+        let handle1 = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let handle2 = from_string(CONNECTION_SM_INVITEE_COMPLETED).unwrap();
+
+        let (messages, errors) = download_all_messages(None, None).unwrap();
+        assert!(messages.iter().all(|(handle, _, _)| *handle != handle1 && *handle != handle2));
+        assert!(errors.iter().any(|(handle, _)| *handle == handle1));
+        assert!(errors.iter().any(|(handle, _)| *handle == handle2));
+
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_regenerate_invitation_of_completed_connection() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let (new_handle, invitation) = regenerate_invitation(handle).unwrap();
+
+        assert_ne!(handle, new_handle);
+        assert_eq!(get_state(new_handle), VcxStateType::VcxStateOfferSent as u32);
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+        assert_eq!(get_source_id(new_handle).unwrap(), get_source_id(handle).unwrap());
+        assert_eq!(invitation, get_invite_details(new_handle).unwrap());
+
+        let invitation: InvitationV3 = serde_json::from_str(&invitation).unwrap();
+        assert!(!invitation.recipient_keys.is_empty());
+
+        let invited = build_test_connection_inviter_invited();
+        assert_eq!(regenerate_invitation(invited).unwrap_err().kind(), VcxErrorKind::ActionNotSupported);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_send_message_with_receipt() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let message = A2AMessage::CredentialOffer(_credential_offer());
+
+        HttpClientMockResponse::set_next_response(Ok(vec![]));
+        assert_eq!(send_message_with_receipt(handle, message.clone()).unwrap(), DeliveryReceipt::Accepted);
+
+        HttpClientMockResponse::set_next_response_with_status(202, vec![]);
+        assert_eq!(send_message_with_receipt(handle, message.clone()).unwrap(), DeliveryReceipt::Queued);
+
+        HttpClientMockResponse::set_next_response_with_status(400, b"unknown recipient".to_vec());
+        assert_eq!(send_message_with_receipt(handle, message.clone()).unwrap(), DeliveryReceipt::Rejected { reason: String::from("unknown recipient") });
+
+        HttpClientMockResponse::set_next_response(Err(agency_client::error::AgencyClientError::from_msg(agency_client::error::AgencyClientErrorKind::PostMessageFailed, "Could not connect")));
+        assert_eq!(send_message_with_receipt(handle, message).unwrap_err().kind(), VcxErrorKind::PostMessageFailed);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_send_message_to_recipient_key() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let message = A2AMessage::CredentialOffer(_credential_offer());
+        let their_recipient_key = get_connection_info_typed(handle).unwrap().their.unwrap().recipient_keys[0].clone();
+
+        send_message_to_recipient_key(handle, message.clone(), &their_recipient_key).unwrap();
+
+        let res = send_message_to_recipient_key(handle, message, "7WGdZPBs4ErKBnbFzoLCJ7aRGmVKmxVE3D4Xuz4xTRnH");
+        assert_eq!(res.unwrap_err().kind(), VcxErrorKind::InvalidOption);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_envelope_version() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        assert_eq!(get_envelope_version(handle).unwrap(), "v1");
+
+        let handle = create_connection_with_invite("alice", &json!(_oob_invitation_with_offer()).to_string()).unwrap();
+        assert_eq!(get_envelope_version(handle).unwrap(), "v1");
+
+        let mut oob_invitation = _oob_invitation_with_offer();
+        oob_invitation.accept = vec![String::from("didcomm/v2"), String::from("didcomm/aip2;env=rfc19")];
+        let handle = create_connection_with_invite("alice", &json!(oob_invitation).to_string()).unwrap();
+        assert_eq!(get_envelope_version(handle).unwrap(), "v1");
+
+        oob_invitation.accept = vec![String::from("didcomm/v2")];
+        assert_eq!(create_connection_with_invite("alice", &json!(oob_invitation).to_string()).unwrap_err().kind(), VcxErrorKind::ActionNotSupported);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_send_rate_limit() {
+        let _setup = SetupMocks::init();
+        let clock = SetupFakeClock::init(1_600_000_000);
+        settings::set_config_value(settings::CONFIG_SEND_RATE_LIMIT, "1");
+        settings::set_config_value(settings::CONFIG_SEND_RATE_BURST, "2");
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        send_rate_limiter::release(&get_pw_did(handle).unwrap()).unwrap();
+
+        send_generic_message(handle, "first", None).unwrap();
+        send_generic_message(handle, "second", None).unwrap();
+        assert_eq!(send_generic_message(handle, "third", None).unwrap_err().kind(), VcxErrorKind::RateLimited);
+
+        clock.clock.advance(Duration::from_millis(500));
+        assert_eq!(send_generic_message(handle, "third", None).unwrap_err().kind(), VcxErrorKind::RateLimited);
+        clock.clock.advance(Duration::from_millis(500));
+        send_generic_message(handle, "third", None).unwrap();
+        assert_eq!(send_generic_message(handle, "fourth", None).unwrap_err().kind(), VcxErrorKind::RateLimited);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_send_rate_limit_charges_only_sent_messages() {
+        let _setup = SetupMocks::init();
+        let _clock = SetupFakeClock::init(1_600_000_000);
+        settings::set_config_value(settings::CONFIG_SEND_RATE_LIMIT, "1");
+        settings::set_config_value(settings::CONFIG_SEND_RATE_BURST, "1");
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        send_rate_limiter::release(&get_pw_did(handle).unwrap()).unwrap();
+
+        transport.queue_response(Ok((500, vec![])));
+        assert_eq!(send_generic_message(handle, "first", None).unwrap_err().kind(), VcxErrorKind::PostMessageFailed);
+        send_generic_message(handle, "first", None).unwrap();
+        assert_eq!(send_generic_message(handle, "second", None).unwrap_err().kind(), VcxErrorKind::RateLimited);
+        assert_eq!(transport.sent_messages().len(), 2);
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_send_rate_limit_reserves_token_while_sending() {
+        let _setup = SetupMocks::init();
+        let _clock = SetupFakeClock::init(1_600_000_000);
+        settings::set_config_value(settings::CONFIG_SEND_RATE_LIMIT, "1");
+        settings::set_config_value(settings::CONFIG_SEND_RATE_BURST, "1");
+        let pw_did = "test_send_rate_limit_reserves_token_while_sending";
+
+        // send racing the one in flight can't take the same token
+        let concurrent = send_rate_limiter::send_limited(pw_did, || {
+            Ok(send_rate_limiter::send_limited(pw_did, || Ok(())))
+        }).unwrap();
+        assert_eq!(concurrent.unwrap_err().kind(), VcxErrorKind::RateLimited);
+
+        send_rate_limiter::release(pw_did).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_invitation_type() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        assert_eq!(get_invitation_type(handle).unwrap(), InvitationType::Legacy);
+
+        let handle = create_connection("alice").unwrap();
+        assert_eq!(get_invitation_type(handle).unwrap(), InvitationType::ConnectionInvitation);
+
+        let handle = create_connection_with_invite("alice", ARIES_CONNECTION_INVITATION).unwrap();
+        assert_eq!(get_invitation_type(handle).unwrap(), InvitationType::ConnectionInvitation);
+
+        let handle = create_connection_with_invite("alice", &json!(_oob_invitation_with_offer()).to_string()).unwrap();
+        assert_eq!(get_invitation_type(handle).unwrap(), InvitationType::OutOfBand);
+
+        let serialized = to_string(handle).unwrap();
+        assert_eq!(get_invitation_type(from_string(&serialized).unwrap()).unwrap(), InvitationType::OutOfBand);
+
+        let (handle, _) = import_bundle(&export_bundle(handle, Value::Null).unwrap()).unwrap();
+        assert_eq!(get_invitation_type(handle).unwrap(), InvitationType::ContactImport);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_connection_tags() {
+        let _setup = SetupMocks::init();
+
+        let bank = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let employer = from_string(CONNECTION_SM_INVITEE_COMPLETED).unwrap();
+        let tag = format!("bank-{}", bank);
+
+        add_tag(bank, &tag).unwrap();
+        add_tag(bank, &tag).unwrap();
+        add_tag(bank, "finance").unwrap();
+        add_tag(employer, "work").unwrap();
+        assert_eq!(add_tag(employer, "").unwrap_err().kind(), VcxErrorKind::InvalidOption);
+
+        assert_eq!(get_tags(bank).unwrap(), vec![tag.clone(), String::from("finance")]);
+        assert_eq!(find_by_tag(&tag).unwrap(), vec![bank]);
+
+        let restored = from_string(&to_string(bank).unwrap()).unwrap();
+        assert_eq!(get_tags(restored).unwrap(), get_tags(bank).unwrap());
+        let mut expected = vec![bank, restored];
+        expected.sort();
+        assert_eq!(find_by_tag(&tag).unwrap(), expected);
+
+        remove_tag(bank, &tag).unwrap();
+        remove_tag(bank, "unknown").unwrap();
+        assert_eq!(get_tags(bank).unwrap(), vec![String::from("finance")]);
+        assert_eq!(find_by_tag(&tag).unwrap(), vec![restored]);
+
+        release(restored).unwrap();
+        assert!(find_by_tag(&tag).unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_from_legacy_string() {
+        let _setup = SetupMocks::init();
+
+        let connections = vec![
+            (CONNECTION_SM_INVITEE_INVITED, VcxStateType::VcxStateOfferSent),
+            (CONNECTION_SM_INVITEE_REQUESTED, VcxStateType::VcxStateRequestReceived),
+            (CONNECTION_SM_INVITEE_COMPLETED, VcxStateType::VcxStateAccepted),
+            (CONNECTION_SM_INVITER_COMPLETED, VcxStateType::VcxStateAccepted),
+            (DEFAULT_SERIALIZED_CONNECTION, VcxStateType::VcxStateInitialized),
+        ];
+        for (serialized, state) in connections {
+            let handle = from_legacy_string(serialized).unwrap();
+            assert_eq!(get_state(handle), state as u32);
+
+            let legacy: Value = serde_json::from_str(serialized).unwrap();
+            let imported: Value = serde_json::from_str(&to_string(handle).unwrap()).unwrap();
+            assert_eq!(imported["source_id"], legacy["source_id"]);
+            assert_eq!(imported["state"], legacy["state"]);
+            assert_eq!(json!(get_pw_did(handle).unwrap()), legacy["data"]["pw_did"]);
+        }
+
+        let handle = from_legacy_string(CONNECTION_SM_INVITEE_COMPLETED).unwrap();
+        assert_eq!(get_their_pw_verkey(handle).unwrap(), "rCw3x5h1jS6gPo7rRrt3EYbXXe5nNjnGbdf1jAwUxuj");
+
+        assert_eq!(from_legacy_string(r#"{"version": "3.0", "data": {}}"#).unwrap_err().kind(), VcxErrorKind::InvalidJson);
+        assert_eq!(from_legacy_string(r#"{"data": {}}"#).unwrap_err().kind(), VcxErrorKind::InvalidJson);
+    }
+
+    // answers downloads of messages by responses queued in `inbox`, other requests by the agency mocks
+    struct InboxTransport {
+        inbox: MockTransport,
+    }
+
+    impl Transport for InboxTransport {
+        fn post(&self, body_content: &[u8], url: &str) -> AgencyClientResult<(u16, Vec<u8>)> {
+            // agency requests are not encrypted in test mode
+            if String::from_utf8_lossy(body_content).contains("GET_MSGS") {
+                return self.inbox.post(body_content, url);
+            }
+            httpclient::mocked_response()
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_establish_from_invite() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(InboxTransport { inbox: MockTransport::default() });
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        // inviter doesn't answer the first download, the connection is established once its response arrives
+        let inviter_vk = "DEKbrMDX9LBGhCk4LBhH6t5B6Kh5iE7GvfepAJYXp7GX";
+        let response = Response::default()
+            .set_service_endpoint(String::from("http://localhost:8080/agency/msg"))
+            .set_keys(vec![inviter_vk.to_string()], vec![])
+            .set_thread_id(&MessageId::id().0)
+            .encode(inviter_vk).unwrap();
+        transport.inbox.queue_messages(inviter_vk, &[]);
+        transport.inbox.queue_messages(inviter_vk, &[&json!(response.to_a2a_message()).to_string()]);
+
+        let handle = establish_from_invite("establish-succeeds", ARIES_CONNECTION_INVITATION, Duration::from_secs(5)).unwrap();
+        httpclient::set_mock_transport(None);
+
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+        assert_eq!(get_their_pw_verkey(handle).unwrap(), inviter_vk);
+        assert!(list().unwrap().iter().any(|(listed, source_id, _)| *listed == handle && source_id == "establish-succeeds"));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_establish_from_invite_times_out_and_deletes_connection() {
+        let _setup = SetupMocks::init();
+
+        let started = Instant::now();
+        let err = establish_from_invite("establish-times-out", ARIES_CONNECTION_INVITATION, Duration::from_millis(500)).unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::Timeout);
+        assert!(started.elapsed() >= Duration::from_millis(500));
+
+        assert!(list().unwrap().iter().all(|(_, source_id, _)| source_id != "establish-times-out"));
+    }
+
+    #[derive(Default)]
+    struct RecordingDeletionObserver {
+        deleting: Mutex<Vec<(u32, String, DeletionReason)>>,
+        completed: Mutex<Vec<(u32, DeletionReason, bool)>>,
+    }
+
+    impl RecordingDeletionObserver {
+        // observer is global, other tests may delete connections concurrently
+        fn deleting(&self, handles: &[u32]) -> Vec<(u32, String, DeletionReason)> {
+            self.deleting.lock().unwrap().iter().filter(|(handle, _, _)| handles.contains(handle)).cloned().collect()
+        }
+
+        fn completed(&self, handles: &[u32]) -> Vec<(u32, DeletionReason, bool)> {
+            self.completed.lock().unwrap().iter().filter(|(handle, _, _)| handles.contains(handle)).cloned().collect()
+        }
+    }
+
+    impl deletion_observer::DeletionObserver for RecordingDeletionObserver {
+        fn on_delete(&self, handle: u32, source_id: &str, reason: DeletionReason) {
+            // handle is still valid while the observer is notified about pending deletion
+            assert!(is_valid_handle(handle));
+            self.deleting.lock().unwrap().push((handle, source_id.to_string(), reason));
+        }
+
+        fn on_delete_completed(&self, handle: u32, _source_id: &str, reason: DeletionReason, deleted: bool) {
+            self.completed.lock().unwrap().push((handle, reason, deleted));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_delete_with_reason_reports_reason_to_observer() {
+        let _setup = SetupMocks::init();
+        let observer = Arc::new(RecordingDeletionObserver::default());
+        deletion_observer::set_deletion_observer(Some(observer.clone()));
+
+        let superseded = create_connection("superseded-connection").unwrap();
+        let deleted = create_connection("deleted-connection").unwrap();
 
-impl From<(SmConnectionState, AgentInfo, String)> for Connection {
-    fn from((state, agent_info, source_id): (SmConnectionState, AgentInfo, String)) -> Connection {
-        Connection::from_parts(source_id, agent_info, state)
+        AgencyMockDecrypted::set_next_decrypted_response(constants::DELETE_CONNECTION_DECRYPTED_RESPONSE);
+        delete_with_reason(superseded, DeletionReason::Superseded).unwrap();
+        AgencyMockDecrypted::set_next_decrypted_response(constants::DELETE_CONNECTION_DECRYPTED_RESPONSE);
+        delete_connection(deleted).unwrap();
+        deletion_observer::set_deletion_observer(None);
+
+        assert_eq!(observer.deleting(&[superseded, deleted]), vec![
+            (superseded, String::from("superseded-connection"), DeletionReason::Superseded),
+            (deleted, String::from("deleted-connection"), DeletionReason::UserInitiated),
+        ]);
+        assert_eq!(observer.completed(&[superseded, deleted]), vec![
+            (superseded, DeletionReason::Superseded, true),
+            (deleted, DeletionReason::UserInitiated, true),
+        ]);
+        assert!(!is_valid_handle(superseded));
+        assert_eq!(delete_with_reason(superseded, DeletionReason::Expired).unwrap_err().kind(), VcxErrorKind::DeleteConnection);
     }
-}
 
-pub fn get_messages(handle: u32) -> VcxResult<HashMap<String, A2AMessage>> {
-    CONNECTION_MAP.get_mut(handle, |connection| {
-        connection.get_messages()
-    })
-}
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_delete_with_reason_reports_failed_deletion_to_observer() {
+        let _setup = SetupMocks::init();
+        let observer = Arc::new(RecordingDeletionObserver::default());
+        deletion_observer::set_deletion_observer(Some(observer.clone()));
 
-pub fn update_message_status(handle: u32, uid: String) -> VcxResult<()> {
-    CONNECTION_MAP.get_mut(handle, |connection| {
-        connection.update_message_status(uid.clone())
-    })
-}
+        let handle = create_connection("not-deleted-connection").unwrap();
 
-pub fn get_message_by_id(handle: u32, msg_id: String) -> VcxResult<A2AMessage> {
-    CONNECTION_MAP.get_mut(handle, |connection| {
-        connection.get_message_by_id(&msg_id)
-    })
-}
+        // agency answers with unexpected message, pairwise agent is not deleted
+        AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
+        assert_eq!(delete_with_reason(handle, DeletionReason::Expired).unwrap_err().kind(), VcxErrorKind::DeleteConnection);
+        deletion_observer::set_deletion_observer(None);
 
-pub fn send_message(handle: u32, message: A2AMessage) -> VcxResult<()> {
-    trace!("connection::send_message >>>");
-    CONNECTION_MAP.get_mut(handle, |connection| {
-        connection.send_message(&message)
-    })
-}
+        assert_eq!(observer.deleting(&[handle]), vec![(handle, String::from("not-deleted-connection"), DeletionReason::Expired)]);
+        assert_eq!(observer.completed(&[handle]), vec![(handle, DeletionReason::Expired, false)]);
+    }
 
-pub fn send_message_to_self_endpoint(message: A2AMessage, did_doc: &DidDoc) -> VcxResult<()> {
-    Connection::send_message_to_self_endpoint(&message, did_doc)
-}
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_list_connections() {
+        let _setup = SetupMocks::init();
 
-pub fn is_v3_connection(connection_handle: u32) -> VcxResult<bool> {
-    CONNECTION_MAP.get(connection_handle, |_| {
-        Ok(true)
-    }).or(Err(VcxError::from(VcxErrorKind::InvalidConnectionHandle)))
-}
+        let handle = create_connection("list-connections").unwrap();
 
-pub fn send_ping(connection_handle: u32, comment: Option<String>) -> VcxResult<()> {
-    CONNECTION_MAP.get_mut(connection_handle, |connection| {
-        connection.send_ping(comment.clone())
-    })
-}
+        let connections = list().unwrap();
+        assert!(connections.contains(&(handle, String::from("list-connections"), get_state(handle))));
 
-pub fn send_discovery_features(connection_handle: u32, query: Option<String>, comment: Option<String>) -> VcxResult<()> {
-    CONNECTION_MAP.get_mut(connection_handle, |connection| {
-        connection.send_discovery_features(query.clone(), comment.clone())
-    })
-}
+        release(handle).unwrap();
+        assert!(list().unwrap().iter().all(|(listed, _, _)| *listed != handle));
+    }
 
-pub fn get_connection_info(handle: u32) -> VcxResult<String> {
-    CONNECTION_MAP.get(handle, |connection| {
-        connection.get_connection_info()
-    })
-}
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_connection_decorators_are_persisted() {
+        let _setup = SetupMocks::init();
 
-pub fn download_messages(conn_handles: Vec<u32>, status_codes: Option<Vec<MessageStatusCode>>, uids: Option<Vec<String>>) -> VcxResult<Vec<MessageByConnection>> {
-    trace!("download_messages >>> cann_handles: {:?}, status_codes: {:?}, uids: {:?}", conn_handles, status_codes, uids);
-    let mut res = Vec::new();
-    for conn_handle in conn_handles {
-        let msg_by_conn = CONNECTION_MAP.get(
-            conn_handle, |connection| {
-                let expected_sender_vk = connection.remote_vk()?;
-                let msgs = connection
-                    .agent_info()
-                    .download_encrypted_messages(uids.clone(), status_codes.clone())?
-                    .iter()
-                    .map(|msg| msg.decrypt_auth(&expected_sender_vk).map_err(|err| err.into()))
-                    .collect::<VcxResult<Vec<Message>>>()?;
-                Ok(MessageByConnection { pairwise_did: connection.agent_info().clone().pw_did, msgs })
-            },
-        )?;
-        res.push(msg_by_conn);
-    };
-    trace!("download_messages <<< res: {:?}", res);
-    Ok(res)
-}
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        assert_eq!(set_decorator(handle, "com.ourcorp/trace", Some("{}")).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+        assert_eq!(set_decorator(handle, "~com.ourcorp/trace", Some("not json")).unwrap_err().kind(), VcxErrorKind::InvalidJson);
 
-#[cfg(test)]
-pub mod tests {
-    use std::thread;
-    use std::time::Duration;
+        set_decorator(handle, "~com.ourcorp/trace", Some(r#"{"trace_id":"abc"}"#)).unwrap();
+        let restored = from_string(&to_string(handle).unwrap()).unwrap();
+        let decorators = CONNECTION_MAP.get(restored, |connection| Ok(connection.agent_info().decorators.clone())).unwrap();
+        assert_eq!(decorators["~com.ourcorp/trace"], json!({"trace_id": "abc"}));
 
-    use serde_json::Value;
+        set_decorator(restored, "~com.ourcorp/trace", None).unwrap();
+        let serialized = to_string(restored).unwrap();
+        assert!(!serialized.contains("com.ourcorp/trace"));
+    }
 
-    use agency_client::get_message::download_messages_noauth;
-    use agency_client::MessageStatusCode;
-    use agency_client::mocking::AgencyMockDecrypted;
-    use agency_client::update_message::{UIDsByConn, update_agency_messages};
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_connection_info_typed() {
+        let _setup = SetupMocks::init();
 
-    use crate::{connection, utils, settings};
-    use crate::api::VcxStateType;
-    use crate::utils::constants;
-    use crate::utils::devsetup::*;
-    use crate::utils::mockdata::mockdata_connection::{ARIES_CONNECTION_ACK, ARIES_CONNECTION_INVITATION, ARIES_CONNECTION_REQUEST, CONNECTION_SM_INVITEE_COMPLETED, CONNECTION_SM_INVITEE_INVITED, CONNECTION_SM_INVITEE_REQUESTED, CONNECTION_SM_INVITER_COMPLETED};
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
 
-    use super::*;
+        let info = get_connection_info_typed(handle).unwrap();
+        assert_eq!(info.my.did, get_pw_did(handle).unwrap());
+        assert_eq!(info.their.as_ref().unwrap().did, get_their_pw_did(handle).unwrap());
+        assert_eq!(info.their.as_ref().unwrap().recipient_keys[0], get_their_pw_verkey(handle).unwrap());
+        assert_eq!(info.protocol_version, "1.0");
+        assert_eq!(info.state, VcxStateType::VcxStateAccepted as u32);
 
-    pub fn build_test_connection_inviter_null() -> u32 {
-        let handle = create_connection("faber_to_alice").unwrap();
-        handle
+        let info_json: ConnectionInfo = serde_json::from_str(&get_connection_info(handle).unwrap()).unwrap();
+        assert_eq!(info, info_json);
     }
 
-    pub fn build_test_connection_inviter_invited() -> u32 {
-        let handle = create_connection("faber_to_alice").unwrap();
-        connect(handle).unwrap();
-        handle
-    }
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_cancel_requested_connection() {
+        let _setup = SetupMocks::init();
 
-    pub fn build_test_connection_invitee_completed() -> u32 {
-        let handle = from_string(CONNECTION_SM_INVITEE_COMPLETED).unwrap();
-        handle
-    }
+        let handle = from_string(CONNECTION_SM_INVITEE_REQUESTED).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateRequestReceived as u32);
 
-    pub fn build_test_connection_inviter_requested() -> u32 {
-        let handle = build_test_connection_inviter_invited();
-        let msg: A2AMessage = serde_json::from_str(ARIES_CONNECTION_REQUEST).unwrap();
-        update_state_with_message(handle, msg).unwrap();
-        handle
+        AgencyMockDecrypted::set_next_decrypted_response(constants::DELETE_CONNECTION_DECRYPTED_RESPONSE);
+        cancel(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateInitialized as u32);
     }
 
-    pub fn create_connected_connections(consumer_handle: Option<u32>, institution_handle: Option<u32>) -> (u32, u32) {
-        debug!("Institution is going to create connection.");
-        utils::devsetup::set_institution(institution_handle);
-        let institution_to_consumer = create_connection("consumer").unwrap();
-        let _my_public_did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap();
-        let details = connect(institution_to_consumer).unwrap().unwrap();
-        // update_state(institution_to_consumer).unwrap();
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_requested_connection_fails_on_problem_report() {
+        let _setup = SetupMocks::init();
 
-        utils::devsetup::set_consumer(consumer_handle);
-        debug!("Consumer is going to accept connection invitation.");
-        let consumer_to_institution = create_connection_with_invite("institution", &details).unwrap();
-        connect(consumer_to_institution).unwrap();
-        update_state(consumer_to_institution).unwrap();
+        let handle = from_string(CONNECTION_SM_INVITEE_REQUESTED).unwrap();
+        assert_eq!(get_last_problem_report(handle).unwrap_err().kind(), VcxErrorKind::NotReady);
 
-        debug!("Institution is going to process connection request.");
-        utils::devsetup::set_institution(institution_handle);
-        thread::sleep(Duration::from_millis(500));
-        update_state(institution_to_consumer).unwrap();
-        assert_eq!(VcxStateType::VcxStateRequestReceived as u32, get_state(institution_to_consumer));
+        let problem_report = _problem_report();
+        update_state_with_message(handle, A2AMessage::ConnectionProblemReport(problem_report.clone())).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateInitialized as u32);
 
-        debug!("Consumer is going to complete the connection protocol.");
-        utils::devsetup::set_consumer(consumer_handle);
-        update_state(consumer_to_institution).unwrap();
-        assert_eq!(VcxStateType::VcxStateAccepted as u32, get_state(consumer_to_institution));
+        let handle = from_string(&to_string(handle).unwrap()).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateInitialized as u32);
+        let last_problem_report: ProblemReport = serde_json::from_str(&get_last_problem_report(handle).unwrap()).unwrap();
+        assert_eq!(last_problem_report, problem_report);
+    }
 
-        debug!("Institution is going to complete the connection protocol.");
-        utils::devsetup::set_institution(institution_handle);
-        thread::sleep(Duration::from_millis(500));
-        update_state(institution_to_consumer).unwrap();
-        assert_eq!(VcxStateType::VcxStateAccepted as u32, get_state(institution_to_consumer));
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_fails_on_response_of_unexpected_did() {
+        let _setup = SetupMocks::init();
 
-        (consumer_to_institution, institution_to_consumer)
+        let mut requested: Value = serde_json::from_str(CONNECTION_SM_INVITEE_REQUESTED).unwrap();
+        requested["state"]["Invitee"]["Requested"]["expected_did"] = json!("VsKV7grR1BUE29mG2Fm2kX");
+        let handle = from_string(&requested.to_string()).unwrap();
+
+        let response = Response::create()
+            .set_did(String::from("2ZHFFhzA2XtTD6hJqzL7ux"))
+            .set_keys(vec![String::from("GJ1SzoWzavQYfNL9XkaJdrQejfztN4XqdsiV4ct3LXKL")], vec![])
+            .set_thread_id("8b58c65b-a585-4976-99e1-f9570a4bd097")
+            .encode("HoNSv4aPCRQ8BsJrVXS26Za4rdEFvtCyyoQEtCS175dw").unwrap();
+
+        let err = update_state_with_message(handle, A2AMessage::ConnectionResponse(response)).unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::ConnectionDidMismatch);
+        assert_eq!(get_state(handle), VcxStateType::VcxStateInitialized as u32);
+        let problem_report: ProblemReport = serde_json::from_str(&get_last_problem_report(handle).unwrap()).unwrap();
+        assert_eq!(problem_report.problem_code, Some(ProblemCode::ResponseNotAccepted));
     }
 
     #[test]
     #[cfg(feature = "general_test")]
-    fn test_create_connection() {
+    fn test_cancel_completed_connection_fails() {
         let _setup = SetupMocks::init();
 
-        let handle = create_connection("test_create_connection").unwrap();
-        assert_eq!(get_state(handle), VcxStateType::VcxStateInitialized as u32);
+        let handle = from_string(CONNECTION_SM_INVITEE_COMPLETED).unwrap();
+
+        assert_eq!(cancel(handle).unwrap_err().kind(), VcxErrorKind::InvalidState);
+        assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_establishment_latency() {
+        let _setup = SetupMocks::init();
 
+        let handle = create_connection("test_establishment_latency").unwrap();
+        assert_eq!(get_establishment_latency(handle).unwrap(), None);
 
         connect(handle).unwrap();
-        assert_eq!(get_pw_did(handle).unwrap(), constants::DID);
-        assert_eq!(get_pw_verkey(handle).unwrap(), constants::VERKEY);
+        assert_eq!(get_establishment_latency(handle).unwrap(), None);
 
         AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
         AgencyMockDecrypted::set_next_decrypted_message(ARIES_CONNECTION_REQUEST);
         update_state(handle).unwrap();
-        assert_eq!(get_state(handle), VcxStateType::VcxStateRequestReceived as u32);
+        assert_eq!(get_establishment_latency(handle).unwrap(), None);
 
         AgencyMockDecrypted::set_next_decrypted_response(constants::GET_MESSAGES_DECRYPTED_RESPONSE);
         AgencyMockDecrypted::set_next_decrypted_message(ARIES_CONNECTION_ACK);
         update_state(handle).unwrap();
         assert_eq!(get_state(handle), VcxStateType::VcxStateAccepted as u32);
+        let latency = get_establishment_latency(handle).unwrap().unwrap();
+
+        let serialized = to_string(handle).unwrap();
+        let handle_restored = from_string(&serialized).unwrap();
+        assert_eq!(get_establishment_latency(handle_restored).unwrap(), Some(latency));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_connect_resumes_deserialized_requested_connection() {
+        let _setup = SetupMocks::init();
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+
+        let handle = from_string(CONNECTION_SM_INVITEE_REQUESTED).unwrap();
+        let pw_did = get_pw_did(handle).unwrap();
+        let pw_vk = get_pw_verkey(handle).unwrap();
+        let their_endpoint = get_connection_info_typed(handle).unwrap().their.unwrap().service_endpoint;
+
+        connect(handle).unwrap();
+
+        // no agent is provisioned and no new request created, only the original request goes to the inviter again
+        let sent = transport.sent_messages();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(transport.sent_to(&their_endpoint).len(), 1);
+        let sent = String::from_utf8(sent[0].body.clone()).unwrap();
+        assert!(sent.contains("8b58c65b-a585-4976-99e1-f9570a4bd097"));
+        assert!(sent.contains(&pw_vk));
+
+        assert_eq!(get_state(handle), VcxStateType::VcxStateRequestReceived as u32);
+        assert_eq!(get_pw_did(handle).unwrap(), pw_did);
+        assert_eq!(get_pw_verkey(handle).unwrap(), pw_vk);
+        let original: Value = serde_json::from_str(CONNECTION_SM_INVITEE_REQUESTED).unwrap();
+        let resumed: Value = serde_json::from_str(&to_string(handle).unwrap()).unwrap();
+        assert_eq!(resumed["state"]["Invitee"]["Requested"]["request"]["@id"], json!("8b58c65b-a585-4976-99e1-f9570a4bd097"));
+        assert_eq!(original["state"], resumed["state"]);
+
+        httpclient::set_mock_transport(None);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_invitation_recipient_key() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITEE_INVITED).unwrap();
+        let invited_key = get_invitation_recipient_key(handle).unwrap();
+        let invitation: Value = serde_json::from_str(CONNECTION_SM_INVITEE_INVITED).unwrap();
+        assert_eq!(json!(invited_key), invitation["state"]["Invitee"]["Invited"]["invitation"]["recipientKeys"][0]);
+
+        let handle = from_string(CONNECTION_SM_INVITEE_REQUESTED).unwrap();
+        assert!(get_invitation_recipient_key(handle).is_ok());
+
+        let handle = from_string(CONNECTION_SM_INVITEE_COMPLETED).unwrap();
+        assert_eq!(get_invitation_recipient_key(handle).unwrap_err().kind(), VcxErrorKind::NotReady);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_send_generic_message_with_thread_id() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+
+        send_generic_message(handle, "Hello", Some("thread-1")).unwrap();
+        send_generic_message(handle, r#"{"@type":"did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/custom/1.0/request","@id":"abc"}"#, Some("thread-1")).unwrap();
+
+        let ping = json!(Ping::create().to_a2a_message()).to_string();
+        assert_eq!(send_generic_message(handle, &ping, Some("thread-1")).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+
+        assert_eq!(send_generic_message(handle, "[1, 2]", Some("thread-1")).unwrap_err().kind(), VcxErrorKind::InvalidJson);
+        assert_eq!(send_generic_message(handle, "42", Some("thread-1")).unwrap_err().kind(), VcxErrorKind::InvalidJson);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_deactivate_connection() {
+        let _setup = SetupMocks::init();
+
+        let handle = from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let their_pw_did = get_their_pw_did(handle).unwrap();
 
         AgencyMockDecrypted::set_next_decrypted_response(constants::DELETE_CONNECTION_DECRYPTED_RESPONSE);
+        deactivate(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateNone as u32);
+        assert_eq!(get_their_pw_did(handle).unwrap(), their_pw_did);
+
+        assert_eq!(send_generic_message(handle, "Hello", None).unwrap_err().kind(), VcxErrorKind::ConnectionDeactivated);
+        assert_eq!(update_state(handle).unwrap_err().kind(), VcxErrorKind::ConnectionDeactivated);
+        assert_eq!(deactivate(handle).unwrap_err().kind(), VcxErrorKind::ConnectionDeactivated);
+
+        let serialized = to_string(handle).unwrap();
+        assert!(serialized.contains("Deactivated"));
+        test_deserialize_and_serialize(&serialized);
+
         assert_eq!(delete_connection(handle).unwrap(), 0);
+    }
 
-        // This errors b/c we release handle in delete connection
-        assert!(release(handle).is_err());
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_deactivate_receiver_connection() {
+        let _setup = SetupMocks::init();
+
+        let handle = create_receiver("test_deactivate_receiver_connection").unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateInitialized as u32);
+
+        AgencyMockDecrypted::set_next_decrypted_response(constants::DELETE_CONNECTION_DECRYPTED_RESPONSE);
+        deactivate(handle).unwrap();
+        assert_eq!(get_state(handle), VcxStateType::VcxStateNone as u32);
+
+        assert_eq!(update_state(handle).unwrap_err().kind(), VcxErrorKind::ConnectionDeactivated);
+        assert_eq!(get_messages(handle).unwrap_err().kind(), VcxErrorKind::ConnectionDeactivated);
     }
 
     #[test]
@@ -522,6 +3222,24 @@ pub mod tests {
         test_deserialize_and_serialize(CONNECTION_SM_INVITER_COMPLETED);
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_serialize_deserialize_msgpack() {
+        let _setup = SetupMocks::init();
+
+        for sm_serialized in vec![CONNECTION_SM_INVITEE_INVITED, CONNECTION_SM_INVITEE_REQUESTED, CONNECTION_SM_INVITEE_COMPLETED, CONNECTION_SM_INVITER_COMPLETED] {
+            settings::set_config_value(settings::CONFIG_SERIALIZATION_FORMAT, "msgpack");
+            let handle = from_string(sm_serialized).unwrap();
+            let compact = to_string(handle).unwrap();
+            assert!(compact.starts_with(serialization::MSGPACK_PREFIX));
+
+            settings::set_config_value(settings::CONFIG_SERIALIZATION_FORMAT, "json");
+            let handle = from_string(&compact).unwrap();
+            let reserialized: Value = serde_json::from_str(&to_string(handle).unwrap()).unwrap();
+            assert_eq!(serde_json::from_str::<Value>(sm_serialized).unwrap(), reserialized);
+        }
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_serialize_deserialize() {
@@ -611,6 +3329,24 @@ pub mod tests {
         assert_eq!(error::SUCCESS.code_num, update_state_with_message(handle, message).unwrap());
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_with_out_of_protocol_message_fails() {
+        let _setup = SetupMocks::init();
+
+        let handle = build_test_connection_inviter_invited();
+        let serialized = to_string(handle).unwrap();
+        let message = BasicMessage::create().set_content(String::from("hello")).to_a2a_message();
+
+        let err = update_state_with_message(handle, message).unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::InvalidMessageForState);
+        assert!(err.to_string().contains("VcxStateOfferSent"));
+        assert!(err.to_string().contains("basicmessage/1.0/message"));
+
+        assert_eq!(get_state(handle), VcxStateType::VcxStateOfferSent as u32);
+        assert_eq!(to_string(handle).unwrap(), serialized);
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_connection_handle_is_found() {
@@ -629,7 +3365,7 @@ pub mod tests {
 
         let handle = connection::tests::build_test_connection_inviter_invited();
 
-        let err = send_generic_message(handle, "this is the message").unwrap_err();
+        let err = send_generic_message(handle, "this is the message", None).unwrap_err();
         assert_eq!(err.kind(), VcxErrorKind::NotReady);
     }
 
@@ -639,12 +3375,12 @@ pub mod tests {
         let _setup = SetupLibraryAgencyV2::init();
         let (alice_to_faber, faber_to_alice) = connection::tests::create_connected_connections(None, None);
 
-        send_generic_message(faber_to_alice, "Hello Alice").unwrap();
-        send_generic_message(faber_to_alice, "How are you Alice?").unwrap();
+        send_generic_message(faber_to_alice, "Hello Alice", None).unwrap();
+        send_generic_message(faber_to_alice, "How are you Alice?", None).unwrap();
 
         // AS CONSUMER GET MESSAGES
         utils::devsetup::set_consumer(None);
-        send_generic_message(alice_to_faber, "Hello Faber").unwrap();
+        send_generic_message(alice_to_faber, "Hello Faber", None).unwrap();
 
         // make sure messages has bee delivered
         thread::sleep(Duration::from_millis(1000));
@@ -702,46 +3438,77 @@ pub mod tests {
         let consumer2_pwdid = get_their_pw_did(consumer2_to_institution).unwrap();
 
         utils::devsetup::set_consumer(Some(consumer1));
-        send_generic_message(consumer1_to_institution, "Hello Institution from consumer1").unwrap();
+        send_generic_message(consumer1_to_institution, "Hello Institution from consumer1", None).unwrap();
         utils::devsetup::set_consumer(Some(consumer2));
-        send_generic_message(consumer2_to_institution, "Hello Institution from consumer2").unwrap();
+        send_generic_message(consumer2_to_institution, "Hello Institution from consumer2", None).unwrap();
 
         utils::devsetup::set_institution(None);
-        let all_msgs = download_messages([institution_to_consumer1, institution_to_consumer2].to_vec(), None, None).unwrap();
+        let all_msgs = download_messages([institution_to_consumer1, institution_to_consumer2].to_vec(), None, None, None, None).unwrap();
         assert_eq!(all_msgs.len(), 2);
         assert_eq!(all_msgs[0].msgs.len(), 2);
         assert_eq!(all_msgs[1].msgs.len(), 2);
 
-        let consumer1_msgs = download_messages([institution_to_consumer1].to_vec(), None, None).unwrap();
+        let consumer1_msgs = download_messages([institution_to_consumer1].to_vec(), None, None, None, None).unwrap();
         assert_eq!(consumer1_msgs.len(), 1);
         assert_eq!(consumer1_msgs[0].msgs.len(), 2);
         assert_eq!(consumer1_msgs[0].pairwise_did, consumer1_pwdid);
 
-        let consumer2_msgs = download_messages([institution_to_consumer2].to_vec(), None, None).unwrap();
+        let consumer2_msgs = download_messages([institution_to_consumer2].to_vec(), None, None, None, None).unwrap();
         assert_eq!(consumer2_msgs.len(), 1);
         assert_eq!(consumer2_msgs[0].msgs.len(), 2);
         assert_eq!(consumer2_msgs[0].pairwise_did, consumer2_pwdid);
 
-        let consumer1_received_msgs = download_messages([institution_to_consumer1].to_vec(), Some(vec![MessageStatusCode::Received]), None).unwrap();
+        let consumer1_received_msgs = download_messages([institution_to_consumer1].to_vec(), Some(vec![MessageStatusCode::Received]), None, None, None).unwrap();
         assert_eq!(consumer1_received_msgs.len(), 1);
         assert_eq!(consumer1_received_msgs[0].msgs.len(), 1);
         assert!(consumer1_received_msgs[0].msgs[0].decrypted_msg.is_some());
 
-        let consumer1_reviewed_msgs = download_messages([institution_to_consumer1].to_vec(), Some(vec![MessageStatusCode::Reviewed]), None).unwrap();
+        let consumer1_reviewed_msgs = download_messages([institution_to_consumer1].to_vec(), Some(vec![MessageStatusCode::Reviewed]), None, None, None).unwrap();
         assert_eq!(consumer1_reviewed_msgs.len(), 1);
         assert_eq!(consumer1_reviewed_msgs[0].msgs.len(), 1);
         assert!(consumer1_reviewed_msgs[0].msgs[0].decrypted_msg.is_some());
     }
 
+    #[cfg(feature = "agency_pool_tests")]
+    #[test]
+    fn test_download_all_messages() {
+        let _setup = SetupLibraryAgencyV2::init();
+        let consumer1 = create_consumer_config();
+        let consumer2 = create_consumer_config();
+
+        let (consumer1_to_institution, institution_to_consumer1) = create_connected_connections(Some(consumer1), None);
+        let (consumer2_to_institution, institution_to_consumer2) = create_connected_connections(Some(consumer2), None);
+
+        utils::devsetup::set_consumer(Some(consumer1));
+        send_generic_message(consumer1_to_institution, "Hello Institution from consumer1", None).unwrap();
+        thread::sleep(Duration::from_secs(1));
+        utils::devsetup::set_consumer(Some(consumer2));
+        send_generic_message(consumer2_to_institution, "Hello Institution from consumer2", None).unwrap();
+
+        utils::devsetup::set_institution(None);
+        let (received_msgs, errors) = download_all_messages(Some(vec![MessageStatusCode::Received]), None).unwrap();
+        assert!(errors.is_empty());
+        let received_msgs: Vec<(u32, A2AMessage, u64)> = received_msgs.into_iter()
+            .filter(|(handle, _, _)| *handle == institution_to_consumer1 || *handle == institution_to_consumer2)
+            .collect();
+        assert_eq!(received_msgs.len(), 2);
+        assert_eq!(received_msgs[0].0, institution_to_consumer1);
+        assert_eq!(received_msgs[1].0, institution_to_consumer2);
+        assert!(received_msgs[0].2 <= received_msgs[1].2);
+
+        let (newer_msgs, _) = download_all_messages(Some(vec![MessageStatusCode::Received]), Some(received_msgs[1].2 + 1)).unwrap();
+        assert!(newer_msgs.iter().all(|(handle, _, _)| *handle != institution_to_consumer1 && *handle != institution_to_consumer2));
+    }
+
     #[cfg(feature = "agency_pool_tests")]
     #[test]
     fn test_update_agency_messages() {
         let _setup = SetupLibraryAgencyV2::init();
         let (_alice_to_faber, faber_to_alice) = connection::tests::create_connected_connections(None, None);
 
-        send_generic_message(faber_to_alice, "Hello 1").unwrap();
-        send_generic_message(faber_to_alice, "Hello 2").unwrap();
-        send_generic_message(faber_to_alice, "Hello 3").unwrap();
+        send_generic_message(faber_to_alice, "Hello 1", None).unwrap();
+        send_generic_message(faber_to_alice, "Hello 2", None).unwrap();
+        send_generic_message(faber_to_alice, "Hello 3", None).unwrap();
 
         thread::sleep(Duration::from_millis(1000));
         utils::devsetup::set_consumer(None);
@@ -9,6 +9,7 @@ use crate::aries::{
 };
 use crate::connection;
 use crate::error::prelude::*;
+use crate::libindy::utils::anoncreds;
 use crate::settings::indy_mocks_enabled;
 use crate::utils::constants::GET_MESSAGES_DECRYPTED_RESPONSE;
 use crate::utils::error;
@@ -118,12 +119,45 @@ pub fn get_credential(handle: u32) -> VcxResult<String> {
     })
 }
 
+/**
+Returns id the received credential is stored under in the wallet.
+ */
+pub fn get_credential_id(handle: u32) -> VcxResult<String> {
+    HANDLE_MAP.get(handle, |credential| {
+        Ok(credential.get_credential()?.0)
+    }).map_err(handle_err)
+}
+
+/**
+Returns info of the credential stored in the wallet under `cred_id`, its `referent` is used to select
+the credential when generating proof.
+ */
+pub fn get_stored_credential(cred_id: &str) -> VcxResult<String> {
+    trace!("Credential::get_stored_credential >>> cred_id: {}", cred_id);
+    anoncreds::libindy_prover_get_credential(cred_id)
+}
+
+/**
+Returns id of the stored credential tagged with `app_id` by `send_credential_request_with_id`,
+fails with WalletRecordNotFound if there's none.
+ */
+pub fn get_credential_id_by_app_id(app_id: &str) -> VcxResult<String> {
+    trace!("Credential::get_credential_id_by_app_id >>> app_id: {}", app_id);
+    anoncreds::get_credential_id_by_app_id(app_id)
+}
+
 pub fn get_attributes(handle: u32) -> VcxResult<String> {
     HANDLE_MAP.get(handle, |credential| {
         credential.get_attributes()
     })
 }
 
+pub fn get_offered_attributes(handle: u32) -> VcxResult<String> {
+    HANDLE_MAP.get(handle, |credential| {
+        credential.get_offered_attributes()
+    })
+}
+
 pub fn get_attachment(handle: u32) -> VcxResult<String> {
     HANDLE_MAP.get(handle, |credential| {
         credential.get_attachment()
@@ -185,9 +219,19 @@ pub fn generate_credential_request_msg(handle: u32, _my_pw_did: &str, _their_pw_
 }
 
 pub fn send_credential_request(handle: u32, connection_handle: u32) -> VcxResult<u32> {
-    trace!("Credential::send_credential_request >>> credential_handle: {}, connection_handle: {}", handle, connection_handle);
+    send_credential_request_with_id(handle, connection_handle, None)
+}
+
+/**
+Sends credential request like `send_credential_request`, the received credential keeps the id generated by libindy
+and is tagged with `app_id` chosen by the application, so its id can be looked up by `get_credential_id_by_app_id`.
+Application ids are unique, if another credential is tagged with `app_id` already, the received credential
+is not stored and the holder sends problem report to the issuer.
+ */
+pub fn send_credential_request_with_id(handle: u32, connection_handle: u32, app_id: Option<&str>) -> VcxResult<u32> {
+    trace!("Credential::send_credential_request >>> credential_handle: {}, connection_handle: {}, app_id: {:?}", handle, connection_handle, app_id);
     HANDLE_MAP.get_mut(handle, |credential| {
-        credential.send_request(connection_handle)?;
+        credential.send_request(connection_handle, app_id.map(String::from))?;
         let new_credential = credential.clone(); // TODO: Why are we doing this exactly?
         *credential = new_credential;
         Ok(error::SUCCESS.code_num)
@@ -276,6 +320,9 @@ pub mod tests {
     use crate::utils::devsetup::*;
     use crate::utils::mockdata::mockdata_credex::{ARIES_CREDENTIAL_OFFER, ARIES_CREDENTIAL_RESPONSE, CREDENTIAL_SM_FINISHED};
     use crate::utils::mockdata::mockdata_credex;
+    use crate::utils::mockdata::mockdata_proof::ARIES_PROOF_REQUEST_PRESENTATION;
+    use crate::utils::constants::{ARIES_PROVER_CREDENTIALS, ARIES_PROVER_SELF_ATTESTED_ATTRS};
+    use crate::disclosed_proof;
 
     use super::*;
 
@@ -373,6 +420,34 @@ pub mod tests {
         assert_eq!(offer_attrs, offer_attrs_expected);
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_credential_tagged_with_app_id_is_used_in_presentation() {
+        let _setup = SetupMocks::init();
+
+        let handle_conn = connection::tests::build_test_connection_inviter_requested();
+        let handle_cred = credential_create_with_offer("TEST_CREDENTIAL", &_get_offer(handle_conn)).unwrap();
+        assert_eq!(send_credential_request_with_id(handle_cred, handle_conn, Some("")).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+        send_credential_request_with_id(handle_cred, handle_conn, Some("app-credential-1")).unwrap();
+
+        AgencyMockDecrypted::set_next_decrypted_response(GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(ARIES_CREDENTIAL_RESPONSE);
+        update_state(handle_cred, None, Some(handle_conn)).unwrap();
+        // libindy keeps its own id, the application id is only a tag, see `anoncreds::tests::test_credential_id_is_found_by_app_id_tag`
+        let cred_id = get_credential_id(handle_cred).unwrap();
+        assert_eq!(cred_id, "cred_id");
+
+        let cred_info: serde_json::Value = serde_json::from_str(&get_stored_credential(&cred_id).unwrap()).unwrap();
+        assert_eq!(cred_info["referent"], cred_id.as_str());
+
+        let mut selected_credentials: serde_json::Value = serde_json::from_str(ARIES_PROVER_CREDENTIALS).unwrap();
+        for (_, credential) in selected_credentials["attrs"].as_object_mut().unwrap().iter_mut() {
+            credential["credential"]["cred_info"]["referent"] = cred_info["referent"].clone();
+        }
+        let handle_proof = disclosed_proof::create_proof("TEST_PROOF", ARIES_PROOF_REQUEST_PRESENTATION).unwrap();
+        disclosed_proof::generate_proof(handle_proof, selected_credentials.to_string(), ARIES_PROVER_SELF_ATTESTED_ATTRS.to_string()).unwrap();
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     #[cfg(feature = "to_restore")] // todo: generate_credential_request_msg is not implemented for v3
@@ -7,6 +7,7 @@ use crate::error::prelude::*;
 use crate::libindy::utils::{anoncreds, ledger};
 use crate::libindy::utils::cache::update_rev_reg_ids_cache;
 use crate::libindy::utils::payments::PaymentTxn;
+use crate::utils::clock;
 use crate::utils::constants::DEFAULT_SERIALIZE_VERSION;
 use crate::utils::object_cache::ObjectCache;
 
@@ -155,7 +156,7 @@ impl CredentialDef {
         if let Some(ref rev_reg_id) = self.get_rev_reg_id() {
             if let (Ok(_), Ok(_), Ok(_)) = (anoncreds::get_cred_def_json(&self.id),
                                             anoncreds::get_rev_reg_def_json(rev_reg_id),
-                                            anoncreds::get_rev_reg(rev_reg_id, time::get_time().sec as u64)) {
+                                            anoncreds::get_rev_reg(rev_reg_id, clock::now_secs())) {
                 self.state = PublicEntityStateType::Published
             }
         } else {
@@ -442,6 +443,14 @@ pub fn get_cred_def_id(handle: u32) -> VcxResult<String> {
     })
 }
 
+/**
+Returns handle of the credential definition with the given id, if it is loaded in memory.
+ */
+pub fn find_by_id(cred_def_id: &str) -> VcxResult<Option<u32>> {
+    let cred_defs = CREDENTIALDEF_MAP.map(|cd| Ok(cd.get_cred_def_id() == cred_def_id))?;
+    Ok(cred_defs.into_iter().find(|(_, matches)| *matches).map(|(handle, _)| handle))
+}
+
 pub fn get_rev_reg_id(handle: u32) -> VcxResult<String> {
     CREDENTIALDEF_MAP.get(handle, |c| {
         c.get_rev_reg_id().cloned().ok_or(VcxError::from_msg(VcxErrorKind::InvalidState, "No revocation registry found - does this credential definiton support revocation?"))
@@ -1,10 +1,15 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
 use serde_json;
 
 use agency_client::mocking::AgencyMockDecrypted;
 
+use crate::api::VcxStateType;
 use crate::connection;
 use crate::aries::{
     handlers::proof_presentation::prover::prover::Prover,
+    messages::proof_presentation::presentation_proposal::PresentationPreview,
     messages::proof_presentation::presentation_request::PresentationRequest,
 };
 use crate::error::prelude::*;
@@ -16,6 +21,9 @@ use crate::utils::object_cache::ObjectCache;
 
 lazy_static! {
     static ref HANDLE_MAP: ObjectCache<Prover> = ObjectCache::<Prover>::new("disclosed-proofs-cache");
+    // prover handle -> (handle of the connection its presentation request was received on, id of the request,
+    // uid of the agency message the request was received in)
+    static ref RECEIVED_REQUESTS: Mutex<HashMap<u32, (u32, String, String)>> = Mutex::new(HashMap::new());
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -114,10 +122,16 @@ pub fn from_string(proof_data: &str) -> VcxResult<u32> {
 }
 
 pub fn release(handle: u32) -> VcxResult<()> {
+    if let Ok(mut received_requests) = RECEIVED_REQUESTS.lock() {
+        received_requests.remove(&handle);
+    }
     HANDLE_MAP.release(handle).map_err(handle_err)
 }
 
 pub fn release_all() {
+    if let Ok(mut received_requests) = RECEIVED_REQUESTS.lock() {
+        received_requests.clear();
+    }
     HANDLE_MAP.drain().ok();
 }
 
@@ -133,6 +147,18 @@ pub fn send_proof(handle: u32, connection_handle: u32) -> VcxResult<u32> {
         let new_proof = proof.clone();
         *proof = new_proof;
         Ok(error::SUCCESS.code_num)
+    })?;
+    _mark_received_request_answered(handle);
+    Ok(error::SUCCESS.code_num)
+}
+
+/**
+Returns generated presentation packed for the service of the proof request, to be delivered to the verifier
+out of band instead of over connection. See `Prover::export_presentation`.
+ */
+pub fn export_presentation(handle: u32) -> VcxResult<String> {
+    HANDLE_MAP.get(handle, |proof| {
+        proof.export_presentation()
     })
 }
 
@@ -149,28 +175,88 @@ pub fn reject_proof(handle: u32, connection_handle: u32) -> VcxResult<u32> {
         let new_proof = proof.clone();
         *proof = new_proof;
         Ok(error::SUCCESS.code_num)
-    })
+    })?;
+    _mark_received_request_answered(handle);
+    Ok(error::SUCCESS.code_num)
 }
 
 pub fn generate_proof(handle: u32, credentials: String, self_attested_attrs: String) -> VcxResult<u32> {
+    generate_proof_in_profile(handle, credentials, self_attested_attrs, None)
+}
+
+/**
+Same as `generate_proof`, but the proof is created in the wallet of the given wallet profile.
+ */
+pub fn generate_proof_in_profile(handle: u32, credentials: String, self_attested_attrs: String, wallet_profile: Option<&str>) -> VcxResult<u32> {
     HANDLE_MAP.get_mut(handle, |proof| {
-        proof.generate_presentation(credentials.clone(), self_attested_attrs.clone())?;
+        proof.generate_presentation(credentials.clone(), self_attested_attrs.clone(), wallet_profile)?;
         Ok(error::SUCCESS.code_num)
     }).map(|_| error::SUCCESS.code_num)
 }
 
+/**
+Returns attribute values and predicates `generate_proof` would disclose for the same arguments, so the user can
+confirm them before the proof is generated.
+ */
+pub fn preview_presentation(handle: u32, credentials: &str, self_attested_attrs: &str) -> VcxResult<PresentationPreview> {
+    HANDLE_MAP.get(handle, |proof| {
+        proof.preview_presentation(credentials, self_attested_attrs)
+    })
+}
+
+/**
+Pre-builds revocation states of the selected credentials, so generating the proof later only applies registry deltas
+published since. Takes the same selected credentials as `generate_proof`.
+ */
+pub fn warm_up_revocation_states(handle: u32, credentials: &str) -> VcxResult<()> {
+    HANDLE_MAP.get(handle, |proof| {
+        proof.warm_up_revocation_states(credentials)
+    })
+}
+
 pub fn decline_presentation_request(handle: u32, connection_handle: u32, reason: Option<String>, proposal: Option<String>) -> VcxResult<u32> {
     HANDLE_MAP.get_mut(handle, |proof| {
         proof.decline_presentation_request(connection_handle, reason.clone(), proposal.clone())?;
         let new_proof = proof.clone();
         *proof = new_proof;
         Ok(error::SUCCESS.code_num)
-    }).map(|_| error::SUCCESS.code_num)
+    })?;
+    _mark_received_request_answered(handle);
+    Ok(error::SUCCESS.code_num)
+}
+
+/**
+Declines the presentation request: the verifier receives a problem report with code `request_declined`
+and the prover finishes with the Declined status.
+ */
+pub fn decline_request(handle: u32, connection_handle: u32, reason: Option<String>) -> VcxResult<()> {
+    HANDLE_MAP.get_mut(handle, |proof| {
+        proof.decline_request(connection_handle, reason.clone())
+    }).map_err(handle_err)?;
+    _mark_received_request_answered(handle);
+    Ok(())
 }
 
 pub fn retrieve_credentials(handle: u32) -> VcxResult<String> {
+    retrieve_credentials_in_profile(handle, None)
+}
+
+/**
+Same as `retrieve_credentials`, but only credentials held in the wallet of the given wallet profile are retrieved.
+ */
+pub fn retrieve_credentials_in_profile(handle: u32, wallet_profile: Option<&str>) -> VcxResult<String> {
     HANDLE_MAP.get_mut(handle, |proof| {
-        proof.retrieve_credentials()
+        proof.retrieve_credentials(wallet_profile)
+    })
+}
+
+/**
+Selects credentials to generate the proof from, a different credential may be chosen for each referent of the
+proof request. See `Prover::select_credentials` for the format of `selection`.
+ */
+pub fn select_credentials(handle: u32, selection: &str) -> VcxResult<String> {
+    HANDLE_MAP.get(handle, |proof| {
+        proof.select_credentials(selection, None)
     })
 }
 
@@ -216,12 +302,94 @@ pub fn get_proof_request_messages(connection_handle: u32) -> VcxResult<String> {
     Ok(json!(presentation_requests).to_string())
 }
 
+/**
+Returns (handle, source_id, state) of every disclosed proof in memory, in ascending order of handles.
+ */
+pub fn list() -> VcxResult<Vec<(u32, String, u32)>> {
+    let proofs = HANDLE_MAP.map(|proof| Ok((proof.get_source_id(), proof.state())))?;
+    Ok(proofs.into_iter().map(|(handle, (source_id, state))| (handle, source_id, state)).collect())
+}
+
+/**
+Records presentation request received on the connection in agency message `uid` by creating a prover for it, its
+source id is the id of the request. Request which was already recorded for the connection is ignored. Called by
+connection `update_state` for requests not consumed by application message handlers. The agency message is left
+unprocessed until the request is answered, so requests recorded before restart are recorded again.
+ */
+pub fn record_received_request(connection_handle: u32, uid: &str, presentation_request: &PresentationRequest) -> VcxResult<()> {
+    let mut received_requests = RECEIVED_REQUESTS.lock()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::PoisonedLock, "Cannot lock received presentation requests"))?;
+    received_requests.retain(|handle, _| HANDLE_MAP.has_handle(*handle));
+
+    // provers are not locked here, the connection is locked by update_state and provers may be locked while sending
+    let recorded = received_requests.values()
+        .any(|(connection, request_id, _)| *connection == connection_handle && *request_id == presentation_request.id.0);
+    if recorded {
+        return Ok(());
+    }
+
+    let proof = Prover::create(&presentation_request.id.0, presentation_request.clone())?;
+    let handle = HANDLE_MAP.add(proof)?;
+    debug!("record_received_request >>> presentation request {} received on connection {} recorded as disclosed proof {}", presentation_request.id.0, connection_handle, handle);
+    received_requests.insert(handle, (connection_handle, presentation_request.id.0.clone(), uid.to_string()));
+    Ok(())
+}
+
+// answer is sent already, failing to mark the message only makes it downloaded again, the record keeps it from
+// being recorded twice. The connection is not updated under the lock, update_state records requests under its own.
+fn _mark_received_request_answered(handle: u32) {
+    let received_request = match RECEIVED_REQUESTS.lock() {
+        Ok(received_requests) => received_requests.get(&handle).cloned(),
+        Err(_) => {
+            warn!("disclosed_proof >>> cannot lock received presentation requests");
+            return;
+        }
+    };
+    if let Some((connection_handle, request_id, uid)) = received_request {
+        match connection::update_message_status(connection_handle, uid) {
+            Ok(()) => {
+                if let Ok(mut received_requests) = RECEIVED_REQUESTS.lock() {
+                    received_requests.remove(&handle);
+                }
+            }
+            Err(err) => warn!("disclosed_proof >>> cannot mark answered presentation request {} as processed: {}", request_id, err)
+        }
+    }
+}
+
+/**
+Returns (handle, presentation request, connection handle) of disclosed proofs created for presentation requests
+received during connection `update_state`, which were not answered yet. Ordered by handles.
+ */
+pub fn list_pending_requests() -> VcxResult<Vec<(u32, PresentationRequest, u32)>> {
+    let received_requests = RECEIVED_REQUESTS.lock()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::PoisonedLock, "Cannot lock received presentation requests"))?
+        .clone();
+    let proofs = HANDLE_MAP.map(|proof| Ok((proof.state(), proof.presentation_request())))?;
+    Ok(proofs.into_iter()
+        .filter(|(_, (state, _))| *state == VcxStateType::VcxStateRequestReceived as u32)
+        .filter_map(|(handle, (_, presentation_request))| {
+            received_requests.get(&handle).map(|(connection_handle, _, _)| (handle, presentation_request, *connection_handle))
+        })
+        .collect())
+}
+
 pub fn get_source_id(handle: u32) -> VcxResult<String> {
     HANDLE_MAP.get(handle, |proof| {
         Ok(proof.get_source_id())
     }).map_err(handle_err)
 }
 
+/**
+Returns parent thread id (`~thread.pthid`) of the presentation request, which correlates the request
+with the protocol it was sent within, such as connectionless invitation.
+ */
+pub fn get_parent_thread_id(handle: u32) -> VcxResult<Option<String>> {
+    HANDLE_MAP.get(handle, |proof| {
+        Ok(proof.get_parent_thread_id())
+    }).map_err(handle_err)
+}
+
 pub fn get_presentation_status(handle: u32) -> VcxResult<u32> {
     HANDLE_MAP.get(handle, |proof| {
         Ok(proof.presentation_status())
@@ -233,17 +401,71 @@ mod tests {
     extern crate serde_json;
 
     use serde_json::Value;
+    use agency_client::mocking::AgencyMockUpdatedMessages;
+
     use crate::api::VcxStateType;
     use crate::aries::messages::proof_presentation::presentation_request::PresentationRequestData;
+    use crate::libindy::proofs::proof_request::ProofRequestData;
+    use crate::libindy::proofs::prover::prover_internal;
     use crate::utils::constants::{ARIES_PROVER_CREDENTIALS, ARIES_PROVER_SELF_ATTESTED_ATTRS, GET_MESSAGES_DECRYPTED_RESPONSE};
     use crate::utils;
+    use crate::libindy::utils::wallet;
+    use crate::settings;
     use crate::utils::devsetup::*;
     use crate::utils::mockdata::mock_settings::MockBuilder;
+    use crate::utils::mockdata::mockdata_connection::CONNECTION_SM_INVITER_COMPLETED;
     use crate::utils::mockdata::mockdata_proof;
     use crate::utils::mockdata::mockdata_proof::{ARIES_PROOF_PRESENTATION_ACK, ARIES_PROOF_REQUEST_PRESENTATION};
 
     use super::*;
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_incoming_request_is_listed_as_pending() {
+        let _setup = SetupMocks::init();
+
+        let connection_h = connection::from_string(CONNECTION_SM_INVITER_COMPLETED).unwrap();
+        let request: PresentationRequest = serde_json::from_str(ARIES_PROOF_REQUEST_PRESENTATION).unwrap();
+
+        // the request is recorded once no matter how many times it's downloaded, its message stays unprocessed
+        AgencyMockUpdatedMessages::take();
+        for _ in 0..2 {
+            AgencyMockDecrypted::set_next_decrypted_response(GET_MESSAGES_DECRYPTED_RESPONSE);
+            AgencyMockDecrypted::set_next_decrypted_message(ARIES_PROOF_REQUEST_PRESENTATION);
+            connection::update_state(connection_h).unwrap();
+            assert!(AgencyMockUpdatedMessages::take().is_empty());
+        }
+
+        let pending = list_pending_requests().unwrap();
+        assert_eq!(pending.len(), 1);
+        let (handle, pending_request, pending_connection_h) = pending[0].clone();
+        assert_eq!(pending_request, request);
+        assert_eq!(pending_connection_h, connection_h);
+        assert_eq!(get_source_id(handle).unwrap(), request.id.0);
+        assert_eq!(get_state(handle).unwrap(), VcxStateType::VcxStateRequestReceived as u32);
+
+        // a request recorded before restart is recorded again from its unprocessed message
+        release_all();
+        assert!(list_pending_requests().unwrap().is_empty());
+        AgencyMockDecrypted::set_next_decrypted_response(GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(ARIES_PROOF_REQUEST_PRESENTATION);
+        connection::update_state(connection_h).unwrap();
+        let pending = list_pending_requests().unwrap();
+        assert_eq!(pending.len(), 1);
+        let (handle, pending_request, _) = pending[0].clone();
+        assert_eq!(pending_request, request);
+
+        // answering the request marks its message as processed
+        reject_proof(handle, connection_h).unwrap();
+        let updated = AgencyMockUpdatedMessages::take();
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].0, settings::get_processed_message_status());
+        assert!(list_pending_requests().unwrap().is_empty());
+
+        release(handle).unwrap();
+        assert!(list_pending_requests().unwrap().is_empty());
+    }
+
     fn _get_proof_request_messages(connection_h: u32) -> String {
         let requests = get_proof_request_messages(connection_h).unwrap();
         let requests: Value = serde_json::from_str(&requests).unwrap();
@@ -259,6 +481,20 @@ mod tests {
         assert!(create_proof("1", ARIES_PROOF_REQUEST_PRESENTATION).unwrap() > 0);
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_parent_thread_id() {
+        let _setup = SetupMocks::init();
+
+        let handle = create_proof("1", ARIES_PROOF_REQUEST_PRESENTATION).unwrap();
+        assert_eq!(None, get_parent_thread_id(handle).unwrap());
+
+        let mut proof_request: Value = serde_json::from_str(ARIES_PROOF_REQUEST_PRESENTATION).unwrap();
+        proof_request["~thread"] = json!({"thid": "4e62363d-6348-4b59-9d98-a86497f9301b", "pthid": "invitation-id"});
+        let handle = create_proof("2", &proof_request.to_string()).unwrap();
+        assert_eq!(Some("invitation-id".to_string()), get_parent_thread_id(handle).unwrap());
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_create_fails() {
@@ -293,6 +529,23 @@ mod tests {
         assert_eq!(VcxStateType::VcxStateAccepted as u32, get_state(handle_proof).unwrap());
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_proof_in_wallet_profile() {
+        let _setup = SetupMocks::init();
+
+        let handle = create_proof("TEST_CREDENTIAL", ARIES_PROOF_REQUEST_PRESENTATION).unwrap();
+
+        assert_eq!(retrieve_credentials_in_profile(handle, Some("test_proof_in_wallet_profile")).unwrap_err().kind(), VcxErrorKind::InvalidWalletHandle);
+        assert_eq!(generate_proof_in_profile(handle, ARIES_PROVER_CREDENTIALS.to_string(), ARIES_PROVER_SELF_ATTESTED_ATTRS.to_string(), Some("test_proof_in_wallet_profile")).unwrap_err().kind(), VcxErrorKind::InvalidWalletHandle);
+        assert_eq!(VcxStateType::VcxStateRequestReceived as u32, get_state(handle).unwrap());
+
+        wallet::register_wallet_profile("test_proof_in_wallet_profile", wallet::get_wallet_handle()).unwrap();
+        generate_proof_in_profile(handle, ARIES_PROVER_CREDENTIALS.to_string(), ARIES_PROVER_SELF_ATTESTED_ATTRS.to_string(), Some("test_proof_in_wallet_profile")).unwrap();
+        assert!(generate_proof_msg(handle).is_ok());
+        wallet::unregister_wallet_profile("test_proof_in_wallet_profile").unwrap();
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_proof_update_state_v2() {
@@ -324,6 +577,85 @@ mod tests {
         assert_eq!(VcxStateType::VcxStateAccepted as u32, get_state(handle).unwrap());
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_preview_presentation_matches_generated_presentation() {
+        let _setup = SetupMocks::init();
+
+        let connection_handle = connection::tests::build_test_connection_inviter_requested();
+        AgencyMockDecrypted::set_next_decrypted_response(GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(mockdata_proof::ARIES_PRESENTATION_REQUEST);
+        let request = _get_proof_request_messages(connection_handle);
+        let handle = create_proof("TEST_CREDENTIAL", &request).unwrap();
+
+        let preview = preview_presentation(handle, ARIES_PROVER_CREDENTIALS, ARIES_PROVER_SELF_ATTESTED_ATTRS).unwrap();
+        assert_eq!(VcxStateType::VcxStateRequestReceived as u32, get_state(handle).unwrap());
+
+        let disclosed: Vec<(&str, Option<&str>, Option<&str>)> = preview.attributes.iter()
+            .map(|attr| (attr.name.as_str(), attr.value.as_deref(), attr.cred_def_id.as_deref()))
+            .collect();
+        let cred_def_id = Some("V4SGRU86Z58d6TV7PBUe6f:3:CL:39:tag1");
+        assert_eq!(disclosed, vec![
+            ("name", Some("alice"), cred_def_id),
+            ("last_name", Some("clark"), cred_def_id),
+            ("sex", Some("female"), cred_def_id),
+            ("date", Some("05-2018"), cred_def_id),
+            ("degree", Some("maths"), cred_def_id),
+            ("nickname", Some("Smith"), None),
+        ]);
+        assert_eq!(preview.predicates.len(), 1);
+        assert_eq!((preview.predicates[0].name.as_str(), preview.predicates[0].predicate.as_str(), preview.predicates[0].threshold), ("age", ">=", 20));
+
+        // every disclosed referent of the preview is requested from the credential the presentation is generated from
+        let proof_request: ProofRequestData = serde_json::from_str(&get_proof_request_data(handle).unwrap()).unwrap();
+        let identifiers = prover_internal::credential_def_identifiers(ARIES_PROVER_CREDENTIALS, &proof_request).unwrap();
+        let requested: Value = serde_json::from_str(&prover_internal::build_requested_credentials_json(&identifiers, ARIES_PROVER_SELF_ATTESTED_ATTRS, &proof_request).unwrap()).unwrap();
+        let mut referents: Vec<&String> = requested["requested_attributes"].as_object().unwrap().keys()
+            .chain(requested["self_attested_attributes"].as_object().unwrap().keys())
+            .collect();
+        referents.sort();
+        assert_eq!(referents, vec!["attribute_0", "attribute_1", "attribute_2", "attribute_3"]);
+        assert_eq!(requested["requested_predicates"].as_object().unwrap().keys().collect::<Vec<_>>(), vec!["predicate_0"]);
+
+        generate_proof(handle, ARIES_PROVER_CREDENTIALS.to_string(), ARIES_PROVER_SELF_ATTESTED_ATTRS.to_string()).unwrap();
+        send_proof(handle, connection_handle).unwrap();
+        assert_eq!(VcxStateType::VcxStateOfferSent as u32, get_state(handle).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_proof_resumed_after_restart() {
+        let _setup = SetupMocks::init();
+
+        let connection_handle = connection::tests::build_test_connection_inviter_requested();
+
+        let handle = create_proof("TEST_CREDENTIAL", mockdata_proof::ARIES_PRESENTATION_REQUEST).unwrap();
+        generate_proof(handle, ARIES_PROVER_CREDENTIALS.to_string(), ARIES_PROVER_SELF_ATTESTED_ATTRS.to_string()).unwrap();
+
+        let serialized = to_string(handle).unwrap();
+        release(handle).unwrap();
+        let handle = from_string(&serialized).unwrap();
+        assert_eq!(VcxStateType::VcxStateRequestReceived as u32, get_state(handle).unwrap());
+
+        send_proof(handle, connection_handle).unwrap();
+        assert_eq!(VcxStateType::VcxStateOfferSent as u32, get_state(handle).unwrap());
+
+        let serialized_connection = connection::to_string(connection_handle).unwrap();
+        let serialized = to_string(handle).unwrap();
+        connection::release(connection_handle).unwrap();
+        release(handle).unwrap();
+
+        connection::from_string(&serialized_connection).unwrap();
+        let handle = from_string(&serialized).unwrap();
+        assert_eq!(VcxStateType::VcxStateOfferSent as u32, get_state(handle).unwrap());
+
+        AgencyMockDecrypted::set_next_decrypted_response(GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(mockdata_proof::ARIES_PROOF_PRESENTATION_ACK);
+
+        update_state(handle, None, None).unwrap();
+        assert_eq!(VcxStateType::VcxStateAccepted as u32, get_state(handle).unwrap());
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_proof_reject_cycle() {
@@ -62,6 +62,12 @@ pub enum VcxErrorKind {
     DeleteConnection,
     #[fail(display = "Error with Connection")]
     GeneralConnectionError,
+    #[fail(display = "Connection was deactivated and can no longer exchange messages")]
+    ConnectionDeactivated,
+    #[fail(display = "Connection counterparty DID does not match the expected DID")]
+    ConnectionDidMismatch,
+    #[fail(display = "Message cannot be handled in the current state")]
+    InvalidMessageForState,
 
     // Payment
     #[fail(display = "No payment information associated with object")]
@@ -116,6 +122,20 @@ pub enum VcxErrorKind {
     InvalidDisclosedProofHandle,
     #[fail(display = "Proof had invalid format")]
     InvalidProof,
+    #[fail(display = "Presentation for this proof request nonce was already received")]
+    ReplayedPresentation,
+    #[fail(display = "Presentation contains credential of untrusted issuer")]
+    UntrustedIssuer,
+    #[fail(display = "Operation was cancelled")]
+    Cancelled,
+    #[fail(display = "Send rate limit of the connection was exceeded")]
+    RateLimited,
+    #[fail(display = "Message has expired")]
+    MessageExpired,
+    #[fail(display = "Operation timed out")]
+    Timeout,
+    #[fail(display = "Agency is unavailable")]
+    AgencyUnavailable,
     #[fail(display = "Schema was invalid or corrupt")]
     InvalidSchema,
     #[fail(display = "The Proof received does not have valid credentials listed.")]
@@ -226,14 +246,34 @@ pub enum VcxErrorKind {
     PoisonedLock,
 }
 
+impl VcxErrorKind {
+    /**
+    Errors of communication with agencies which may go away when the request is repeated.
+     */
+    pub fn is_transient(&self) -> bool {
+        match self {
+            VcxErrorKind::PostMessageFailed |
+            VcxErrorKind::AgencyUnavailable |
+            VcxErrorKind::InvalidHttpResponse |
+            VcxErrorKind::IOError |
+            VcxErrorKind::RateLimited => true,
+            _ => false
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct VcxError {
-    inner: Context<VcxErrorKind>
+    inner: Context<VcxErrorKind>,
+    source: Option<Box<VcxError>>,
 }
 
 impl Fail for VcxError {
     fn cause(&self) -> Option<&dyn Fail> {
-        self.inner.cause()
+        match &self.source {
+            Some(source) => Some(source.as_ref()),
+            None => self.inner.cause()
+        }
     }
 
     fn backtrace(&self) -> Option<&Backtrace> {
@@ -261,22 +301,65 @@ impl fmt::Display for VcxError {
 impl VcxError {
     pub fn from_msg<D>(kind: VcxErrorKind, msg: D) -> VcxError
         where D: fmt::Display + fmt::Debug + Send + Sync + 'static {
-        VcxError { inner: Context::new(msg).context(kind) }
+        VcxError { inner: Context::new(msg).context(kind), source: None }
     }
 
     pub fn kind(&self) -> VcxErrorKind {
         *self.inner.get_context()
     }
 
+    /// Stable numeric code of the error kind, the same value returned over the C API.
+    pub fn code(&self) -> u32 {
+        self.kind().into()
+    }
+
+    pub fn source(&self) -> Option<&VcxError> {
+        self.source.as_ref().map(|source| source.as_ref())
+    }
+
+    /// Wraps this error as the source of a new error of the same kind with a more specific message.
     pub fn extend<D>(self, msg: D) -> VcxError
         where D: fmt::Display + fmt::Debug + Send + Sync + 'static {
         let kind = self.kind();
-        VcxError { inner: self.inner.map(|_| msg).context(kind) }
+        VcxError { inner: Context::new(msg).context(kind), source: Some(Box::new(self)) }
     }
 
     pub fn map<D>(self, kind: VcxErrorKind, msg: D) -> VcxError
         where D: fmt::Display + fmt::Debug + Send + Sync + 'static {
-        VcxError { inner: self.inner.map(|_| msg).context(kind) }
+        VcxError { inner: self.inner.map(|_| msg).context(kind), source: self.source }
+    }
+
+    pub fn message(&self) -> String {
+        self.messages().first().cloned().unwrap_or(self.kind().to_string())
+    }
+
+    /// Messages of all underlying errors, starting with the closest one.
+    pub fn causes(&self) -> Vec<String> {
+        self.chain().into_iter().skip(1).collect()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "code": self.code(),
+            "kind": format!("{:?}", self.kind()),
+            "message": self.message(),
+            "causes": self.causes(),
+        })
+    }
+
+    fn messages(&self) -> Vec<String> {
+        Fail::iter_chain(&self.inner).skip(1).map(|cause| cause.to_string()).collect()
+    }
+
+    fn chain(&self) -> Vec<String> {
+        let mut chain = self.messages();
+        if chain.is_empty() {
+            chain.push(self.kind().to_string());
+        }
+        if let Some(source) = &self.source {
+            chain.extend(source.chain());
+        }
+        chain
     }
 }
 
@@ -300,13 +383,13 @@ impl From<agency_client::error::AgencyClientError> for VcxError {
 
 impl<T> From<sync::PoisonError<T>> for VcxError {
     fn from(_: sync::PoisonError<T>) -> Self {
-        VcxError { inner: Context::new(Backtrace::new()).context(VcxErrorKind::PoisonedLock) }
+        VcxError { inner: Context::new(Backtrace::new()).context(VcxErrorKind::PoisonedLock), source: None }
     }
 }
 
 impl From<Context<VcxErrorKind>> for VcxError {
     fn from(inner: Context<VcxErrorKind>) -> VcxError {
-        VcxError { inner }
+        VcxError { inner, source: None }
     }
 }
 
@@ -341,6 +424,9 @@ impl From<VcxErrorKind> for u32 {
             VcxErrorKind::InvalidInviteDetail => error::INVALID_INVITE_DETAILS.code_num,
             VcxErrorKind::InvalidRedirectDetail => error::INVALID_REDIRECT_DETAILS.code_num,
             VcxErrorKind::DeleteConnection => error::CANNOT_DELETE_CONNECTION.code_num,
+            VcxErrorKind::ConnectionDeactivated => error::CONNECTION_DEACTIVATED.code_num,
+            VcxErrorKind::ConnectionDidMismatch => error::CONNECTION_DID_MISMATCH.code_num,
+            VcxErrorKind::InvalidMessageForState => error::INVALID_MESSAGE_FOR_STATE.code_num,
             VcxErrorKind::CreateCredDef => error::CREATE_CREDENTIAL_DEF_ERR.code_num,
             VcxErrorKind::CredDefAlreadyCreated => error::CREDENTIAL_DEF_ALREADY_CREATED.code_num,
             VcxErrorKind::InvalidCredDefHandle => error::INVALID_CREDENTIAL_DEF_HANDLE.code_num,
@@ -355,6 +441,13 @@ impl From<VcxErrorKind> for u32 {
             VcxErrorKind::InvalidProofHandle => error::INVALID_PROOF_HANDLE.code_num,
             VcxErrorKind::InvalidDisclosedProofHandle => error::INVALID_DISCLOSED_PROOF_HANDLE.code_num,
             VcxErrorKind::InvalidProof => error::INVALID_PROOF.code_num,
+            VcxErrorKind::ReplayedPresentation => error::REPLAYED_PRESENTATION.code_num,
+            VcxErrorKind::UntrustedIssuer => error::UNTRUSTED_ISSUER.code_num,
+            VcxErrorKind::Cancelled => error::CANCELLED.code_num,
+            VcxErrorKind::RateLimited => error::RATE_LIMITED.code_num,
+            VcxErrorKind::MessageExpired => error::MESSAGE_EXPIRED.code_num,
+            VcxErrorKind::Timeout => error::TIMEOUT.code_num,
+            VcxErrorKind::AgencyUnavailable => error::AGENCY_UNAVAILABLE.code_num,
             VcxErrorKind::InvalidSchema => error::INVALID_SCHEMA.code_num,
             VcxErrorKind::InvalidProofCredentialData => error::INVALID_PROOF_CREDENTIAL_DATA.code_num,
             VcxErrorKind::CreateProof => error::CREATE_PROOF_ERROR.code_num,
@@ -436,6 +529,9 @@ impl From<u32> for VcxErrorKind {
             _ if { error::INVALID_INVITE_DETAILS.code_num == code } => VcxErrorKind::InvalidInviteDetail,
             _ if { error::INVALID_REDIRECT_DETAILS.code_num == code } => VcxErrorKind::InvalidRedirectDetail,
             _ if { error::CANNOT_DELETE_CONNECTION.code_num == code } => VcxErrorKind::DeleteConnection,
+            _ if { error::CONNECTION_DEACTIVATED.code_num == code } => VcxErrorKind::ConnectionDeactivated,
+            _ if { error::CONNECTION_DID_MISMATCH.code_num == code } => VcxErrorKind::ConnectionDidMismatch,
+            _ if { error::INVALID_MESSAGE_FOR_STATE.code_num == code } => VcxErrorKind::InvalidMessageForState,
             _ if { error::CREATE_CREDENTIAL_DEF_ERR.code_num == code } => VcxErrorKind::CreateCredDef,
             _ if { error::CREDENTIAL_DEF_ALREADY_CREATED.code_num == code } => VcxErrorKind::CredDefAlreadyCreated,
             _ if { error::INVALID_CREDENTIAL_DEF_HANDLE.code_num == code } => VcxErrorKind::InvalidCredDefHandle,
@@ -450,6 +546,13 @@ impl From<u32> for VcxErrorKind {
             _ if { error::INVALID_PROOF_HANDLE.code_num == code } => VcxErrorKind::InvalidProofHandle,
             _ if { error::INVALID_DISCLOSED_PROOF_HANDLE.code_num == code } => VcxErrorKind::InvalidDisclosedProofHandle,
             _ if { error::INVALID_PROOF.code_num == code } => VcxErrorKind::InvalidProof,
+            _ if { error::REPLAYED_PRESENTATION.code_num == code } => VcxErrorKind::ReplayedPresentation,
+            _ if { error::UNTRUSTED_ISSUER.code_num == code } => VcxErrorKind::UntrustedIssuer,
+            _ if { error::CANCELLED.code_num == code } => VcxErrorKind::Cancelled,
+            _ if { error::RATE_LIMITED.code_num == code } => VcxErrorKind::RateLimited,
+            _ if { error::MESSAGE_EXPIRED.code_num == code } => VcxErrorKind::MessageExpired,
+            _ if { error::TIMEOUT.code_num == code } => VcxErrorKind::Timeout,
+            _ if { error::AGENCY_UNAVAILABLE.code_num == code } => VcxErrorKind::AgencyUnavailable,
             _ if { error::INVALID_SCHEMA.code_num == code } => VcxErrorKind::InvalidSchema,
             _ if { error::INVALID_PROOF_CREDENTIAL_DATA.code_num == code } => VcxErrorKind::InvalidProofCredentialData,
             _ if { error::CREATE_PROOF_ERROR.code_num == code } => VcxErrorKind::CreateProof,
@@ -546,7 +649,8 @@ pub fn set_current_error(err: &VcxError) {
         let error_json = json!({
             "error": err.kind().to_string(),
             "message": err.to_string(),
-            "cause": Fail::find_root_cause(err).to_string(),
+            // source errors are exposed by `VcxError::to_json`, the C API error is kept as it was
+            "cause": Fail::find_root_cause(&err.inner).to_string(),
             "backtrace": err.backtrace().map(|bt| bt.to_string())
         }).to_string();
         error.replace(Some(CStringUtils::string_to_cstring(error_json)));
@@ -564,3 +668,48 @@ pub fn get_current_error_c_json() -> *const c_char {
 
     value
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_error_code_matches_kind_mapping() {
+        let err = VcxError::from_msg(VcxErrorKind::InvalidJson, "bad json");
+        assert_eq!(err.code(), error::INVALID_JSON.code_num);
+        assert_eq!(VcxErrorKind::from(err.code()), VcxErrorKind::InvalidJson);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_extend_keeps_source_chain() {
+        let err = VcxError::from_msg(VcxErrorKind::InvalidJson, "unexpected token at 1:5")
+            .extend("Cannot parse credential offer")
+            .extend("Cannot create credential");
+
+        assert_eq!(err.kind(), VcxErrorKind::InvalidJson);
+        assert_eq!(err.message(), "Cannot create credential");
+        assert_eq!(err.causes(), vec!["Cannot parse credential offer".to_string(), "unexpected token at 1:5".to_string()]);
+        assert_eq!(err.source().unwrap().message(), "Cannot parse credential offer");
+
+        // Display lists only the error itself, source errors are reachable through `Fail::cause`
+        let display = err.to_string();
+        assert!(display.contains("Caused by: Cannot create credential"));
+        assert!(!display.contains("Cannot parse credential offer"));
+        assert_eq!(Fail::iter_chain(&err).count(), 4);
+        assert_eq!(Fail::find_root_cause(&err).to_string(), "unexpected token at 1:5");
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_error_to_json() {
+        let err = VcxError::from_msg(VcxErrorKind::InvalidState, "inner").extend("outer");
+
+        let json = err.to_json();
+        assert_eq!(json["code"], json!(error::INVALID_STATE.code_num));
+        assert_eq!(json["kind"], json!("InvalidState"));
+        assert_eq!(json["message"], json!("outer"));
+        assert_eq!(json["causes"], json!(["inner"]));
+    }
+}
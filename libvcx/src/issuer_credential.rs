@@ -1,11 +1,15 @@
+use std::time::Duration;
+
 use serde_json;
 
+use crate::api::VcxStateType;
 use crate::aries::handlers::issuance::issuer::issuer::Issuer;
 use crate::aries::messages::a2a::A2AMessage;
-use crate::connection;
+use crate::{connection, credential_def};
 use crate::error::prelude::*;
 use crate::utils::error;
 use crate::utils::object_cache::ObjectCache;
+use crate::utils::polling;
 
 lazy_static! {
     static ref ISSUER_CREDENTIAL_MAP: ObjectCache<Issuer> = ObjectCache::<Issuer>::new("issuer-credentials-cache");
@@ -31,6 +35,59 @@ pub fn issuer_credential_create(cred_def_handle: u32,
     ISSUER_CREDENTIAL_MAP.add(issuer)
 }
 
+/**
+Offers credential of the loaded credential definition `cred_def_id` to the counterparty of the connection, keeps
+updating its state and sends the credential once requested. State is polled with exponential backoff starting at
+100ms and capped at 5s, transient agency errors are retried. If the credential is not issued within `timeout` or
+the holder rejects the offer, the issuer credential is released and the error returned. Holder still waiting on
+the issuer is sent a problem report first, so it doesn't wait for a credential which never comes.
+ */
+pub fn issue_to_connection(connection_handle: u32, cred_def_id: &str, credential_data: &str, timeout: Duration) -> VcxResult<u32> {
+    trace!("issue_to_connection >>> connection_handle: {}, cred_def_id: {}, credential_data: {}, timeout: {:?}",
+           connection_handle, cred_def_id, secret!(&credential_data), timeout);
+    let cred_def_handle = credential_def::find_by_id(cred_def_id)?
+        .ok_or(VcxError::from_msg(VcxErrorKind::InvalidCredDefHandle, format!("Credential definition {} is not loaded", cred_def_id)))?;
+    let handle = ISSUER_CREDENTIAL_MAP.add(Issuer::create(cred_def_handle, credential_data, cred_def_id)?)?;
+
+    match _issue(handle, connection_handle, timeout) {
+        Ok(()) => Ok(handle),
+        Err(err) => {
+            _abandon(handle, connection_handle, &err);
+            release(handle).ok();
+            Err(err)
+        }
+    }
+}
+
+fn _abandon(handle: u32, connection_handle: u32, err: &VcxError) {
+    let result = ISSUER_CREDENTIAL_MAP.get_mut(handle, |credential| {
+        credential.abandon(connection_handle, &err.to_string())
+    });
+    if let Err(send_err) = result {
+        warn!("issue_to_connection >>> problem report was not sent to the holder: {}", send_err);
+    }
+}
+
+fn _issue(handle: u32, connection_handle: u32, timeout: Duration) -> VcxResult<()> {
+    send_credential_offer(handle, connection_handle, None)?;
+    polling::poll_until(timeout, || {
+        update_state(handle, None, Some(connection_handle))?;
+        match get_state(handle)? {
+            state if state == VcxStateType::VcxStateRequestReceived as u32 => {
+                send_credential(handle, connection_handle)?;
+                match get_state(handle)? {
+                    state if state == VcxStateType::VcxStateAccepted as u32 => Ok(Some(())),
+                    _ => Err(VcxError::from_msg(VcxErrorKind::InvalidState, "Credential was not issued, problem report was sent to the holder"))
+                }
+            }
+            state if state == VcxStateType::VcxStateNone as u32 => {
+                Err(VcxError::from_msg(VcxErrorKind::InvalidState, "Credential was not issued, the holder rejected the offer"))
+            }
+            _ => Ok(None)
+        }
+    })?.ok_or(VcxError::from_msg(VcxErrorKind::Timeout, format!("Credential was not issued within {:?}", timeout)))
+}
+
 pub fn update_state(handle: u32, message: Option<&str>, connection_handle: Option<u32>) -> VcxResult<u32> {
     ISSUER_CREDENTIAL_MAP.get_mut(handle, |credential| {
         trace!("issuer_credential::update_state >>> ");
@@ -116,6 +173,14 @@ pub fn send_credential_offer(handle: u32, connection_handle: u32, comment: Optio
     })
 }
 
+pub fn send_counter_offer(handle: u32, connection_handle: u32, offer_json: &str) -> VcxResult<u32> {
+    trace!("send_counter_offer >>> handle: {}, connection_handle: {}, offer_json: {}", handle, connection_handle, offer_json);
+    ISSUER_CREDENTIAL_MAP.get_mut(handle, |credential| {
+        credential.send_counter_offer(connection_handle, offer_json)?;
+        Ok(error::SUCCESS.code_num)
+    })
+}
+
 pub fn generate_credential_msg(handle: u32, _my_pw_did: &str) -> VcxResult<String> {
     ISSUER_CREDENTIAL_MAP.get_mut(handle, |_| {
         Err(VcxError::from_msg(VcxErrorKind::ActionNotSupported, "Not implemented yet")) // TODO: implement
@@ -142,6 +207,19 @@ pub fn revoke_credential_local(handle: u32) -> VcxResult<()> {
     })
 }
 
+pub fn is_revoked(handle: u32) -> VcxResult<bool> {
+    ISSUER_CREDENTIAL_MAP.get(handle, |credential| {
+        Ok(credential.is_revoked())
+    })
+}
+
+pub fn send_revocation_notification(handle: u32, connection_handle: u32, comment: Option<String>) -> VcxResult<()> {
+    trace!("send_revocation_notification >>> handle: {}, connection_handle: {}, comment: {:?}", handle, connection_handle, comment);
+    ISSUER_CREDENTIAL_MAP.get(handle, |credential| {
+        credential.send_revocation_notification(connection_handle, comment.clone())
+    })
+}
+
 pub fn convert_to_map(s: &str) -> VcxResult<serde_json::Map<String, serde_json::Value>> {
     serde_json::from_str(s)
         .map_err(|_| {
@@ -170,15 +248,21 @@ pub fn get_source_id(handle: u32) -> VcxResult<String> {
 
 #[cfg(test)]
 pub mod tests {
-    use agency_client::mocking::HttpClientMockResponse;
+    use std::rc::Rc;
+
+    use agency_client::httpclient::{self, Transport};
+    use agency_client::mocking::{AgencyMockDecrypted, HttpClientMockResponse, MockTransport};
 
     use crate::{issuer_credential, settings};
-    use crate::api::VcxStateType;
+    use crate::aries::messages::error::ProblemReport as CommonProblemReport;
+    use crate::aries::messages::issuance::credential_offer::tests::thread_id;
+    use crate::aries::messages::issuance::credential_proposal::tests::_credential_proposal;
+    use crate::aries::messages::issuance::credential_request::tests::_credential_request;
     use crate::connection::tests::build_test_connection_inviter_requested;
     use crate::credential_def::tests::create_cred_def_fake;
     use crate::libindy::utils::anoncreds::libindy_create_and_store_credential_def;
     use crate::libindy::utils::LibindyMock;
-    use crate::utils::constants::{REV_REG_ID, SCHEMAS_JSON, V3_OBJECT_SERIALIZE_VERSION};
+    use crate::utils::constants::{GET_MESSAGES_DECRYPTED_RESPONSE, REV_REG_ID, SCHEMAS_JSON, V3_OBJECT_SERIALIZE_VERSION};
     #[allow(unused_imports)]
     use crate::utils::devsetup::*;
     use crate::utils::mockdata::mockdata_connection::ARIES_CONNECTION_ACK;
@@ -333,6 +417,24 @@ pub mod tests {
         assert_eq!(get_state(handle_cred).unwrap(), VcxStateType::VcxStateRequestReceived as u32);
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_update_state_picks_up_proposal_and_sends_counter_offer() {
+        let _setup = SetupMocks::init();
+
+        let handle_conn = build_test_connection_inviter_requested();
+        let handle_cred = _issuer_credential_create();
+
+        AgencyMockDecrypted::set_next_decrypted_response(GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&json!(_credential_proposal().to_a2a_message()).to_string());
+        issuer_credential::update_state(handle_cred, None, Some(handle_conn)).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&to_string(handle_cred).unwrap()).unwrap();
+        assert!(value["data"]["issuer_sm"]["state"]["ProposalReceived"].is_object());
+
+        send_counter_offer(handle_cred, handle_conn, &json!({"name": "bob"}).to_string()).unwrap();
+        assert_eq!(get_state(handle_cred).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_update_state_with_bad_message() {
@@ -350,6 +452,73 @@ pub mod tests {
         assert_eq!(get_state(handle_cred).unwrap(), VcxStateType::VcxStateOfferSent as u32);
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_issue_to_connection() {
+        let _setup = SetupMocks::init();
+
+        let handle_conn = build_test_connection_inviter_requested();
+        let cred_def_id = credential_def::get_cred_def_id(create_cred_def_fake()).unwrap();
+
+        AgencyMockDecrypted::set_next_decrypted_response(GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&json!(_credential_request().to_a2a_message()).to_string());
+        let handle_cred = issue_to_connection(handle_conn, &cred_def_id, "{\"attr\":\"value\"}", Duration::from_secs(5)).unwrap();
+
+        assert_eq!(get_state(handle_cred).unwrap(), VcxStateType::VcxStateAccepted as u32);
+        let value: serde_json::Value = serde_json::from_str(&to_string(handle_cred).unwrap()).unwrap();
+        assert!(value["data"]["issuer_sm"]["state"]["Finished"].is_object());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_issue_to_connection_fails_when_offer_is_rejected() {
+        let _setup = SetupMocks::init();
+
+        let handle_conn = build_test_connection_inviter_requested();
+        let cred_def_id = credential_def::get_cred_def_id(create_cred_def_fake()).unwrap();
+
+        let problem_report = CommonProblemReport::create()
+            .set_comment(String::from("Offer rejected"))
+            .set_thread_id(&thread_id());
+        AgencyMockDecrypted::set_next_decrypted_response(GET_MESSAGES_DECRYPTED_RESPONSE);
+        AgencyMockDecrypted::set_next_decrypted_message(&json!(problem_report.to_a2a_message()).to_string());
+        let err = issue_to_connection(handle_conn, &cred_def_id, "{\"attr\":\"value\"}", Duration::from_secs(5)).unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::InvalidState);
+
+        assert_eq!(issue_to_connection(handle_conn, "unknown-cred-def", "{}", Duration::from_secs(5)).unwrap_err().kind(),
+                   VcxErrorKind::InvalidCredDefHandle);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_issue_to_connection_reports_problem_to_holder_on_timeout() {
+        let _setup = SetupMocks::init();
+
+        let handle_conn = build_test_connection_inviter_requested();
+        let cred_def_id = credential_def::get_cred_def_id(create_cred_def_fake()).unwrap();
+        let their_endpoint = connection::get_connection_info_typed(handle_conn).unwrap().their.unwrap().service_endpoint;
+
+        let transport = Rc::new(MockTransport::default());
+        httpclient::set_mock_transport(Some(transport.clone() as Rc<dyn Transport>));
+        // offer is delivered, but the holder never answers
+        transport.queue_response(Ok((200, vec![])));
+        for _ in 0..20 {
+            transport.queue_messages("", &[]);
+        }
+        let err = issue_to_connection(handle_conn, &cred_def_id, "{\"attr\":\"value\"}", Duration::from_millis(300)).unwrap_err();
+        httpclient::set_mock_transport(None);
+        assert_eq!(err.kind(), VcxErrorKind::Timeout);
+
+        // agency requests and messages are not encrypted in test mode
+        let sent: Vec<String> = transport.sent_to(&their_endpoint).iter()
+            .map(|message| String::from_utf8(message.body.clone()).unwrap())
+            .collect();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].contains("offer-credential"));
+        assert!(sent[1].contains("problem-report"));
+        assert!(sent[1].contains(&thread_id()));
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_release_all() {
@@ -400,5 +569,37 @@ pub mod tests {
         assert_eq!(revoc_result.unwrap_err().kind(), VcxErrorKind::InvalidRevocationDetails)
     }
 
-    // todo: Write test which will use use credetial definition supporting revocation, then actually revoke credential
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_revoke_credential_publishes_delta() {
+        let _setup = SetupMocks::init();
+
+        let handle_conn = build_test_connection_inviter_requested();
+
+        let handle_cred = _issuer_credential_create();
+        send_credential_offer(handle_cred, handle_conn, None).unwrap();
+        issuer_credential::update_state(handle_cred, Some(ARIES_CREDENTIAL_REQUEST), Some(handle_conn)).unwrap();
+        issuer_credential::send_credential(handle_cred, handle_conn).unwrap();
+        assert_eq!(get_state(handle_cred).unwrap(), VcxStateType::VcxStateAccepted as u32);
+
+        let mut value: serde_json::Value = serde_json::from_str(&to_string(handle_cred).unwrap()).unwrap();
+        value["data"]["issuer_sm"]["state"]["Finished"]["revocation_info_v1"] = json!({
+            "cred_rev_id": "1",
+            "rev_reg_id": REV_REG_ID,
+            "tails_file": "/tmp/tails"
+        });
+        let handle_cred = from_string(&value.to_string()).unwrap();
+        assert!(!is_revoked(handle_cred).unwrap());
+        assert_eq!(send_revocation_notification(handle_cred, handle_conn, None).unwrap_err().kind(), VcxErrorKind::NotReady);
+
+        revoke_credential(handle_cred).unwrap();
+        assert!(is_revoked(handle_cred).unwrap());
+        assert_eq!(get_state(handle_cred).unwrap(), VcxStateType::VcxStateAccepted as u32);
+
+        send_revocation_notification(handle_cred, handle_conn, Some("Credential revoked".to_string())).unwrap();
+
+        let handle_cred = from_string(&to_string(handle_cred).unwrap()).unwrap();
+        assert!(is_revoked(handle_cred).unwrap());
+        assert_eq!(revoke_credential(handle_cred).unwrap_err().kind(), VcxErrorKind::InvalidState);
+    }
 }
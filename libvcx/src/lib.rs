@@ -24,10 +24,13 @@ extern crate serde;
 extern crate serde_derive;
 #[macro_use]
 extern crate serde_json;
+extern crate sodiumoxide;
 extern crate strum;
 #[macro_use]
 extern crate strum_macros;
 extern crate time;
+#[cfg(feature = "tracing")]
+extern crate tracing_crate as tracing;
 extern crate url;
 extern crate uuid;
 
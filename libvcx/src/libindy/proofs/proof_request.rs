@@ -1,13 +1,47 @@
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use std::vec::Vec;
 
 use serde_json;
 
 use crate::error::prelude::*;
-use crate::libindy::proofs::proof_request_internal::{AttrInfo, NonRevokedInterval, PredicateInfo};
+use crate::libindy::proofs::proof_request_internal::{AttrInfo, NonRevokedInterval, PredicateInfo, Restrictions};
 use crate::libindy::utils::anoncreds;
 use crate::utils::qualifier;
 
+/**
+Supplies nonces of proof requests in place of libindy, eg. fixed nonce for reproducible tests or nonce drawn from
+a hardware RNG. The nonce must be a decimal number of at most 80 bits.
+ */
+pub type NonceProvider = dyn Fn() -> VcxResult<String> + Send + Sync;
+
+lazy_static! {
+    static ref NONCE_PROVIDER: RwLock<Option<Arc<NonceProvider>>> = RwLock::new(None);
+}
+
+/**
+Registers provider of proof request nonces, `None` restores nonces generated by libindy.
+ */
+pub fn set_nonce_provider(provider: Option<Arc<NonceProvider>>) {
+    *NONCE_PROVIDER.write().unwrap() = provider;
+}
+
+fn generate_nonce() -> VcxResult<String> {
+    // provider is cloned out so it may build proof requests itself without deadlocking
+    let provider = NONCE_PROVIDER.read().unwrap().clone();
+    match provider {
+        Some(provider) => _validate_provided_nonce(provider()?),
+        None => anoncreds::generate_nonce()
+    }
+}
+
+fn _validate_provided_nonce(nonce: String) -> VcxResult<String> {
+    if nonce.is_empty() || !nonce.chars().all(|c| c.is_ascii_digit()) {
+        return Err(VcxError::from_msg(VcxErrorKind::InvalidProofRequest, format!("Nonce provider returned invalid nonce: {:?}", nonce)));
+    }
+    Ok(nonce)
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct ProofRequestData {
     pub nonce: String,
@@ -35,7 +69,7 @@ impl ProofRequestData {
     }
 
     pub fn set_nonce(mut self) -> VcxResult<ProofRequestData> {
-        self.nonce = anoncreds::generate_nonce()?;
+        self.nonce = generate_nonce()?;
         Ok(self)
     }
 
@@ -129,6 +163,134 @@ impl Default for ProofRequestVersion {
     }
 }
 
+/**
+Typed alternative to building proof request from json strings. Requested attributes and predicates are referenced as
+`attribute_{index}` and `predicate_{index}` in order they were added, same as when they are passed as json.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ProofRequestBuilder {
+    name: String,
+    nonce: Option<String>,
+    requested_attributes: Vec<AttrInfo>,
+    requested_predicates: Vec<PredicateInfo>,
+    non_revoked: Option<NonRevokedInterval>,
+}
+
+impl ProofRequestBuilder {
+    const PREDICATE_TYPES: [&'static str; 4] = [">=", ">", "<=", "<"];
+
+    pub fn new(name: &str) -> ProofRequestBuilder {
+        ProofRequestBuilder { name: name.to_string(), ..ProofRequestBuilder::default() }
+    }
+
+    pub fn requested_attribute(self, name: &str, restrictions: Option<Restrictions>) -> ProofRequestBuilder {
+        self.requested_attribute_info(AttrInfo {
+            name: Some(name.to_string()),
+            names: None,
+            restrictions,
+            non_revoked: None,
+            self_attest_allowed: None,
+        })
+    }
+
+    pub fn requested_attribute_group(self, names: Vec<&str>, restrictions: Option<Restrictions>) -> ProofRequestBuilder {
+        self.requested_attribute_info(AttrInfo {
+            name: None,
+            names: Some(names.into_iter().map(String::from).collect()),
+            restrictions,
+            non_revoked: None,
+            self_attest_allowed: None,
+        })
+    }
+
+    pub fn requested_attribute_info(mut self, attribute: AttrInfo) -> ProofRequestBuilder {
+        self.requested_attributes.push(attribute);
+        self
+    }
+
+    pub fn requested_predicate(mut self, name: &str, p_type: &str, p_value: i32, restrictions: Option<Restrictions>) -> ProofRequestBuilder {
+        self.requested_predicates.push(PredicateInfo {
+            name: name.to_string(),
+            p_type: p_type.to_string(),
+            p_value,
+            restrictions,
+            non_revoked: None,
+        });
+        self
+    }
+
+    pub fn non_revoked(mut self, from: Option<u64>, to: Option<u64>) -> ProofRequestBuilder {
+        self.non_revoked = match (from, to) {
+            (None, None) => None,
+            (from, to) => Some(NonRevokedInterval { from, to })
+        };
+        self
+    }
+
+    /**
+    Sets the nonce, it's generated when the request is built if not set.
+     */
+    pub fn nonce(mut self, nonce: &str) -> ProofRequestBuilder {
+        self.nonce = Some(nonce.to_string());
+        self
+    }
+
+    pub fn build(self) -> VcxResult<ProofRequestData> {
+        if self.requested_attributes.is_empty() && self.requested_predicates.is_empty() {
+            return Err(VcxError::from_msg(VcxErrorKind::InvalidProofRequest, "Proof request must contain at least one requested attribute or predicate"));
+        }
+        for attribute in self.requested_attributes.iter() {
+            match (&attribute.name, &attribute.names) {
+                (Some(_), Some(_)) => return Err(VcxError::from_msg(VcxErrorKind::InvalidProofRequest,
+                                                                    format!("Requested attribute can contain either 'name' or 'names'. Not both: {:?}", attribute))),
+                (None, None) => return Err(VcxError::from_msg(VcxErrorKind::InvalidProofRequest,
+                                                              format!("Requested attribute must contain 'name' or 'names': {:?}", attribute))),
+                (None, Some(names)) if names.is_empty() => return Err(VcxError::from_msg(VcxErrorKind::InvalidProofRequest,
+                                                                                         format!("Requested attribute 'names' can not be empty: {:?}", attribute))),
+                _ => {}
+            }
+        }
+        for predicate in self.requested_predicates.iter() {
+            if !Self::PREDICATE_TYPES.contains(&predicate.p_type.as_str()) {
+                return Err(VcxError::from_msg(VcxErrorKind::InvalidProofRequest,
+                                              format!("Unsupported predicate type {:?}, expected one of {:?}", predicate.p_type, Self::PREDICATE_TYPES)));
+            }
+        }
+        if let Some(NonRevokedInterval { from: Some(from), to: Some(to) }) = self.non_revoked {
+            if from > to {
+                return Err(VcxError::from_msg(VcxErrorKind::InvalidProofRequest,
+                                              format!("Non revoked interval starts after it ends, from: {}, to: {}", from, to)));
+            }
+        }
+        if let Some(ref nonce) = self.nonce {
+            if nonce.is_empty() || !nonce.chars().all(|c| c.is_ascii_digit()) {
+                return Err(VcxError::from_msg(VcxErrorKind::InvalidProofRequest, format!("Nonce must be a decimal number, got: {:?}", nonce)));
+            }
+        }
+
+        let nonce = match self.nonce {
+            Some(nonce) => nonce,
+            None => generate_nonce()?
+        };
+        Ok(ProofRequestData {
+            nonce,
+            name: self.name,
+            requested_attributes: self.requested_attributes
+                .into_iter()
+                .enumerate()
+                .map(|(index, attribute)| (format!("attribute_{}", index), attribute))
+                .collect(),
+            requested_predicates: self.requested_predicates
+                .into_iter()
+                .enumerate()
+                .map(|(index, predicate)| (format!("predicate_{}", index), predicate))
+                .collect(),
+            non_revoked: self.non_revoked,
+            ..ProofRequestData::default()
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::utils;
@@ -138,6 +300,45 @@ mod tests {
     use super::*;
     use serde_json::Value;
 
+    // provider is global, the guard restores the previous one even if the test panics
+    struct NonceProviderGuard(Option<Arc<NonceProvider>>);
+
+    impl NonceProviderGuard {
+        fn set(provider: Arc<NonceProvider>) -> NonceProviderGuard {
+            let previous = NONCE_PROVIDER.read().unwrap().clone();
+            set_nonce_provider(Some(provider));
+            NonceProviderGuard(previous)
+        }
+    }
+
+    impl Drop for NonceProviderGuard {
+        fn drop(&mut self) {
+            set_nonce_provider(self.0.take());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_proof_request_with_fixed_nonce_provider() {
+        let _setup = SetupDefaults::init();
+
+        let (request, built) = {
+            let _provider = NonceProviderGuard::set(Arc::new(|| Ok(String::from("123456789"))));
+            let request = ProofRequestData::create().set_nonce().unwrap();
+            let built = ProofRequestBuilder::new("Test")
+                .requested_attribute("name", None)
+                .build().unwrap();
+            (request, built)
+        };
+
+        assert_eq!(request.nonce, "123456789");
+        assert_eq!(built.nonce, "123456789");
+        assert_ne!(ProofRequestData::create().set_nonce().unwrap().nonce, "123456789");
+        // invalid nonce is checked without registering the provider, so concurrently running tests are not affected
+        assert_eq!(_validate_provided_nonce(String::from("not a number")).unwrap_err().kind(), VcxErrorKind::InvalidProofRequest);
+        assert_eq!(_validate_provided_nonce(String::new()).unwrap_err().kind(), VcxErrorKind::InvalidProofRequest);
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_proof_request_msg() {
@@ -313,4 +514,57 @@ mod tests {
 
         let _proof_req: ProofRequestData = serde_json::from_str(utils::constants::INDY_PROOF_REQ_JSON).unwrap();
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_proof_request_builder_builds_request() {
+        let _setup = SetupDefaults::init();
+
+        let restrictions = Restrictions::V2(json!([{"cred_def_id": "V4SGRU86Z58d6TV7PBUe6f:3:CL:67:tag1"}]));
+        let request = ProofRequestBuilder::new("Employment")
+            .requested_attribute("name", Some(restrictions.clone()))
+            .requested_attribute_group(vec!["street", "city"], None)
+            .requested_predicate("age", ">=", 18, Some(restrictions))
+            .non_revoked(Some(1100000000), Some(1600000000))
+            .nonce("123456")
+            .build().unwrap();
+
+        let request = serde_json::to_value(&request).unwrap();
+        assert_eq!(request["name"], "Employment");
+        assert_eq!(request["nonce"], "123456");
+        assert_eq!(request["version"], "1.0");
+        assert_eq!(request["non_revoked"], json!({"from": 1100000000, "to": 1600000000}));
+        assert_eq!(request["requested_attributes"]["attribute_0"], json!({"name": "name", "restrictions": [{"cred_def_id": "V4SGRU86Z58d6TV7PBUe6f:3:CL:67:tag1"}]}));
+        assert_eq!(request["requested_attributes"]["attribute_1"], json!({"names": ["street", "city"]}));
+        assert_eq!(request["requested_predicates"]["predicate_0"]["p_type"], ">=");
+        assert_eq!(request["requested_predicates"]["predicate_0"]["p_value"], 18);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_proof_request_builder_rejects_invalid_request() {
+        let _setup = SetupDefaults::init();
+
+        let conflicting_attribute = AttrInfo {
+            name: Some("name".to_string()),
+            names: Some(vec!["name".to_string(), "age".to_string()]),
+            restrictions: None,
+            non_revoked: None,
+            self_attest_allowed: None,
+        };
+        let err = ProofRequestBuilder::new("Test").requested_attribute_info(conflicting_attribute).build().unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::InvalidProofRequest);
+
+        let err = ProofRequestBuilder::new("Test").requested_predicate("age", "GE", 18, None).build().unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::InvalidProofRequest);
+
+        let err = ProofRequestBuilder::new("Test").requested_attribute("name", None).non_revoked(Some(20), Some(10)).build().unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::InvalidProofRequest);
+
+        let err = ProofRequestBuilder::new("Test").requested_attribute("name", None).nonce("abc").build().unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::InvalidProofRequest);
+
+        let err = ProofRequestBuilder::new("Test").build().unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::InvalidProofRequest);
+    }
 }
@@ -46,3 +46,12 @@ pub struct NonRevokedInterval {
     pub from: Option<u64>,
     pub to: Option<u64>,
 }
+
+impl NonRevokedInterval {
+    /**
+    Checks the timestamp lies within the interval, open ends of the interval are not limited.
+     */
+    pub fn contains(&self, timestamp: u64) -> bool {
+        self.from.map_or(true, |from| from <= timestamp) && self.to.map_or(true, |to| timestamp <= to)
+    }
+}
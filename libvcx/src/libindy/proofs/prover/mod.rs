@@ -1,2 +1,2 @@
 pub mod prover;
-mod prover_internal;
\ No newline at end of file
+pub mod prover_internal;
\ No newline at end of file
@@ -1,12 +1,30 @@
 use crate::error::prelude::*;
 use crate::libindy::proofs::proof_request::ProofRequestData;
 use crate::libindy::proofs::prover::prover_internal::{build_cred_defs_json_prover, build_requested_credentials_json, build_rev_states_json, build_schemas_json_prover, credential_def_identifiers};
-use crate::libindy::utils::anoncreds;
+use crate::libindy::utils::{anoncreds, wallet};
 use crate::settings;
 use crate::utils::mockdata::mock_settings::get_mock_generate_indy_proof;
 
-pub fn generate_indy_proof(credentials: &str, self_attested_attrs: &str, proof_req_data_json: &str) -> VcxResult<String> {
-    trace!("generate_indy_proof >>> credentials: {}, self_attested_attrs: {}", secret!(&credentials), secret!(&self_attested_attrs));
+/**
+Builds revocation states of the selected revocable credentials ahead of proof generation, so `generate_indy_proof`
+only needs to apply deltas published in the meantime.
+ */
+pub fn warm_up_revocation_states(credentials: &str, proof_req_data_json: &str) -> VcxResult<()> {
+    trace!("warm_up_revocation_states >>> credentials: {}", secret!(&credentials));
+
+    let proof_request: ProofRequestData = serde_json::from_str(&proof_req_data_json)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize proof request: {}", err)))?;
+
+    let mut credentials_identifiers = credential_def_identifiers(credentials, &proof_request)?;
+    build_rev_states_json(&mut credentials_identifiers)?;
+    Ok(())
+}
+
+/**
+Creates proof using credentials and link secret from the wallet of the given wallet profile, or the main wallet if no profile is given.
+ */
+pub fn generate_indy_proof(credentials: &str, self_attested_attrs: &str, proof_req_data_json: &str, wallet_profile: Option<&str>) -> VcxResult<String> {
+    trace!("generate_indy_proof >>> credentials: {}, self_attested_attrs: {}, wallet_profile: {:?}", secret!(&credentials), secret!(&self_attested_attrs), wallet_profile);
 
     match get_mock_generate_indy_proof() {
         None => {}
@@ -29,11 +47,13 @@ pub fn generate_indy_proof(credentials: &str, self_attested_attrs: &str, proof_r
     let schemas_json = build_schemas_json_prover(&credentials_identifiers)?;
     let credential_defs_json = build_cred_defs_json_prover(&credentials_identifiers)?;
 
-    let proof = anoncreds::libindy_prover_create_proof(&proof_req_data_json,
-                                                       &requested_credentials,
-                                                       settings::DEFAULT_LINK_SECRET_ALIAS,
-                                                       &schemas_json,
-                                                       &credential_defs_json,
-                                                       Some(&revoc_states_json))?;
+    let wallet_handle = wallet::get_profile_wallet_handle(wallet_profile)?;
+    let proof = anoncreds::libindy_prover_create_proof_in_wallet(wallet_handle,
+                                                                 &proof_req_data_json,
+                                                                 &requested_credentials,
+                                                                 settings::DEFAULT_LINK_SECRET_ALIAS,
+                                                                 &schemas_json,
+                                                                 &credential_defs_json,
+                                                                 Some(&revoc_states_json))?;
     Ok(proof)
 }
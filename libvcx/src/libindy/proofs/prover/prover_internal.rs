@@ -1,12 +1,14 @@
 use std::collections::HashMap;
 
-use serde_json::Value;
+use serde_json::{Map, Value};
 
+use crate::aries::messages::proof_presentation::presentation_proposal::{Attribute, PresentationPreview, Predicate};
 use crate::error::prelude::*;
 use crate::libindy::proofs::proof_request::ProofRequestData;
-use crate::libindy::proofs::proof_request_internal::NonRevokedInterval;
+use crate::libindy::proofs::proof_request_internal::{NonRevokedInterval, Restrictions};
 use crate::libindy::utils::anoncreds;
 use crate::libindy::utils::anoncreds::{get_rev_reg_def_json, get_rev_reg_delta_json};
+use crate::libindy::utils::cache::{self, RevStateCache};
 
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct CredInfoProver {
@@ -19,6 +21,59 @@ pub struct CredInfoProver {
     pub revocation_interval: Option<NonRevokedInterval>,
     pub tails_file: Option<String>,
     pub timestamp: Option<u64>,
+    /// Whether the value of the attribute is revealed in the proof, unrevealed attribute is only proven to be known
+    #[serde(default = "default_revealed")]
+    pub revealed: bool,
+}
+
+fn default_revealed() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct CredentialSelection {
+    pub cred_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tails_file: Option<String>,
+    /// Set to false to prove the attribute without revealing its value, predicates are never revealed
+    #[serde(default = "default_revealed")]
+    pub revealed: bool,
+    /// Pins non-revocation proof to the revocation registry state as of this timestamp, it must lie within
+    /// the requested non-revoked interval. The latest state within the interval is used if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+}
+
+/**
+Builds selected credentials from credentials retrieved for a proof request, taking the credential with the given
+`cred_id` for each referent. Referents may be satisfied by different credentials, the proof is then assembled from
+all of them.
+ */
+pub fn select_credentials(retrieved_credentials: &str, selection: &HashMap<String, CredentialSelection>) -> VcxResult<String> {
+    trace!("select_credentials >>> retrieved_credentials: {:?}, selection: {:?}", retrieved_credentials, selection);
+    let retrieved_credentials: Value = serde_json::from_str(retrieved_credentials)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize retrieved credentials: {}", err)))?;
+
+    let mut rtn: Value = json!({"attrs": {}, "predicates": {}});
+    for (referent, selected) in selection {
+        let credential = retrieved_credentials["attrs"][referent]
+            .as_array()
+            .and_then(|credentials| credentials.iter().find(|credential| credential["cred_info"]["referent"].as_str() == Some(&selected.cred_id)))
+            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidProofCredentialData,
+                                      format!("Credential {} does not match referent {}", selected.cred_id, referent)))?;
+
+        rtn["attrs"][referent] = json!({"credential": credential});
+        if let Some(ref tails_file) = selected.tails_file {
+            rtn["attrs"][referent]["tails_file"] = json!(tails_file);
+        }
+        if !selected.revealed {
+            rtn["attrs"][referent]["revealed"] = json!(false);
+        }
+        if let Some(timestamp) = selected.timestamp {
+            rtn["attrs"][referent]["timestamp"] = json!(timestamp);
+        }
+    }
+    Ok(rtn.to_string())
 }
 
 pub fn build_schemas_json_prover(credentials_identifiers: &Vec<CredInfoProver>) -> VcxResult<String> {
@@ -82,14 +137,28 @@ pub fn credential_def_identifiers(credentials: &str, proof_req: &ProofRequestDat
                     .as_str()
                     .map(|x| x.to_string());
 
+                let revealed = value["revealed"].as_bool().unwrap_or(true);
+
+                let revocation_interval = _get_revocation_interval(&requested_attr, &proof_req)?;
+
+                // timestamp pinned by the prover, only revocable credentials are proven non-revoked
+                let timestamp = rev_reg_id.as_ref().and(value["timestamp"].as_u64());
+                if let (Some(timestamp), Some(interval)) = (timestamp, &revocation_interval) {
+                    if !interval.contains(timestamp) {
+                        return Err(VcxError::from_msg(VcxErrorKind::InvalidProofCredentialData,
+                                                      format!("Pinned timestamp {} of \"{}\" is outside of requested non-revoked interval {:?}", timestamp, requested_attr, interval)));
+                    }
+                }
+
                 rtn.push(
                     CredInfoProver {
                         requested_attr: requested_attr.to_string(),
                         referent: referent.to_string(),
                         schema_id: schema_id.to_string(),
                         cred_def_id: cred_def_id.to_string(),
-                        revocation_interval: _get_revocation_interval(&requested_attr, &proof_req)?,
-                        timestamp: None,
+                        revocation_interval,
+                        timestamp,
+                        revealed,
                         rev_reg_id,
                         cred_rev_id,
                         tails_file,
@@ -113,6 +182,56 @@ fn _get_revocation_interval(attr_name: &str, proof_req: &ProofRequestData) -> Vc
     }
 }
 
+/**
+Returns revocation state of the credential for the latest registry state not later than `to`, together with its timestamp.
+Revocation state built earlier is kept in the wallet cache and updated with deltas published since it was built,
+so the whole history of the registry is fetched only when there's no usable cached state.
+ */
+pub fn build_rev_state(rev_reg_id: &str, cred_rev_id: &str, tails_file: &str, from: Option<u64>, to: Option<u64>) -> VcxResult<(String, u64)> {
+    trace!("build_rev_state >>> rev_reg_id: {}, cred_rev_id: {}, tails_file: {}, from: {:?}, to: {:?}", rev_reg_id, cred_rev_id, tails_file, from, to);
+
+    let cached = cache::get_rev_state_cache(rev_reg_id, cred_rev_id);
+    let rev_state = _build_rev_state(cached.clone(), rev_reg_id, cred_rev_id, tails_file, from, to)?;
+    if _is_newer_than_cached(cached.as_ref(), &rev_state) {
+        cache::set_rev_state_cache(rev_reg_id, cred_rev_id, &rev_state)?;
+    }
+    Ok((rev_state.rev_state, rev_state.timestamp))
+}
+
+// states rebuilt for an interval ending before the cached state must not replace it, the cache only moves forward
+fn _is_newer_than_cached(cached: Option<&RevStateCache>, rev_state: &RevStateCache) -> bool {
+    cached.map_or(true, |cached| rev_state.timestamp > cached.timestamp)
+}
+
+fn _build_rev_state(cached: Option<RevStateCache>, rev_reg_id: &str, cred_rev_id: &str, tails_file: &str, from: Option<u64>, to: Option<u64>) -> VcxResult<RevStateCache> {
+    let (_, rev_reg_def_json) = get_rev_reg_def_json(rev_reg_id)?;
+
+    match cached {
+        // cached state can only move forward in time, states newer than requested are rebuilt
+        Some(cached) if to.map_or(true, |to| cached.timestamp <= to) => {
+            let (_, rev_reg_delta_json, timestamp) = get_rev_reg_delta_json(rev_reg_id, Some(cached.timestamp), to)?;
+            if timestamp <= cached.timestamp {
+                trace!("build_rev_state >>> no new delta since {}, using cached revocation state", cached.timestamp);
+                return Ok(cached);
+            }
+            let rev_state = anoncreds::libindy_prover_update_revocation_state(&rev_reg_def_json,
+                                                                             &cached.rev_state,
+                                                                             &rev_reg_delta_json,
+                                                                             cred_rev_id,
+                                                                             tails_file)?;
+            Ok(RevStateCache { rev_state, timestamp })
+        }
+        _ => {
+            let (_, rev_reg_delta_json, timestamp) = get_rev_reg_delta_json(rev_reg_id, from, to)?;
+            let rev_state = anoncreds::libindy_prover_create_revocation_state(&rev_reg_def_json,
+                                                                             &rev_reg_delta_json,
+                                                                             cred_rev_id,
+                                                                             tails_file)?;
+            Ok(RevStateCache { rev_state, timestamp })
+        }
+    }
+}
+
 pub fn build_rev_states_json(credentials_identifiers: &mut Vec<CredInfoProver>) -> VcxResult<String> {
     trace!("build_rev_states_json >> credentials_identifiers: {:?}", credentials_identifiers);
     let mut rtn: Value = json!({});
@@ -121,31 +240,28 @@ pub fn build_rev_states_json(credentials_identifiers: &mut Vec<CredInfoProver>)
     for cred_info in credentials_identifiers.iter_mut() {
         if let (Some(rev_reg_id), Some(cred_rev_id), Some(tails_file)) =
         (&cred_info.rev_reg_id, &cred_info.cred_rev_id, &cred_info.tails_file) {
-            if rtn.get(&rev_reg_id).is_none() { // Does this make sense in case cred_info's for same rev_reg_ids have different revocation intervals
+            if let Some(pinned) = cred_info.timestamp {
+                // revocation state of the registry as of the timestamp pinned by the prover
+                if rtn[rev_reg_id.as_str()].get(pinned.to_string()).is_none() {
+                    let (rev_state_json, _) = build_rev_state(&rev_reg_id, &cred_rev_id, &tails_file, None, Some(pinned))?;
+                    rtn[rev_reg_id.as_str()][pinned.to_string()] = serde_json::from_str(&rev_state_json)
+                        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize RevocationState: {}", err)))?;
+                }
+                continue;
+            }
+
+            if !timestamps.contains_key(rev_reg_id) { // Does this make sense in case cred_info's for same rev_reg_ids have different revocation intervals
                 let (from, to) = if let Some(ref interval) = cred_info.revocation_interval
                 { (interval.from, interval.to) } else { (None, None) };
 
-                let (_, rev_reg_def_json) = get_rev_reg_def_json(&rev_reg_id)?;
-
-                let (rev_reg_id, rev_reg_delta_json, timestamp) = get_rev_reg_delta_json(
-                    &rev_reg_id,
-                    from,
-                    to,
-                )?;
-
-                let rev_state_json = anoncreds::libindy_prover_create_revocation_state(
-                    &rev_reg_def_json,
-                    &rev_reg_delta_json,
-                    &cred_rev_id,
-                    &tails_file,
-                )?;
+                let (rev_state_json, timestamp) = build_rev_state(&rev_reg_id, &cred_rev_id, &tails_file, from, to)?;
 
                 let rev_state_json: Value = serde_json::from_str(&rev_state_json)
                     .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize RevocationState: {}", err)))?;
 
                 // TODO: proover should be able to create multiple states of same revocation policy for different timestamps
                 // see ticket IS-1108
-                rtn[rev_reg_id.to_string()] = json!({timestamp.to_string(): rev_state_json});
+                rtn[rev_reg_id.as_str()][timestamp.to_string()] = rev_state_json;
                 cred_info.timestamp = Some(timestamp);
 
                 // Cache timestamp for future attributes that have the same rev_reg_id
@@ -175,8 +291,12 @@ pub fn build_requested_credentials_json(credentials_identifiers: &Vec<CredInfoPr
     // do same for predicates and self_attested
     if let Value::Object(ref mut map) = rtn["requested_attributes"] {
         for ref cred_info in credentials_identifiers {
-            if let Some(_) = proof_req.requested_attributes.get(&cred_info.requested_attr) {
-                let insert_val = json!({"cred_id": cred_info.referent, "revealed": true, "timestamp": cred_info.timestamp});
+            if let Some(attr_info) = proof_req.requested_attributes.get(&cred_info.requested_attr) {
+                if !cred_info.revealed && attr_info.names.is_some() {
+                    return Err(VcxError::from_msg(VcxErrorKind::InvalidProofCredentialData,
+                                                  format!("Attribute group {} can't be left unrevealed", cred_info.requested_attr)));
+                }
+                let insert_val = json!({"cred_id": cred_info.referent, "revealed": cred_info.revealed, "timestamp": cred_info.timestamp});
                 map.insert(cred_info.requested_attr.to_owned(), insert_val);
             }
         }
@@ -192,13 +312,115 @@ pub fn build_requested_credentials_json(credentials_identifiers: &Vec<CredInfoPr
     }
 
     // handle if the attribute is not revealed
-    let self_attested_attrs: Value = serde_json::from_str(self_attested_attrs)
+    let mut self_attested_attrs: Map<String, Value> = serde_json::from_str(self_attested_attrs)
         .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize self attested attributes: {}", err)))?;
-    rtn["self_attested_attributes"] = self_attested_attrs;
+
+    // credential is preferred, self attested value is only a fallback for unrestricted attributes without credential
+    for (referent, attr_info) in proof_req.requested_attributes.iter() {
+        if rtn["requested_attributes"].get(referent).is_some() {
+            if self_attested_attrs.remove(referent).is_some() {
+                debug!("build_requested_credentials_json >> using credential instead of self attested value for attribute {}", referent);
+            }
+        } else if self_attested_attrs.contains_key(referent) && _has_restrictions(&attr_info.restrictions) {
+            return Err(VcxError::from_msg(VcxErrorKind::InvalidProofCredentialData,
+                                          format!("Attribute {} is restricted, it must be provided from credential", referent)));
+        }
+    }
+    rtn["self_attested_attributes"] = Value::Object(self_attested_attrs);
 
     Ok(rtn.to_string())
 }
 
+/**
+Resolves what a presentation generated from the same `credentials` and `self_attested_attrs` discloses: values of
+revealed attributes and predicate statements, along with the credential definitions they come from.
+Disclosed referents are decided by `build_requested_credentials_json`, so the preview can't diverge from the proof.
+ */
+pub fn build_presentation_preview(credentials: &str, self_attested_attrs: &str, proof_req: &ProofRequestData) -> VcxResult<PresentationPreview> {
+    trace!("build_presentation_preview >>> credentials: {}, self_attested_attrs: {}, proof_req: {:?}", secret!(&credentials), secret!(&self_attested_attrs), proof_req);
+    let credentials_identifiers = credential_def_identifiers(credentials, proof_req)?;
+    let requested_credentials: Value = serde_json::from_str(&build_requested_credentials_json(&credentials_identifiers, self_attested_attrs, proof_req)?)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize requested credentials: {}", err)))?;
+    let credentials: Value = serde_json::from_str(credentials)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize credentials: {}", err)))?;
+
+    let mut preview = PresentationPreview::create();
+
+    for referent in _sorted_keys(&requested_credentials["requested_attributes"]) {
+        let attr_info = &proof_req.requested_attributes[&referent];
+        let cred_info = _selected_cred_info(&credentials, &referent)
+            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidProofCredentialData, format!("No credential selected for attribute {}", referent)))?;
+        let revealed = requested_credentials["requested_attributes"][&referent]["revealed"].as_bool().unwrap_or(true);
+        for name in _attribute_names(&referent, attr_info.name.as_ref(), attr_info.names.as_ref()) {
+            let value = cred_info["attrs"][&name].as_str()
+                .ok_or(VcxError::from_msg(VcxErrorKind::InvalidProofCredentialData, format!("Credential selected for {} has no attribute {}", referent, name)))?;
+            // value of unrevealed attribute is omitted, the attribute is only proven to be known
+            preview.attributes.push(Attribute {
+                name,
+                cred_def_id: cred_info["cred_def_id"].as_str().map(String::from),
+                mime_type: None,
+                value: if revealed { Some(value.to_string()) } else { None },
+                filter: None,
+            });
+        }
+    }
+
+    for referent in _sorted_keys(&requested_credentials["self_attested_attributes"]) {
+        let attr_info = proof_req.requested_attributes.get(&referent)
+            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidProofCredentialData, format!("Self attested attribute {} is not requested", referent)))?;
+        let value = requested_credentials["self_attested_attributes"][&referent].as_str().map(String::from);
+        for name in _attribute_names(&referent, attr_info.name.as_ref(), attr_info.names.as_ref()) {
+            preview.attributes.push(Attribute { name, cred_def_id: None, mime_type: None, value: value.clone(), filter: None });
+        }
+    }
+
+    for referent in _sorted_keys(&requested_credentials["requested_predicates"]) {
+        let predicate_info = &proof_req.requested_predicates[&referent];
+        let cred_info = _selected_cred_info(&credentials, &referent)
+            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidProofCredentialData, format!("No credential selected for predicate {}", referent)))?;
+        preview.predicates.push(Predicate {
+            name: predicate_info.name.clone(),
+            cred_def_id: cred_info["cred_def_id"].as_str().map(String::from),
+            predicate: predicate_info.p_type.clone(),
+            threshold: predicate_info.p_value as i64,
+            filter: None,
+        });
+    }
+
+    Ok(preview)
+}
+
+fn _sorted_keys(value: &Value) -> Vec<String> {
+    let mut keys: Vec<String> = value.as_object().map(|map| map.keys().cloned().collect()).unwrap_or_default();
+    keys.sort();
+    keys
+}
+
+fn _selected_cred_info<'a>(credentials: &'a Value, referent: &str) -> Option<&'a Value> {
+    ["attrs", "predicates"].iter()
+        .map(|section| &credentials[section][referent]["credential"]["cred_info"])
+        .find(|cred_info| cred_info.is_object())
+}
+
+fn _attribute_names(referent: &str, name: Option<&String>, names: Option<&Vec<String>>) -> Vec<String> {
+    match (name, names) {
+        (Some(name), _) => vec![name.to_string()],
+        (None, Some(names)) => names.clone(),
+        (None, None) => vec![referent.to_string()]
+    }
+}
+
+fn _has_restrictions(restrictions: &Option<Restrictions>) -> bool {
+    match restrictions {
+        None => false,
+        Some(Restrictions::V1(filters)) => !filters.is_empty(),
+        Some(Restrictions::V2(Value::Array(filters))) => !filters.is_empty(),
+        Some(Restrictions::V2(Value::Object(filter))) => !filter.is_empty(),
+        Some(Restrictions::V2(Value::Null)) => false,
+        Some(Restrictions::V2(_)) => true,
+    }
+}
+
 
 #[cfg(test)]
 pub mod tests {
@@ -247,6 +469,7 @@ pub mod tests {
             revocation_interval: None,
             tails_file: None,
             timestamp: None,
+            revealed: true,
         };
         let cred2 = CredInfoProver {
             requested_attr: "zip_2".to_string(),
@@ -258,6 +481,7 @@ pub mod tests {
             revocation_interval: None,
             tails_file: None,
             timestamp: None,
+            revealed: true,
         };
         let creds = vec![cred1, cred2];
 
@@ -281,6 +505,7 @@ pub mod tests {
             revocation_interval: None,
             tails_file: None,
             timestamp: None,
+            revealed: true,
         }];
         assert_eq!(build_cred_defs_json_prover(&credential_ids).unwrap_err().kind(), VcxErrorKind::InvalidProofCredentialData);
     }
@@ -300,6 +525,7 @@ pub mod tests {
             revocation_interval: None,
             tails_file: None,
             timestamp: None,
+            revealed: true,
         }];
         assert_eq!(build_schemas_json_prover(&credential_ids).unwrap_err().kind(), VcxErrorKind::InvalidSchema);
     }
@@ -321,6 +547,7 @@ pub mod tests {
             revocation_interval: None,
             tails_file: None,
             timestamp: None,
+            revealed: true,
         };
         let cred2 = CredInfoProver {
             requested_attr: "zip_2".to_string(),
@@ -332,6 +559,7 @@ pub mod tests {
             revocation_interval: None,
             tails_file: None,
             timestamp: None,
+            revealed: true,
         };
         let creds = vec![cred1, cred2];
 
@@ -355,6 +583,7 @@ pub mod tests {
             revocation_interval: Some(NonRevokedInterval { from: Some(123), to: Some(456) }),
             tails_file: Some(get_temp_dir_path(TEST_TAILS_FILE).to_str().unwrap().to_string()),
             timestamp: None,
+            revealed: true,
         };
         let cred2 = CredInfoProver {
             requested_attr: "zip_2".to_string(),
@@ -366,6 +595,7 @@ pub mod tests {
             revocation_interval: Some(NonRevokedInterval { from: None, to: Some(987) }),
             tails_file: None,
             timestamp: None,
+            revealed: true,
         };
         let selected_credentials: Value = json!({
            "attrs":{
@@ -426,6 +656,99 @@ pub mod tests {
         assert_eq!(creds, vec![cred1, cred2]);
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_select_credentials_from_distinct_credentials() {
+        let _setup = SetupDefaults::init();
+
+        let licence = json!({
+            "cred_info": {"referent": LICENCE_CRED_ID, "attrs": {"height": "4'11", "zip": "84000"}, "schema_id": SCHEMA_ID, "cred_def_id": CRED_DEF_ID, "rev_reg_id": null, "cred_rev_id": null},
+            "interval": null
+        });
+        let address = json!({
+            "cred_info": {"referent": ADDRESS_CRED_ID, "attrs": {"zip": "87121"}, "schema_id": ADDRESS_SCHEMA_ID, "cred_def_id": ADDRESS_CRED_DEF_ID, "rev_reg_id": null, "cred_rev_id": null},
+            "interval": null
+        });
+        let retrieved_credentials = json!({
+            "attrs": {
+                "height_1": [licence],
+                "zip_2": [licence, address]
+            }
+        }).to_string();
+        let proof_req: ProofRequestData = serde_json::from_value(json!({
+            "nonce": "123432421212",
+            "name": "proof_req_1",
+            "version": "0.1",
+            "requested_attributes": {
+                "height_1": { "name": "height" },
+                "zip_2": { "name": "zip" }
+            },
+            "requested_predicates": {}
+        })).unwrap();
+
+        let selection = map! {
+            "height_1".to_string() => CredentialSelection { cred_id: LICENCE_CRED_ID.to_string(), tails_file: None, revealed: true, timestamp: None },
+            "zip_2".to_string() => CredentialSelection { cred_id: ADDRESS_CRED_ID.to_string(), tails_file: None, revealed: true, timestamp: None }
+        };
+        let selected_credentials = select_credentials(&retrieved_credentials, &selection).unwrap();
+
+        let creds = credential_def_identifiers(&selected_credentials, &proof_req).unwrap();
+        let requested_credentials: Value = serde_json::from_str(&build_requested_credentials_json(&creds, "{}", &proof_req).unwrap()).unwrap();
+        assert_eq!(requested_credentials["requested_attributes"]["height_1"]["cred_id"], json!(LICENCE_CRED_ID));
+        assert_eq!(requested_credentials["requested_attributes"]["zip_2"]["cred_id"], json!(ADDRESS_CRED_ID));
+
+        let selection = map! {
+            "height_1".to_string() => CredentialSelection { cred_id: ADDRESS_CRED_ID.to_string(), tails_file: None, revealed: true, timestamp: None }
+        };
+        assert_eq!(select_credentials(&retrieved_credentials, &selection).unwrap_err().kind(), VcxErrorKind::InvalidProofCredentialData);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_select_credentials_with_unrevealed_attribute() {
+        let _setup = SetupDefaults::init();
+
+        let licence = json!({
+            "cred_info": {"referent": LICENCE_CRED_ID, "attrs": {"height": "4'11", "zip": "84000"}, "schema_id": SCHEMA_ID, "cred_def_id": CRED_DEF_ID, "rev_reg_id": null, "cred_rev_id": null},
+            "interval": null
+        });
+        let retrieved_credentials = json!({
+            "attrs": {
+                "height_1": [licence],
+                "zip_2": [licence]
+            }
+        }).to_string();
+        let proof_req: ProofRequestData = serde_json::from_value(json!({
+            "nonce": "123432421212",
+            "name": "proof_req_1",
+            "version": "0.1",
+            "requested_attributes": {
+                "height_1": { "name": "height" },
+                "zip_2": { "name": "zip" }
+            },
+            "requested_predicates": {}
+        })).unwrap();
+
+        let selection: HashMap<String, CredentialSelection> = serde_json::from_value(json!({
+            "height_1": {"cred_id": LICENCE_CRED_ID},
+            "zip_2": {"cred_id": LICENCE_CRED_ID, "revealed": false}
+        })).unwrap();
+        let selected_credentials = select_credentials(&retrieved_credentials, &selection).unwrap();
+
+        let creds = credential_def_identifiers(&selected_credentials, &proof_req).unwrap();
+        let requested_credentials: Value = serde_json::from_str(&build_requested_credentials_json(&creds, "{}", &proof_req).unwrap()).unwrap();
+        assert_eq!(requested_credentials["requested_attributes"]["height_1"], json!({"cred_id": LICENCE_CRED_ID, "revealed": true, "timestamp": null}));
+        assert_eq!(requested_credentials["requested_attributes"]["zip_2"], json!({"cred_id": LICENCE_CRED_ID, "revealed": false, "timestamp": null}));
+
+        // the unrevealed attribute is proven from the credential, but its value is not disclosed
+        let preview = build_presentation_preview(&selected_credentials, "{}", &proof_req).unwrap();
+        let zip = preview.attributes.iter().find(|attribute| attribute.name == "zip").unwrap();
+        assert_eq!(zip.cred_def_id, Some(CRED_DEF_ID.to_string()));
+        assert_eq!(zip.value, None);
+        let height = preview.attributes.iter().find(|attribute| attribute.name == "height").unwrap();
+        assert_eq!(height.value, Some("4'11".to_string()));
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_credential_def_identifiers_failure() {
@@ -484,6 +807,7 @@ pub mod tests {
             revocation_interval: None,
             tails_file: Some(get_temp_dir_path(TEST_TAILS_FILE).to_str().unwrap().to_string()),
             timestamp: None,
+            revealed: true,
         }];
         assert_eq!(&credential_def_identifiers(&selected_credentials.to_string(), &proof_req_no_interval()).unwrap(), &creds);
 
@@ -537,6 +861,7 @@ pub mod tests {
             revocation_interval: None,
             tails_file: None,
             timestamp: Some(800),
+            revealed: true,
         };
         let cred2 = CredInfoProver {
             requested_attr: "zip_2".to_string(),
@@ -548,6 +873,7 @@ pub mod tests {
             revocation_interval: None,
             tails_file: None,
             timestamp: Some(800),
+            revealed: true,
         };
         let creds = vec![cred1, cred2];
         let self_attested_attrs = json!({
@@ -586,6 +912,117 @@ pub mod tests {
         assert_eq!(test.to_string(), requested_credential);
     }
 
+    fn _proof_req_with_optional_credential(restrictions: Value) -> ProofRequestData {
+        serde_json::from_value(json!({
+            "nonce": "123432421212",
+            "name": "proof_req_1",
+            "version": "0.1",
+            "requested_attributes": {
+                "address_1": { "name": "address", "restrictions": restrictions }
+            },
+            "requested_predicates": {}
+        })).unwrap()
+    }
+
+    fn _address_cred_info() -> CredInfoProver {
+        CredInfoProver {
+            requested_attr: "address_1".to_string(),
+            referent: ADDRESS_CRED_ID.to_string(),
+            schema_id: ADDRESS_SCHEMA_ID.to_string(),
+            cred_def_id: ADDRESS_CRED_DEF_ID.to_string(),
+            rev_reg_id: None,
+            cred_rev_id: None,
+            revocation_interval: None,
+            tails_file: None,
+            timestamp: None,
+            revealed: true,
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_build_requested_credentials_prefers_credential_over_self_attested() {
+        let _setup = SetupMocks::init();
+
+        let self_attested_attrs = json!({"address_1": "self attested address"}).to_string();
+        let requested_credentials = build_requested_credentials_json(&vec![_address_cred_info()], &self_attested_attrs, &_proof_req_with_optional_credential(json!([]))).unwrap();
+
+        let expected = json!({
+            "self_attested_attributes": {},
+            "requested_attributes": {
+                "address_1": {"cred_id": ADDRESS_CRED_ID, "revealed": true, "timestamp": null}
+            },
+            "requested_predicates": {}
+        });
+        assert_eq!(expected, serde_json::from_str::<Value>(&requested_credentials).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_build_requested_credentials_falls_back_to_self_attested() {
+        let _setup = SetupMocks::init();
+
+        let self_attested_attrs = json!({"address_1": "self attested address"}).to_string();
+        let requested_credentials = build_requested_credentials_json(&vec![], &self_attested_attrs, &_proof_req_with_optional_credential(json!([]))).unwrap();
+
+        let expected = json!({
+            "self_attested_attributes": {"address_1": "self attested address"},
+            "requested_attributes": {},
+            "requested_predicates": {}
+        });
+        assert_eq!(expected, serde_json::from_str::<Value>(&requested_credentials).unwrap());
+
+        let restricted = _proof_req_with_optional_credential(json!([{"issuer_did": "V4SGRU86Z58d6TV7PBUe6f"}]));
+        assert_eq!(build_requested_credentials_json(&vec![], &self_attested_attrs, &restricted).unwrap_err().kind(), VcxErrorKind::InvalidProofCredentialData);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_build_rev_states_json_for_pinned_timestamp() {
+        let _setup = SetupMocks::init();
+
+        let licence = json!({
+            "cred_info": {"referent": LICENCE_CRED_ID, "attrs": {"height": "4'11"}, "schema_id": SCHEMA_ID, "cred_def_id": CRED_DEF_ID, "rev_reg_id": REV_REG_ID, "cred_rev_id": CRED_REV_ID},
+            "interval": null
+        });
+        let retrieved_credentials = json!({"attrs": {"height_1": [licence]}}).to_string();
+        let proof_req: ProofRequestData = serde_json::from_value(json!({
+            "nonce": "123432421212",
+            "name": "proof_req_1",
+            "version": "0.1",
+            "requested_attributes": {
+                "height_1": { "name": "height", "non_revoked": {"from": 100, "to": 200} }
+            },
+            "requested_predicates": {}
+        })).unwrap();
+        let tails_file = get_temp_dir_path(TEST_TAILS_FILE).to_str().unwrap().to_string();
+
+        let selection: HashMap<String, CredentialSelection> = serde_json::from_value(json!({
+            "height_1": {"cred_id": LICENCE_CRED_ID, "tails_file": tails_file, "timestamp": 150}
+        })).unwrap();
+        let selected_credentials = select_credentials(&retrieved_credentials, &selection).unwrap();
+        let mut creds = credential_def_identifiers(&selected_credentials, &proof_req).unwrap();
+        assert_eq!(creds[0].timestamp, Some(150));
+
+        // revocation state is provided for the pinned timestamp, which the proof is created for
+        let states: Value = serde_json::from_str(&build_rev_states_json(&mut creds).unwrap()).unwrap();
+        assert_eq!(states, json!({REV_REG_ID: {"150": serde_json::from_str::<Value>(REV_STATE_JSON).unwrap()}}));
+        let requested_credentials: Value = serde_json::from_str(&build_requested_credentials_json(&creds, "{}", &proof_req).unwrap()).unwrap();
+        assert_eq!(requested_credentials["requested_attributes"]["height_1"]["timestamp"], json!(150));
+
+        // the pinned timestamp must lie within the requested interval
+        let selection: HashMap<String, CredentialSelection> = serde_json::from_value(json!({
+            "height_1": {"cred_id": LICENCE_CRED_ID, "tails_file": tails_file, "timestamp": 250}
+        })).unwrap();
+        let selected_credentials = select_credentials(&retrieved_credentials, &selection).unwrap();
+        assert_eq!(credential_def_identifiers(&selected_credentials, &proof_req).unwrap_err().kind(), VcxErrorKind::InvalidProofCredentialData);
+        let selection: HashMap<String, CredentialSelection> = serde_json::from_value(json!({
+            "height_1": {"cred_id": LICENCE_CRED_ID, "tails_file": tails_file, "timestamp": 50}
+        })).unwrap();
+        let selected_credentials = select_credentials(&retrieved_credentials, &selection).unwrap();
+        assert_eq!(credential_def_identifiers(&selected_credentials, &proof_req).unwrap_err().kind(), VcxErrorKind::InvalidProofCredentialData);
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_build_rev_states_json() {
@@ -601,6 +1038,7 @@ pub mod tests {
             tails_file: Some(get_temp_dir_path(TEST_TAILS_FILE).to_str().unwrap().to_string()),
             revocation_interval: None,
             timestamp: None,
+            revealed: true,
         };
         let mut cred_info = vec![cred1];
         let states = build_rev_states_json(cred_info.as_mut()).unwrap();
@@ -629,10 +1067,160 @@ pub mod tests {
             tails_file: Some(get_temp_dir_path(TEST_TAILS_FILE).to_str().unwrap().to_string()),
             revocation_interval: None,
             timestamp: None,
+            revealed: true,
         };
         assert_eq!(build_rev_states_json(vec![cred1].as_mut()).unwrap(), "{}".to_string());
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_build_rev_state_applies_delta_to_cached_state() {
+        let _setup = SetupMocks::init();
+
+        let tails_file = get_temp_dir_path(TEST_TAILS_FILE).to_str().unwrap().to_string();
+        let cached = |timestamp| RevStateCache { rev_state: String::from("cached_state"), timestamp };
+
+        // mocked ledger has no delta newer than timestamp 1
+        let rev_state = _build_rev_state(Some(cached(1)), REV_REG_ID, CRED_REV_ID, &tails_file, None, None).unwrap();
+        assert_eq!(rev_state, cached(1));
+
+        let rev_state = _build_rev_state(Some(cached(0)), REV_REG_ID, CRED_REV_ID, &tails_file, None, None).unwrap();
+        assert_eq!(rev_state, RevStateCache { rev_state: REV_STATE_JSON.to_string(), timestamp: 1 });
+
+        // cached state is newer than requested, it's built from scratch
+        let rev_state = _build_rev_state(Some(cached(5)), REV_REG_ID, CRED_REV_ID, &tails_file, None, Some(3)).unwrap();
+        assert_eq!(rev_state, RevStateCache { rev_state: REV_STATE_JSON.to_string(), timestamp: 1 });
+
+        let rev_state = _build_rev_state(None, REV_REG_ID, CRED_REV_ID, &tails_file, None, None).unwrap();
+        assert_eq!(rev_state, RevStateCache { rev_state: REV_STATE_JSON.to_string(), timestamp: 1 });
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_rev_state_cache_is_replaced_only_by_newer_state() {
+        let _setup = SetupMocks::init();
+
+        let state = |timestamp| RevStateCache { rev_state: String::from("state"), timestamp };
+
+        assert!(_is_newer_than_cached(None, &state(1)));
+        assert!(_is_newer_than_cached(Some(&state(1)), &state(2)));
+        assert!(!_is_newer_than_cached(Some(&state(2)), &state(2)));
+        // state built for an older interval doesn't roll the cache back
+        assert!(!_is_newer_than_cached(Some(&state(5)), &state(1)));
+    }
+
+    #[cfg(feature = "pool_tests")]
+    #[test]
+    fn test_incrementally_updated_rev_state_proves_non_revocation() {
+        let _setup = SetupLibraryWalletPoolZeroFees::init();
+
+        let did = crate::settings::get_config_value(crate::settings::CONFIG_INSTITUTION_DID).unwrap();
+        let tails_file = get_temp_dir_path(TEST_TAILS_FILE).to_str().unwrap().to_string();
+        let (schema_id, _, cred_def_id, _, offer, req, _, cred_id, rev_reg_id, cred_rev_id)
+            = libindy::utils::anoncreds::tests::create_and_store_credential(crate::utils::constants::DEFAULT_SCHEMA_ATTRS, true);
+        let (rev_reg_id, cred_rev_id) = (rev_reg_id.unwrap(), cred_rev_id.unwrap());
+
+        let credentials = json!({"attrs": {"address1_1": {
+            "credential": {"cred_info": {"referent": cred_id, "schema_id": schema_id, "cred_def_id": cred_def_id, "rev_reg_id": rev_reg_id, "cred_rev_id": cred_rev_id}},
+            "tails_file": tails_file
+        }}}).to_string();
+        let proof_req = |to: u64| json!({
+            "nonce": "123432421212",
+            "name": "proof_req_1",
+            "version": "0.1",
+            "requested_attributes": {
+                "address1_1": {"name": "address1", "restrictions": [{"issuer_did": did}], "non_revoked": {"to": to}}
+            },
+            "requested_predicates": {}
+        }).to_string();
+
+        crate::libindy::proofs::prover::prover::warm_up_revocation_states(&credentials, &proof_req(crate::utils::clock::now_secs())).unwrap();
+        let warmed_up = cache::get_rev_state_cache(&rev_reg_id, &cred_rev_id).unwrap();
+
+        // another credential of the registry is revoked, publishing delta the warmed up state is missing
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        let encoded_attributes = crate::aries::handlers::issuance::issuer::utils::encode_attributes(r#"{"address1": ["Other St"], "address2": ["-"], "city": ["Draper"], "state": ["UT"], "zip": ["84000"]}"#).unwrap();
+        let (_, other_cred_rev_id, _) = anoncreds::libindy_issuer_create_credential(&offer, &req, &encoded_attributes, Some(rev_reg_id.clone()), Some(tails_file.clone())).unwrap();
+        anoncreds::revoke_credential(&tails_file, &rev_reg_id, &other_cred_rev_id.unwrap()).unwrap();
+
+        let proof_req = proof_req(crate::utils::clock::now_secs());
+        let proof = crate::libindy::proofs::prover::prover::generate_indy_proof(&credentials, "{}", &proof_req, None).unwrap();
+        let updated = cache::get_rev_state_cache(&rev_reg_id, &cred_rev_id).unwrap();
+        assert!(updated.timestamp > warmed_up.timestamp);
+
+        assert!(crate::libindy::proofs::verifier::verifier::validate_indy_proof(&proof, &proof_req).unwrap());
+    }
+
+    #[cfg(feature = "pool_tests")]
+    #[test]
+    fn test_presentation_preview_matches_generated_proof() {
+        let _setup = SetupLibraryWalletPoolZeroFees::init();
+
+        let did = crate::settings::get_config_value(crate::settings::CONFIG_INSTITUTION_DID).unwrap();
+        libindy::utils::anoncreds::tests::create_and_store_credential(crate::utils::constants::DEFAULT_SCHEMA_ATTRS, false);
+        let proof_req = json!({
+            "nonce": "123432421212",
+            "name": "proof_req_1",
+            "version": "0.1",
+            "requested_attributes": {
+                "address1_1": {"name": "address1", "restrictions": [{"issuer_did": did}]},
+                "city_2": {"name": "city", "restrictions": [{"issuer_did": did}]},
+                "nickname_3": {"name": "nickname"}
+            },
+            "requested_predicates": {
+                "zip_4": {"name": "zip", "p_type": ">=", "p_value": 84000, "restrictions": [{"issuer_did": did}]}
+            }
+        }).to_string();
+
+        let retrieved: Value = serde_json::from_str(&anoncreds::libindy_prover_get_credentials_for_proof_req(&proof_req).unwrap()).unwrap();
+        let credentials = json!({
+            "attrs": {
+                "address1_1": {"credential": retrieved["attrs"]["address1_1"][0]},
+                "city_2": {"credential": retrieved["attrs"]["city_2"][0], "revealed": false},
+            },
+            "predicates": {
+                "zip_4": {"credential": retrieved["attrs"]["zip_4"][0]}
+            }
+        }).to_string();
+        let self_attested = json!({"nickname_3": "Bob"}).to_string();
+
+        let proof: Value = serde_json::from_str(&crate::libindy::proofs::prover::prover::generate_indy_proof(&credentials, &self_attested, &proof_req, None).unwrap()).unwrap();
+        let preview = build_presentation_preview(&credentials, &self_attested, &serde_json::from_str(&proof_req).unwrap()).unwrap();
+
+        let proof_req: Value = serde_json::from_str(&proof_req).unwrap();
+        let requested_proof = &proof["requested_proof"];
+        let cred_def_id = |disclosed: &Value| proof["identifiers"][disclosed["sub_proof_index"].as_u64().unwrap() as usize]["cred_def_id"].as_str().map(String::from);
+        let name = |section: &str, referent: &str| proof_req[section][referent]["name"].as_str().unwrap().to_string();
+
+        let mut disclosed_attributes: Vec<(String, Option<String>, Option<String>)> = Vec::new();
+        for (referent, disclosed) in requested_proof["revealed_attrs"].as_object().unwrap() {
+            disclosed_attributes.push((name("requested_attributes", referent), cred_def_id(disclosed), disclosed["raw"].as_str().map(String::from)));
+        }
+        for (referent, disclosed) in requested_proof["unrevealed_attrs"].as_object().unwrap() {
+            disclosed_attributes.push((name("requested_attributes", referent), cred_def_id(disclosed), None));
+        }
+        for (referent, value) in requested_proof["self_attested_attrs"].as_object().unwrap() {
+            disclosed_attributes.push((name("requested_attributes", referent), None, value.as_str().map(String::from)));
+        }
+        disclosed_attributes.sort();
+        let mut previewed_attributes: Vec<(String, Option<String>, Option<String>)> = preview.attributes.iter()
+            .map(|attribute| (attribute.name.clone(), attribute.cred_def_id.clone(), attribute.value.clone()))
+            .collect();
+        previewed_attributes.sort();
+        assert_eq!(previewed_attributes.len(), 3);
+        assert_eq!(previewed_attributes, disclosed_attributes);
+
+        let disclosed_predicates: Vec<(String, Option<String>)> = requested_proof["predicates"].as_object().unwrap().iter()
+            .map(|(referent, disclosed)| (name("requested_predicates", referent), cred_def_id(disclosed)))
+            .collect();
+        let previewed_predicates: Vec<(String, Option<String>)> = preview.predicates.iter()
+            .map(|predicate| (predicate.name.clone(), predicate.cred_def_id.clone()))
+            .collect();
+        assert_eq!(previewed_predicates, disclosed_predicates);
+        assert_eq!(preview.predicates[0].predicate, ">=");
+        assert_eq!(preview.predicates[0].threshold, 84000);
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_get_credential_intervals_from_proof_req() {
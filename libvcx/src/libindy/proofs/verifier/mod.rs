@@ -1,2 +1,2 @@
 pub mod verifier;
-mod verifier_internal;
\ No newline at end of file
+pub mod verifier_internal;
\ No newline at end of file
@@ -1,5 +1,5 @@
 use crate::error::prelude::*;
-use crate::libindy::proofs::verifier::verifier_internal::{build_cred_defs_json_verifier, build_rev_reg_defs_json, build_rev_reg_json, build_schemas_json_verifier, get_credential_info, validate_proof_revealed_attributes};
+use crate::libindy::proofs::verifier::verifier_internal::{build_cred_defs_json_verifier, build_rev_reg_defs_json, build_rev_reg_json, build_schemas_json_verifier, get_credential_info, LedgerObjects, validate_proof_revealed_attributes, validate_proof_timestamps, validate_proof_timestamps_offline};
 use crate::libindy::utils::anoncreds;
 use crate::utils::mockdata::mock_settings::get_mock_result_for_validate_indy_proof;
 
@@ -9,6 +9,7 @@ pub fn validate_indy_proof(proof_json: &str, proof_req_json: &str) -> VcxResult<
     }
 
     validate_proof_revealed_attributes(&proof_json)?;
+    validate_proof_timestamps(&proof_json, &proof_req_json)?;
 
     let credential_data = get_credential_info(&proof_json)?;
 
@@ -35,6 +36,28 @@ pub fn validate_indy_proof(proof_json: &str, proof_req_json: &str) -> VcxResult<
                                              &rev_regs_json)
 }
 
+/**
+Verifies proof against ledger objects supplied by the caller, the ledger is never contacted.
+ */
+pub fn validate_indy_proof_offline(proof_json: &str, proof_req_json: &str, ledger_objects: &LedgerObjects) -> VcxResult<bool> {
+    validate_proof_revealed_attributes(&proof_json)?;
+    validate_proof_timestamps_offline(&proof_json, &proof_req_json)?;
+
+    let credential_data = get_credential_info(&proof_json)?;
+    let (schemas_json, credential_defs_json, rev_reg_defs_json, rev_regs_json) = ledger_objects.select(&credential_data)?;
+
+    if let Some(mock_result) = get_mock_result_for_validate_indy_proof() {
+        return mock_result;
+    }
+
+    anoncreds::libindy_verifier_verify_proof(proof_req_json,
+                                             proof_json,
+                                             &schemas_json,
+                                             &credential_defs_json,
+                                             &rev_reg_defs_json,
+                                             &rev_regs_json)
+}
+
 #[cfg(test)]
 pub mod tests {
     use crate::{libindy, utils, settings};
@@ -218,5 +241,40 @@ pub mod tests {
             assert_eq!(validate_indy_proof(&prover_proof_json, &proof_req_json).unwrap_err().kind(), VcxErrorKind::InvalidProof);
         }
     }
+
+    #[test]
+    #[cfg(feature = "pool_tests")]
+    fn test_prover_proof_for_registry_unchanged_since_before_interval_is_valid() {
+        let _setup = SetupLibraryWalletPoolZeroFees::init();
+
+        let did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap();
+        let tails_file = utils::get_temp_dir_path(utils::constants::TEST_TAILS_FILE).to_str().unwrap().to_string();
+        let (schema_id, _, cred_def_id, _, _offer, _req, _req_meta, cred_id, rev_reg_id, cred_rev_id)
+            = libindy::utils::anoncreds::tests::create_and_store_credential(utils::constants::DEFAULT_SCHEMA_ATTRS, true);
+
+        // the registry doesn't change within the interval, its latest state was published before the interval started,
+        // verifier confirms on the ledger the registry didn't change since
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        let from = utils::clock::now_secs();
+        let proof_req_json = json!({
+            "nonce": "123432421212",
+            "name": "proof_req_1",
+            "version": "0.1",
+            "requested_attributes": {
+                "address1_1": {"name": "address1", "restrictions": [{"issuer_did": did}], "non_revoked": {"from": from, "to": from + 1}}
+            },
+            "requested_predicates": {}
+        }).to_string();
+        let credentials = json!({"attrs": {"address1_1": {
+            "credential": {"cred_info": {"referent": cred_id, "schema_id": schema_id, "cred_def_id": cred_def_id, "rev_reg_id": rev_reg_id, "cred_rev_id": cred_rev_id}},
+            "tails_file": tails_file
+        }}}).to_string();
+
+        let proof_json = libindy::proofs::prover::prover::generate_indy_proof(&credentials, "{}", &proof_req_json, None).unwrap();
+        let proof: serde_json::Value = serde_json::from_str(&proof_json).unwrap();
+        assert!(proof["identifiers"][0]["timestamp"].as_u64().unwrap() < from);
+
+        assert_eq!(validate_indy_proof(&proof_json, &proof_req_json).unwrap(), true);
+    }
 }
 
@@ -1,7 +1,8 @@
 use serde_json;
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 use crate::error::prelude::*;
+use crate::libindy::proofs::proof_request::ProofRequestData;
 use crate::libindy::utils::anoncreds;
 use crate::settings;
 use crate::utils::openssl::encode;
@@ -44,6 +45,253 @@ pub fn get_credential_info(proof: &str) -> VcxResult<Vec<CredInfoVerifier>> {
     Ok(rtn)
 }
 
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct PresentationIdentifier {
+    pub schema_id: String,
+    pub cred_def_id: String,
+    pub rev_reg_id: Option<String>,
+    pub timestamp: Option<u64>,
+    pub referents: Vec<String>,
+}
+
+/**
+Returns identifiers of credentials the proof was created from, each paired with referents of the proof request
+satisfied by the credential. Self attested attributes are not backed by any credential.
+ */
+pub fn get_presentation_identifiers(proof: &str) -> VcxResult<Vec<PresentationIdentifier>> {
+    let credentials = get_credential_info(proof)?;
+
+    let proof: Value = serde_json::from_str(&proof)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize libndy proof: {}", err)))?;
+
+    let mut rtn: Vec<PresentationIdentifier> = credentials.into_iter()
+        .map(|cred_info| PresentationIdentifier {
+            schema_id: cred_info.schema_id,
+            cred_def_id: cred_info.cred_def_id,
+            rev_reg_id: cred_info.rev_reg_id,
+            timestamp: cred_info.timestamp,
+            referents: Vec::new(),
+        })
+        .collect();
+
+    for section in ["revealed_attrs", "revealed_attr_groups", "unrevealed_attrs", "predicates"].iter() {
+        if let Value::Object(ref referents) = proof["requested_proof"][section] {
+            for (referent, info) in referents {
+                let identifier = info["sub_proof_index"].as_u64()
+                    .and_then(|sub_proof_index| rtn.get_mut(sub_proof_index as usize))
+                    .ok_or(VcxError::from_msg(VcxErrorKind::InvalidProof, format!("Cannot get identifier for \"{}\" referent", referent)))?;
+                identifier.referents.push(referent.to_string());
+            }
+        }
+    }
+
+    Ok(rtn)
+}
+
+/**
+Allow-list of issuers whose credentials the verifier accepts. Credential is trusted if either its credential
+definition or the DID of its issuer is listed.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct TrustedIssuers {
+    #[serde(default)]
+    pub issuer_dids: Vec<String>,
+    #[serde(default)]
+    pub cred_def_ids: Vec<String>,
+}
+
+impl TrustedIssuers {
+    pub fn validate_proof_issuers(&self, proof: &str) -> VcxResult<()> {
+        for cred_info in get_credential_info(proof)? {
+            let issuer_did = _issuer_did(&cred_info.cred_def_id);
+            if !self.cred_def_ids.contains(&cred_info.cred_def_id) && !self.issuer_dids.iter().any(|did| did == issuer_did) {
+                return Err(VcxError::from_msg(VcxErrorKind::UntrustedIssuer,
+                                              format!("Credential definition {} of issuer {} is not trusted", cred_info.cred_def_id, issuer_did)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+Schemas, credential definitions and revocation registries supplied by the caller instead of being fetched from
+the ledger, keyed by their ids. Revocation registries are additionally keyed by timestamp the proof was created for.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct LedgerObjects {
+    #[serde(default)]
+    pub schemas: Map<String, Value>,
+    #[serde(default)]
+    pub cred_defs: Map<String, Value>,
+    #[serde(default)]
+    pub rev_reg_defs: Map<String, Value>,
+    #[serde(default)]
+    pub rev_regs: Map<String, Value>,
+    #[serde(skip)]
+    fetched: u32,
+}
+
+impl LedgerObjects {
+    /**
+    Returns schemas, credential definitions, revocation registry definitions and revocation registries json
+    required to verify proof created from given credentials, fails with the id of the first missing object.
+     */
+    pub fn select(&self, credential_data: &Vec<CredInfoVerifier>) -> VcxResult<(String, String, String, String)> {
+        let mut schemas_json = json!({});
+        let mut cred_defs_json = json!({});
+        let mut rev_reg_defs_json = json!({});
+        let mut rev_regs_json = json!({});
+
+        for cred_info in credential_data.iter() {
+            schemas_json[&cred_info.schema_id] = _ledger_object(&self.schemas, &cred_info.schema_id, "Schema")?.clone();
+            cred_defs_json[&cred_info.cred_def_id] = _ledger_object(&self.cred_defs, &cred_info.cred_def_id, "Credential definition")?.clone();
+
+            if let Some(ref rev_reg_id) = cred_info.rev_reg_id {
+                rev_reg_defs_json[rev_reg_id] = _ledger_object(&self.rev_reg_defs, rev_reg_id, "Revocation registry definition")?.clone();
+
+                if let Some(timestamp) = cred_info.timestamp {
+                    let rev_reg = _ledger_object(&self.rev_regs, rev_reg_id, "Revocation registry")?
+                        .get(&timestamp.to_string())
+                        .ok_or(VcxError::from_msg(VcxErrorKind::InvalidProofCredentialData,
+                                                  format!("Revocation registry {} for timestamp {} was not supplied", rev_reg_id, timestamp)))?;
+                    rev_regs_json[rev_reg_id][timestamp.to_string()] = rev_reg.clone();
+                }
+            }
+        }
+
+        Ok((schemas_json.to_string(), cred_defs_json.to_string(), rev_reg_defs_json.to_string(), rev_regs_json.to_string()))
+    }
+
+    /**
+    Fetches from the ledger objects required to verify proof created from given credentials which are not present yet,
+    so objects shared by several proofs are fetched only once. Returns the number of objects fetched.
+     */
+    pub fn fetch_missing(&mut self, credential_data: &Vec<CredInfoVerifier>) -> VcxResult<u32> {
+        let mut fetched = 0;
+
+        for cred_info in credential_data.iter() {
+            if !self.schemas.contains_key(&cred_info.schema_id) {
+                let (_, schema_json) = anoncreds::get_schema_json(&cred_info.schema_id)
+                    .map_err(|err| err.map(VcxErrorKind::InvalidSchema, "Cannot get schema"))?;
+                self.schemas.insert(cred_info.schema_id.clone(), _parse_ledger_object(&schema_json, "schema")?);
+                fetched += 1;
+            }
+
+            if !self.cred_defs.contains_key(&cred_info.cred_def_id) {
+                let (_, cred_def_json) = anoncreds::get_cred_def_json(&cred_info.cred_def_id)?;
+                self.cred_defs.insert(cred_info.cred_def_id.clone(), _parse_ledger_object(&cred_def_json, "credential definition")?);
+                fetched += 1;
+            }
+
+            if let Some(ref rev_reg_id) = cred_info.rev_reg_id {
+                if !self.rev_reg_defs.contains_key(rev_reg_id) {
+                    let (_, rev_reg_def_json) = anoncreds::get_rev_reg_def_json(rev_reg_id)
+                        .or(Err(VcxError::from(VcxErrorKind::InvalidRevocationDetails)))?;
+                    self.rev_reg_defs.insert(rev_reg_id.clone(), _parse_ledger_object(&rev_reg_def_json, "revocation registry definition")?);
+                    fetched += 1;
+                }
+
+                if let Some(timestamp) = cred_info.timestamp {
+                    let rev_regs = self.rev_regs.entry(rev_reg_id.clone()).or_insert(json!({}));
+                    if rev_regs.get(&timestamp.to_string()).is_none() {
+                        let (_, rev_reg_json, _) = anoncreds::get_rev_reg(rev_reg_id, timestamp)
+                            .or(Err(VcxError::from(VcxErrorKind::InvalidRevocationDetails)))?;
+                        rev_regs[timestamp.to_string()] = _parse_ledger_object(&rev_reg_json, "revocation registry")?;
+                        fetched += 1;
+                    }
+                }
+            }
+        }
+
+        self.fetched += fetched;
+        Ok(fetched)
+    }
+
+    /**
+    Returns the number of objects fetched from the ledger into these objects so far.
+     */
+    pub fn fetched(&self) -> u32 {
+        self.fetched
+    }
+}
+
+fn _parse_ledger_object(json: &str, name: &str) -> VcxResult<Value> {
+    serde_json::from_str(json)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidProofCredentialData, format!("Cannot deserialize {}: {}", name, err)))
+}
+
+fn _ledger_object<'a>(objects: &'a Map<String, Value>, id: &str, name: &str) -> VcxResult<&'a Value> {
+    objects.get(id)
+        .ok_or(VcxError::from_msg(VcxErrorKind::InvalidProofCredentialData, format!("{} {} was not supplied", name, id)))
+}
+
+fn _issuer_did(cred_def_id: &str) -> &str {
+    cred_def_id
+        .trim_start_matches("creddef:sov:")
+        .trim_start_matches("did:sov:")
+        .split(':')
+        .next()
+        .unwrap_or_default()
+}
+
+/**
+Checks that timestamps of non-revocation proofs lie within non-revoked intervals requested for the referents they
+prove. Prover can pin the timestamp, so it's never trusted on its own: timestamp before the start of the interval
+is accepted only if the ledger confirms the revocation registry didn't change since, the registry state is then
+the latest one within the interval.
+ */
+pub fn validate_proof_timestamps(proof_json: &str, proof_req_json: &str) -> VcxResult<()> {
+    _validate_proof_timestamps(proof_json, proof_req_json, _rev_reg_unchanged_since)
+}
+
+/**
+Same as `validate_proof_timestamps`, but the ledger is never contacted, so timestamps before the start of
+the interval are rejected.
+ */
+pub fn validate_proof_timestamps_offline(proof_json: &str, proof_req_json: &str) -> VcxResult<()> {
+    _validate_proof_timestamps(proof_json, proof_req_json, |_, _, _| Ok(false))
+}
+
+fn _validate_proof_timestamps<F>(proof_json: &str, proof_req_json: &str, unchanged_since: F) -> VcxResult<()>
+    where F: Fn(&str, u64, u64) -> VcxResult<bool> {
+    let proof_req: ProofRequestData = serde_json::from_str(proof_req_json)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize proof request: {}", err)))?;
+
+    for identifier in get_presentation_identifiers(proof_json)? {
+        let timestamp = match identifier.timestamp {
+            Some(timestamp) => timestamp,
+            None => continue
+        };
+        for referent in identifier.referents.iter() {
+            let interval = proof_req.requested_attributes.get(referent).and_then(|attr| attr.non_revoked.as_ref())
+                .or(proof_req.requested_predicates.get(referent).and_then(|predicate| predicate.non_revoked.as_ref()))
+                .or(proof_req.non_revoked.as_ref());
+            let interval = match interval {
+                Some(interval) if !interval.contains(timestamp) => interval,
+                _ => continue
+            };
+            let unchanged = match (interval.from, &identifier.rev_reg_id) {
+                (Some(from), Some(rev_reg_id)) if timestamp < from => unchanged_since(rev_reg_id, timestamp, from)?,
+                _ => false
+            };
+            if !unchanged {
+                return Err(VcxError::from_msg(VcxErrorKind::InvalidProof,
+                                              format!("Timestamp {} of \"{}\" is outside of requested non-revoked interval {:?}", timestamp, referent, interval)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/**
+Returns whether the revocation registry didn't change between `timestamp` and `from`, ie. the last registry
+state published as of `from` is not newer than `timestamp`.
+ */
+fn _rev_reg_unchanged_since(rev_reg_id: &str, timestamp: u64, from: u64) -> VcxResult<bool> {
+    let (_, _, state_timestamp) = anoncreds::get_rev_reg(rev_reg_id, from)?;
+    Ok(state_timestamp <= timestamp)
+}
+
 pub fn validate_proof_revealed_attributes(proof_json: &str) -> VcxResult<()> {
     if settings::indy_mocks_enabled() { return Ok(()); }
 
@@ -167,9 +415,154 @@ pub fn build_rev_reg_json(credential_data: &Vec<CredInfoVerifier>) -> VcxResult<
 pub mod tests {
     use crate::utils::constants::*;
     use crate::utils::devsetup::*;
+    use crate::utils::mockdata::mock_settings::MockBuilder;
 
     use super::*;
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_build_rev_reg_defs_json_resolves_each_namespace_from_its_pool() {
+        let _setup = SetupDefaults::init();
+
+        let sovrin_rev_reg_id = "revreg:indy:did:indy:sovrin:Th7MpTaRZVRYnPiabds81Y:4:creddef:indy:did:indy:sovrin:Th7MpTaRZVRYnPiabds81Y:3:CL:12:tag:CL_ACCUM:tag1";
+        let idunion_rev_reg_id = "revreg:indy:did:indy:idunion:test:V4SGRU86Z58d6TV7PBUe6f:4:creddef:indy:did:indy:idunion:test:V4SGRU86Z58d6TV7PBUe6f:3:CL:12:tag:CL_ACCUM:tag1";
+        let ledger_reply = |rev_reg_id: &str, tag: &str| {
+            let mut rev_reg_def: Value = serde_json::from_str(&rev_def_json()).unwrap();
+            rev_reg_def["id"] = json!(rev_reg_id);
+            rev_reg_def["tag"] = json!(tag);
+            json!({"op": "REPLY", "result": {"type": "115", "identifier": "Th7MpTaRZVRYnPiabds81Y", "reqId": 1, "seqNo": 10, "txnTime": 1600000000, "id": rev_reg_id, "data": rev_reg_def}}).to_string()
+        };
+        crate::libindy::utils::pool::set_network_pool_handle("sovrin", Some(11));
+        crate::libindy::utils::pool::set_network_pool_handle("idunion:test", Some(12));
+        let _mock_builder = MockBuilder::init()
+            .set_mock_ledger_response_for_pool(11, &ledger_reply(sovrin_rev_reg_id, "sovrin"))
+            .set_mock_ledger_response_for_pool(12, &ledger_reply(idunion_rev_reg_id, "idunion"));
+
+        let cred_info = |rev_reg_id: &str| CredInfoVerifier {
+            schema_id: SCHEMA_ID.to_string(),
+            cred_def_id: CRED_DEF_ID.to_string(),
+            rev_reg_id: Some(rev_reg_id.to_string()),
+            timestamp: None,
+        };
+        let rev_reg_defs: Value = serde_json::from_str(&build_rev_reg_defs_json(&vec![cred_info(sovrin_rev_reg_id), cred_info(idunion_rev_reg_id)]).unwrap()).unwrap();
+
+        // each definition is read from the pool of its namespace
+        assert_eq!(rev_reg_defs[sovrin_rev_reg_id]["tag"], json!("sovrin"));
+        assert_eq!(rev_reg_defs[idunion_rev_reg_id]["tag"], json!("idunion"));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_validate_proof_timestamps_enforces_requested_interval() {
+        let _setup = SetupDefaults::init();
+
+        let proof = |timestamp: u64| json!({
+            "identifiers": [
+                {"schema_id": SCHEMA_ID, "cred_def_id": CRED_DEF_ID, "rev_reg_id": REV_REG_ID, "timestamp": timestamp}
+            ],
+            "requested_proof": {
+                "revealed_attrs": {"height_1": {"sub_proof_index": 0, "raw": "4'11", "encoded": "3"}},
+                "predicates": {"age_1": {"sub_proof_index": 0}}
+            }
+        }).to_string();
+        let proof_req = json!({
+            "nonce": "123432421212",
+            "name": "proof_req_1",
+            "version": "0.1",
+            "requested_attributes": {
+                "height_1": {"name": "height", "non_revoked": {"from": 100, "to": 200}}
+            },
+            "requested_predicates": {
+                "age_1": {"name": "age", "p_type": "GE", "p_value": 18}
+            },
+            "non_revoked": {"from": 150}
+        }).to_string();
+
+        validate_proof_timestamps(&proof(150), &proof_req).unwrap();
+        validate_proof_timestamps(&proof(200), &proof_req).unwrap();
+        // past the end of the interval of the attribute
+        assert_eq!(validate_proof_timestamps(&proof(250), &proof_req).unwrap_err().kind(), VcxErrorKind::InvalidProof);
+        // before the start of the interval, the ledger is not contacted offline
+        assert_eq!(validate_proof_timestamps_offline(&proof(120), &proof_req).unwrap_err().kind(), VcxErrorKind::InvalidProof);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_validate_proof_timestamps_before_interval_is_confirmed_on_ledger() {
+        let _setup = SetupMocks::init();
+
+        let proof = |timestamp: u64| json!({
+            "identifiers": [
+                {"schema_id": SCHEMA_ID, "cred_def_id": CRED_DEF_ID, "rev_reg_id": REV_REG_ID, "timestamp": timestamp}
+            ],
+            "requested_proof": {
+                "revealed_attrs": {"height_1": {"sub_proof_index": 0, "raw": "4'11", "encoded": "3"}}
+            }
+        }).to_string();
+        let proof_req = json!({
+            "nonce": "123432421212",
+            "name": "proof_req_1",
+            "version": "0.1",
+            "requested_attributes": {
+                "height_1": {"name": "height", "non_revoked": {"from": 100, "to": 200}}
+            },
+            "requested_predicates": {}
+        }).to_string();
+
+        // mocked ledger has the registry state as of the start of the interval published at 1
+        validate_proof_timestamps(&proof(50), &proof_req).unwrap();
+        validate_proof_timestamps(&proof(1), &proof_req).unwrap();
+        // the registry changed after the proven state, before the interval started
+        assert_eq!(validate_proof_timestamps(&proof(0), &proof_req).unwrap_err().kind(), VcxErrorKind::InvalidProof);
+        assert_eq!(validate_proof_timestamps_offline(&proof(50), &proof_req).unwrap_err().kind(), VcxErrorKind::InvalidProof);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_trusted_issuers_reject_untrusted_cred_def() {
+        let _setup = SetupDefaults::init();
+
+        let proof = json!({
+            "identifiers": [
+                {"schema_id": SCHEMA_ID, "cred_def_id": CRED_DEF_ID, "rev_reg_id": null, "timestamp": null},
+                {"schema_id": ADDRESS_SCHEMA_ID, "cred_def_id": ADDRESS_CRED_DEF_ID, "rev_reg_id": null, "timestamp": null}
+            ]
+        }).to_string();
+
+        let trusted_issuers = TrustedIssuers { issuer_dids: vec![], cred_def_ids: vec![CRED_DEF_ID.to_string(), ADDRESS_CRED_DEF_ID.to_string()] };
+        trusted_issuers.validate_proof_issuers(&proof).unwrap();
+
+        let trusted_issuers = TrustedIssuers { issuer_dids: vec![_issuer_did(ADDRESS_CRED_DEF_ID).to_string()], cred_def_ids: vec![CRED_DEF_ID.to_string()] };
+        trusted_issuers.validate_proof_issuers(&proof).unwrap();
+
+        let trusted_issuers = TrustedIssuers { issuer_dids: vec![], cred_def_ids: vec![CRED_DEF_ID.to_string()] };
+        let err = trusted_issuers.validate_proof_issuers(&proof).unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::UntrustedIssuer);
+        assert!(err.to_string().contains(ADDRESS_CRED_DEF_ID));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_ledger_objects_fetch_missing_fetches_shared_objects_once() {
+        let _setup = SetupMocks::init();
+
+        let cred_info = || CredInfoVerifier {
+            schema_id: SCHEMA_ID.to_string(),
+            cred_def_id: CRED_DEF_ID.to_string(),
+            rev_reg_id: Some(REV_REG_ID.to_string()),
+            timestamp: Some(1),
+        };
+
+        let mut ledger_objects = LedgerObjects::default();
+        assert_eq!(ledger_objects.fetch_missing(&vec![cred_info()]).unwrap(), 4);
+        assert_eq!(ledger_objects.fetch_missing(&vec![cred_info()]).unwrap(), 0);
+        ledger_objects.select(&vec![cred_info()]).unwrap();
+
+        let other_timestamp = CredInfoVerifier { timestamp: Some(2), ..cred_info() };
+        assert_eq!(ledger_objects.fetch_missing(&vec![cred_info(), other_timestamp]).unwrap(), 1);
+        assert_eq!(ledger_objects.fetched(), 5);
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_build_cred_defs_json_verifier_with_multiple_credentials() {
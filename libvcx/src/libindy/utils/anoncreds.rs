@@ -1,21 +1,22 @@
 use futures::Future;
-use indy::{anoncreds, blob_storage, ledger};
+use indy::{anoncreds, blob_storage, ledger, WalletHandle};
 use serde_json;
 use serde_json::{map::Map, Value};
-use time;
 
 use crate::{libindy, settings, utils};
 use crate::error::prelude::*;
-use crate::libindy::utils::{LibindyMock, wallet::get_wallet_handle};
+use crate::libindy::utils::{LibindyMock, wallet, wallet::get_wallet_handle};
 use crate::libindy::utils::cache::{clear_rev_reg_delta_cache, get_rev_reg_delta_cache, set_rev_reg_delta_cache};
 use crate::libindy::utils::ledger::*;
 use crate::libindy::utils::payments::{pay_for_txn, PaymentTxn};
 use crate::utils::constants::{ATTRS, LIBINDY_CRED_OFFER, PROOF_REQUESTED_PREDICATES, REQUESTED_ATTRIBUTES, REV_STATE_JSON};
 use crate::utils::constants::{CREATE_CRED_DEF_ACTION, CREATE_REV_REG_DEF_ACTION, CREATE_REV_REG_DELTA_ACTION, CREATE_SCHEMA_ACTION, CRED_DEF_ID, CRED_DEF_JSON, CRED_DEF_REQ, rev_def_json, REV_REG_DELTA_JSON, REV_REG_ID, REV_REG_JSON, REVOC_REG_TYPE, SCHEMA_ID, SCHEMA_JSON, SCHEMA_TXN};
+use crate::utils::clock;
 use crate::utils::mockdata::mock_settings::get_mock_creds_retrieved_for_proof_request;
 
 const BLOB_STORAGE_TYPE: &str = "default";
 const REVOCATION_REGISTRY_TYPE: &str = "ISSUANCE_BY_DEFAULT";
+const CREDENTIAL_APP_ID_TYPE: &str = "credential_app_id";
 
 pub fn libindy_verifier_verify_proof(proof_req_json: &str,
                                      proof_json: &str,
@@ -111,10 +112,26 @@ pub fn libindy_prover_create_proof(proof_req_json: &str,
                                    schemas_json: &str,
                                    credential_defs_json: &str,
                                    revoc_states_json: Option<&str>) -> VcxResult<String> {
+    libindy_prover_create_proof_in_wallet(get_wallet_handle(),
+                                          proof_req_json,
+                                          requested_credentials_json,
+                                          master_secret_id,
+                                          schemas_json,
+                                          credential_defs_json,
+                                          revoc_states_json)
+}
+
+pub fn libindy_prover_create_proof_in_wallet(wallet_handle: WalletHandle,
+                                             proof_req_json: &str,
+                                             requested_credentials_json: &str,
+                                             master_secret_id: &str,
+                                             schemas_json: &str,
+                                             credential_defs_json: &str,
+                                             revoc_states_json: Option<&str>) -> VcxResult<String> {
     if settings::indy_mocks_enabled() { return Ok(utils::constants::PROOF_JSON.to_owned()); }
 
     let revoc_states_json = revoc_states_json.unwrap_or("{}");
-    anoncreds::prover_create_proof(get_wallet_handle(),
+    anoncreds::prover_create_proof(wallet_handle,
                                    proof_req_json,
                                    requested_credentials_json,
                                    master_secret_id,
@@ -146,7 +163,11 @@ fn close_search_handle(search_handle: i32) -> VcxResult<()> {
 }
 
 pub fn libindy_prover_get_credentials_for_proof_req(proof_req: &str) -> VcxResult<String> {
-    trace!("libindy_prover_get_credentials_for_proof_req >>> proof_req={}", proof_req);
+    libindy_prover_get_credentials_for_proof_req_in_wallet(get_wallet_handle(), proof_req)
+}
+
+pub fn libindy_prover_get_credentials_for_proof_req_in_wallet(wallet_handle: WalletHandle, proof_req: &str) -> VcxResult<String> {
+    trace!("libindy_prover_get_credentials_for_proof_req_in_wallet >>> wallet_handle={:?}, proof_req={}", wallet_handle, proof_req);
     match get_mock_creds_retrieved_for_proof_request() {
         None => {}
         Some(mocked_creds) => {
@@ -155,8 +176,6 @@ pub fn libindy_prover_get_credentials_for_proof_req(proof_req: &str) -> VcxResul
         }
     }
 
-    let wallet_handle = get_wallet_handle();
-
     // this may be too redundant since Prover::search_credentials will validate the proof reqeuest already.
     let proof_request_json: Map<String, Value> = serde_json::from_str(proof_req)
         .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidProofRequest, format!("Cannot deserialize ProofRequest: {:?}", err)))?;
@@ -259,11 +278,92 @@ pub fn libindy_prover_store_credential(cred_id: Option<&str>,
         .map_err(VcxError::from)
 }
 
+/**
+Returns info of the credential stored in the wallet under `cred_id`, in the format the credentials for proof
+request are returned in.
+ */
+pub fn libindy_prover_get_credential(cred_id: &str) -> VcxResult<String> {
+    if settings::indy_mocks_enabled() {
+        return Ok(json!({
+            "referent": cred_id,
+            "attrs": {},
+            "schema_id": SCHEMA_ID,
+            "cred_def_id": CRED_DEF_ID,
+            "rev_reg_id": null,
+            "cred_rev_id": null
+        }).to_string());
+    }
+
+    anoncreds::prover_get_credential(get_wallet_handle(), cred_id)
+        .wait()
+        .map_err(VcxError::from)
+}
+
+/**
+Stores credential like `libindy_prover_store_credential` and tags it with id chosen by the application, see
+`get_credential_id_by_app_id`. Application ids are unique, if another credential is tagged with `app_id` already,
+the credential is deleted again and DuplicationWalletRecord returned, so no credential the application can't find
+is left in the wallet.
+ */
+pub fn libindy_prover_store_credential_with_app_id(cred_id: Option<&str>,
+                                                   app_id: &str,
+                                                   cred_req_meta: &str,
+                                                   cred_json: &str,
+                                                   cred_def_json: &str,
+                                                   rev_reg_def_json: Option<&str>) -> VcxResult<String> {
+    trace!("libindy_prover_store_credential_with_app_id >>> cred_id: {:?}, app_id: {}", cred_id, app_id);
+    let cred_id = libindy_prover_store_credential(cred_id, cred_req_meta, cred_json, cred_def_json, rev_reg_def_json)?;
+
+    // records of libindy credentials can't be tagged through the wallet API, the tag is a record of its own stored
+    // under the application id, so the wallet itself refuses the same application id twice
+    let tags = json!({"cred_id": cred_id}).to_string();
+    if let Err(err) = wallet::add_record(CREDENTIAL_APP_ID_TYPE, app_id, &cred_id, Some(&tags)) {
+        if let Err(delete_err) = anoncreds::prover_delete_credential(get_wallet_handle(), &cred_id).wait() {
+            warn!("libindy_prover_store_credential_with_app_id >>> cannot delete credential {} which was not tagged: {:?}", cred_id, delete_err);
+        }
+        return Err(err);
+    }
+    Ok(cred_id)
+}
+
 pub fn libindy_prover_delete_credential(cred_id: &str) -> VcxResult<()> {
     anoncreds::prover_delete_credential(get_wallet_handle(),
                                         cred_id)
         .wait()
-        .map_err(VcxError::from)
+        .map_err(VcxError::from)?;
+    // the application id tag, if there's any, goes away with the credential
+    if let Err(err) = _delete_app_id_tag(cred_id) {
+        warn!("libindy_prover_delete_credential >>> cannot delete application id tag of credential {}: {}", cred_id, err);
+    }
+    Ok(())
+}
+
+fn _delete_app_id_tag(cred_id: &str) -> VcxResult<()> {
+    let query = json!({"cred_id": cred_id}).to_string();
+    let options = json!({"retrieveRecords": true, "retrieveTotalCount": false, "retrieveType": false, "retrieveValue": false, "retrieveTags": false}).to_string();
+    let search_handle = wallet::open_search(CREDENTIAL_APP_ID_TYPE, &query, &options)?;
+    let records = wallet::fetch_next_records(search_handle, 1);
+    wallet::close_search(search_handle)?;
+
+    let records: Value = serde_json::from_str(&records?)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize wallet records: {}", err)))?;
+    if let Some(app_id) = records["records"][0]["id"].as_str() {
+        wallet::delete_record(CREDENTIAL_APP_ID_TYPE, app_id)?;
+    }
+    Ok(())
+}
+
+/**
+Returns id of the stored credential tagged with `app_id`, fails with WalletRecordNotFound if there's none.
+ */
+pub fn get_credential_id_by_app_id(app_id: &str) -> VcxResult<String> {
+    trace!("get_credential_id_by_app_id >>> app_id: {}", app_id);
+    let options = json!({"retrieveType": false, "retrieveValue": true, "retrieveTags": false}).to_string();
+    let record: Value = serde_json::from_str(&wallet::get_record(CREDENTIAL_APP_ID_TYPE, app_id, &options)?)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize wallet record: {}", err)))?;
+    record["value"].as_str()
+        .map(String::from)
+        .ok_or(VcxError::from_msg(VcxErrorKind::WalletRecordNotFound, format!("No credential is tagged with application id {}", app_id)))
 }
 
 pub fn libindy_prover_create_master_secret(master_secret_id: &str) -> VcxResult<String> {
@@ -516,7 +616,7 @@ pub fn get_rev_reg_def_json(rev_reg_id: &str) -> VcxResult<(String, String)> {
     let submitter_did = crate::utils::random::generate_random_did();
 
     libindy_build_get_revoc_reg_def_request(&submitter_did, rev_reg_id)
-        .and_then(|req| libindy_submit_request(&req))
+        .and_then(|req| libindy_submit_request_for(rev_reg_id, &req))
         .and_then(|response| libindy_parse_get_revoc_reg_def_response(&response))
 }
 
@@ -543,10 +643,10 @@ pub fn get_rev_reg_delta_json(rev_reg_id: &str, from: Option<u64>, to: Option<u6
     let submitter_did = crate::utils::random::generate_random_did();
 
     let from: i64 = if let Some(_from) = from { _from as i64 } else { -1 };
-    let to = if let Some(_to) = to { _to as i64 } else { time::get_time().sec };
+    let to = if let Some(_to) = to { _to as i64 } else { clock::now_secs() as i64 };
 
     libindy_build_get_revoc_reg_delta_request(&submitter_did, rev_reg_id, from, to)
-        .and_then(|req| libindy_submit_request(&req))
+        .and_then(|req| libindy_submit_request_for(rev_reg_id, &req))
         .and_then(|response| libindy_parse_get_revoc_reg_delta_response(&response))
 }
 
@@ -556,7 +656,7 @@ pub fn get_rev_reg(rev_reg_id: &str, timestamp: u64) -> VcxResult<(String, Strin
     let submitter_did = crate::utils::random::generate_random_did();
 
     libindy_build_get_revoc_reg_request(&submitter_did, rev_reg_id, timestamp)
-        .and_then(|req| libindy_submit_request(&req))
+        .and_then(|req| libindy_submit_request_for(rev_reg_id, &req))
         .and_then(|response| libindy_parse_get_revoc_reg_response(&response))
 }
 
@@ -901,6 +1001,44 @@ pub mod tests {
         ).unwrap_err();
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_app_id_tag_record() {
+        let _setup = SetupLibraryWallet::init();
+
+        wallet::add_record(CREDENTIAL_APP_ID_TYPE, "app-credential-1", "libindy-cred-id", Some(&json!({"cred_id": "libindy-cred-id"}).to_string())).unwrap();
+        assert_eq!(get_credential_id_by_app_id("app-credential-1").unwrap(), "libindy-cred-id");
+        assert_eq!(wallet::add_record(CREDENTIAL_APP_ID_TYPE, "app-credential-1", "other-cred-id", None).unwrap_err().kind(),
+                   VcxErrorKind::DuplicationWalletRecord);
+
+        _delete_app_id_tag("libindy-cred-id").unwrap();
+        assert_eq!(get_credential_id_by_app_id("app-credential-1").unwrap_err().kind(), VcxErrorKind::WalletRecordNotFound);
+    }
+
+    #[cfg(feature = "pool_tests")]
+    #[test]
+    fn test_credential_id_is_found_by_app_id_tag() {
+        let _setup = SetupLibraryWalletPool::init();
+
+        let (_, _, _, cred_def_json, offer, req, req_meta, _) = create_credential_req(utils::constants::DEFAULT_SCHEMA_ATTRS, false);
+        let encoded_attributes = encode_attributes(r#"{"address1": ["123 Main St"], "address2": ["Suite 3"], "city": ["Draper"], "state": ["UT"], "zip": ["84000"]}"#).unwrap();
+        let (cred, _, _) = libindy_issuer_create_credential(&offer, &req, &encoded_attributes, None, None).unwrap();
+
+        let cred_id = libindy_prover_store_credential_with_app_id(None, "app-credential-1", &req_meta, &cred, &cred_def_json, None).unwrap();
+        assert_eq!(get_credential_id_by_app_id("app-credential-1").unwrap(), cred_id);
+        assert_eq!(get_credential_id_by_app_id("app-credential-2").unwrap_err().kind(), VcxErrorKind::WalletRecordNotFound);
+
+        // application id is taken, the credential is not left in the wallet
+        let err = libindy_prover_store_credential_with_app_id(Some("other-cred-id"), "app-credential-1", &req_meta, &cred, &cred_def_json, None).unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::DuplicationWalletRecord);
+        assert!(libindy_prover_get_credential("other-cred-id").is_err());
+        assert_eq!(get_credential_id_by_app_id("app-credential-1").unwrap(), cred_id);
+
+        // the tag is deleted with the credential
+        libindy_prover_delete_credential(&cred_id).unwrap();
+        assert_eq!(get_credential_id_by_app_id("app-credential-1").unwrap_err().kind(), VcxErrorKind::WalletRecordNotFound);
+    }
+
     #[cfg(feature = "pool_tests")]
     #[test]
     fn tests_libindy_prover_get_credentials() {
@@ -1035,7 +1173,7 @@ pub mod tests {
             libindy::utils::anoncreds::tests::create_and_store_credential_def(attrs, true);
         let rev_reg_id = rev_reg_id.unwrap();
 
-        let (id, _rev_reg, _timestamp) = get_rev_reg(&rev_reg_id, time::get_time().sec as u64).unwrap();
+        let (id, _rev_reg, _timestamp) = get_rev_reg(&rev_reg_id, clock::now_secs()).unwrap();
         assert_eq!(id, rev_reg_id);
     }
 
@@ -6,6 +6,7 @@ use crate::libindy::utils::wallet::{add_record, delete_record, get_record, updat
 static CACHE_TYPE: &str = "cache";
 static REV_REG_DELTA_CACHE_PREFIX: &str = "rev_reg_delta:";
 static REV_REG_IDS_CACHE_PREFIX: &str = "rev_reg_ids:";
+static REV_STATE_CACHE_PREFIX: &str = "rev_state:";
 
 // TODO: Maybe we need to persist more info
 #[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
@@ -13,6 +14,16 @@ pub struct RevRegIdsCache {
     pub rev_reg_ids: Vec<String>
 }
 
+/**
+Revocation state of a credential built by prover, along with the timestamp of the registry it reflects,
+so it can be updated with deltas published after the timestamp instead of being built from the whole history.
+ */
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct RevStateCache {
+    pub rev_state: String,
+    pub timestamp: u64,
+}
+
 fn set_rev_reg_ids_cache(cred_def_id: &str, cache: &str) -> VcxResult<()> {
     debug!("Setting rev_reg_ids for cred_def_id {}, cache {}", cred_def_id, cache);
     match serde_json::to_string(cache) {
@@ -151,3 +162,52 @@ pub fn clear_rev_reg_delta_cache(rev_reg_id: &str) -> VcxResult<String> {
         Err(VcxError::from(VcxErrorKind::IOError))
     }
 }
+
+///
+/// Returns cached revocation state of credential.
+///
+/// # Arguments
+/// `rev_reg_id`: revocation registry id
+/// `cred_rev_id`: revocation id of the credential in the registry
+///
+/// # Returns
+/// Revocation state with the timestamp it was built for
+pub fn get_rev_state_cache(rev_reg_id: &str, cred_rev_id: &str) -> Option<RevStateCache> {
+    debug!("Getting rev_state_cache for rev_reg_id {}, cred_rev_id {}", rev_reg_id, cred_rev_id);
+    let wallet_id = format!("{}{}:{}", REV_STATE_CACHE_PREFIX, rev_reg_id, cred_rev_id);
+
+    match get_record(CACHE_TYPE, &wallet_id, &json!({"retrieveType": false, "retrieveValue": true, "retrieveTags": false}).to_string()) {
+        Ok(json) => {
+            match serde_json::from_str(&json)
+                .and_then(|x: serde_json::Value|
+                    serde_json::from_str(x.get("value").unwrap_or(&serde_json::Value::Null).as_str().unwrap_or(""))) {
+                Ok(cache) => Some(cache),
+                Err(err) => {
+                    warn!("Unable to convert rev_state cache for rev_reg_id: {}, json: {}, error: {}", rev_reg_id, json, err);
+                    None
+                }
+            }
+        }
+        Err(err) => {
+            debug!("Unable to get rev_state cache for rev_reg_id: {}, error: {}", rev_reg_id, err);
+            None
+        }
+    }
+}
+
+///
+/// Saves revocation state of credential, replacing previously cached one.
+///
+/// # Arguments
+/// `rev_reg_id`: revocation registry id
+/// `cred_rev_id`: revocation id of the credential in the registry
+/// `cache`: revocation state with the timestamp it was built for
+///
+pub fn set_rev_state_cache(rev_reg_id: &str, cred_rev_id: &str, cache: &RevStateCache) -> VcxResult<()> {
+    debug!("Setting rev_state_cache for rev_reg_id {}, cred_rev_id {}, timestamp {}", rev_reg_id, cred_rev_id, cache.timestamp);
+    let json = serde_json::to_string(cache)
+        .map_err(|_| VcxError::from(VcxErrorKind::SerializationError))?;
+    let wallet_id = format!("{}{}:{}", REV_STATE_CACHE_PREFIX, rev_reg_id, cred_rev_id);
+    update_record_value(CACHE_TYPE, &wallet_id, &json)
+        .or(add_record(CACHE_TYPE, &wallet_id, &json, None))
+}
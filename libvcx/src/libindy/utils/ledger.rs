@@ -7,8 +7,9 @@ use serde_json;
 
 use crate::{settings, utils};
 use crate::error::prelude::*;
-use crate::libindy::utils::pool::get_pool_handle;
+use crate::libindy::utils::pool::{get_pool_handle, get_pool_handle_for};
 use crate::libindy::utils::wallet::get_wallet_handle;
+use crate::utils::mockdata::mock_settings::get_mock_ledger_response_for_pool;
 use crate::utils::random::generate_random_did;
 
 pub fn multisign_request(did: &str, request: &str) -> VcxResult<String> {
@@ -43,6 +44,18 @@ pub fn libindy_submit_request(request_json: &str) -> VcxResult<String> {
         .map_err(VcxError::from)
 }
 
+/**
+Submits read request of the ledger object `identifier` to the pool of its ledger, see `pool::get_pool_handle_for`.
+ */
+pub fn libindy_submit_request_for(identifier: &str, request_json: &str) -> VcxResult<String> {
+    let pool_handle = get_pool_handle_for(identifier)?;
+    if let Some(response) = get_mock_ledger_response_for_pool(pool_handle) { return Ok(response); }
+
+    ledger::submit_request(pool_handle, request_json)
+        .wait()
+        .map_err(VcxError::from)
+}
+
 pub fn libindy_build_schema_request(submitter_did: &str, data: &str) -> VcxResult<String> {
     trace!("libindy_build_schema_request >>> submitter_did: {}, data: {}", submitter_did, data);
     ledger::build_schema_request(submitter_did, data)
@@ -375,7 +388,7 @@ pub fn parse_response(response: &str) -> VcxResult<Response> {
 }
 
 pub fn libindy_get_schema(submitter_did: &str, schema_id: &str) -> VcxResult<String> {
-    let pool_handle = get_pool_handle()?;
+    let pool_handle = get_pool_handle_for(schema_id)?;
     let wallet_handle = get_wallet_handle();
 
     cache::get_schema(pool_handle, wallet_handle, submitter_did, schema_id, "{}")
@@ -384,7 +397,7 @@ pub fn libindy_get_schema(submitter_did: &str, schema_id: &str) -> VcxResult<Str
 }
 
 pub fn libindy_get_cred_def(cred_def_id: &str) -> VcxResult<String> {
-    let pool_handle = get_pool_handle()?;
+    let pool_handle = get_pool_handle_for(cred_def_id)?;
     let wallet_handle = get_wallet_handle();
     let submitter_did = generate_random_did();
     trace!("libindy_get_cred_def >>> pool_handle: {}, wallet_handle: {:?}, submitter_did: {}", pool_handle, wallet_handle, submitter_did);
@@ -1,13 +1,18 @@
+use std::collections::HashMap;
 use std::sync::RwLock;
 
 use futures::Future;
 use indy::{ErrorCode, pool};
+use regex::Regex;
 
 use crate::error::prelude::*;
 use crate::settings;
 
 lazy_static! {
     static ref POOL_HANDLE: RwLock<Option<i32>> = RwLock::new(None);
+    // pools of ledgers identified by did:indy namespace, such as `sovrin` or `sovrin:staging`
+    static ref NETWORK_POOL_HANDLES: RwLock<HashMap<String, i32>> = RwLock::new(HashMap::new());
+    static ref DID_INDY_NAMESPACE: Regex = Regex::new("did:indy:([a-z0-9_-]+(?::[a-z0-9_-]+)*):[1-9A-HJ-NP-Za-km-z]{21,22}(?:$|[:/])").unwrap();
 }
 
 pub fn set_pool_handle(handle: Option<i32>) {
@@ -25,7 +30,43 @@ pub fn is_pool_open() -> bool {
     get_pool_handle().is_ok()
 }
 
-pub fn reset_pool_handle() { set_pool_handle(None); }
+/**
+Forgets the default pool as well as pools registered for did:indy namespaces.
+ */
+pub fn reset_pool_handle() {
+    set_pool_handle(None);
+    NETWORK_POOL_HANDLES.write().unwrap().clear();
+}
+
+/**
+Registers pool handle of the ledger with given did:indy namespace, `None` unregisters it.
+Ledger objects of identifiers in the namespace are resolved from this pool.
+ */
+pub fn set_network_pool_handle(namespace: &str, handle: Option<i32>) {
+    let mut handles = NETWORK_POOL_HANDLES.write().unwrap();
+    match handle {
+        Some(handle) => handles.insert(namespace.to_string(), handle),
+        None => handles.remove(namespace),
+    };
+}
+
+/**
+Returns handle of the pool the identifier's ledger objects are resolved from. Identifiers qualified by did:indy
+are resolved from the pool registered for their namespace, other identifiers from the default pool.
+Fails with NoPoolOpen if no pool is registered for the namespace.
+ */
+pub fn get_pool_handle_for(identifier: &str) -> VcxResult<i32> {
+    let namespace = match DID_INDY_NAMESPACE.captures(identifier) {
+        Some(captures) => captures[1].to_string(),
+        None => return get_pool_handle()
+    };
+    NETWORK_POOL_HANDLES.read()
+        .or(Err(VcxError::from_msg(VcxErrorKind::NoPoolOpen, "There is no pool opened")))?
+        .get(&namespace)
+        .cloned()
+        .ok_or(VcxError::from_msg(VcxErrorKind::NoPoolOpen,
+                                  format!("There is no pool configured for ledger namespace {} of {}", namespace, identifier)))
+}
 
 pub fn set_protocol_version() -> VcxResult<()> {
     pool::set_protocol_version(settings::get_protocol_version())
@@ -160,6 +201,25 @@ pub mod tests {
         file_path
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_pool_handle_for_resolves_pool_by_namespace() {
+        let sovrin_schema_id = "schema:indy:did:indy:sovrin:Th7MpTaRZVRYnPiabds81Y:2:degree:1.0";
+        let idunion_cred_def_id = "creddef:indy:did:indy:idunion:test:V4SGRU86Z58d6TV7PBUe6f:3:CL:12:tag";
+        set_network_pool_handle("sovrin", Some(11));
+        set_network_pool_handle("idunion:test", Some(12));
+
+        assert_eq!(get_pool_handle_for(sovrin_schema_id).unwrap(), 11);
+        assert_eq!(get_pool_handle_for(idunion_cred_def_id).unwrap(), 12);
+        assert_eq!(get_pool_handle_for("did:indy:bcovrin:Th7MpTaRZVRYnPiabds81Y").unwrap_err().kind(), VcxErrorKind::NoPoolOpen);
+
+        set_network_pool_handle("idunion:test", None);
+        assert_eq!(get_pool_handle_for(idunion_cred_def_id).unwrap_err().kind(), VcxErrorKind::NoPoolOpen);
+
+        reset_pool_handle();
+        assert_eq!(get_pool_handle_for(sovrin_schema_id).unwrap_err().kind(), VcxErrorKind::NoPoolOpen);
+    }
+
     #[cfg(feature = "pool_tests")]
     #[test]
     fn test_open_close_pool() {
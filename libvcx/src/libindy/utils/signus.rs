@@ -17,3 +17,17 @@ pub fn create_and_store_my_did(seed: Option<&str>, method_name: Option<&str>) ->
         .wait()
         .map_err(VcxError::from)
 }
+
+/**
+Returns verkey of DID whose keys are stored in the wallet, fails with WalletRecordNotFound if the wallet doesn't have it.
+ */
+pub fn key_for_local_did(did: &str) -> VcxResult<String> {
+    trace!("key_for_local_did >>> did: {}", did);
+    if settings::indy_mocks_enabled() {
+        return Ok(utils::constants::VERKEY.to_string());
+    }
+
+    did::key_for_local_did(get_wallet_handle(), did)
+        .wait()
+        .map_err(VcxError::from)
+}
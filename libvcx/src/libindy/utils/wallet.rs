@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
 use futures::Future;
 use indy::{ErrorCode, wallet};
 use indy::{INVALID_WALLET_HANDLE, SearchHandle, WalletHandle};
@@ -6,6 +9,7 @@ use crate::error::prelude::*;
 use crate::init::open_as_main_wallet;
 use crate::settings;
 use crate::libindy::utils::{anoncreds, signus};
+use crate::utils::validation;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct WalletConfig {
@@ -59,6 +63,45 @@ pub fn reset_wallet_handle() {
     settings::get_agency_client_mut().unwrap().reset_wallet_handle();
 }
 
+lazy_static! {
+    /*
+    Wallet profiles are additional wallets opened by the application, e.g. to keep work and personal credentials apart.
+    They are referenced by name, the main wallet is used wherever no profile is given.
+     */
+    static ref WALLET_PROFILES: RwLock<HashMap<String, WalletHandle>> = RwLock::new(HashMap::new());
+}
+
+pub fn register_wallet_profile(name: &str, handle: WalletHandle) -> VcxResult<()> {
+    trace!("register_wallet_profile >>> name: {}, handle: {:?}", name, handle);
+    WALLET_PROFILES.write()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::PoisonedLock, "Cannot register wallet profile"))?
+        .insert(name.to_string(), handle);
+    Ok(())
+}
+
+pub fn unregister_wallet_profile(name: &str) -> VcxResult<()> {
+    trace!("unregister_wallet_profile >>> name: {}", name);
+    WALLET_PROFILES.write()
+        .map_err(|_| VcxError::from_msg(VcxErrorKind::PoisonedLock, "Cannot unregister wallet profile"))?
+        .remove(name)
+        .map(|_| ())
+        .ok_or(VcxError::from_msg(VcxErrorKind::InvalidWalletHandle, format!("Wallet profile {} is not registered", name)))
+}
+
+/**
+Returns handle of the wallet registered for the profile, or the main wallet handle if no profile is given.
+ */
+pub fn get_profile_wallet_handle(wallet_profile: Option<&str>) -> VcxResult<WalletHandle> {
+    match wallet_profile {
+        None => Ok(get_wallet_handle()),
+        Some(name) => WALLET_PROFILES.read()
+            .map_err(|_| VcxError::from_msg(VcxErrorKind::PoisonedLock, "Cannot read wallet profiles"))?
+            .get(name)
+            .cloned()
+            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidWalletHandle, format!("Wallet profile {} is not registered", name)))
+    }
+}
+
 pub fn create_wallet_from_config(config: &str) -> VcxResult<()> {
     let config: WalletConfig = serde_json::from_str(config)
         .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize WalletConfig {:?}, err: {:?}", config, err)))?;
@@ -111,6 +154,7 @@ pub fn build_wallet_credentials(key: &str, storage_creds: Option<&str>, key_deri
 
 pub fn create_wallet(wallet_name: &str, wallet_key: &str, key_derivation: &str, wallet_type: Option<&str>, storage_config: Option<&str>, storage_creds: Option<&str>) -> VcxResult<()> {
     trace!("creating wallet: {}", wallet_name);
+    validation::validate_key_derivation(key_derivation)?;
 
     let config = build_wallet_config(wallet_name, wallet_type, storage_config);
     let credentials = build_wallet_credentials(wallet_key, storage_creds, key_derivation);
@@ -134,6 +178,7 @@ pub fn create_wallet(wallet_name: &str, wallet_key: &str, key_derivation: &str,
 }
 
 pub fn create_and_open_as_main_wallet(wallet_name: &str, wallet_key: &str, key_derivation: &str, wallet_type: Option<&str>, storage_config: Option<&str>, storage_creds: Option<&str>) -> VcxResult<WalletHandle> {
+    validation::validate_key_derivation(key_derivation)?;
     if settings::indy_mocks_enabled() {
         warn!("open_as_main_wallet ::: Indy mocks enabled, skipping opening main wallet.");
         return Ok(set_wallet_handle(WalletHandle(1)));
@@ -729,4 +774,20 @@ pub mod tests {
         assert_eq!(initial_record, expected_initial_record);
         assert_eq!(changed_record, expected_updated_record);
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_wallet_profiles() {
+        let _setup = SetupDefaults::init();
+
+        assert_eq!(get_profile_wallet_handle(None).unwrap(), get_wallet_handle());
+        assert_eq!(get_profile_wallet_handle(Some("test_wallet_profiles")).unwrap_err().kind(), VcxErrorKind::InvalidWalletHandle);
+
+        register_wallet_profile("test_wallet_profiles", WalletHandle(42)).unwrap();
+        assert_eq!(get_profile_wallet_handle(Some("test_wallet_profiles")).unwrap(), WalletHandle(42));
+
+        unregister_wallet_profile("test_wallet_profiles").unwrap();
+        assert_eq!(get_profile_wallet_handle(Some("test_wallet_profiles")).unwrap_err().kind(), VcxErrorKind::InvalidWalletHandle);
+        assert_eq!(unregister_wallet_profile("test_wallet_profiles").unwrap_err().kind(), VcxErrorKind::InvalidWalletHandle);
+    }
 }
@@ -1,8 +1,17 @@
+use std::collections::HashSet;
+
 use serde_json;
 
+use crate::aries::handlers::proof_presentation::verifier::consumed_nonces::NonceStore;
+use crate::aries::handlers::proof_presentation::verifier::ephemeral;
 use crate::aries::handlers::proof_presentation::verifier::verifier::Verifier;
+use crate::aries::messages::proof_presentation::presentation::Presentation;
+use crate::aries::messages::proof_presentation::presentation_request::PresentationRequest;
+use crate::aries::utils::ephemeral_key::EphemeralKey;
 use crate::connection;
 use crate::error::prelude::*;
+use crate::libindy::proofs::proof_request::ProofRequestData;
+use crate::libindy::proofs::verifier::verifier_internal::{LedgerObjects, PresentationIdentifier, TrustedIssuers};
 use crate::utils::error;
 use crate::utils::object_cache::ObjectCache;
 
@@ -27,6 +36,12 @@ pub fn create_proof(source_id: String,
         .or(Err(VcxError::from(VcxErrorKind::CreateProof)))
 }
 
+pub fn create_proof_from_request(source_id: String, proof_request: ProofRequestData) -> VcxResult<u32> {
+    let verifier = Verifier::create_from_request(source_id, proof_request)?;
+    PROOF_MAP.add(verifier)
+        .or(Err(VcxError::from(VcxErrorKind::CreateProof)))
+}
+
 pub fn is_valid_handle(handle: u32) -> bool {
     PROOF_MAP.has_handle(handle)
 }
@@ -108,22 +123,137 @@ pub fn send_proof_request(handle: u32, connection_handle: u32) -> VcxResult<u32>
     })
 }
 
+/**
+Verifies presentation exported with `disclosed_proof::export_presentation` and delivered out of band.
+The proof request must have been sent before, the proof ends up in the same state as if received over connection.
+ */
+pub fn verify_exported_presentation(handle: u32, packed_presentation: &str) -> VcxResult<u32> {
+    PROOF_MAP.get_mut(handle, |proof| {
+        proof.verify_exported_presentation(packed_presentation)?;
+        Ok(proof.state())
+    })
+}
+
 pub fn get_proof(handle: u32) -> VcxResult<String> {
     PROOF_MAP.get(handle, |proof| {
         proof.get_presentation()
     })
 }
 
+/**
+Sets allow-list of trusted issuers as JSON `{"issuer_dids": [..], "cred_def_ids": [..]}`, presentations containing
+credentials of other issuers are rejected. Passing `None` accepts credentials of any issuer.
+ */
+pub fn set_trusted_issuers(handle: u32, trusted_issuers: Option<&str>) -> VcxResult<()> {
+    let trusted_issuers = _parse_trusted_issuers(trusted_issuers)?;
+    PROOF_MAP.get_mut(handle, |proof| {
+        proof.set_trusted_issuers(trusted_issuers.clone());
+        Ok(())
+    })
+}
+
+fn _parse_trusted_issuers(trusted_issuers: Option<&str>) -> VcxResult<Option<TrustedIssuers>> {
+    match trusted_issuers {
+        Some(trusted_issuers) => Ok(Some(serde_json::from_str::<TrustedIssuers>(trusted_issuers)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize trusted issuers: {}", err)))?)),
+        None => Ok(None)
+    }
+}
+
+/**
+Builds connectionless proof request with `~service` pointing to `service_endpoint`, without creating proof handle
+or writing to the wallet. Returns the request and the JSON of its in-memory key pair, which the caller keeps
+until the presentation arrives. See `ephemeral` verifier module for details of the stateless verification.
+ */
+pub fn create_ephemeral_request(proof_request: ProofRequestData, service_endpoint: &str) -> VcxResult<(String, String)> {
+    let (presentation_request, recipient_key) = ephemeral::create_request(&proof_request, service_endpoint, None)?;
+    let recipient_key = serde_json::to_string(&recipient_key)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize ephemeral key: {}", err)))?;
+    Ok((presentation_request.to_json()?, recipient_key))
+}
+
+/**
+Verifies packed presentation received at the endpoint of the connectionless proof request `presentation_request_json`
+using the key pair `recipient_key_json` created along with the request, and returns the verified presentation.
+No proof handle is created, presentation replaying a request nonce already consumed in `nonce_store` is rejected.
+Trusted issuers are passed in the same format as to `set_trusted_issuers`.
+ */
+pub fn verify_ephemeral_presentation(presentation_request_json: &str, recipient_key_json: &str, packed_presentation: Vec<u8>,
+                                     trusted_issuers: Option<&str>, nonce_store: &dyn NonceStore) -> VcxResult<String> {
+    let presentation_request: PresentationRequest = serde_json::from_str(presentation_request_json)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize proof request: {}", err)))?;
+    let recipient_key: EphemeralKey = serde_json::from_str(recipient_key_json)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize ephemeral key: {}", err)))?;
+    let trusted_issuers = _parse_trusted_issuers(trusted_issuers)?;
+    let presentation = ephemeral::verify_presentation(&presentation_request, &recipient_key, packed_presentation, trusted_issuers.as_ref(), nonce_store)?;
+    Ok(json!(presentation.to_a2a_message()).to_string())
+}
+
+/**
+Returns schema, credential definition and revocation registry ids of credentials backing the verified presentation,
+paired with referents of the proof request they satisfied.
+ */
+pub fn get_presentation_identifiers(handle: u32) -> VcxResult<Vec<PresentationIdentifier>> {
+    PROOF_MAP.get(handle, |proof| {
+        proof.get_presentation_identifiers()
+    })
+}
+
+/**
+Verifies the received presentation without ledger access. Ledger objects are passed as JSON
+`{"schemas": {id: schema}, "cred_defs": {id: cred_def}, "rev_reg_defs": {id: rev_reg_def}, "rev_regs": {id: {timestamp: rev_reg}}}`,
+verification fails with the id of the first object the presentation requires but which was not supplied.
+ */
+pub fn verify_presentation_offline(handle: u32, ledger_objects_json: &str) -> VcxResult<bool> {
+    let ledger_objects: LedgerObjects = serde_json::from_str(ledger_objects_json)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize ledger objects: {}", err)))?;
+    PROOF_MAP.get(handle, |proof| {
+        proof.verify_presentation_offline(&ledger_objects)
+    })
+}
+
+/**
+Verifies presentations, passed as JSON, against proof requests of the paired proof handles. Ledger objects are
+fetched once for the whole batch, so presentations of credentials sharing credential definition or schema don't
+fetch it repeatedly. Results map one-to-one with the inputs. Verification has no side effects: the states of the
+proofs and the consumed nonces are not changed, so the proofs still accept their presentations afterwards.
+Presentation for a request nonce already consumed, or verified earlier in the batch, fails with ReplayedPresentation.
+ */
+pub fn verify_batch(requests_and_presentations: Vec<(u32, String)>) -> Vec<VcxResult<bool>> {
+    _verify_batch(requests_and_presentations, &mut LedgerObjects::default())
+}
+
+fn _verify_batch(requests_and_presentations: Vec<(u32, String)>, ledger_objects: &mut LedgerObjects) -> Vec<VcxResult<bool>> {
+    let mut verified_nonces = HashSet::new();
+    requests_and_presentations.iter()
+        .map(|(handle, presentation)| {
+            let presentation: Presentation = serde_json::from_str(presentation)
+                .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize presentation: {}", err)))?;
+            let proof = PROOF_MAP.get(*handle, |proof| Ok(proof.clone()))?;
+            proof.verify_presentation_with_ledger_objects(&presentation, ledger_objects, &mut verified_nonces)
+        })
+        .collect()
+}
+
+pub fn get_problem_report(handle: u32) -> VcxResult<String> {
+    PROOF_MAP.get(handle, |proof| {
+        proof.get_problem_report()
+    })
+}
+
 #[cfg(test)]
 pub mod tests {
     use serde_json::Value;
 
-    use agency_client::mocking::HttpClientMockResponse;
+    use agency_client::mocking::{AgencyMockDecrypted, HttpClientMockResponse};
 
-    use crate::proof;
-    use crate::api::VcxStateType;
+    use crate::{disclosed_proof, proof, settings};
+    use crate::api::{ProofStateType, VcxStateType};
     use crate::aries::handlers::proof_presentation::verifier::verifier::Verifier;
     use crate::connection::tests::build_test_connection_inviter_requested;
+    use crate::aries::messages::connection::service::Service;
+    use crate::aries::messages::proof_presentation::presentation_request::PresentationRequest;
+    use crate::libindy::proofs::proof_request::ProofRequestBuilder;
     use crate::utils::constants::*;
     use crate::utils::devsetup::*;
     use crate::utils::mockdata::mock_settings::MockBuilder;
@@ -152,6 +282,25 @@ pub mod tests {
                      "Optional".to_owned()).unwrap();
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_create_proof_from_request() {
+        let _setup = SetupMocks::init();
+
+        let proof_request = ProofRequestBuilder::new("Optional")
+            .requested_attribute("name", None)
+            .requested_predicate("age", ">=", 18, None)
+            .nonce("123456")
+            .build().unwrap();
+        let handle = create_proof_from_request("1".to_string(), proof_request).unwrap();
+
+        let request: PresentationRequest = serde_json::from_str(&generate_proof_request_msg(handle).unwrap()).unwrap();
+        let request: Value = serde_json::from_str(&request.request_presentations_attach.content().unwrap()).unwrap();
+        assert_eq!(request["nonce"], "123456");
+        assert_eq!(request["requested_attributes"]["attribute_0"]["name"], "name");
+        assert_eq!(request["requested_predicates"]["predicate_0"]["name"], "age");
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_revocation_details() {
@@ -274,6 +423,117 @@ pub mod tests {
         assert_eq!(get_state(handle).unwrap(), VcxStateType::VcxStateAccepted as u32);
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_verify_presentation_offline() {
+        let _setup = SetupMocks::init();
+        let _mock_builder = MockBuilder::init().
+            set_mock_result_for_validate_indy_proof(Ok(true));
+
+        let connection_handle = build_test_connection_inviter_requested();
+
+        let mut proof = create_default_proof();
+        proof.send_presentation_request(connection_handle).unwrap();
+
+        let handle = PROOF_MAP.add(proof).unwrap();
+        update_state(handle, Some(mockdata_proof::ARIES_PROOF_PRESENTATION), None).unwrap();
+
+        let schema_id = "V4SGRU86Z58d6TV7PBUe6f:2:FaberVcx:100.3.17";
+        let cred_def_id = "V4SGRU86Z58d6TV7PBUe6f:3:CL:67:tag1";
+        let rev_reg_id = "V4SGRU86Z58d6TV7PBUe6f:4:V4SGRU86Z58d6TV7PBUe6f:3:CL:67:tag1:CL_ACCUM:tag1";
+        let mut ledger_objects = json!({
+            "schemas": {schema_id: {}},
+            "cred_defs": {cred_def_id: {}},
+            "rev_reg_defs": {rev_reg_id: {}},
+            "rev_regs": {rev_reg_id: {"1599834693": {}}}
+        });
+        assert_eq!(verify_presentation_offline(handle, &ledger_objects.to_string()).unwrap(), true);
+
+        ledger_objects["cred_defs"] = json!({});
+        let err = verify_presentation_offline(handle, &ledger_objects.to_string()).unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::InvalidProofCredentialData);
+        assert!(err.to_string().contains(cred_def_id));
+
+        assert_eq!(verify_presentation_offline(handle, "not json").unwrap_err().kind(), VcxErrorKind::InvalidJson);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_verify_presentation_offline_after_failed_online_verification() {
+        let _setup = SetupMocks::init();
+
+        let connection_handle = build_test_connection_inviter_requested();
+
+        let mut proof = create_default_proof();
+        proof.send_presentation_request(connection_handle).unwrap();
+        let handle = PROOF_MAP.add(proof).unwrap();
+
+        {
+            let _mock_builder = MockBuilder::init().
+                set_mock_result_for_validate_indy_proof(Err(VcxError::from(VcxErrorKind::InvalidLedgerResponse)));
+            update_state(handle, Some(mockdata_proof::ARIES_PROOF_PRESENTATION), None).unwrap();
+        }
+        assert_eq!(get_state(handle).unwrap(), VcxStateType::VcxStateNone as u32);
+        assert_eq!(get_proof_state(handle).unwrap(), ProofStateType::ProofInvalid as u32);
+
+        let _mock_builder = MockBuilder::init().
+            set_mock_result_for_validate_indy_proof(Ok(true));
+        let cred_def_id = "V4SGRU86Z58d6TV7PBUe6f:3:CL:67:tag1";
+        let rev_reg_id = "V4SGRU86Z58d6TV7PBUe6f:4:V4SGRU86Z58d6TV7PBUe6f:3:CL:67:tag1:CL_ACCUM:tag1";
+        let ledger_objects = json!({
+            "schemas": {"V4SGRU86Z58d6TV7PBUe6f:2:FaberVcx:100.3.17": {}},
+            "cred_defs": {cred_def_id: {}},
+            "rev_reg_defs": {rev_reg_id: {}},
+            "rev_regs": {rev_reg_id: {"1599834693": {}}}
+        });
+        assert_eq!(verify_presentation_offline(handle, &ledger_objects.to_string()).unwrap(), true);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_verify_batch() {
+        let _setup = SetupMocks::init();
+        let _mock_builder = MockBuilder::init().
+            set_mock_result_for_validate_indy_proof(Ok(true));
+
+        let connection_handle = build_test_connection_inviter_requested();
+        let handles: Vec<u32> = (0..2).map(|_| {
+            let mut proof = create_default_proof();
+            proof.send_presentation_request(connection_handle).unwrap();
+            PROOF_MAP.add(proof).unwrap()
+        }).collect();
+
+        let presentation = mockdata_proof::ARIES_PROOF_PRESENTATION.to_string();
+        let mut single_ledger_objects = LedgerObjects::default();
+        assert_eq!(_verify_batch(vec![(handles[0], presentation.clone())], &mut single_ledger_objects)[0].as_ref().unwrap(), &true);
+
+        let mut ledger_objects = LedgerObjects::default();
+        let results = _verify_batch(vec![
+            (handles[0], presentation.clone()),
+            (handles[1], String::from("not json")),
+            (handles[1], presentation.clone()),
+            (0, presentation.clone()),
+            (handles[0], presentation.clone()),
+        ], &mut ledger_objects);
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].as_ref().unwrap(), &true);
+        assert_eq!(results[1].as_ref().unwrap_err().kind(), VcxErrorKind::InvalidJson);
+        assert_eq!(results[2].as_ref().unwrap(), &true);
+        assert_eq!(results[3].as_ref().unwrap_err().kind(), VcxErrorKind::InvalidHandle);
+        assert_eq!(results[4].as_ref().unwrap_err().kind(), VcxErrorKind::ReplayedPresentation);
+
+        // both presentations share the credential definition, the batch fetches as much as verifying just one of them
+        assert_eq!(ledger_objects.cred_defs.len(), 1);
+        assert!(single_ledger_objects.fetched() > 0);
+        assert_eq!(ledger_objects.fetched(), single_ledger_objects.fetched());
+
+        // verification doesn't change the states of the proofs nor consumes their nonces
+        assert_eq!(get_state(handles[0]).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+        update_state(handles[0], Some(&presentation), None).unwrap();
+        assert_eq!(get_state(handles[0]).unwrap(), VcxStateType::VcxStateAccepted as u32);
+        assert_eq!(get_proof_state(handles[0]).unwrap(), ProofStateType::ProofValidated as u32);
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_update_state_with_message() {
@@ -314,6 +574,72 @@ pub mod tests {
         assert_eq!(proof_str, mockdata_proof::ARIES_PROOF_PRESENTATION.replace("\n", "").replace(" ", ""));
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_get_presentation_identifiers() {
+        let _setup = SetupMocks::init();
+        let _mock_builder = MockBuilder::init().
+            set_mock_result_for_validate_indy_proof(Ok(true));
+
+        let connection_handle = build_test_connection_inviter_requested();
+        let mut proof = create_default_proof();
+        proof.send_presentation_request(connection_handle).unwrap();
+
+        let handle = PROOF_MAP.add(proof).unwrap();
+        assert_eq!(get_presentation_identifiers(handle).unwrap_err().kind(), VcxErrorKind::InvalidProof);
+
+        update_state(handle, Some(mockdata_proof::ARIES_PROOF_PRESENTATION), None).unwrap();
+
+        let mut identifiers = get_presentation_identifiers(handle).unwrap();
+        assert_eq!(identifiers.len(), 1);
+        identifiers[0].referents.sort();
+        assert_eq!(identifiers[0], PresentationIdentifier {
+            schema_id: "V4SGRU86Z58d6TV7PBUe6f:2:FaberVcx:100.3.17".to_string(),
+            cred_def_id: "V4SGRU86Z58d6TV7PBUe6f:3:CL:67:tag1".to_string(),
+            rev_reg_id: Some("V4SGRU86Z58d6TV7PBUe6f:4:V4SGRU86Z58d6TV7PBUe6f:3:CL:67:tag1:CL_ACCUM:tag1".to_string()),
+            timestamp: Some(1599834693),
+            referents: vec!["attribute_0".to_string(), "attribute_1".to_string(), "attribute_2".to_string(), "predicate_0".to_string()],
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_presentation_from_untrusted_cred_def_is_rejected() {
+        let _setup = SetupMocks::init();
+        let _mock_builder = MockBuilder::init().
+            set_mock_result_for_validate_indy_proof(Ok(true));
+
+        let connection_handle = build_test_connection_inviter_requested();
+        let mut proof = create_default_proof();
+        proof.send_presentation_request(connection_handle).unwrap();
+
+        let handle = PROOF_MAP.add(proof).unwrap();
+        set_trusted_issuers(handle, Some(r#"{"cred_def_ids": ["2hoqvcwupRTUNkXn6ArYzs:3:CL:2471"]}"#)).unwrap();
+        update_state(handle, Some(mockdata_proof::ARIES_PROOF_PRESENTATION), None).unwrap();
+
+        assert_eq!(get_state(handle).unwrap(), VcxStateType::VcxStateNone as u32);
+        assert!(get_problem_report(handle).unwrap().contains("V4SGRU86Z58d6TV7PBUe6f:3:CL:67:tag1"));
+        assert_eq!(get_presentation_identifiers(handle).unwrap_err().kind(), VcxErrorKind::InvalidProof);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_presentation_from_trusted_issuer_is_accepted() {
+        let _setup = SetupMocks::init();
+        let _mock_builder = MockBuilder::init().
+            set_mock_result_for_validate_indy_proof(Ok(true));
+
+        let connection_handle = build_test_connection_inviter_requested();
+        let mut proof = create_default_proof();
+        proof.send_presentation_request(connection_handle).unwrap();
+
+        let handle = PROOF_MAP.add(proof).unwrap();
+        set_trusted_issuers(handle, Some(r#"{"issuer_dids": ["V4SGRU86Z58d6TV7PBUe6f"]}"#)).unwrap();
+        update_state(handle, Some(mockdata_proof::ARIES_PROOF_PRESENTATION), None).unwrap();
+
+        assert_eq!(get_state(handle).unwrap(), VcxStateType::VcxStateAccepted as u32);
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn test_release_all() {
@@ -400,4 +726,46 @@ pub mod tests {
         assert_eq!(get_source_id(bad_handle).unwrap_err().kind(), VcxErrorKind::InvalidHandle);
         assert_eq!(from_string(empty).unwrap_err().kind(), VcxErrorKind::InvalidJson);
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_verify_exported_presentation() {
+        let _setup = SetupMocks::init();
+        let _mock_builder = MockBuilder::init().
+            set_mock_result_for_validate_indy_proof(Ok(true));
+
+        let handle = create_proof("1".to_string(),
+                                  REQUESTED_ATTRS.to_owned(),
+                                  REQUESTED_PREDICATES.to_owned(),
+                                  r#"{"support_revocation":false}"#.to_string(),
+                                  "Optional".to_owned()).unwrap();
+        let connection_handle = build_test_connection_inviter_requested();
+        send_proof_request(handle, connection_handle).unwrap();
+
+        // the request reaches the prover out of band, together with the service to pack the presentation for
+        let service = Service::create()
+            .set_service_endpoint("http://verifier.example.org/presentations".to_string())
+            .set_recipient_keys(vec![settings::DEFAULT_VERKEY.to_string()]);
+        let request: PresentationRequest = serde_json::from_str(&generate_proof_request_msg(handle).unwrap()).unwrap();
+        let request = request.set_service(Some(service));
+
+        let prover_handle = disclosed_proof::create_proof("prover", &request.to_json().unwrap()).unwrap();
+        disclosed_proof::generate_proof(prover_handle, ARIES_PROVER_CREDENTIALS.to_string(), ARIES_PROVER_SELF_ATTESTED_ATTRS.to_string()).unwrap();
+        let exported = disclosed_proof::export_presentation(prover_handle).unwrap();
+        assert_eq!(disclosed_proof::get_state(prover_handle).unwrap(), VcxStateType::VcxStateRequestReceived as u32);
+
+        AgencyMockDecrypted::set_next_decrypted_message(&exported);
+        assert_eq!(verify_exported_presentation(handle, &exported).unwrap(), VcxStateType::VcxStateAccepted as u32);
+        assert_eq!(get_proof_state(handle).unwrap(), ProofStateType::ProofValidated as u32);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_export_presentation_fails_without_service() {
+        let _setup = SetupMocks::init();
+
+        let prover_handle = disclosed_proof::create_proof("prover", mockdata_proof::ARIES_PROOF_REQUEST_PRESENTATION).unwrap();
+        disclosed_proof::generate_proof(prover_handle, ARIES_PROVER_CREDENTIALS.to_string(), ARIES_PROVER_SELF_ATTESTED_ATTRS.to_string()).unwrap();
+        assert_eq!(disclosed_proof::export_presentation(prover_handle).unwrap_err().kind(), VcxErrorKind::ActionNotSupported);
+    }
 }
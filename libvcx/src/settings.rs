@@ -6,6 +6,7 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::sync::{RwLockWriteGuard, RwLockReadGuard};
 use std::sync::RwLock;
+use std::time::Duration;
 
 use indy_sys::INVALID_WALLET_HANDLE;
 use serde_json::Value;
@@ -13,7 +14,9 @@ use strum::IntoEnumIterator;
 use url::Url;
 
 use agency_client::agency_settings;
+use agency_client::MessageStatusCode;
 
+use crate::aries::messages::a2a::message_family::MessageFamilies;
 use crate::error::prelude::*;
 use crate::utils::{error, get_temp_dir_path};
 use crate::utils::file::read_file;
@@ -49,6 +52,25 @@ pub static CONFIG_POOL_CONFIG: &'static str = "pool_config";
 pub static CONFIG_DID_METHOD: &str = "did_method";
 // proprietary or aries
 pub static CONFIG_ACTORS: &str = "actors";
+// status code set on agency messages once they were processed by update_state
+pub static CONFIG_PROCESSED_MESSAGE_STATUS: &str = "processed_message_status";
+pub static CONFIG_SERIALIZATION_FORMAT: &str = "serialization_format";
+// maximal number of routing keys of counterparty's DidDoc, each of them adds a forward envelope around sent messages
+pub static CONFIG_MAX_ROUTING_DEPTH: &str = "max_routing_depth";
+// messages per second each connection can send, sending is not limited if not set
+pub static CONFIG_SEND_RATE_LIMIT: &str = "send_rate_limit";
+// messages each connection can send at once, defaults to the rate
+pub static CONFIG_SEND_RATE_BURST: &str = "send_rate_burst";
+// seconds within which the invitee must accept connection response sent by inviter, responses don't expire if not set
+pub static CONFIG_CONNECTION_RESPONSE_TTL: &str = "connection_response_ttl";
+// json array of protocol families (e.g. ["connections", "trust_ping"]) advertised to peers, all are advertised if not set
+pub static CONFIG_SUPPORTED_PROTOCOLS: &str = "supported_protocols";
+// "true" or "false", whether update_state answers trust pings of completed connections, enabled if not set
+pub static CONFIG_AUTO_RESPOND_TO_PING: &str = "auto_respond_to_ping";
+// "true" or "false", whether update_state marks messages of unsupported types processed, disabled if not set
+pub static CONFIG_MARK_UNKNOWN_MESSAGES_PROCESSED: &str = "mark_unknown_messages_processed";
+// "true" or "false", whether from_string rejects connections whose pairwise keys are not in the wallet, disabled if not set
+pub static CONFIG_VALIDATE_CONNECTION_KEYS: &str = "validate_connection_keys";
 
 pub static DEFAULT_PROTOCOL_VERSION: usize = 2;
 pub static MAX_SUPPORTED_PROTOCOL_VERSION: usize = 2;
@@ -67,9 +89,11 @@ pub static DEFAULT_ENABLE_TEST_MODE: &str = "false";
 pub static DEFAULT_WALLET_BACKUP_KEY: &str = "backup_wallet_key";
 pub static DEFAULT_WALLET_KEY: &str = "8dvfYSt5d1taSd6yJdpjq4emkwsPDDLYxkNFysFD2cZY";
 pub static DEFAULT_THREADPOOL_SIZE: usize = 8;
+pub static DEFAULT_MAX_ROUTING_DEPTH: usize = 8;
 pub static MASK_VALUE: &str = "********";
 pub static WALLET_KDF_RAW: &str = "RAW";
 pub static WALLET_KDF_ARGON2I_INT: &str = "ARGON2I_INT";
+pub static WALLET_KDF_ARGON2I_MOD: &str = "ARGON2I_MOD";
 pub static WALLET_KDF_DEFAULT: &str = WALLET_KDF_ARGON2I_INT;
 #[cfg(not(target_os = "macos"))]
 pub static DEFAULT_PAYMENT_PLUGIN: &str = "libnullpay.so";
@@ -149,6 +173,17 @@ pub fn validate_config(config: &HashMap<String, String>) -> VcxResult<u32> {
     validate_optional_config_val(config.get(CONFIG_INSTITUTION_VERKEY), VcxErrorKind::InvalidVerkey, validation::validate_verkey)?;
     validate_optional_config_val(config.get(CONFIG_WEBHOOK_URL), VcxErrorKind::InvalidUrl, Url::parse)?;
     validate_optional_config_val(config.get(CONFIG_ACTORS), VcxErrorKind::InvalidOption, validation::validate_actors)?;
+    validate_optional_config_val(config.get(CONFIG_PROCESSED_MESSAGE_STATUS), VcxErrorKind::InvalidOption, validation::validate_message_status)?;
+    validate_optional_config_val(config.get(CONFIG_SERIALIZATION_FORMAT), VcxErrorKind::InvalidOption, validation::validate_serialization_format)?;
+    validate_optional_config_val(config.get(CONFIG_MAX_ROUTING_DEPTH), VcxErrorKind::InvalidOption, validation::validate_max_routing_depth)?;
+    validate_optional_config_val(config.get(CONFIG_SEND_RATE_LIMIT), VcxErrorKind::InvalidOption, validation::validate_send_rate)?;
+    validate_optional_config_val(config.get(CONFIG_SEND_RATE_BURST), VcxErrorKind::InvalidOption, validation::validate_send_rate)?;
+    validate_optional_config_val(config.get(CONFIG_CONNECTION_RESPONSE_TTL), VcxErrorKind::InvalidOption, validation::validate_ttl)?;
+    validate_optional_config_val(config.get(CONFIG_SUPPORTED_PROTOCOLS), VcxErrorKind::InvalidOption, validation::validate_supported_protocols)?;
+    validate_optional_config_val(config.get(CONFIG_AUTO_RESPOND_TO_PING), VcxErrorKind::InvalidOption, validation::validate_flag)?;
+    validate_optional_config_val(config.get(CONFIG_MARK_UNKNOWN_MESSAGES_PROCESSED), VcxErrorKind::InvalidOption, validation::validate_flag)?;
+    validate_optional_config_val(config.get(CONFIG_VALIDATE_CONNECTION_KEYS), VcxErrorKind::InvalidOption, validation::validate_flag)?;
+    validate_optional_config_val(config.get(CONFIG_WALLET_KEY_DERIVATION), VcxErrorKind::InvalidConfiguration, validation::validate_key_derivation)?;
 
     get_agency_client()?.validate()?;
     Ok(error::SUCCESS.code_num)
@@ -323,6 +358,92 @@ pub fn get_actors() -> Vec<Actors> {
         ).unwrap_or_else(|_| Actors::iter().collect())
 }
 
+pub fn get_processed_message_status() -> MessageStatusCode {
+    get_config_value(CONFIG_PROCESSED_MESSAGE_STATUS)
+        .and_then(|status| validation::validate_message_status(&status))
+        .unwrap_or(MessageStatusCode::Reviewed)
+}
+
+pub fn get_serialization_format() -> SerializationFormat {
+    get_config_value(CONFIG_SERIALIZATION_FORMAT)
+        .and_then(|format| validation::validate_serialization_format(&format))
+        .unwrap_or(SerializationFormat::Json)
+}
+
+pub fn get_max_routing_depth() -> usize {
+    get_config_value(CONFIG_MAX_ROUTING_DEPTH)
+        .and_then(|depth| validation::validate_max_routing_depth(&depth))
+        .unwrap_or(DEFAULT_MAX_ROUTING_DEPTH)
+}
+
+/**
+Returns time to live of connection responses sent as inviter, `None` if responses don't expire.
+ */
+pub fn get_connection_response_ttl() -> Option<Duration> {
+    get_config_value(CONFIG_CONNECTION_RESPONSE_TTL)
+        .and_then(|ttl| validation::validate_ttl(&ttl))
+        .map(Duration::from_secs)
+        .ok()
+}
+
+/**
+Returns protocol families the application restricted advertised protocols to, `None` if all are advertised.
+ */
+pub fn get_supported_protocols() -> Option<Vec<MessageFamilies>> {
+    get_config_value(CONFIG_SUPPORTED_PROTOCOLS)
+        .and_then(|families| validation::validate_supported_protocols(&families))
+        .ok()
+}
+
+/**
+Returns whether trust pings requesting response are answered by update_state. If disabled, pings received on completed
+connections are left unprocessed in the agency for the application to pick up by get_messages.
+ */
+pub fn auto_respond_to_ping() -> bool {
+    get_config_value(CONFIG_AUTO_RESPOND_TO_PING)
+        .and_then(|enabled| validation::validate_flag(&enabled))
+        .unwrap_or(true)
+}
+
+/**
+Returns whether update_state marks messages of unsupported types processed, so they are not downloaded again. If
+disabled, such messages stay unprocessed in the agency for the application to pick up by get_messages.
+ */
+pub fn mark_unknown_messages_processed() -> bool {
+    get_config_value(CONFIG_MARK_UNKNOWN_MESSAGES_PROCESSED)
+        .and_then(|enabled| validation::validate_flag(&enabled))
+        .unwrap_or(false)
+}
+
+/**
+Returns whether deserialized connections are checked to have their pairwise keys stored in the current wallet.
+ */
+pub fn validate_connection_keys() -> bool {
+    get_config_value(CONFIG_VALIDATE_CONNECTION_KEYS)
+        .and_then(|enabled| validation::validate_flag(&enabled))
+        .unwrap_or(false)
+}
+
+/**
+Returns (rate, burst) of messages each connection can send, `None` if sending is not limited.
+ */
+pub fn get_send_rate_limit() -> Option<(u32, u32)> {
+    let rate = get_config_value(CONFIG_SEND_RATE_LIMIT)
+        .and_then(|rate| validation::validate_send_rate(&rate))
+        .ok()?;
+    let burst = get_config_value(CONFIG_SEND_RATE_BURST)
+        .and_then(|burst| validation::validate_send_rate(&burst))
+        .unwrap_or(rate);
+    Some((rate, burst))
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SerializationFormat {
+    Json,
+    Msgpack,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, EnumIter)]
 #[serde(rename_all = "lowercase")]
 pub enum Actors {
@@ -461,6 +582,10 @@ pub mod tests {
         let mut config = _mandatory_config();
         config.insert(CONFIG_WEBHOOK_URL.to_string(), invalid.to_string());
         assert_eq!(validate_config(&config).unwrap_err().kind(), VcxErrorKind::InvalidUrl);
+
+        let mut config = _mandatory_config();
+        config.insert(CONFIG_WALLET_KEY_DERIVATION.to_string(), invalid.to_string());
+        assert_eq!(validate_config(&config).unwrap_err().kind(), VcxErrorKind::InvalidConfiguration);
     }
 
     #[test]
@@ -552,4 +677,161 @@ pub mod tests {
         config["actors"] = json!(["wrong"]);
         assert_eq!(process_config_string(&config.to_string(), true).unwrap_err().kind(), VcxErrorKind::InvalidOption);
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_process_config_str_for_supported_protocols() {
+        let _setup = SetupDefaults::init();
+
+        assert_eq!(None, get_supported_protocols());
+
+        let mut config = base_config();
+        config["supported_protocols"] = json!(["connections", "present-proof"]);
+
+        process_config_string(&config.to_string(), true).unwrap();
+
+        assert_eq!(Some(vec![MessageFamilies::Connections, MessageFamilies::PresentProof]), get_supported_protocols());
+
+        config["supported_protocols"] = json!(["unknown-protocol"]);
+        assert_eq!(process_config_string(&config.to_string(), true).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_process_config_str_for_processed_message_status() {
+        let _setup = SetupDefaults::init();
+
+        assert_eq!(MessageStatusCode::Reviewed, get_processed_message_status());
+
+        let mut config = base_config();
+        config["processed_message_status"] = json!("MS-105");
+        process_config_string(&config.to_string(), true).unwrap();
+        assert_eq!(MessageStatusCode::Rejected, get_processed_message_status());
+
+        config["processed_message_status"] = json!("MS-103");
+        process_config_string(&config.to_string(), true).unwrap();
+        assert_eq!(MessageStatusCode::Received, get_processed_message_status());
+
+        // passed invalid status code
+        config["processed_message_status"] = json!("MS-999");
+        assert_eq!(process_config_string(&config.to_string(), true).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_process_config_str_for_serialization_format() {
+        let _setup = SetupDefaults::init();
+
+        assert_eq!(SerializationFormat::Json, get_serialization_format());
+
+        let mut config = base_config();
+        config["serialization_format"] = json!("msgpack");
+        process_config_string(&config.to_string(), true).unwrap();
+        assert_eq!(SerializationFormat::Msgpack, get_serialization_format());
+
+        config["serialization_format"] = json!("xml");
+        assert_eq!(process_config_string(&config.to_string(), true).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_process_config_str_for_auto_respond_to_ping() {
+        let _setup = SetupDefaults::init();
+
+        assert_eq!(true, auto_respond_to_ping());
+
+        let mut config = base_config();
+        config["auto_respond_to_ping"] = json!("false");
+        process_config_string(&config.to_string(), true).unwrap();
+        assert_eq!(false, auto_respond_to_ping());
+
+        config["auto_respond_to_ping"] = json!("no");
+        assert_eq!(process_config_string(&config.to_string(), true).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_process_config_str_for_max_routing_depth() {
+        let _setup = SetupDefaults::init();
+
+        assert_eq!(DEFAULT_MAX_ROUTING_DEPTH, get_max_routing_depth());
+
+        let mut config = base_config();
+        config["max_routing_depth"] = json!("2");
+        process_config_string(&config.to_string(), true).unwrap();
+        assert_eq!(2, get_max_routing_depth());
+
+        config["max_routing_depth"] = json!("deep");
+        assert_eq!(process_config_string(&config.to_string(), true).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_process_config_str_for_send_rate_limit() {
+        let _setup = SetupDefaults::init();
+
+        assert_eq!(None, get_send_rate_limit());
+
+        let mut config = base_config();
+        config["send_rate_limit"] = json!("5");
+        process_config_string(&config.to_string(), true).unwrap();
+        assert_eq!(Some((5, 5)), get_send_rate_limit());
+
+        config["send_rate_burst"] = json!("10");
+        process_config_string(&config.to_string(), true).unwrap();
+        assert_eq!(Some((5, 10)), get_send_rate_limit());
+
+        config["send_rate_limit"] = json!("0");
+        assert_eq!(process_config_string(&config.to_string(), true).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_process_config_str_for_connection_response_ttl() {
+        let _setup = SetupDefaults::init();
+
+        assert_eq!(None, get_connection_response_ttl());
+
+        let mut config = base_config();
+        config["connection_response_ttl"] = json!("300");
+        process_config_string(&config.to_string(), true).unwrap();
+        assert_eq!(Some(Duration::from_secs(300)), get_connection_response_ttl());
+
+        config["connection_response_ttl"] = json!("-1");
+        assert_eq!(process_config_string(&config.to_string(), true).unwrap_err().kind(), VcxErrorKind::InvalidOption);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_process_config_str_for_agency_circuit_breaker() {
+        let _setup = SetupDefaults::init();
+
+        let status: Value = serde_json::from_str(&crate::connection::get_agency_circuit_breaker_status().unwrap()).unwrap();
+        assert_eq!(status, json!({"enabled": false, "state": "Closed", "consecutive_failures": 0}));
+
+        let mut config = base_config();
+        config["agency_circuit_breaker_threshold"] = json!("3");
+        config["agency_circuit_breaker_cooldown_ms"] = json!("1000");
+        process_config_string(&config.to_string(), true).unwrap();
+        assert_eq!(agency_settings::get_circuit_breaker_config(), Some((3, Duration::from_millis(1000))));
+
+        config["agency_circuit_breaker_threshold"] = json!("0");
+        assert_eq!(process_config_string(&config.to_string(), true).unwrap_err().kind(), VcxErrorKind::InvalidConfiguration);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_process_config_str_for_agency_protocol_version() {
+        let _setup = SetupDefaults::init();
+
+        assert_eq!(crate::connection::get_agency_protocol_version().unwrap(), "2.0");
+
+        let mut config = base_config();
+        config["agency_protocol_version"] = json!("2.0");
+        process_config_string(&config.to_string(), true).unwrap();
+        assert_eq!(crate::connection::get_agency_protocol_version().unwrap(), "2.0");
+
+        config["agency_protocol_version"] = json!("1.0");
+        assert_eq!(process_config_string(&config.to_string(), true).unwrap_err().kind(), VcxErrorKind::InvalidConfiguration);
+    }
 }
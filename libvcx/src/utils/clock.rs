@@ -0,0 +1,138 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(test)]
+use std::cell::RefCell;
+#[cfg(test)]
+use std::sync::Mutex;
+#[cfg(test)]
+use std::time::Duration;
+
+/**
+Source of the current time. Time-dependent logic reads the time through `now`, `now_secs` and `now_millis`
+so tests can replace the clock and move time explicitly.
+ */
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    // clock is overridden per thread so tests running in parallel don't affect each other
+    static TEST_CLOCK: RefCell<Option<Arc<dyn Clock>>> = RefCell::new(None);
+}
+
+pub fn now() -> SystemTime {
+    #[cfg(test)]
+    {
+        if let Some(clock) = TEST_CLOCK.with(|clock| clock.borrow().clone()) {
+            return clock.now();
+        }
+    }
+    SystemClock.now()
+}
+
+pub fn now_secs() -> u64 {
+    now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
+}
+
+pub fn now_millis() -> u64 {
+    now().duration_since(UNIX_EPOCH).map(|duration| duration.as_millis() as u64).unwrap_or_default()
+}
+
+#[cfg(test)]
+pub fn set_clock(clock: Arc<dyn Clock>) {
+    TEST_CLOCK.with(|test_clock| *test_clock.borrow_mut() = Some(clock));
+}
+
+#[cfg(test)]
+pub fn reset_clock() {
+    TEST_CLOCK.with(|test_clock| *test_clock.borrow_mut() = None);
+}
+
+/**
+Clock which stands still until advanced explicitly.
+ */
+#[cfg(test)]
+pub struct FakeClock {
+    time: Mutex<SystemTime>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new(secs_since_epoch: u64) -> FakeClock {
+        FakeClock { time: Mutex::new(UNIX_EPOCH + Duration::from_secs(secs_since_epoch)) }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut time = self.time.lock().unwrap();
+        *time += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> SystemTime {
+        *self.time.lock().unwrap()
+    }
+}
+
+/**
+Installs fake clock for the current thread, the original clock is restored when dropped.
+ */
+#[cfg(test)]
+pub struct SetupFakeClock {
+    pub clock: Arc<FakeClock>,
+}
+
+#[cfg(test)]
+impl SetupFakeClock {
+    pub fn init(secs_since_epoch: u64) -> SetupFakeClock {
+        let clock = Arc::new(FakeClock::new(secs_since_epoch));
+        set_clock(clock.clone());
+        SetupFakeClock { clock }
+    }
+}
+
+#[cfg(test)]
+impl Drop for SetupFakeClock {
+    fn drop(&mut self) {
+        reset_clock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_fake_clock_is_advanced_explicitly() {
+        let setup = SetupFakeClock::init(1_600_000_000);
+
+        assert_eq!(now_secs(), 1_600_000_000);
+        assert_eq!(now_millis(), 1_600_000_000_000);
+
+        setup.clock.advance(Duration::from_millis(1500));
+        assert_eq!(now_secs(), 1_600_000_001);
+        assert_eq!(now_millis(), 1_600_000_001_500);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_system_clock_is_used_after_reset() {
+        {
+            let _setup = SetupFakeClock::init(0);
+            assert_eq!(now_secs(), 0);
+        }
+        assert!(now_secs() > 1_600_000_000);
+    }
+}
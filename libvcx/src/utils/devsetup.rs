@@ -6,9 +6,11 @@ use indy::WalletHandle;
 use rand::Rng;
 use serde_json::Value;
 
+use crate::agency_client::agency_settings;
 use crate::agency_client::mocking::AgencyMockDecrypted;
 
 use crate::{api, init, libindy, settings, utils};
+use crate::aries::utils::decorators;
 use crate::libindy::utils::pool::reset_pool_handle;
 use crate::libindy::utils::pool::tests::{create_test_ledger_config, delete_test_pool, open_test_pool};
 use crate::libindy::utils::wallet::{close_main_wallet, create_and_open_as_main_wallet, create_wallet, delete_wallet, reset_wallet_handle};
@@ -75,10 +77,13 @@ fn setup_empty() {
 
 fn tear_down() {
     settings::clear_config();
+    // agency settings are kept apart from settings, they reset the circuit breaker and header provider too
+    agency_settings::clear_config_agency();
     reset_wallet_handle();
     reset_pool_handle();
     settings::get_agency_client_mut().unwrap().disable_test_mode();
     AgencyMockDecrypted::clear_mocks();
+    decorators::clear_global_decorators();
 }
 
 impl SetupEmpty {
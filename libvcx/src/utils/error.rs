@@ -117,6 +117,16 @@ pub static NO_AGENT_INFO: Error = Error { code_num: 1106, message: "Agent pairwi
 pub static REV_REG_DEF_NOT_FOUND: Error = Error { code_num: 1107, message: "No revocation definition found" };
 pub static REV_DELTA_NOT_FOUND: Error = Error { code_num: 1108, message: "No revocation delta found in storage for this revocation registry. Were any credentials locally revoked?" };
 pub static POISONED_LOCK: Error = Error { code_num: 1109, message: "Attempted to lock a poisoned lock" };
+pub static CONNECTION_DEACTIVATED: Error = Error { code_num: 1110, message: "Connection was deactivated and can no longer exchange messages" };
+pub static CONNECTION_DID_MISMATCH: Error = Error { code_num: 1111, message: "Connection counterparty DID does not match the expected DID" };
+pub static REPLAYED_PRESENTATION: Error = Error { code_num: 1112, message: "Presentation for this proof request nonce was already received" };
+pub static INVALID_MESSAGE_FOR_STATE: Error = Error { code_num: 1113, message: "Message cannot be handled in the current state" };
+pub static UNTRUSTED_ISSUER: Error = Error { code_num: 1114, message: "Presentation contains credential of untrusted issuer" };
+pub static CANCELLED: Error = Error { code_num: 1115, message: "Operation was cancelled" };
+pub static RATE_LIMITED: Error = Error { code_num: 1116, message: "Send rate limit of the connection was exceeded" };
+pub static MESSAGE_EXPIRED: Error = Error { code_num: 1117, message: "Message has expired" };
+pub static TIMEOUT: Error = Error { code_num: 1118, message: "Operation timed out" };
+pub static AGENCY_UNAVAILABLE: Error = Error { code_num: 1119, message: "Agency is unavailable" };
 
 lazy_static! {
     static ref ERROR_C_MESSAGES: HashMap<u32, CString> = {
@@ -223,6 +233,16 @@ lazy_static! {
         insert_c_message(&mut m, &ACTION_NOT_SUPPORTED);
         insert_c_message(&mut m, &INVALID_REDIRECT_DETAILS);
         insert_c_message(&mut m, &NO_AGENT_INFO);
+        insert_c_message(&mut m, &CONNECTION_DEACTIVATED);
+        insert_c_message(&mut m, &CONNECTION_DID_MISMATCH);
+        insert_c_message(&mut m, &REPLAYED_PRESENTATION);
+        insert_c_message(&mut m, &INVALID_MESSAGE_FOR_STATE);
+        insert_c_message(&mut m, &UNTRUSTED_ISSUER);
+        insert_c_message(&mut m, &CANCELLED);
+        insert_c_message(&mut m, &RATE_LIMITED);
+        insert_c_message(&mut m, &MESSAGE_EXPIRED);
+        insert_c_message(&mut m, &TIMEOUT);
+        insert_c_message(&mut m, &AGENCY_UNAVAILABLE);
 
         m
     };
@@ -473,4 +493,64 @@ mod tests {
     fn test_invalid_master_secret() {
         assert_eq!(error_message(&INVALID_MASTER_SECRET.code_num), INVALID_MASTER_SECRET.message);
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_connection_deactivated() {
+        assert_eq!(error_message(&CONNECTION_DEACTIVATED.code_num), CONNECTION_DEACTIVATED.message);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_connection_did_mismatch() {
+        assert_eq!(error_message(&CONNECTION_DID_MISMATCH.code_num), CONNECTION_DID_MISMATCH.message);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_replayed_presentation() {
+        assert_eq!(error_message(&REPLAYED_PRESENTATION.code_num), REPLAYED_PRESENTATION.message);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_invalid_message_for_state() {
+        assert_eq!(error_message(&INVALID_MESSAGE_FOR_STATE.code_num), INVALID_MESSAGE_FOR_STATE.message);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_untrusted_issuer() {
+        assert_eq!(error_message(&UNTRUSTED_ISSUER.code_num), UNTRUSTED_ISSUER.message);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_cancelled() {
+        assert_eq!(error_message(&CANCELLED.code_num), CANCELLED.message);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_rate_limited() {
+        assert_eq!(error_message(&RATE_LIMITED.code_num), RATE_LIMITED.message);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_message_expired() {
+        assert_eq!(error_message(&MESSAGE_EXPIRED.code_num), MESSAGE_EXPIRED.message);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_timeout() {
+        assert_eq!(error_message(&TIMEOUT.code_num), TIMEOUT.message);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_agency_unavailable() {
+        assert_eq!(error_message(&AGENCY_UNAVAILABLE.code_num), AGENCY_UNAVAILABLE.message);
+    }
 }
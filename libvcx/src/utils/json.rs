@@ -67,6 +67,29 @@ fn _collect_keys(map: &Map<String, Value>) -> Vec<String> {
     rtn
 }
 
+/*
+Applies JSON merge patch (RFC 7386) to the value. Members of patch objects are merged recursively,
+null removes the member and any other value replaces the target.
+*/
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            if let Value::Object(target) = target {
+                for (key, value) in patch {
+                    if value.is_null() {
+                        target.remove(key);
+                    } else {
+                        merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+                    }
+                }
+            }
+        }
+        patch => *target = patch.clone()
+    }
+}
 
 #[cfg(test)]
 mod tests {
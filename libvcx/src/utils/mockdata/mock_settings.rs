@@ -10,6 +10,8 @@ static MOCKED_VALIDATE_INDY_PROOF: &str = "mocked_validate_indy_proof";
 lazy_static! {
     static ref MOCK_SETTINGS: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
     static ref MOCK_SETTINGS_RESULT_BOOL: RwLock<HashMap<String, VcxResult<bool>>> = RwLock::new(HashMap::new());
+    // pool handle -> reply of the ledger to read requests submitted to the pool
+    static ref MOCK_LEDGER_RESPONSES: RwLock<HashMap<i32, String>> = RwLock::new(HashMap::new());
 }
 
 pub struct MockBuilder; // empty
@@ -40,10 +42,18 @@ impl MockBuilder {
         self
     }
 
+    pub fn set_mock_ledger_response_for_pool(self, pool_handle: i32, response: &str) -> MockBuilder {
+        warn!("MockBuilder::set_mock_ledger_response_for_pool >>> pool_handle={}, response={}", pool_handle, response);
+        let mut responses = MOCK_LEDGER_RESPONSES.write().unwrap();
+        responses.insert(pool_handle, response.into());
+        self
+    }
+
     pub fn reset_mock_settings(&self) {
         warn!("MockBuilder::reset_mock_settings >>>");
         let mut config = MOCK_SETTINGS.write().unwrap();
         config.clear();
+        MOCK_LEDGER_RESPONSES.write().unwrap().clear();
     }
 }
 
@@ -77,3 +87,10 @@ pub fn get_mock_result_for_validate_indy_proof() -> Option<VcxResult<bool>> {
             Err(err) => Err(VcxError::from(err.kind()))
         })
 }
+
+pub fn get_mock_ledger_response_for_pool(pool_handle: i32) -> Option<String> {
+    let responses = MOCK_LEDGER_RESPONSES.read().unwrap();
+    responses
+        .get(&pool_handle)
+        .map(|s| String::from(s))
+}
@@ -1,3 +1,6 @@
+// Connections below were serialized by libvcx 0.15.0, they cover importing connections of that release by
+// `connection::from_legacy_string` and must be kept as they are
+
 // Alice receives invitation via out of band channel
 pub const ARIES_CONNECTION_INVITATION: &str = r#"
 {
@@ -52,12 +52,17 @@ pub mod file;
 pub mod mockdata;
 pub mod provision;
 pub mod random;
+pub mod serialization;
+pub mod clock;
+pub mod polling;
 
 #[cfg(test)]
 pub mod plugins;
 
 #[macro_use]
 pub mod logger;
+#[macro_use]
+pub mod span;
 pub mod object_cache;
 pub mod validation;
 
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::ops::Deref;
 use std::ops::DerefMut;
+use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::MutexGuard;
 
@@ -8,9 +9,20 @@ use rand::Rng;
 
 use crate::error::prelude::*;
 
+/**
+Thread-safe storage of objects addressed by handles.
+
+Locking contract:
+  - the store lock guards only the handle -> object mapping and is held just long enough to look up,
+    insert or remove an entry; it is never held while a closure passed to `get` / `get_mut` runs,
+  - every object has its own lock, which is held for the whole duration of the closure.
+
+Hence operations on different handles run concurrently, while operations on the same handle are
+serialized. Closures must not access the same handle of the same cache again, otherwise they deadlock.
+ */
 pub struct ObjectCache<T> {
     pub cache_name: String,
-    pub store: Mutex<HashMap<u32, Mutex<T>>>,
+    pub store: Mutex<HashMap<u32, Arc<Mutex<T>>>>,
 }
 
 impl<T> ObjectCache<T> {
@@ -21,7 +33,7 @@ impl<T> ObjectCache<T> {
         }
     }
 
-    fn _lock_store(&self) -> VcxResult<MutexGuard<HashMap<u32, Mutex<T>>>> {
+    fn _lock_store(&self) -> VcxResult<MutexGuard<HashMap<u32, Arc<Mutex<T>>>>> {
         match self.store.lock() {
             Ok(g) => Ok(g),
             Err(e) => {
@@ -39,28 +51,27 @@ impl<T> ObjectCache<T> {
         store.contains_key(&handle)
     }
 
+    fn _get_object(&self, handle: u32) -> VcxResult<Arc<Mutex<T>>> {
+        let store = self._lock_store()?;
+        store.get(&handle)
+            .cloned()
+            .ok_or(VcxError::from_msg(VcxErrorKind::InvalidHandle, format!("[ObjectCache: {}] Object not found for handle: {}", self.cache_name, handle)))
+    }
+
     pub fn get<F, R>(&self, handle: u32, closure: F) -> VcxResult<R>
         where F: Fn(&T) -> VcxResult<R> {
-        let store = self._lock_store()?;
-        match store.get(&handle) {
-            Some(m) => match m.lock() {
-                Ok(obj) => closure(obj.deref()),
-                Err(_) => Err(VcxError::from_msg(VcxErrorKind::Common(10), format!("[ObjectCache: {}] Unable to lock Object Store", self.cache_name))) //TODO better error
-            },
-            None => Err(VcxError::from_msg(VcxErrorKind::InvalidHandle, format!("[ObjectCache: {}] Object not found for handle: {}", self.cache_name, handle)))
-        }
+        let object = self._get_object(handle)?;
+        let obj = object.lock()
+            .map_err(|_| VcxError::from_msg(VcxErrorKind::Common(10), format!("[ObjectCache: {}] Unable to lock Object Store", self.cache_name)))?; //TODO better error
+        closure(obj.deref())
     }
 
     pub fn get_mut<F, R>(&self, handle: u32, closure: F) -> VcxResult<R>
         where F: Fn(&mut T) -> VcxResult<R> {
-        let mut store = self._lock_store()?;
-        match store.get_mut(&handle) {
-            Some(m) => match m.lock() {
-                Ok(mut obj) => closure(obj.deref_mut()),
-                Err(_) => Err(VcxError::from_msg(VcxErrorKind::Common(10), format!("[ObjectCache: {}] Unable to lock Object Store", self.cache_name))) //TODO better error
-            },
-            None => Err(VcxError::from_msg(VcxErrorKind::InvalidHandle, format!("[ObjectCache: {}] Object not found for handle: {}", self.cache_name, handle)))
-        }
+        let object = self._get_object(handle)?;
+        let mut obj = object.lock()
+            .map_err(|_| VcxError::from_msg(VcxErrorKind::Common(10), format!("[ObjectCache: {}] Unable to lock Object Store", self.cache_name)))?; //TODO better error
+        closure(obj.deref_mut())
     }
 
     pub fn add(&self, obj: T) -> VcxResult<u32> {
@@ -74,7 +85,7 @@ impl<T> ObjectCache<T> {
             new_handle = rand::thread_rng().gen::<u32>();
         }
 
-        match store.insert(new_handle, Mutex::new(obj)) {
+        match store.insert(new_handle, Arc::new(Mutex::new(obj))) {
             Some(_) => Ok(new_handle),
             None => Ok(new_handle)
         }
@@ -83,7 +94,7 @@ impl<T> ObjectCache<T> {
     pub fn insert(&self, handle: u32, obj: T) -> VcxResult<()> {
         let mut store = self._lock_store()?;
 
-        match store.insert(handle, Mutex::new(obj)) {
+        match store.insert(handle, Arc::new(Mutex::new(obj))) {
             _ => Ok(()),
         }
     }
@@ -101,6 +112,35 @@ impl<T> ObjectCache<T> {
         Ok(store.clear())
     }
 
+    pub fn handles(&self) -> VcxResult<Vec<u32>> {
+        let store = self._lock_store()?;
+        Ok(store.keys().cloned().collect())
+    }
+
+    /**
+    Applies the closure to every object, results are paired with handles in ascending order. The store lock
+    is not held while the closure runs, so objects used by other threads are waited for like in `get` and
+    objects released meanwhile are skipped.
+     */
+    pub fn map<F, R>(&self, closure: F) -> VcxResult<Vec<(u32, R)>>
+        where F: Fn(&T) -> VcxResult<R> {
+        let mut handles = self.handles()?;
+        handles.sort();
+
+        let mut results = Vec::new();
+        for handle in handles {
+            let object = match self._get_object(handle) {
+                Ok(object) => object,
+                Err(ref err) if err.kind() == VcxErrorKind::InvalidHandle => continue,
+                Err(err) => return Err(err)
+            };
+            let obj = object.lock()
+                .map_err(|_| VcxError::from_msg(VcxErrorKind::Common(10), format!("[ObjectCache: {}] Unable to lock Object Store", self.cache_name)))?;
+            results.push((handle, closure(obj.deref())?));
+        }
+        Ok(results)
+    }
+
     pub fn len(&self) -> VcxResult<usize> {
         let store = self._lock_store()?;
         Ok(store.len())
@@ -109,6 +149,10 @@ impl<T> ObjectCache<T> {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, mpsc};
+    use std::thread;
+    use std::time::Duration;
+
     use crate::utils::object_cache::ObjectCache;
     use crate::utils::devsetup::SetupDefaults;
 
@@ -120,6 +164,25 @@ mod tests {
         let _c: ObjectCache<u32> = ObjectCache::new("cache0-u32");
     }
 
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn handles_test() {
+        let _setup = SetupDefaults::init();
+
+        let test: ObjectCache<u32> = ObjectCache::new("cache-test");
+        let handle1 = test.add(1).unwrap();
+        let handle2 = test.add(2).unwrap();
+
+        let mut handles = test.handles().unwrap();
+        handles.sort();
+        let mut expected = vec![handle1, handle2];
+        expected.sort();
+        assert_eq!(expected, handles);
+
+        test.release(handle1).unwrap();
+        assert_eq!(vec![handle2], test.handles().unwrap());
+    }
+
     #[test]
     #[cfg(feature = "general_test")]
     fn get_closure() {
@@ -164,4 +227,94 @@ mod tests {
 
         assert_eq!("TEST", string);
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn concurrent_get_mut_test() {
+        let _setup = SetupDefaults::init();
+
+        let test: Arc<ObjectCache<u32>> = Arc::new(ObjectCache::new("cache4-u32"));
+        let handles: Vec<u32> = (0..8).map(|_| test.add(0).unwrap()).collect();
+
+        let mut threads = vec![];
+        for (i, handle) in handles.iter().enumerate() {
+            for _ in 0..4 {
+                let test = test.clone();
+                let handle = *handle;
+                threads.push(thread::spawn(move || {
+                    for _ in 0..100 {
+                        test.get_mut(handle, |obj| {
+                            *obj += i as u32 + 1;
+                            Ok(())
+                        }).unwrap();
+                    }
+                }));
+            }
+        }
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        for (i, handle) in handles.iter().enumerate() {
+            let value = test.get(*handle, |obj| Ok(*obj)).unwrap();
+            assert_eq!(value, 4 * 100 * (i as u32 + 1));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn get_mut_does_not_block_other_handles_test() {
+        let _setup = SetupDefaults::init();
+
+        let test: Arc<ObjectCache<u32>> = Arc::new(ObjectCache::new("cache5-u32"));
+        let handle1 = test.add(1).unwrap();
+        let handle2 = test.add(2).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let test_clone = test.clone();
+        let t = thread::spawn(move || {
+            test_clone.get_mut(handle1, |_obj| {
+                // keep handle1 locked until main thread managed to update handle2
+                receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+                Ok(())
+            }).unwrap();
+        });
+
+        thread::sleep(Duration::from_millis(100));
+        test.get_mut(handle2, |obj| {
+            *obj = 20;
+            Ok(())
+        }).unwrap();
+        sender.send(()).unwrap();
+        t.join().unwrap();
+
+        assert_eq!(20, test.get(handle2, |obj| Ok(*obj)).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn map_waits_for_objects_used_by_other_threads_test() {
+        let _setup = SetupDefaults::init();
+
+        let test: Arc<ObjectCache<u32>> = Arc::new(ObjectCache::new("cache6-u32"));
+        let handle1 = test.add(1).unwrap();
+        let handle2 = test.add(2).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let test_clone = test.clone();
+        let t = thread::spawn(move || {
+            test_clone.get_mut(handle1, |obj| {
+                sender.send(()).unwrap();
+                thread::sleep(Duration::from_millis(200));
+                *obj = 10;
+                Ok(())
+            }).unwrap();
+        });
+
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        let mut expected = vec![(handle1, 10), (handle2, 2)];
+        expected.sort();
+        assert_eq!(expected, test.map(|obj| Ok(*obj)).unwrap());
+        t.join().unwrap();
+    }
 }
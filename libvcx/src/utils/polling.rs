@@ -0,0 +1,80 @@
+use std::cmp;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::prelude::*;
+
+const POLL_INITIAL_DELAY: Duration = Duration::from_millis(100);
+const POLL_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/**
+Calls `poll` with exponential backoff starting at 100ms and capped at 5s until it returns a value, returns `None`
+if it doesn't within `timeout`. Transient errors, see `VcxErrorKind::is_transient`, are retried until the deadline,
+other errors are returned right away.
+ */
+pub fn poll_until<T, F>(timeout: Duration, mut poll: F) -> VcxResult<Option<T>>
+    where F: FnMut() -> VcxResult<Option<T>> {
+    let deadline = Instant::now() + timeout;
+    let mut delay = POLL_INITIAL_DELAY;
+
+    loop {
+        match poll() {
+            Ok(Some(value)) => return Ok(Some(value)),
+            Ok(None) => {}
+            Err(err) if err.kind().is_transient() => {
+                warn!("poll_until >>> transient error, will retry: {}", err);
+            }
+            Err(err) => return Err(err)
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(cmp::min(delay, deadline - now));
+        delay = cmp::min(delay * 2, POLL_MAX_DELAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_poll_until_retries_transient_errors() {
+        let mut attempts = 0;
+        let result = poll_until(Duration::from_secs(5), || {
+            attempts += 1;
+            match attempts {
+                1 => Err(VcxError::from_msg(VcxErrorKind::PostMessageFailed, "agency is down")),
+                2 => Ok(None),
+                _ => Ok(Some(attempts))
+            }
+        }).unwrap();
+        assert_eq!(result, Some(3));
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_poll_until_returns_other_errors() {
+        let mut attempts = 0;
+        let err = poll_until::<(), _>(Duration::from_secs(5), || {
+            attempts += 1;
+            Err(VcxError::from_msg(VcxErrorKind::InvalidState, "cannot continue"))
+        }).unwrap_err();
+        assert_eq!(err.kind(), VcxErrorKind::InvalidState);
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_poll_until_times_out() {
+        let started = Instant::now();
+        let result = poll_until::<(), _>(Duration::from_millis(300), || {
+            Err(VcxError::from_msg(VcxErrorKind::AgencyUnavailable, "agency is down"))
+        }).unwrap();
+        assert_eq!(result, None);
+        assert!(started.elapsed() >= Duration::from_millis(300));
+    }
+}
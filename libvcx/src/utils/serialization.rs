@@ -0,0 +1,70 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::prelude::*;
+use crate::settings::{self, SerializationFormat};
+
+/// Prefix marking objects serialized as base64 encoded messagepack.
+pub const MSGPACK_PREFIX: &str = "msgpack:";
+
+/**
+Serializes object in the format selected by `serialization_format` setting. JSON is the default,
+messagepack is prefixed by `MSGPACK_PREFIX` so it can be told apart when deserializing.
+ */
+pub fn serialize<T: Serialize>(object: &T) -> VcxResult<String> {
+    serialize_as(object, settings::get_serialization_format())
+}
+
+pub fn serialize_as<T: Serialize>(object: &T, format: SerializationFormat) -> VcxResult<String> {
+    match format {
+        SerializationFormat::Json => {
+            serde_json::to_string(object)
+                .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize object to JSON: {:?}", err)))
+        }
+        SerializationFormat::Msgpack => {
+            let bytes = rmp_serde::to_vec_named(object)
+                .map_err(|err| VcxError::from_msg(VcxErrorKind::SerializationError, format!("Cannot serialize object to messagepack: {:?}", err)))?;
+            Ok(format!("{}{}", MSGPACK_PREFIX, base64::encode(&bytes)))
+        }
+    }
+}
+
+/**
+Deserializes object from either format, regardless of the current `serialization_format` setting.
+ */
+pub fn deserialize<T: DeserializeOwned>(data: &str) -> VcxResult<T> {
+    if data.starts_with(MSGPACK_PREFIX) {
+        let bytes = base64::decode(&data[MSGPACK_PREFIX.len()..])
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot decode messagepack data: {:?}", err)))?;
+        rmp_serde::from_slice(&bytes)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize object from messagepack: {:?}", err)))
+    } else {
+        serde_json::from_str(data)
+            .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Cannot deserialize object from JSON: {:?}", err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::utils::devsetup::SetupDefaults;
+
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_serialize_deserialize_both_formats() {
+        let _setup = SetupDefaults::init();
+
+        let object = json!({"source_id": "foo", "state": {"Completed": {"protocols": null, "keys": ["a", "b"]}}});
+
+        let json = serialize_as(&object, SerializationFormat::Json).unwrap();
+        assert_eq!(object, serde_json::from_str::<Value>(&json).unwrap());
+        assert_eq!(object, deserialize::<Value>(&json).unwrap());
+
+        let msgpack = serialize_as(&object, SerializationFormat::Msgpack).unwrap();
+        assert!(msgpack.starts_with(MSGPACK_PREFIX));
+        assert_eq!(object, deserialize::<Value>(&msgpack).unwrap());
+    }
+}
@@ -0,0 +1,38 @@
+/*
+Spans and events describing high level operations on connections. With `tracing` feature enabled they are
+emitted as structured `tracing` spans and events carrying connection handle, source id and state, so logs can
+be filtered by connection. Without the feature they fall back to plain `log` messages.
+*/
+
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! connection_span {
+    ($operation:expr, $handle:expr, $connection:expr) => (
+        // the guard is kept until the end of the block the macro is used in
+        let _span = tracing::info_span!("connection", operation = $operation, handle = $handle, source_id = %$connection.source_id(), state = $connection.state()).entered();
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! connection_span {
+    ($operation:expr, $handle:expr, $connection:expr) => (
+        trace!("connection::{} >>> handle: {}, source_id: {}, state: {}", $operation, $handle, $connection.source_id(), $connection.state());
+    );
+}
+
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! connection_transition_event {
+    ($source_id:expr, $from:expr, $to:expr) => (
+        tracing::info!(source_id = %$source_id, from = $from, to = $to, "connection state transition")
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! connection_transition_event {
+    ($source_id:expr, $from:expr, $to:expr) => (
+        debug!("connection state transition >>> source_id: {}, from: {}, to: {}", $source_id, $from, $to)
+    );
+}
@@ -1,8 +1,11 @@
 extern crate openssl;
 extern crate rust_base58;
 
+use agency_client::MessageStatusCode;
+
+use crate::aries::messages::a2a::message_family::MessageFamilies;
 use crate::error::prelude::*;
-use crate::settings::Actors;
+use crate::settings::{self, Actors, SerializationFormat};
 use crate::utils::qualifier;
 
 use self::openssl::bn::BigNum;
@@ -58,6 +61,64 @@ pub fn validate_actors(actors: &str) -> VcxResult<Vec<Actors>> {
         .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Invalid actors: {:?}", err)))
 }
 
+pub fn validate_supported_protocols(families: &str) -> VcxResult<Vec<MessageFamilies>> {
+    let families: Vec<String> = ::serde_json::from_str(&families)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Invalid supported protocols: {:?}", err)))?;
+    families.into_iter()
+        .map(|family| match MessageFamilies::from(family) {
+            MessageFamilies::Unknown(family) => Err(VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Unknown protocol family: {:?}", family))),
+            family => Ok(family)
+        })
+        .collect()
+}
+
+pub fn validate_message_status(status: &str) -> VcxResult<MessageStatusCode> {
+    ::serde_json::from_value(::serde_json::Value::String(status.to_string()))
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Invalid message status code: {:?}", err)))
+}
+
+pub fn validate_serialization_format(format: &str) -> VcxResult<SerializationFormat> {
+    ::serde_json::from_value(::serde_json::Value::String(format.to_string()))
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Invalid serialization format: {:?}", err)))
+}
+
+pub fn validate_max_routing_depth(depth: &str) -> VcxResult<usize> {
+    ::serde_json::from_str(depth)
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidJson, format!("Invalid max routing depth: {:?}", err)))
+}
+
+pub fn validate_flag(flag: &str) -> VcxResult<bool> {
+    flag.parse::<bool>()
+        .map_err(|err| VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Invalid flag, expected \"true\" or \"false\": {:?}", err)))
+}
+
+pub fn validate_send_rate(rate: &str) -> VcxResult<u32> {
+    match rate.parse::<u32>() {
+        Ok(rate) if rate > 0 => Ok(rate),
+        _ => Err(VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Invalid send rate, expected positive number: {:?}", rate)))
+    }
+}
+
+pub fn validate_ttl(ttl: &str) -> VcxResult<u64> {
+    match ttl.parse::<u64>() {
+        Ok(ttl) if ttl > 0 => Ok(ttl),
+        _ => Err(VcxError::from_msg(VcxErrorKind::InvalidOption, format!("Invalid time to live, expected positive number of seconds: {:?}", ttl)))
+    }
+}
+
+/**
+Key derivation method of the wallet key, the wallet protects all stored keys including pairwise keys of connections.
+ARGON2I_INT is lighter than ARGON2I_MOD, RAW expects key which is already derived.
+ */
+pub fn validate_key_derivation(key_derivation: &str) -> VcxResult<String> {
+    let supported = [settings::WALLET_KDF_RAW, settings::WALLET_KDF_ARGON2I_INT, settings::WALLET_KDF_ARGON2I_MOD];
+    if !supported.contains(&key_derivation) {
+        return Err(VcxError::from_msg(VcxErrorKind::InvalidConfiguration,
+                                      format!("Unsupported wallet key derivation method {:?}, expected one of {:?}", key_derivation, supported)));
+    }
+    Ok(key_derivation.to_string())
+}
+
 pub fn validate_phone_number(p_num: &str) -> VcxResult<String> {
     Ok(String::from(p_num))
 }
@@ -162,4 +223,26 @@ mod tests {
 
         assert_eq!(validate_payment_method("").unwrap_err().kind(), VcxErrorKind::MissingPaymentMethod);
     }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_validate_key_derivation() {
+        let _setup = SetupDefaults::init();
+
+        assert_eq!(validate_key_derivation("RAW").unwrap(), "RAW");
+        assert_eq!(validate_key_derivation("ARGON2I_INT").unwrap(), "ARGON2I_INT");
+        assert_eq!(validate_key_derivation("ARGON2I_MOD").unwrap(), "ARGON2I_MOD");
+        assert_eq!(validate_key_derivation("argon2i_mod").unwrap_err().kind(), VcxErrorKind::InvalidConfiguration);
+        assert_eq!(validate_key_derivation("SCRYPT").unwrap_err().kind(), VcxErrorKind::InvalidConfiguration);
+    }
+
+    #[test]
+    #[cfg(feature = "general_test")]
+    fn test_validate_max_routing_depth() {
+        let _setup = SetupDefaults::init();
+
+        assert_eq!(validate_max_routing_depth("2").unwrap(), 2);
+        assert_eq!(validate_max_routing_depth("deep").unwrap_err().kind(), VcxErrorKind::InvalidJson);
+        assert_eq!(validate_max_routing_depth("-1").unwrap_err().kind(), VcxErrorKind::InvalidJson);
+    }
 }